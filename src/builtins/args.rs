@@ -0,0 +1,238 @@
+use crate::error::{ErrorCode, NebulaError};
+use crate::interp::Value;
+use std::collections::HashMap;
+pub trait FromArg: Sized {
+    const EXPECTED: &'static str;
+    const CODE: ErrorCode = ErrorCode::E030;
+    fn from_arg(value: &Value) -> Option<Self>;
+}
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+pub trait IntoNativeResult {
+    fn into_native_result(self) -> Result<Value, String>;
+}
+impl FromArg for Value {
+    const EXPECTED: &'static str = "any";
+    fn from_arg(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+impl FromArg for f64 {
+    const EXPECTED: &'static str = "nb";
+    const CODE: ErrorCode = ErrorCode::E031;
+    fn from_arg(value: &Value) -> Option<Self> {
+        value.as_number()
+    }
+}
+impl FromArg for i64 {
+    const EXPECTED: &'static str = "int";
+    const CODE: ErrorCode = ErrorCode::E031;
+    fn from_arg(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) | Value::Float(n) if n.fract() == 0.0 => Some(*n as i64),
+            other => other.as_integer(),
+        }
+    }
+}
+impl FromArg for usize {
+    const EXPECTED: &'static str = "non-negative int";
+    const CODE: ErrorCode = ErrorCode::E031;
+    fn from_arg(value: &Value) -> Option<Self> {
+        i64::from_arg(value).and_then(|n| usize::try_from(n).ok())
+    }
+}
+impl FromArg for bool {
+    const EXPECTED: &'static str = "bool";
+    fn from_arg(value: &Value) -> Option<Self> {
+        value.as_bool()
+    }
+}
+impl FromArg for String {
+    const EXPECTED: &'static str = "wrd";
+    fn from_arg(value: &Value) -> Option<Self> {
+        value.as_string().map(str::to_string)
+    }
+}
+impl FromArg for Vec<Value> {
+    const EXPECTED: &'static str = "lst";
+    fn from_arg(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(items) | Value::Tuple(items) => Some(items.clone()),
+            Value::Range(range) => Some(range.iter().map(Value::Integer).collect()),
+            _ => None,
+        }
+    }
+}
+impl FromArg for HashMap<String, Value> {
+    const EXPECTED: &'static str = "map";
+    fn from_arg(value: &Value) -> Option<Self> {
+        match value {
+            Value::Map(map) => Some(map.clone()),
+            _ => None,
+        }
+    }
+}
+impl<T: FromArg> FromArg for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+    const CODE: ErrorCode = T::CODE;
+    fn from_arg(value: &Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(None),
+            other => T::from_arg(other).map(Some),
+        }
+    }
+}
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+impl IntoValue for usize {
+    fn into_value(self) -> Value {
+        Value::Integer(self as i64)
+    }
+}
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Nil, IntoValue::into_value)
+    }
+}
+impl<T: IntoValue> IntoNativeResult for T {
+    fn into_native_result(self) -> Result<Value, String> {
+        Ok(self.into_value())
+    }
+}
+impl<T: IntoValue> IntoNativeResult for Result<T, String> {
+    fn into_native_result(self) -> Result<Value, String> {
+        self.map(IntoValue::into_value)
+    }
+}
+pub fn coded_error(code: ErrorCode, detail: impl Into<String>) -> String {
+    NebulaError::coded(code, detail).to_string()
+}
+pub fn check_arity(func: &str, args: &[Value], expected: usize) -> Result<(), String> {
+    if args.len() == expected {
+        return Ok(());
+    }
+    Err(coded_error(
+        ErrorCode::E012,
+        format!("{}() expected {} args, got {}", func, expected, args.len()),
+    ))
+}
+pub fn arg<T: FromArg>(func: &str, args: &[Value], index: usize) -> Result<T, String> {
+    let value = args.get(index).unwrap_or(&Value::Nil);
+    T::from_arg(value).ok_or_else(|| {
+        coded_error(
+            T::CODE,
+            format!(
+                "{}() argument {} expects {}, got {}",
+                func,
+                index + 1,
+                T::EXPECTED,
+                value.type_name()
+            ),
+        )
+    })
+}
+#[macro_export]
+macro_rules! native_fn {
+    (fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty $body:block) => {
+        $crate::interp::NativeFn::new(
+            stringify!($name),
+            Some(<[&str]>::len(&[$(stringify!($arg)),*])),
+            |_ctx, args| {
+                let func = stringify!($name);
+                let arity = <[&str]>::len(&[$(stringify!($arg)),*]);
+                $crate::builtins::check_arity(func, args, arity)?;
+                $crate::native_fn!(@bind func args 0; $($arg: $ty),*);
+                let result: $ret = $body;
+                $crate::builtins::IntoNativeResult::into_native_result(result)
+            },
+        )
+    };
+    (@bind $func:ident $args:ident $index:expr;) => {};
+    (@bind $func:ident $args:ident $index:expr; $arg:ident: $ty:ty $(, $rest:ident: $rest_ty:ty)*) => {
+        let $arg: $ty = $crate::builtins::arg($func, $args, $index)?;
+        $crate::native_fn!(@bind $func $args $index + 1; $($rest: $rest_ty),*);
+    };
+}
+#[cfg(test)]
+mod tests {
+    use crate::builtins::TestContext;
+    use crate::interp::Value;
+    #[test]
+    fn test_native_fn_macro() {
+        let repeat = native_fn!(
+            fn repeat(text: String, times: usize, sep: Option<String>) -> String {
+                vec![text; times].join(sep.as_deref().unwrap_or(""))
+            }
+        );
+        let call = |args: &[Value]| (repeat.func)(&mut TestContext, args);
+        let s = |text: &str| Value::String(text.into());
+        assert_eq!(repeat.arity, Some(3));
+        assert_eq!(
+            call(&[s("ab"), Value::Number(2.0), s("-")]).unwrap(),
+            s("ab-ab")
+        );
+        assert_eq!(
+            call(&[s("x"), Value::Integer(3), Value::Nil]).unwrap(),
+            s("xxx")
+        );
+        let err = call(&[s("x"), s("3"), Value::Nil]).unwrap_err();
+        assert_eq!(
+            err,
+            "[E031] not a number: repeat() argument 2 expects non-negative int, got wrd"
+        );
+        assert!(call(&[s("x")]).unwrap_err().starts_with("[E012]"));
+        let halve = native_fn!(
+            fn halve(n: i64) -> Result<i64, String> {
+                if n % 2 == 0 {
+                    Ok(n / 2)
+                } else {
+                    Err(format!("{} is odd", n))
+                }
+            }
+        );
+        assert_eq!(
+            (halve.func)(&mut TestContext, &[Value::Integer(4)]).unwrap(),
+            Value::Integer(2)
+        );
+        assert!((halve.func)(&mut TestContext, &[Value::Integer(3)]).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::interp::{NativeFn, Value};
+use std::rc::Rc;
+fn first_difference(left: &Value, right: &Value, path: &str) -> Option<String> {
+    match (left, right) {
+        (Value::List(a), Value::List(b)) | (Value::Tuple(a), Value::Tuple(b)) => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                if x != y {
+                    return first_difference(x, y, &format!("{}[{}]", path, i));
+                }
+            }
+            if a.len() != b.len() {
+                return Some(format!("{} length {} != {}", path, a.len(), b.len()));
+            }
+            None
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            let mut keys: Vec<_> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = format!("{}[{:?}]", path, key);
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) if x != y => return first_difference(x, y, &child),
+                    (Some(_), None) => return Some(format!("{} missing on right", child)),
+                    (None, Some(_)) => return Some(format!("{} missing on left", child)),
+                    _ => {}
+                }
+            }
+            None
+        }
+        _ if left == right => None,
+        _ => Some(format!(
+            "{}: {} ({}) != {} ({})",
+            path,
+            left.repr(),
+            left.type_name(),
+            right.repr(),
+            right.type_name()
+        )),
+    }
+}
+fn check_args(args: &[Value], func: &str) -> Result<Option<String>, String> {
+    match args.len() {
+        2 => Ok(None),
+        3 => Ok(Some(args[2].to_display_string())),
+        n => Err(format!("{}() expected 2 or 3 arguments, got {}", func, n)),
+    }
+}
+fn failure(func: &str, message: Option<String>) -> String {
+    match message {
+        Some(message) => format!("{} failed: {}", func, message),
+        None => format!("{} failed", func),
+    }
+}
+pub fn assert_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "assert_eq",
+            NativeFn {
+                name: "assert_eq".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let message = check_args(args, "assert_eq")?;
+                    let (left, right) = (&args[0], &args[1]);
+                    if left == right {
+                        return Ok(Value::Nil);
+                    }
+                    let mut report = format!(
+                        "{}\n  left:  {}\n  right: {}",
+                        failure("assert_eq", message),
+                        left.repr(),
+                        right.repr()
+                    );
+                    if let Some(diff) = first_difference(left, right, "value") {
+                        report.push_str(&format!("\n  first difference at {}", diff));
+                    }
+                    Err(report)
+                }),
+            },
+        ),
+        (
+            "assert_ne",
+            NativeFn {
+                name: "assert_ne".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let message = check_args(args, "assert_ne")?;
+                    if args[0] != args[1] {
+                        return Ok(Value::Nil);
+                    }
+                    Err(format!(
+                        "{}\n  both:  {}",
+                        failure("assert_ne", message),
+                        args[0].repr()
+                    ))
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    use std::collections::HashMap;
+    #[test]
+    fn test_assert_eq_reports_difference() {
+        let mut inner = HashMap::new();
+        inner.insert("k".to_string(), Value::Integer(1));
+        let left = Value::List(vec![Value::Integer(1), Value::Map(inner.clone())]);
+        assert_eq!(
+            call("assert_eq", &[left.clone(), left.clone()]),
+            Ok(Value::Nil)
+        );
+        inner.insert("k".to_string(), Value::String("1".into()));
+        let right = Value::List(vec![Value::Integer(1), Value::Map(inner)]);
+        let report = call("assert_eq", &[left.clone(), right.clone()]).unwrap_err();
+        assert!(report.contains("left:  lst(1, map(\"k\": 1))"));
+        assert!(report.contains("right: lst(1, map(\"k\": \"1\"))"));
+        assert!(report.contains("value[1][\"k\"]: 1 (int) != \"1\" (wrd)"));
+        assert_eq!(call("assert_ne", &[left.clone(), right]), Ok(Value::Nil));
+        let report = call(
+            "assert_ne",
+            &[left.clone(), left, Value::String("ids".into())],
+        )
+        .unwrap_err();
+        assert!(report.starts_with("assert_ne failed: ids"));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::interp::{NativeFn, Value};
+use std::rc::Rc;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+}
+impl Field {
+    fn size(self) -> usize {
+        match self {
+            Field::I8 | Field::U8 => 1,
+            Field::I16 | Field::U16 => 2,
+            Field::I32 | Field::U32 | Field::F32 => 4,
+            Field::I64 | Field::U64 | Field::F64 => 8,
+        }
+    }
+}
+fn parse_format(fmt: &str, func: &str) -> Result<Vec<(Field, bool)>, String> {
+    let mut little = cfg!(target_endian = "little");
+    let mut fields = Vec::new();
+    for c in fmt.chars() {
+        let field = match c {
+            '<' => {
+                little = true;
+                continue;
+            }
+            '>' | '!' => {
+                little = false;
+                continue;
+            }
+            '=' => {
+                little = cfg!(target_endian = "little");
+                continue;
+            }
+            ' ' => continue,
+            'b' => Field::I8,
+            'B' => Field::U8,
+            'h' => Field::I16,
+            'H' => Field::U16,
+            'i' => Field::I32,
+            'I' => Field::U32,
+            'l' => Field::I64,
+            'L' => Field::U64,
+            'f' => Field::F32,
+            'd' => Field::F64,
+            other => return Err(format!("{}(): unknown format character '{}'", func, other)),
+        };
+        fields.push((field, little));
+    }
+    Ok(fields)
+}
+fn encode(field: Field, little: bool, value: &Value) -> Result<Vec<u8>, String> {
+    macro_rules! int_bytes {
+        ($ty:ty) => {{
+            let n = value
+                .as_integer()
+                .ok_or_else(|| format!("pack() requires integer, got {}", value.type_name()))?;
+            let n = <$ty>::try_from(n)
+                .map_err(|_| format!("pack(): {} out of range for {}", n, stringify!($ty)))?;
+            if little {
+                n.to_le_bytes().to_vec()
+            } else {
+                n.to_be_bytes().to_vec()
+            }
+        }};
+    }
+    let bytes = match field {
+        Field::I8 => int_bytes!(i8),
+        Field::U8 => int_bytes!(u8),
+        Field::I16 => int_bytes!(i16),
+        Field::U16 => int_bytes!(u16),
+        Field::I32 => int_bytes!(i32),
+        Field::U32 => int_bytes!(u32),
+        Field::I64 => int_bytes!(i64),
+        Field::U64 => int_bytes!(u64),
+        Field::F32 | Field::F64 => {
+            let x = value
+                .as_number()
+                .ok_or_else(|| format!("pack() requires number, got {}", value.type_name()))?;
+            match (field, little) {
+                (Field::F32, true) => (x as f32).to_le_bytes().to_vec(),
+                (Field::F32, false) => (x as f32).to_be_bytes().to_vec(),
+                (_, true) => x.to_le_bytes().to_vec(),
+                (_, false) => x.to_be_bytes().to_vec(),
+            }
+        }
+    };
+    Ok(bytes)
+}
+fn decode(field: Field, little: bool, bytes: &[u8]) -> Result<Value, String> {
+    macro_rules! from_bytes {
+        ($ty:ty) => {{
+            let raw = bytes.try_into().expect("field width checked");
+            if little {
+                <$ty>::from_le_bytes(raw)
+            } else {
+                <$ty>::from_be_bytes(raw)
+            }
+        }};
+    }
+    let value = match field {
+        Field::I8 => Value::Integer(from_bytes!(i8) as i64),
+        Field::U8 => Value::Integer(from_bytes!(u8) as i64),
+        Field::I16 => Value::Integer(from_bytes!(i16) as i64),
+        Field::U16 => Value::Integer(from_bytes!(u16) as i64),
+        Field::I32 => Value::Integer(from_bytes!(i32) as i64),
+        Field::U32 => Value::Integer(from_bytes!(u32) as i64),
+        Field::I64 => Value::Integer(from_bytes!(i64)),
+        Field::U64 => {
+            let n = from_bytes!(u64);
+            Value::Integer(
+                i64::try_from(n)
+                    .map_err(|_| format!("unpack(): {} out of range for integer", n))?,
+            )
+        }
+        Field::F32 => Value::Number(from_bytes!(f32) as f64),
+        Field::F64 => Value::Number(from_bytes!(f64)),
+    };
+    Ok(value)
+}
+fn byte_list(value: &Value) -> Result<Vec<u8>, String> {
+    let items = match value {
+        Value::List(items) => items,
+        other => {
+            return Err(format!(
+                "unpack() requires byte list, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    items
+        .iter()
+        .map(|v| match v {
+            Value::Byte(b) => Ok(*b),
+            other => other
+                .as_integer()
+                .and_then(|n| u8::try_from(n).ok())
+                .ok_or_else(|| format!("unpack(): {} is not a byte", other)),
+        })
+        .collect()
+}
+pub fn binary_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "pack",
+            NativeFn {
+                name: "pack".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let fmt = args
+                        .first()
+                        .and_then(|v| v.as_string())
+                        .ok_or("pack() requires format string")?;
+                    let fields = parse_format(fmt, "pack")?;
+                    let values = &args[1..];
+                    if values.len() != fields.len() {
+                        return Err(format!(
+                            "pack(): format expects {} values, got {}",
+                            fields.len(),
+                            values.len()
+                        ));
+                    }
+                    let mut out = Vec::new();
+                    for ((field, little), value) in fields.into_iter().zip(values) {
+                        out.extend(encode(field, little, value)?);
+                    }
+                    Ok(Value::List(
+                        out.into_iter().map(|b| Value::Integer(b as i64)).collect(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "unpack",
+            NativeFn {
+                name: "unpack".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let fmt = args[0]
+                        .as_string()
+                        .ok_or("unpack() requires format string")?;
+                    let fields = parse_format(fmt, "unpack")?;
+                    let bytes = byte_list(&args[1])?;
+                    let needed: usize = fields.iter().map(|(f, _)| f.size()).sum();
+                    if bytes.len() < needed {
+                        return Err(format!(
+                            "unpack(): format needs {} bytes, got {}",
+                            needed,
+                            bytes.len()
+                        ));
+                    }
+                    let mut offset = 0;
+                    let mut values = Vec::with_capacity(fields.len());
+                    for (field, little) in fields {
+                        let end = offset + field.size();
+                        values.push(decode(field, little, &bytes[offset..end])?);
+                        offset = end;
+                    }
+                    Ok(Value::List(values))
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    fn bytes(values: &[i64]) -> Value {
+        Value::List(values.iter().copied().map(Value::Integer).collect())
+    }
+    #[test]
+    fn test_pack_round_trip() {
+        let fmt = Value::String(">HBi".into());
+        let packed = call(
+            "pack",
+            &[
+                fmt.clone(),
+                Value::Integer(258),
+                Value::Integer(7),
+                Value::Integer(-2),
+            ],
+        )
+        .unwrap();
+        assert_eq!(packed, bytes(&[1, 2, 7, 255, 255, 255, 254]));
+        assert_eq!(
+            call("unpack", &[fmt, packed]).unwrap(),
+            Value::List(vec![
+                Value::Integer(258),
+                Value::Integer(7),
+                Value::Integer(-2)
+            ])
+        );
+        let le = call("pack", &[Value::String("<d".into()), Value::Number(1.5)]).unwrap();
+        assert_eq!(
+            call("unpack", &[Value::String("<d".into()), le]).unwrap(),
+            Value::List(vec![Value::Number(1.5)])
+        );
+        assert!(call("pack", &[Value::String("B".into()), Value::Integer(300)]).is_err());
+        assert!(call("unpack", &[Value::String("I".into()), bytes(&[1, 2])]).is_err());
+    }
+    #[test]
+    fn test_unpack_u64_beyond_integer_range() {
+        let fmt = Value::String(">L".into());
+        let max = call("pack", &[fmt.clone(), Value::Integer(i64::MAX)]).unwrap();
+        assert_eq!(
+            call("unpack", &[fmt.clone(), max]).unwrap(),
+            Value::List(vec![Value::Integer(i64::MAX)])
+        );
+        let err = call("unpack", &[fmt, bytes(&[128, 0, 0, 0, 0, 0, 0, 0])]).unwrap_err();
+        assert_eq!(
+            err,
+            "unpack(): 9223372036854775808 out of range for integer"
+        );
+    }
+}
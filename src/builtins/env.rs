@@ -0,0 +1,58 @@
+use crate::interp::{NativeFn, Value};
+use std::rc::Rc;
+pub fn env_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "env",
+            NativeFn {
+                name: "env".to_string(),
+                arity: Some(1),
+                func: Rc::new(|ctx, args| {
+                    ctx.policy().check_env("env")?;
+                    let name = args[0].as_string().ok_or("env() requires name string")?;
+                    Ok(std::env::var(name).map(|s| Value::String(s.into())).unwrap_or(Value::Nil))
+                }),
+            },
+        ),
+        (
+            "set_env",
+            NativeFn {
+                name: "set_env".to_string(),
+                arity: Some(2),
+                func: Rc::new(|ctx, args| {
+                    ctx.policy().check_env("set_env")?;
+                    let name = args[0].as_string().ok_or("set_env() requires name string")?;
+                    if name.is_empty() || name.contains('=') || name.contains('\0') {
+                        return Err(format!("set_env(): invalid variable name '{}'", name));
+                    }
+                    let value = args[1].to_display_string();
+                    if value.contains('\0') {
+                        return Err("set_env(): value must not contain NUL".to_string());
+                    }
+                    std::env::set_var(name, value);
+                    Ok(Value::Nil)
+                }),
+            },
+        ),
+        (
+            "envs",
+            NativeFn {
+                name: "envs".to_string(),
+                arity: Some(0),
+                func: Rc::new(|ctx, _args| {
+                    ctx.policy().check_env("envs")?;
+                    Ok(Value::Map(
+                        std::env::vars_os()
+                            .map(|(k, v)| {
+                                (
+                                    k.to_string_lossy().into_owned(),
+                                    Value::String(v.to_string_lossy().into_owned().into()),
+                                )
+                            })
+                            .collect(),
+                    ))
+                }),
+            },
+        ),
+    ]
+}
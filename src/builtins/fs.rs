@@ -0,0 +1,178 @@
+use crate::interp::{NativeContext, NativeFn, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+fn path_arg<'a>(args: &'a [Value], idx: usize, func: &str) -> Result<&'a str, String> {
+    args.get(idx)
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| format!("{}() requires path string", func))
+}
+fn read_path<'a>(
+    ctx: &dyn NativeContext,
+    args: &'a [Value],
+    idx: usize,
+    func: &str,
+) -> Result<&'a str, String> {
+    let path = path_arg(args, idx, func)?;
+    ctx.policy().check_read(func, path)?;
+    Ok(path)
+}
+pub(super) fn copy_file(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let from = read_path(ctx, args, 0, "copy")?;
+    let to = write_path(ctx, args, 1, "copy")?;
+    let bytes = fs::copy(from, to).map_err(|e| format!("copy('{}', '{}'): {}", from, to, e))?;
+    Ok(Value::Integer(bytes as i64))
+}
+fn write_path<'a>(
+    ctx: &dyn NativeContext,
+    args: &'a [Value],
+    idx: usize,
+    func: &str,
+) -> Result<&'a str, String> {
+    let path = path_arg(args, idx, func)?;
+    ctx.policy().check_write(func, path)?;
+    Ok(path)
+}
+pub fn fs_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "list_dir",
+            NativeFn {
+                name: "list_dir".to_string(),
+                arity: Some(1),
+                func: Rc::new(|ctx, args| {
+                    let path = read_path(ctx, args, 0, "list_dir")?;
+                    let entries =
+                        fs::read_dir(path).map_err(|e| format!("list_dir('{}'): {}", path, e))?;
+                    let mut names = Vec::new();
+                    for entry in entries {
+                        let entry = entry.map_err(|e| format!("list_dir('{}'): {}", path, e))?;
+                        names.push(entry.file_name().to_string_lossy().into_owned());
+                    }
+                    names.sort();
+                    Ok(Value::List(names.into_iter().map(|s| Value::String(s.into())).collect()))
+                }),
+            },
+        ),
+        (
+            "mkdir",
+            NativeFn {
+                name: "mkdir".to_string(),
+                arity: Some(1),
+                func: Rc::new(|ctx, args| {
+                    let path = write_path(ctx, args, 0, "mkdir")?;
+                    fs::create_dir_all(path).map_err(|e| format!("mkdir('{}'): {}", path, e))?;
+                    Ok(Value::Nil)
+                }),
+            },
+        ),
+        (
+            "remove_file",
+            NativeFn {
+                name: "remove_file".to_string(),
+                arity: Some(1),
+                func: Rc::new(|ctx, args| {
+                    let path = write_path(ctx, args, 0, "remove_file")?;
+                    fs::remove_file(path)
+                        .map_err(|e| format!("remove_file('{}'): {}", path, e))?;
+                    Ok(Value::Nil)
+                }),
+            },
+        ),
+        (
+            "remove_dir",
+            NativeFn {
+                name: "remove_dir".to_string(),
+                arity: None,
+                func: Rc::new(|ctx, args| {
+                    if args.is_empty() || args.len() > 2 {
+                        return Err(format!(
+                            "remove_dir() expected 1 or 2 arguments, got {}",
+                            args.len()
+                        ));
+                    }
+                    let path = write_path(ctx, args, 0, "remove_dir")?;
+                    let recursive = args.get(1).map(|v| v.is_truthy()).unwrap_or(false);
+                    let result = if recursive {
+                        fs::remove_dir_all(path)
+                    } else {
+                        fs::remove_dir(path)
+                    };
+                    result.map_err(|e| format!("remove_dir('{}'): {}", path, e))?;
+                    Ok(Value::Nil)
+                }),
+            },
+        ),
+        (
+            "rename",
+            NativeFn {
+                name: "rename".to_string(),
+                arity: Some(2),
+                func: Rc::new(|ctx, args| {
+                    let from = write_path(ctx, args, 0, "rename")?;
+                    let to = write_path(ctx, args, 1, "rename")?;
+                    fs::rename(from, to)
+                        .map_err(|e| format!("rename('{}', '{}'): {}", from, to, e))?;
+                    Ok(Value::Nil)
+                }),
+            },
+        ),
+        (
+            "stat",
+            NativeFn {
+                name: "stat".to_string(),
+                arity: Some(1),
+                func: Rc::new(|ctx, args| {
+                    let path = read_path(ctx, args, 0, "stat")?;
+                    let meta = fs::metadata(path).map_err(|e| format!("stat('{}'): {}", path, e))?;
+                    let modified = meta
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map(|d| Value::Number(d.as_millis() as f64))
+                        .unwrap_or(Value::Nil);
+                    let mut info = HashMap::new();
+                    info.insert("size".to_string(), Value::Integer(meta.len() as i64));
+                    info.insert("modified".to_string(), modified);
+                    info.insert("is_dir".to_string(), Value::Bool(meta.is_dir()));
+                    Ok(Value::Map(info))
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    fn s(v: &str) -> Value {
+        Value::String(v.into())
+    }
+    #[test]
+    fn test_dir_lifecycle() {
+        let root = std::env::temp_dir().join(format!("nebula_fs_{}", std::process::id()));
+        let root_str = root.to_string_lossy().into_owned();
+        let nested = root.join("a/b").to_string_lossy().into_owned();
+        call("mkdir", &[s(&nested)]).unwrap();
+        let file = root.join("a/f.txt").to_string_lossy().into_owned();
+        fs::write(&file, "hello").unwrap();
+        let listing = call("list_dir", &[s(&root.join("a").to_string_lossy())]).unwrap();
+        assert_eq!(listing, Value::List(vec![s("b"), s("f.txt")]));
+        let copy = root.join("a/g.txt").to_string_lossy().into_owned();
+        assert_eq!(call("copy", &[s(&file), s(&copy)]).unwrap(), Value::Integer(5));
+        let moved = root.join("h.txt").to_string_lossy().into_owned();
+        call("rename", &[s(&copy), s(&moved)]).unwrap();
+        match call("stat", &[s(&moved)]).unwrap() {
+            Value::Map(info) => {
+                assert_eq!(info["size"], Value::Integer(5));
+                assert_eq!(info["is_dir"], Value::Bool(false));
+            }
+            other => panic!("expected map, got {}", other),
+        }
+        call("remove_file", &[s(&moved)]).unwrap();
+        assert!(call("remove_dir", &[s(&root_str)]).is_err());
+        call("remove_dir", &[s(&root_str), Value::Bool(true)]).unwrap();
+        assert!(!root.exists());
+    }
+}
@@ -1,28 +1,85 @@
-use crate::interp::{Value, NativeFn};
+use crate::interp::{with_stdio, Stdio, Value, NativeFn};
+use std::rc::Rc;
+fn joined(args: &[Value]) -> String {
+    args.iter().map(|a| a.to_display_string()).collect::<Vec<_>>().join(" ")
+}
 pub fn io_builtins() -> Vec<(&'static str, NativeFn)> {
     vec![
         ("input", NativeFn {
             name: "input".to_string(),
-            arity: Some(0),
-            func: |_args| {
-                let mut line = String::new();
-                std::io::stdin().read_line(&mut line)
-                    .map_err(|e| e.to_string())?;
-                Ok(Value::String(line.trim().to_string()))
-            },
+            arity: None,
+            func: Rc::new(|ctx, args| {
+                if args.len() > 1 {
+                    return Err(format!("input() expected 0 or 1 arguments, got {}", args.len()));
+                }
+                let line = with_stdio(ctx, |io| {
+                    if let Some(prompt) = args.first() {
+                        io.write(&prompt.to_string())?;
+                        io.flush()?;
+                    }
+                    io.read_line()
+                })?;
+                Ok(Value::String(line.unwrap_or_default().trim().into()))
+            }),
         }),
         ("input_prompt", NativeFn {
             name: "input_prompt".to_string(),
             arity: Some(1),
-            func: |args| {
-                use std::io::{self, Write};
-                print!("{}", args[0]);
-                io::stdout().flush().map_err(|e| e.to_string())?;
-                let mut line = String::new();
-                std::io::stdin().read_line(&mut line)
-                    .map_err(|e| e.to_string())?;
-                Ok(Value::String(line.trim().to_string()))
-            },
+            func: Rc::new(|ctx, args| {
+                let line = with_stdio(ctx, |io| {
+                    io.write(&args[0].to_string())?;
+                    io.flush()?;
+                    io.read_line()
+                })?;
+                Ok(Value::String(line.unwrap_or_default().trim().into()))
+            }),
+        }),
+        ("read_line", NativeFn {
+            name: "read_line".to_string(),
+            arity: Some(0),
+            func: Rc::new(|ctx, _args| {
+                let line = with_stdio(ctx, Stdio::read_line)?;
+                Ok(line.map_or(Value::Nil, |s| Value::String(s.into())))
+            }),
+        }),
+        ("read_all", NativeFn {
+            name: "read_all".to_string(),
+            arity: Some(0),
+            func: Rc::new(|ctx, _args| {
+                Ok(Value::String(with_stdio(ctx, Stdio::read_all)?.into()))
+            }),
+        }),
+        ("print", NativeFn {
+            name: "print".to_string(),
+            arity: None,
+            func: Rc::new(|ctx, args| {
+                with_stdio(ctx, |io| io.write(&joined(args)))?;
+                Ok(Value::Nil)
+            }),
+        }),
+        ("eprint", NativeFn {
+            name: "eprint".to_string(),
+            arity: None,
+            func: Rc::new(|ctx, args| {
+                with_stdio(ctx, |io| io.write_error(&joined(args)))?;
+                Ok(Value::Nil)
+            }),
+        }),
+        ("elog", NativeFn {
+            name: "elog".to_string(),
+            arity: None,
+            func: Rc::new(|ctx, args| {
+                with_stdio(ctx, |io| io.write_error(&format!("{}\n", joined(args))))?;
+                Ok(Value::Nil)
+            }),
+        }),
+        ("flush", NativeFn {
+            name: "flush".to_string(),
+            arity: Some(0),
+            func: Rc::new(|ctx, _args| {
+                with_stdio(ctx, Stdio::flush)?;
+                Ok(Value::Nil)
+            }),
         }),
     ]
 }
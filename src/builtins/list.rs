@@ -0,0 +1,361 @@
+use crate::interp::{NativeContext, NativeFn, RangeValue, Value};
+use std::cmp::Ordering;
+use std::rc::Rc;
+fn list_arg<'a>(
+    args: &'a [Value],
+    func: &str,
+) -> Result<Box<dyn Iterator<Item = Value> + 'a>, String> {
+    match &args[0] {
+        Value::List(items) => Ok(Box::new(items.iter().cloned())),
+        Value::Range(range) => Ok(Box::new(range.iter().map(Value::Integer))),
+        other => Err(format!(
+            "{}() requires list, got {}",
+            func,
+            other.type_name()
+        )),
+    }
+}
+fn compare(a: &Value, b: &Value, func: &str) -> Result<Ordering, String> {
+    match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => Ok(x.cmp(y)),
+        (Value::String(x), Value::String(y)) => Ok(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Ok(x.cmp(y)),
+        _ => match (a.as_number(), b.as_number()) {
+            (Some(x), Some(y)) => Ok(x.total_cmp(&y)),
+            _ => Err(format!(
+                "{}(): cannot compare {} with {}",
+                func,
+                a.type_name(),
+                b.type_name()
+            )),
+        },
+    }
+}
+fn merge_sort<F>(items: Vec<Value>, cmp: &mut F) -> Result<Vec<Value>, String>
+where
+    F: FnMut(&Value, &Value) -> Result<Ordering, String>,
+{
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let left = merge_sort(left, cmp)?;
+    let right = merge_sort(right, cmp)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if cmp(r, l)? == Ordering::Less {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+fn comparator_result(result: Value) -> Result<Ordering, String> {
+    match result.as_number() {
+        Some(n) if n < 0.0 => Ok(Ordering::Less),
+        Some(n) if n > 0.0 => Ok(Ordering::Greater),
+        Some(_) => Ok(Ordering::Equal),
+        None => Err(format!(
+            "sort_by() comparator must return a number, got {}",
+            result.type_name()
+        )),
+    }
+}
+fn sort_by(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let items = list_arg(args, "sort_by")?.collect();
+    let comparator = &args[1];
+    let sorted = merge_sort(items, &mut |a, b| {
+        comparator_result(ctx.call(comparator, &[a.clone(), b.clone()])?)
+    })?;
+    Ok(Value::List(sorted))
+}
+fn map(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let items = list_arg(args, "map")?;
+    let mut mapped = Vec::with_capacity(items.size_hint().0);
+    for item in items {
+        mapped.push(ctx.call(&args[1], &[item])?);
+    }
+    Ok(Value::List(mapped))
+}
+fn filter(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let items = list_arg(args, "filter")?;
+    let mut kept = Vec::new();
+    for item in items {
+        if ctx.call(&args[1], std::slice::from_ref(&item))?.is_truthy() {
+            kept.push(item);
+        }
+    }
+    Ok(Value::List(kept))
+}
+fn reduce(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let items = list_arg(args, "reduce")?;
+    let mut acc = args[2].clone();
+    for item in items {
+        acc = ctx.call(&args[1], &[acc, item])?;
+    }
+    Ok(acc)
+}
+fn predicate_args<'a>(args: &'a [Value], func: &str) -> Result<Option<&'a Value>, String> {
+    match args.len() {
+        1 => Ok(None),
+        2 => Ok(Some(&args[1])),
+        n => Err(format!("{}() expected 1 or 2 arguments, got {}", func, n)),
+    }
+}
+fn test_item(
+    ctx: &mut dyn NativeContext,
+    predicate: Option<&Value>,
+    item: Value,
+) -> Result<bool, String> {
+    match predicate {
+        Some(predicate) => Ok(ctx.call(predicate, &[item])?.is_truthy()),
+        None => Ok(item.is_truthy()),
+    }
+}
+fn any(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let predicate = predicate_args(args, "any")?;
+    for item in list_arg(args, "any")? {
+        if test_item(ctx, predicate, item)? {
+            return Ok(Value::Bool(true));
+        }
+    }
+    Ok(Value::Bool(false))
+}
+fn all(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let predicate = predicate_args(args, "all")?;
+    for item in list_arg(args, "all")? {
+        if !test_item(ctx, predicate, item)? {
+            return Ok(Value::Bool(false));
+        }
+    }
+    Ok(Value::Bool(true))
+}
+fn range(_ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let mut bounds = Vec::with_capacity(args.len());
+    for arg in args {
+        bounds.push(
+            arg.as_integer()
+                .ok_or_else(|| format!("range() requires integers, got {}", arg.type_name()))?,
+        );
+    }
+    let (start, end, step) = match bounds[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => {
+            return Err(format!(
+                "range() expected 1 to 3 arguments, got {}",
+                args.len()
+            ))
+        }
+    };
+    if step == 0 {
+        return Err("range() step must not be zero".to_string());
+    }
+    Ok(Value::Range(RangeValue {
+        start,
+        end,
+        step,
+        inclusive: false,
+    }))
+}
+pub fn list_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "range",
+            NativeFn {
+                name: "range".to_string(),
+                arity: None,
+                func: Rc::new(range),
+            },
+        ),
+        (
+            "sort",
+            NativeFn {
+                name: "sort".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let items = list_arg(args, "sort")?.collect();
+                    let sorted = merge_sort(items, &mut |a, b| compare(a, b, "sort"))?;
+                    Ok(Value::List(sorted))
+                }),
+            },
+        ),
+        (
+            "sort_desc",
+            NativeFn {
+                name: "sort_desc".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let items = list_arg(args, "sort_desc")?.collect();
+                    let sorted = merge_sort(items, &mut |a, b| compare(b, a, "sort_desc"))?;
+                    Ok(Value::List(sorted))
+                }),
+            },
+        ),
+        (
+            "sort_by",
+            NativeFn {
+                name: "sort_by".to_string(),
+                arity: Some(2),
+                func: Rc::new(sort_by),
+            },
+        ),
+        (
+            "map",
+            NativeFn {
+                name: "map".to_string(),
+                arity: Some(2),
+                func: Rc::new(map),
+            },
+        ),
+        (
+            "filter",
+            NativeFn {
+                name: "filter".to_string(),
+                arity: Some(2),
+                func: Rc::new(filter),
+            },
+        ),
+        (
+            "reduce",
+            NativeFn {
+                name: "reduce".to_string(),
+                arity: Some(3),
+                func: Rc::new(reduce),
+            },
+        ),
+        (
+            "any",
+            NativeFn {
+                name: "any".to_string(),
+                arity: None,
+                func: Rc::new(any),
+            },
+        ),
+        (
+            "all",
+            NativeFn {
+                name: "all".to_string(),
+                arity: None,
+                func: Rc::new(all),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    fn ints(values: &[i64]) -> Value {
+        Value::List(values.iter().copied().map(Value::Integer).collect())
+    }
+    #[test]
+    fn test_sort_variants() {
+        assert_eq!(call("sort", &[ints(&[3, 1, 2])]).unwrap(), ints(&[1, 2, 3]));
+        assert_eq!(
+            call("sort_desc", &[ints(&[3, 1, 2])]).unwrap(),
+            ints(&[3, 2, 1])
+        );
+        let mixed = Value::List(vec![Value::Integer(1), Value::String("a".into())]);
+        assert!(call("sort", &[mixed]).is_err());
+        let by_distance = NativeFn {
+            name: "by_distance".to_string(),
+            arity: Some(2),
+            func: Rc::new(|_ctx, args| {
+                let a = (args[0].as_integer().unwrap() - 5).abs();
+                let b = (args[1].as_integer().unwrap() - 5).abs();
+                Ok(Value::Integer(a - b))
+            }),
+        };
+        let sorted = call(
+            "sort_by",
+            &[ints(&[1, 9, 4, 6, 5]), Value::NativeFunction(by_distance)],
+        )
+        .unwrap();
+        assert_eq!(sorted, ints(&[5, 4, 6, 1, 9]));
+    }
+    fn double_fn() -> NativeFn {
+        NativeFn {
+            name: "double".to_string(),
+            arity: Some(1),
+            func: Rc::new(|_ctx, args| Ok(Value::Integer(args[0].as_integer().unwrap() * 2))),
+        }
+    }
+    #[test]
+    fn test_higher_order() {
+        let double = Value::NativeFunction(double_fn());
+        let is_even = Value::NativeFunction(NativeFn {
+            name: "is_even".to_string(),
+            arity: Some(1),
+            func: Rc::new(|_ctx, args| Ok(Value::Bool(args[0].as_integer().unwrap() % 2 == 0))),
+        });
+        let add = Value::NativeFunction(NativeFn {
+            name: "add".to_string(),
+            arity: Some(2),
+            func: Rc::new(|_ctx, args| {
+                Ok(Value::Integer(
+                    args[0].as_integer().unwrap() + args[1].as_integer().unwrap(),
+                ))
+            }),
+        });
+        let xs = ints(&[1, 2, 3, 4]);
+        assert_eq!(
+            call("map", &[xs.clone(), double]).unwrap(),
+            ints(&[2, 4, 6, 8])
+        );
+        assert_eq!(
+            call("filter", &[xs.clone(), is_even.clone()]).unwrap(),
+            ints(&[2, 4])
+        );
+        assert_eq!(
+            call("reduce", &[xs.clone(), add, Value::Integer(10)]).unwrap(),
+            Value::Integer(20)
+        );
+        assert_eq!(
+            call("any", &[xs.clone(), is_even.clone()]).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(call("all", &[xs, is_even]).unwrap(), Value::Bool(false));
+        assert_eq!(call("all", &[ints(&[1, 2])]).unwrap(), Value::Bool(true));
+    }
+    #[test]
+    fn test_range_is_lazy() {
+        let huge = call("range", &[Value::Integer(0), Value::Integer(i64::MAX)]).unwrap();
+        match &huge {
+            Value::Range(r) => assert_eq!(r.len(), i64::MAX as usize),
+            other => panic!("expected range, got {}", other),
+        }
+        assert_eq!(
+            call("any", std::slice::from_ref(&huge)).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(call("all", &[huge]).unwrap(), Value::Bool(false));
+        let full = RangeValue::new(i64::MIN, i64::MAX, true);
+        assert_eq!(full.len(), i64::MAX as usize);
+        let down = call(
+            "range",
+            &[Value::Integer(10), Value::Integer(0), Value::Integer(-3)],
+        )
+        .unwrap();
+        assert_eq!(
+            call("map", &[down, Value::NativeFunction(double_fn())]).unwrap(),
+            ints(&[20, 14, 8, 2])
+        );
+        assert_eq!(
+            call("range", &[Value::Integer(3)]).unwrap().to_string(),
+            "0..<3"
+        );
+        assert!(call(
+            "range",
+            &[Value::Integer(0), Value::Integer(5), Value::Integer(0)]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::interp::{NativeFn, Value};
+use std::rc::Rc;
+fn numbers<'a>(args: &'a [Value], func: &str) -> Result<&'a [Value], String> {
+    let items = match args {
+        [Value::List(items)] => items.as_slice(),
+        _ => args,
+    };
+    if let Some(bad) = items.iter().find(|v| v.as_number().is_none()) {
+        return Err(format!(
+            "{}() requires numbers, got {}",
+            func,
+            bad.type_name()
+        ));
+    }
+    Ok(items)
+}
+fn all_integers(items: &[Value]) -> bool {
+    items.iter().all(|v| matches!(v, Value::Integer(_)))
+}
+fn extreme(args: &[Value], func: &str, pick_later: fn(f64, f64) -> bool) -> Result<Value, String> {
+    let items = numbers(args, func)?;
+    let mut best = items
+        .first()
+        .ok_or_else(|| format!("{}() of empty sequence", func))?;
+    for item in &items[1..] {
+        if pick_later(
+            best.as_number().unwrap_or(0.0),
+            item.as_number().unwrap_or(0.0),
+        ) {
+            best = item;
+        }
+    }
+    Ok(best.clone())
+}
+fn number_arg(args: &[Value], idx: usize, func: &str) -> Result<f64, String> {
+    args.get(idx)
+        .and_then(|v| v.as_number())
+        .ok_or_else(|| format!("{}() requires number", func))
+}
+pub fn math_constants() -> Vec<(&'static str, Value)> {
+    vec![
+        ("PI", Value::Number(std::f64::consts::PI)),
+        ("E", Value::Number(std::f64::consts::E)),
+    ]
+}
+pub fn math_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "min",
+            NativeFn {
+                name: "min".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| extreme(args, "min", |best, next| next < best)),
+            },
+        ),
+        (
+            "max",
+            NativeFn {
+                name: "max".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| extreme(args, "max", |best, next| next > best)),
+            },
+        ),
+        (
+            "sum",
+            NativeFn {
+                name: "sum".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let items = numbers(args, "sum")?;
+                    if all_integers(items) {
+                        let mut total: i64 = 0;
+                        for item in items {
+                            total = total
+                                .checked_add(item.as_integer().unwrap_or(0))
+                                .ok_or("sum() integer overflow")?;
+                        }
+                        return Ok(Value::Integer(total));
+                    }
+                    Ok(Value::Number(
+                        items.iter().filter_map(|v| v.as_number()).sum(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "product",
+            NativeFn {
+                name: "product".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let items = numbers(args, "product")?;
+                    if all_integers(items) {
+                        let mut total: i64 = 1;
+                        for item in items {
+                            total = total
+                                .checked_mul(item.as_integer().unwrap_or(1))
+                                .ok_or("product() integer overflow")?;
+                        }
+                        return Ok(Value::Integer(total));
+                    }
+                    Ok(Value::Number(
+                        items.iter().filter_map(|v| v.as_number()).product(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "avg",
+            NativeFn {
+                name: "avg".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let items = numbers(args, "avg")?;
+                    if items.is_empty() {
+                        return Err("avg() of empty sequence".to_string());
+                    }
+                    let total: f64 = items.iter().filter_map(|v| v.as_number()).sum();
+                    Ok(Value::Number(total / items.len() as f64))
+                }),
+            },
+        ),
+        (
+            "log2",
+            crate::native_fn!(
+                fn log2(x: f64) -> f64 {
+                    x.log2()
+                }
+            ),
+        ),
+        (
+            "log10",
+            crate::native_fn!(
+                fn log10(x: f64) -> f64 {
+                    x.log10()
+                }
+            ),
+        ),
+        (
+            "trunc",
+            crate::native_fn!(
+                fn trunc(x: f64) -> f64 {
+                    x.trunc()
+                }
+            ),
+        ),
+        (
+            "degrees",
+            crate::native_fn!(
+                fn degrees(x: f64) -> f64 {
+                    x.to_degrees()
+                }
+            ),
+        ),
+        (
+            "radians",
+            crate::native_fn!(
+                fn radians(x: f64) -> f64 {
+                    x.to_radians()
+                }
+            ),
+        ),
+        (
+            "atan2",
+            crate::native_fn!(
+                fn atan2(y: f64, x: f64) -> f64 {
+                    y.atan2(x)
+                }
+            ),
+        ),
+        (
+            "hypot",
+            crate::native_fn!(
+                fn hypot(y: f64, x: f64) -> f64 {
+                    y.hypot(x)
+                }
+            ),
+        ),
+        (
+            "clamp",
+            NativeFn {
+                name: "clamp".to_string(),
+                arity: Some(3),
+                func: Rc::new(|_ctx, args| {
+                    if let [Value::Integer(x), Value::Integer(lo), Value::Integer(hi)] = args {
+                        if lo > hi {
+                            return Err(format!("clamp(): lower bound {} above {}", lo, hi));
+                        }
+                        return Ok(Value::Integer(*x.max(lo).min(hi)));
+                    }
+                    let x = number_arg(args, 0, "clamp")?;
+                    let lo = number_arg(args, 1, "clamp")?;
+                    let hi = number_arg(args, 2, "clamp")?;
+                    if lo > hi {
+                        return Err(format!("clamp(): lower bound {} above {}", lo, hi));
+                    }
+                    Ok(Value::Number(x.max(lo).min(hi)))
+                }),
+            },
+        ),
+        (
+            "sign",
+            NativeFn {
+                name: "sign".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| match &args[0] {
+                    Value::Integer(n) => Ok(Value::Integer(n.signum())),
+                    _ => {
+                        let x = number_arg(args, 0, "sign")?;
+                        Ok(Value::Number(if x == 0.0 { 0.0 } else { x.signum() }))
+                    }
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    #[test]
+    fn test_aggregates() {
+        let list = Value::List(vec![
+            Value::Integer(4),
+            Value::Integer(1),
+            Value::Integer(7),
+        ]);
+        assert_eq!(
+            call("min", std::slice::from_ref(&list)).unwrap(),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            call("max", std::slice::from_ref(&list)).unwrap(),
+            Value::Integer(7)
+        );
+        assert_eq!(
+            call("sum", std::slice::from_ref(&list)).unwrap(),
+            Value::Integer(12)
+        );
+        assert_eq!(
+            call("product", std::slice::from_ref(&list)).unwrap(),
+            Value::Integer(28)
+        );
+        assert_eq!(call("avg", &[list]).unwrap(), Value::Number(4.0));
+        assert_eq!(
+            call("max", &[Value::Integer(2), Value::Number(2.5)]).unwrap(),
+            Value::Number(2.5)
+        );
+        assert_eq!(call("sum", &[]).unwrap(), Value::Integer(0));
+        assert!(call("min", &[Value::List(vec![])]).is_err());
+        assert!(call("sum", &[Value::String("x".into())]).is_err());
+    }
+    #[test]
+    fn test_scalar_functions() {
+        let n = |x: f64| Value::Number(x);
+        assert_eq!(call("log2", &[n(8.0)]).unwrap(), n(3.0));
+        assert_eq!(call("log10", &[n(1000.0)]).unwrap(), n(3.0));
+        assert_eq!(call("hypot", &[n(3.0), n(4.0)]).unwrap(), n(5.0));
+        assert_eq!(call("atan2", &[n(0.0), n(1.0)]).unwrap(), n(0.0));
+        assert_eq!(call("trunc", &[n(-2.7)]).unwrap(), n(-2.0));
+        assert_eq!(
+            call("degrees", &[n(std::f64::consts::PI)]).unwrap(),
+            n(180.0)
+        );
+        assert_eq!(
+            call(
+                "clamp",
+                &[Value::Integer(12), Value::Integer(0), Value::Integer(10)]
+            )
+            .unwrap(),
+            Value::Integer(10)
+        );
+        assert_eq!(call("clamp", &[n(-0.5), n(0.0), n(1.0)]).unwrap(), n(0.0));
+        assert_eq!(
+            call("sign", &[Value::Integer(-4)]).unwrap(),
+            Value::Integer(-1)
+        );
+        assert_eq!(call("sign", &[n(0.0)]).unwrap(), n(0.0));
+        assert!(call("clamp", &[n(1.0), n(2.0), n(0.0)]).is_err());
+    }
+}
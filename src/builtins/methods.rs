@@ -0,0 +1,376 @@
+use crate::interp::Value;
+use std::collections::HashMap;
+fn index_arg(args: &[Value], method: &str, len: usize) -> Result<usize, String> {
+    let idx = args
+        .first()
+        .and_then(|v| v.as_integer())
+        .ok_or_else(|| format!("{}() requires integer index", method))?;
+    if idx < 0 || idx as usize > len {
+        return Err(format!(
+            "{}(): index {} out of bounds (len {})",
+            method, idx, len
+        ));
+    }
+    Ok(idx as usize)
+}
+fn string_arg<'a>(args: &'a [Value], idx: usize, method: &str) -> Result<&'a str, String> {
+    args.get(idx)
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| format!("{}() requires string argument", method))
+}
+fn count_arg(args: &[Value], idx: usize, method: &str) -> Result<usize, String> {
+    match args.get(idx).and_then(|v| v.as_integer()) {
+        Some(n) if n >= 0 => Ok(n as usize),
+        _ => Err(format!("{}() requires non-negative integer", method)),
+    }
+}
+fn pad(s: &str, args: &[Value], method: &str, left: bool) -> Result<Value, String> {
+    let width = count_arg(args, 0, method)?;
+    let fill = match args.get(1) {
+        Some(v) => {
+            let fill = v
+                .as_string()
+                .ok_or_else(|| format!("{}() requires fill string", method))?;
+            let mut chars = fill.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("{}() fill must be a single character", method)),
+            }
+        }
+        None => ' ',
+    };
+    let missing = width.saturating_sub(s.chars().count());
+    let padding: String = std::iter::repeat_n(fill, missing).collect();
+    Ok(Value::String(if left {
+        format!("{}{}", padding, s).into()
+    } else {
+        format!("{}{}", s, padding).into()
+    }))
+}
+pub fn method_names(receiver: &Value) -> &'static [&'static str] {
+    match receiver.thawed() {
+        Value::List(_) => &[
+            "len", "push", "pop", "insert", "remove", "index_of", "contains", "reverse", "join",
+            "flatten", "first", "last", "clear",
+        ],
+        Value::Range(_) => &["len", "contains", "to_list"],
+        Value::String(_) => &[
+            "len",
+            "upper",
+            "lower",
+            "trim",
+            "split",
+            "replace",
+            "starts_with",
+            "ends_with",
+            "contains",
+            "find",
+            "repeat",
+            "pad_left",
+            "pad_right",
+            "chars",
+            "lines",
+            "substr",
+            "to_int",
+            "to_float",
+        ],
+        Value::Map(_) => &[
+            "keys", "values", "has", "remove", "get", "merge", "entries", "clear",
+        ],
+        _ => &[],
+    }
+}
+pub fn call_method(receiver: &Value, method: &str, args: &[Value]) -> Result<Value, String> {
+    match (receiver, method) {
+        (Value::List(arr), "len") => Ok(Value::Integer(arr.len() as i64)),
+        (Value::List(arr), "push") if !args.is_empty() => {
+            let mut new_arr = arr.clone();
+            for arg in args {
+                new_arr.push(arg.clone());
+            }
+            Ok(Value::List(new_arr))
+        }
+        (Value::List(arr), "pop") => {
+            let mut new_arr = arr.clone();
+            let val = new_arr.pop().unwrap_or(Value::Nil);
+            Ok(val)
+        }
+        (Value::List(arr), "insert") if args.len() == 2 => {
+            let idx = index_arg(args, "insert", arr.len())?;
+            let mut new_arr = arr.clone();
+            new_arr.insert(idx, args[1].clone());
+            Ok(Value::List(new_arr))
+        }
+        (Value::List(arr), "remove") if args.len() == 1 => {
+            let idx = index_arg(args, "remove", arr.len())?;
+            if idx == arr.len() {
+                return Err(format!(
+                    "remove(): index {} out of bounds (len {})",
+                    idx,
+                    arr.len()
+                ));
+            }
+            let mut new_arr = arr.clone();
+            new_arr.remove(idx);
+            Ok(Value::List(new_arr))
+        }
+        (Value::List(arr), "index_of") if args.len() == 1 => Ok(Value::Integer(
+            arr.iter()
+                .position(|v| *v == args[0])
+                .map(|i| i as i64)
+                .unwrap_or(-1),
+        )),
+        (Value::List(arr), "contains") if args.len() == 1 => {
+            Ok(Value::Bool(arr.contains(&args[0])))
+        }
+        (Value::List(arr), "reverse") => Ok(Value::List(arr.iter().rev().cloned().collect())),
+        (Value::List(arr), "join") if args.len() <= 1 => {
+            let sep = args
+                .first()
+                .map(|v| v.to_display_string())
+                .unwrap_or_default();
+            let parts: Vec<_> = arr.iter().map(|v| v.to_display_string()).collect();
+            Ok(Value::String(parts.join(&sep).into()))
+        }
+        (Value::List(arr), "flatten") => {
+            let mut flat = Vec::with_capacity(arr.len());
+            for item in arr {
+                match item {
+                    Value::List(inner) => flat.extend(inner.iter().cloned()),
+                    other => flat.push(other.clone()),
+                }
+            }
+            Ok(Value::List(flat))
+        }
+        (Value::List(arr), "first") => Ok(arr.first().cloned().unwrap_or(Value::Nil)),
+        (Value::List(arr), "last") => Ok(arr.last().cloned().unwrap_or(Value::Nil)),
+        (Value::List(_), "clear") => Ok(Value::List(Vec::new())),
+        (Value::Range(r), "len") => Ok(Value::Integer(r.len() as i64)),
+        (Value::Range(r), "contains") if args.len() == 1 => Ok(Value::Bool(
+            args[0].as_integer().is_some_and(|n| r.contains(n)),
+        )),
+        (Value::Range(r), "to_list") => Ok(Value::List(r.iter().map(Value::Integer).collect())),
+        (Value::String(s), "len") => Ok(Value::Integer(s.chars().count() as i64)),
+        (Value::String(s), "upper") => Ok(Value::String(s.to_uppercase().into())),
+        (Value::String(s), "lower") => Ok(Value::String(s.to_lowercase().into())),
+        (Value::String(s), "trim") => Ok(Value::String(s.trim().into())),
+        (Value::String(s), "split") if !args.is_empty() => {
+            let sep = args[0].to_display_string();
+            let parts: Vec<_> = s.split(&sep).map(|p| Value::String(p.into())).collect();
+            Ok(Value::List(parts))
+        }
+        (Value::String(s), "replace") if args.len() == 2 => {
+            let from = string_arg(args, 0, "replace")?;
+            let to = string_arg(args, 1, "replace")?;
+            Ok(Value::String(s.replace(from, to).into()))
+        }
+        (Value::String(s), "starts_with") if args.len() == 1 => Ok(Value::Bool(
+            s.starts_with(string_arg(args, 0, "starts_with")?),
+        )),
+        (Value::String(s), "ends_with") if args.len() == 1 => {
+            Ok(Value::Bool(s.ends_with(string_arg(args, 0, "ends_with")?)))
+        }
+        (Value::String(s), "contains") if args.len() == 1 => {
+            Ok(Value::Bool(s.contains(string_arg(args, 0, "contains")?)))
+        }
+        (Value::String(s), "find") if args.len() == 1 => {
+            let needle = string_arg(args, 0, "find")?;
+            Ok(Value::Integer(
+                s.find(needle)
+                    .map(|byte| s[..byte].chars().count() as i64)
+                    .unwrap_or(-1),
+            ))
+        }
+        (Value::String(s), "repeat") if args.len() == 1 => Ok(Value::String(
+            s.repeat(count_arg(args, 0, "repeat")?).into(),
+        )),
+        (Value::String(s), "pad_left") if matches!(args.len(), 1 | 2) => {
+            pad(s, args, "pad_left", true)
+        }
+        (Value::String(s), "pad_right") if matches!(args.len(), 1 | 2) => {
+            pad(s, args, "pad_right", false)
+        }
+        (Value::String(s), "chars") => Ok(Value::List(
+            s.chars()
+                .map(|c| Value::String(c.to_string().into()))
+                .collect(),
+        )),
+        (Value::String(s), "lines") => Ok(Value::List(
+            s.lines().map(|l| Value::String(l.into())).collect(),
+        )),
+        (Value::String(s), "substr") if matches!(args.len(), 1 | 2) => {
+            let start = count_arg(args, 0, "substr")?;
+            let chars = s.chars().skip(start);
+            Ok(Value::String(
+                match args.get(1) {
+                    Some(_) => chars
+                        .take(count_arg(args, 1, "substr")?)
+                        .collect::<String>(),
+                    None => chars.collect::<String>(),
+                }
+                .into(),
+            ))
+        }
+        (Value::String(s), "to_int") => Ok(s
+            .trim()
+            .parse::<i64>()
+            .map(Value::Integer)
+            .unwrap_or(Value::Nil)),
+        (Value::String(s), "to_float") => Ok(s
+            .trim()
+            .parse::<f64>()
+            .map(Value::Number)
+            .unwrap_or(Value::Nil)),
+        (Value::Map(m), "keys") => Ok(Value::List(
+            m.keys().map(|k| Value::String(k.as_str().into())).collect(),
+        )),
+        (Value::Map(m), "values") => Ok(Value::List(m.values().cloned().collect())),
+        (Value::Map(m), "has") if args.len() == 1 => {
+            Ok(Value::Bool(m.contains_key(&args[0].to_display_string())))
+        }
+        (Value::Map(m), "remove") if args.len() == 1 => {
+            let mut new_map = m.clone();
+            new_map.remove(&args[0].to_display_string());
+            Ok(Value::Map(new_map))
+        }
+        (Value::Map(m), "get") if matches!(args.len(), 1 | 2) => Ok(m
+            .get(&args[0].to_display_string())
+            .cloned()
+            .unwrap_or_else(|| args.get(1).cloned().unwrap_or(Value::Nil))),
+        (Value::Map(m), "merge") if args.len() == 1 => match &args[0] {
+            Value::Map(other) => {
+                let mut new_map = m.clone();
+                new_map.extend(other.iter().map(|(k, v)| (k.clone(), v.clone())));
+                Ok(Value::Map(new_map))
+            }
+            other => Err(format!("merge() requires map, got {}", other.type_name())),
+        },
+        (Value::Map(m), "entries") => {
+            let mut keys: Vec<_> = m.keys().collect();
+            keys.sort();
+            Ok(Value::List(
+                keys.into_iter()
+                    .map(|k| Value::Tuple(vec![Value::String(k.as_str().into()), m[k].clone()]))
+                    .collect(),
+            ))
+        }
+        (Value::Map(_), "clear") => Ok(Value::Map(HashMap::new())),
+        _ => Err(format!(
+            "No method '{}' on {}{}",
+            method,
+            receiver.type_name(),
+            crate::error::suggest(method, method_names(receiver).iter().copied())
+        )),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn ints(values: &[i64]) -> Value {
+        Value::List(values.iter().copied().map(Value::Integer).collect())
+    }
+    #[test]
+    fn test_list_methods() {
+        let xs = ints(&[1, 2, 3]);
+        let call = |method: &str, args: &[Value]| call_method(&xs, method, args).unwrap();
+        assert_eq!(
+            call("insert", &[Value::Integer(1), Value::Integer(9)]),
+            ints(&[1, 9, 2, 3])
+        );
+        assert_eq!(call("remove", &[Value::Integer(0)]), ints(&[2, 3]));
+        assert_eq!(call("index_of", &[Value::Integer(3)]), Value::Integer(2));
+        assert_eq!(call("index_of", &[Value::Integer(7)]), Value::Integer(-1));
+        assert_eq!(call("contains", &[Value::Integer(2)]), Value::Bool(true));
+        assert_eq!(call("reverse", &[]), ints(&[3, 2, 1]));
+        assert_eq!(
+            call("join", &[Value::String("-".into())]),
+            Value::String("1-2-3".into())
+        );
+        assert_eq!(call("first", &[]), Value::Integer(1));
+        assert_eq!(call("last", &[]), Value::Integer(3));
+        assert_eq!(call("clear", &[]), ints(&[]));
+        let nested = Value::List(vec![ints(&[1, 2]), Value::Integer(3), ints(&[])]);
+        assert_eq!(call_method(&nested, "flatten", &[]).unwrap(), xs);
+        assert!(call_method(&xs, "remove", &[Value::Integer(3)]).is_err());
+        assert!(call_method(&xs, "shout", &[]).is_err());
+        let typo = call_method(&xs, "revrese", &[]).unwrap_err();
+        assert!(typo.ends_with("(did you mean 'reverse'?)"));
+    }
+    #[test]
+    fn test_string_methods() {
+        let text = Value::String("héllo world".into());
+        let s = |v: &str| Value::String(v.into());
+        let call = |method: &str, args: &[Value]| call_method(&text, method, args).unwrap();
+        assert_eq!(call("replace", &[s("o"), s("0")]), s("héll0 w0rld"));
+        assert_eq!(call("starts_with", &[s("hé")]), Value::Bool(true));
+        assert_eq!(call("ends_with", &[s("x")]), Value::Bool(false));
+        assert_eq!(call("contains", &[s("lo w")]), Value::Bool(true));
+        assert_eq!(call("find", &[s("world")]), Value::Integer(6));
+        assert_eq!(call("find", &[s("z")]), Value::Integer(-1));
+        assert_eq!(
+            call("substr", &[Value::Integer(1), Value::Integer(4)]),
+            s("éllo")
+        );
+        assert_eq!(
+            call_method(&s("ab"), "repeat", &[Value::Integer(3)]).unwrap(),
+            s("ababab")
+        );
+        assert_eq!(
+            call_method(&s("7"), "pad_left", &[Value::Integer(3), s("0")]).unwrap(),
+            s("007")
+        );
+        assert_eq!(
+            call_method(&s("ab"), "pad_right", &[Value::Integer(4)]).unwrap(),
+            s("ab  ")
+        );
+        assert_eq!(
+            call_method(&s("a\nb"), "lines", &[]).unwrap(),
+            Value::List(vec![s("a"), s("b")])
+        );
+        assert_eq!(
+            call_method(&s("hé"), "chars", &[]).unwrap(),
+            Value::List(vec![s("h"), s("é")])
+        );
+        assert_eq!(
+            call_method(&s(" 42 "), "to_int", &[]).unwrap(),
+            Value::Integer(42)
+        );
+        assert_eq!(call_method(&s("x"), "to_int", &[]).unwrap(), Value::Nil);
+        assert_eq!(
+            call_method(&s("2.5"), "to_float", &[]).unwrap(),
+            Value::Number(2.5)
+        );
+    }
+    #[test]
+    fn test_map_methods() {
+        let s = |v: &str| Value::String(v.into());
+        let mut base = HashMap::new();
+        base.insert("a".to_string(), Value::Integer(1));
+        base.insert("b".to_string(), Value::Integer(2));
+        let m = Value::Map(base);
+        assert_eq!(
+            call_method(&m, "has", &[s("a")]).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            call_method(&m, "get", &[s("z"), Value::Integer(0)]).unwrap(),
+            Value::Integer(0)
+        );
+        let mut extra = HashMap::new();
+        extra.insert("b".to_string(), Value::Integer(5));
+        let merged = call_method(&m, "merge", &[Value::Map(extra)]).unwrap();
+        assert_eq!(
+            call_method(&merged, "get", &[s("b")]).unwrap(),
+            Value::Integer(5)
+        );
+        let removed = call_method(&merged, "remove", &[s("a")]).unwrap();
+        assert_eq!(
+            call_method(&removed, "entries", &[]).unwrap(),
+            Value::List(vec![Value::Tuple(vec![s("b"), Value::Integer(5)])])
+        );
+        match call_method(&m, "clear", &[]).unwrap() {
+            Value::Map(cleared) => assert!(cleared.is_empty()),
+            other => panic!("expected map, got {}", other),
+        }
+    }
+}
@@ -1,4 +1,110 @@
-use crate::interp::{NativeFn, Value};
+mod args;
+mod assert;
+mod binary;
+#[cfg(feature = "io-builtins")]
+mod env;
+#[cfg(feature = "io-builtins")]
+mod fs;
+mod io;
+mod list;
+mod math;
+mod methods;
+#[cfg(feature = "io-builtins")]
+mod process;
+mod random;
+mod re;
+mod sandbox;
+mod sys;
+mod time;
+mod value;
+use crate::interp::{with_stdio, NativeFn, Stdio, Value};
+pub use args::{arg, check_arity, coded_error, FromArg, IntoNativeResult, IntoValue};
+pub use assert::assert_builtins;
+pub use binary::binary_builtins;
+#[cfg(feature = "io-builtins")]
+pub use env::env_builtins;
+#[cfg(feature = "io-builtins")]
+pub use fs::fs_builtins;
+pub use io::io_builtins;
+pub use list::list_builtins;
+pub use math::{math_builtins, math_constants};
+pub use methods::call_method;
+#[cfg(feature = "io-builtins")]
+pub use process::process_builtins;
+pub use random::{random_builtins, random_f64};
+pub use re::re_builtins;
+pub use sandbox::{SandboxPolicy, LOCKED};
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::OnceLock;
+pub(crate) use sys::coded_message;
+pub use sys::{
+    callback_error, native_error, script_args, set_script_args, sys_builtins,
+};
+pub use time::{sleep, time_builtins};
+pub use value::value_builtins;
+pub fn is_builtin(name: &str) -> bool {
+    static NAMES: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    NAMES
+        .get_or_init(|| {
+            let mut names: HashSet<&'static str> =
+                crate::vm::BUILTIN_NAMES.iter().copied().collect();
+            names.extend(stdlib().into_iter().map(|(n, _)| n));
+            names.extend(math_constants().into_iter().map(|(n, _)| n));
+            names
+        })
+        .contains(name)
+}
+pub fn stdlib() -> Vec<(&'static str, NativeFn)> {
+    let mut all = io_builtins();
+    #[cfg(feature = "io-builtins")]
+    all.extend(fs_builtins());
+    #[cfg(feature = "io-builtins")]
+    all.extend(env_builtins());
+    all.extend(sys_builtins());
+    #[cfg(feature = "io-builtins")]
+    all.extend(process_builtins());
+    all.extend(re_builtins());
+    all.extend(time_builtins());
+    all.extend(random_builtins());
+    all.extend(list_builtins());
+    all.extend(math_builtins());
+    all.extend(binary_builtins());
+    all.extend(assert_builtins());
+    all.extend(value_builtins());
+    all
+}
+#[cfg(test)]
+pub(crate) struct TestContext;
+#[cfg(test)]
+impl crate::interp::NativeContext for TestContext {
+    fn call(&mut self, callee: &Value, args: &[Value]) -> Result<Value, String> {
+        match callee {
+            Value::NativeFunction(native) => (native.func)(self, args),
+            other => Err(format!("cannot call {}", other.type_name())),
+        }
+    }
+    fn policy(&self) -> &SandboxPolicy {
+        static OPEN: SandboxPolicy = SandboxPolicy::permissive();
+        &OPEN
+    }
+}
+#[cfg(test)]
+pub(crate) fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let (_, native) = stdlib()
+        .into_iter()
+        .find(|(n, _)| *n == name)
+        .expect("builtin registered");
+    (native.func)(&mut TestContext, args)
+}
+#[cfg(all(test, feature = "io-builtins"))]
+pub(crate) struct SandboxedContext;
+#[cfg(all(test, feature = "io-builtins"))]
+impl crate::interp::NativeContext for SandboxedContext {
+    fn call(&mut self, callee: &Value, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("cannot call {}", callee.type_name()))
+    }
+}
 pub fn get_builtins() -> Vec<(&'static str, NativeFn)> {
     vec![
         (
@@ -6,11 +112,11 @@ pub fn get_builtins() -> Vec<(&'static str, NativeFn)> {
             NativeFn {
                 name: "log".to_string(),
                 arity: None,
-                func: |args| {
+                func: Rc::new(|ctx, args| {
                     let output: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
-                    println!("{}", output.join(" "));
+                    with_stdio(ctx, |io| io.write(&format!("{}\n", output.join(" "))))?;
                     Ok(Value::Nil)
-                },
+                }),
             },
         ),
         (
@@ -18,13 +124,10 @@ pub fn get_builtins() -> Vec<(&'static str, NativeFn)> {
             NativeFn {
                 name: "get".to_string(),
                 arity: Some(0),
-                func: |_args| {
-                    let mut line = String::new();
-                    std::io::stdin()
-                        .read_line(&mut line)
-                        .map_err(|e| e.to_string())?;
-                    Ok(Value::String(line.trim().to_string()))
-                },
+                func: Rc::new(|ctx, _args| {
+                    let line = with_stdio(ctx, Stdio::read_line)?.unwrap_or_default();
+                    Ok(Value::String(line.trim().into()))
+                }),
             },
         ),
         (
@@ -32,12 +135,12 @@ pub fn get_builtins() -> Vec<(&'static str, NativeFn)> {
             NativeFn {
                 name: "sqrt".to_string(),
                 arity: Some(1),
-                func: |args| {
+                func: Rc::new(|_ctx, args| {
                     let n = args[0]
                         .as_number()
                         .ok_or("sqrt() requires numeric argument")?;
                     Ok(Value::Number(n.sqrt()))
-                },
+                }),
             },
         ),
         (
@@ -45,11 +148,11 @@ pub fn get_builtins() -> Vec<(&'static str, NativeFn)> {
             NativeFn {
                 name: "abs".to_string(),
                 arity: Some(1),
-                func: |args| match &args[0] {
+                func: Rc::new(|_ctx, args| match &args[0] {
                     Value::Number(n) => Ok(Value::Number(n.abs())),
                     Value::Integer(n) => Ok(Value::Integer(n.abs())),
                     _ => Err("abs() requires numeric argument".to_string()),
-                },
+                }),
             },
         ),
         (
@@ -57,8 +160,21 @@ pub fn get_builtins() -> Vec<(&'static str, NativeFn)> {
             NativeFn {
                 name: "typeof".to_string(),
                 arity: Some(1),
-                func: |args| Ok(Value::String(args[0].type_name().to_string())),
+                func: Rc::new(|_ctx, args| Ok(Value::String(args[0].type_name().into()))),
             },
         ),
     ]
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    #[test]
+    fn test_stdlib_names_are_unique() {
+        let mut seen = HashSet::new();
+        let names = stdlib().into_iter().map(|(name, _)| name);
+        let constants = math_constants().into_iter().map(|(name, _)| name);
+        let duplicates: Vec<_> = names.chain(constants).filter(|n| !seen.insert(*n)).collect();
+        assert!(duplicates.is_empty(), "{:?}", duplicates);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::interp::{with_stdio, NativeContext, NativeFn, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+fn build_command(ctx: &dyn NativeContext, func: &str, args: &[Value]) -> Result<Command, String> {
+    ctx.policy().check_exec(func)?;
+    if args.is_empty() || args.len() > 2 {
+        return Err(format!(
+            "{}() expected 1 or 2 arguments, got {}",
+            func,
+            args.len()
+        ));
+    }
+    let program = args[0]
+        .as_string()
+        .ok_or_else(|| format!("{}() requires command string", func))?;
+    let mut command = Command::new(program);
+    match args.get(1) {
+        None | Some(Value::Nil) => {}
+        Some(Value::List(items)) => {
+            command.args(items.iter().map(|a| a.to_display_string()));
+        }
+        Some(other) => {
+            return Err(format!(
+                "{}() requires argument list, got {}",
+                func,
+                other.type_name()
+            ))
+        }
+    }
+    Ok(command)
+}
+fn exit_code(status: std::process::ExitStatus) -> Value {
+    status
+        .code()
+        .map(|c| Value::Integer(c as i64))
+        .unwrap_or(Value::Nil)
+}
+fn forward_lines(
+    pipe: impl Read + Send + 'static,
+    to_stderr: bool,
+    lines: Sender<(bool, Vec<u8>)>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if lines.send((to_stderr, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+pub fn process_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "exec",
+            NativeFn {
+                name: "exec".to_string(),
+                arity: None,
+                func: Rc::new(|ctx, args| {
+                    let mut command = build_command(ctx, "exec", args)?;
+                    let output = command
+                        .stdin(Stdio::null())
+                        .output()
+                        .map_err(|e| format!("exec('{}'): {}", args[0], e))?;
+                    let mut result = HashMap::new();
+                    result.insert(
+                        "stdout".to_string(),
+                        Value::String(String::from_utf8_lossy(&output.stdout).into_owned().into()),
+                    );
+                    result.insert(
+                        "stderr".to_string(),
+                        Value::String(String::from_utf8_lossy(&output.stderr).into_owned().into()),
+                    );
+                    result.insert("code".to_string(), exit_code(output.status));
+                    Ok(Value::Map(result))
+                }),
+            },
+        ),
+        (
+            "exec_stream",
+            NativeFn {
+                name: "exec_stream".to_string(),
+                arity: None,
+                func: Rc::new(|ctx, args| {
+                    let mut command = build_command(ctx, "exec_stream", args)?;
+                    let mut child = command
+                        .stdin(Stdio::inherit())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped())
+                        .spawn()
+                        .map_err(|e| format!("exec_stream('{}'): {}", args[0], e))?;
+                    let (sender, lines) = mpsc::channel();
+                    let readers = [
+                        child
+                            .stdout
+                            .take()
+                            .map(|out| forward_lines(out, false, sender.clone())),
+                        child
+                            .stderr
+                            .take()
+                            .map(|err| forward_lines(err, true, sender)),
+                    ];
+                    let written = with_stdio(ctx, |io| {
+                        lines.iter().try_for_each(|(to_stderr, line)| {
+                            let text = String::from_utf8_lossy(&line);
+                            if to_stderr {
+                                io.write_error(&text)
+                            } else {
+                                io.write(&text)
+                            }
+                        })
+                    });
+                    drop(lines);
+                    for reader in readers.into_iter().flatten() {
+                        let _ = reader.join();
+                    }
+                    let status = child
+                        .wait()
+                        .map_err(|e| format!("exec_stream('{}'): {}", args[0], e))?;
+                    written?;
+                    Ok(exit_code(status))
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    #[cfg(unix)]
+    #[test]
+    fn test_exec_and_sandbox() {
+        let args = [
+            Value::String("sh".into()),
+            Value::List(vec![
+                Value::String("-c".into()),
+                Value::String("echo out; echo err >&2; exit 4".into()),
+            ]),
+        ];
+        match call("exec", &args).unwrap() {
+            Value::Map(result) => {
+                assert_eq!(result["stdout"], Value::String("out\n".into()));
+                assert_eq!(result["stderr"], Value::String("err\n".into()));
+                assert_eq!(result["code"], Value::Integer(4));
+            }
+            other => panic!("expected map, got {}", other),
+        }
+        let (_, native) = process_builtins()
+            .into_iter()
+            .find(|(n, _)| *n == "exec_stream")
+            .unwrap();
+        let denied = (native.func)(&mut crate::builtins::SandboxedContext, &args);
+        assert!(denied.unwrap_err().contains("sandbox"));
+    }
+}
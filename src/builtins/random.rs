@@ -0,0 +1,170 @@
+use crate::interp::{NativeFn, Value};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+thread_local! {
+    static STATE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+fn initial_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    nanos ^ (u64::from(std::process::id()) << 32)
+}
+pub fn seed(value: u64) {
+    STATE.with(|state| state.set(Some(value)));
+}
+pub fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let current = state.get().unwrap_or_else(initial_seed);
+        let next = current.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state.set(Some(next));
+        let mut z = next;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    })
+}
+pub fn random_f64() -> f64 {
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+fn below(bound: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+        let r = next_u64();
+        if r < zone {
+            return r % bound;
+        }
+    }
+}
+fn list_arg<'a>(args: &'a [Value], func: &str) -> Result<&'a Vec<Value>, String> {
+    match &args[0] {
+        Value::List(items) => Ok(items),
+        other => Err(format!(
+            "{}() requires list, got {}",
+            func,
+            other.type_name()
+        )),
+    }
+}
+pub fn random_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "rnd",
+            NativeFn {
+                name: "rnd".to_string(),
+                arity: Some(0),
+                func: Rc::new(|_ctx, _args| Ok(Value::Number(random_f64()))),
+            },
+        ),
+        (
+            "rnd_seed",
+            NativeFn {
+                name: "rnd_seed".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let n = args[0].as_number().ok_or("rnd_seed() requires number")?;
+                    seed(n as i64 as u64);
+                    Ok(Value::Nil)
+                }),
+            },
+        ),
+        (
+            "rnd_int",
+            NativeFn {
+                name: "rnd_int".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let lo = args[0].as_number().ok_or("rnd_int() requires numbers")? as i64;
+                    let hi = args[1].as_number().ok_or("rnd_int() requires numbers")? as i64;
+                    if lo > hi {
+                        return Err(format!("rnd_int(): empty range {}..{}", lo, hi));
+                    }
+                    let span = hi.wrapping_sub(lo) as u64;
+                    let offset = if span == u64::MAX {
+                        next_u64()
+                    } else {
+                        below(span + 1)
+                    };
+                    Ok(Value::Integer(lo.wrapping_add(offset as i64)))
+                }),
+            },
+        ),
+        (
+            "rnd_range",
+            NativeFn {
+                name: "rnd_range".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let lo = args[0].as_number().ok_or("rnd_range() requires numbers")?;
+                    let hi = args[1].as_number().ok_or("rnd_range() requires numbers")?;
+                    if lo > hi {
+                        return Err(format!("rnd_range(): empty range {}..{}", lo, hi));
+                    }
+                    Ok(Value::Number(lo + (hi - lo) * random_f64()))
+                }),
+            },
+        ),
+        (
+            "choice",
+            NativeFn {
+                name: "choice".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let items = list_arg(args, "choice")?;
+                    if items.is_empty() {
+                        return Err("choice() on empty list".to_string());
+                    }
+                    Ok(items[below(items.len() as u64) as usize].clone())
+                }),
+            },
+        ),
+        (
+            "shuffle",
+            NativeFn {
+                name: "shuffle".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let mut items = list_arg(args, "shuffle")?.clone();
+                    for i in (1..items.len()).rev() {
+                        let j = below(i as u64 + 1) as usize;
+                        items.swap(i, j);
+                    }
+                    Ok(Value::List(items))
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    #[test]
+    fn test_seeded_sequence_repeats() {
+        seed(42);
+        let first: Vec<u64> = (0..4).map(|_| next_u64()).collect();
+        seed(42);
+        let second: Vec<u64> = (0..4).map(|_| next_u64()).collect();
+        assert_eq!(first, second);
+        assert_ne!(first[0], first[1]);
+        for _ in 0..200 {
+            match call("rnd_int", &[Value::Integer(-2), Value::Integer(2)]).unwrap() {
+                Value::Integer(n) => assert!((-2..=2).contains(&n)),
+                other => panic!("expected int, got {}", other),
+            }
+            let r = random_f64();
+            assert!((0.0..1.0).contains(&r));
+        }
+        let list = Value::List((1..=10).map(Value::Integer).collect());
+        match call("shuffle", std::slice::from_ref(&list)).unwrap() {
+            Value::List(mut items) => {
+                items.sort_by_key(|v| v.as_number().unwrap() as i64);
+                assert_eq!(Value::List(items), list);
+            }
+            other => panic!("expected list, got {}", other),
+        }
+        assert!(call("choice", &[Value::List(vec![])]).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::interp::{NativeFn, Value};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+const CACHE_CAPACITY: usize = 64;
+thread_local! {
+    static CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+fn compiled(pattern: &Value, func: &str) -> Result<Regex, String> {
+    let pattern = pattern
+        .as_string()
+        .ok_or_else(|| format!("{}() requires pattern string", func))?;
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some(re) = cache.get(pattern) {
+            return Ok(re.clone());
+        }
+        let re = Regex::new(pattern).map_err(|e| format!("{}(): invalid pattern: {}", func, e))?;
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(pattern.to_string(), re.clone());
+        Ok(re)
+    })
+}
+fn text_arg<'a>(args: &'a [Value], idx: usize, func: &str) -> Result<&'a str, String> {
+    args[idx]
+        .as_string()
+        .ok_or_else(|| format!("{}() requires text string", func))
+}
+pub fn re_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "re_match",
+            NativeFn {
+                name: "re_match".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let re = compiled(&args[0], "re_match")?;
+                    Ok(Value::Bool(re.is_match(text_arg(args, 1, "re_match")?)))
+                }),
+            },
+        ),
+        (
+            "re_find_all",
+            NativeFn {
+                name: "re_find_all".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let re = compiled(&args[0], "re_find_all")?;
+                    let text = text_arg(args, 1, "re_find_all")?;
+                    Ok(Value::List(
+                        re.find_iter(text)
+                            .map(|m| Value::String(m.as_str().into()))
+                            .collect(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "re_replace",
+            NativeFn {
+                name: "re_replace".to_string(),
+                arity: Some(3),
+                func: Rc::new(|_ctx, args| {
+                    let re = compiled(&args[0], "re_replace")?;
+                    let text = text_arg(args, 1, "re_replace")?;
+                    let replacement = text_arg(args, 2, "re_replace")?;
+                    Ok(Value::String(
+                        re.replace_all(text, replacement).into_owned().into(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "re_captures",
+            NativeFn {
+                name: "re_captures".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let re = compiled(&args[0], "re_captures")?;
+                    let text = text_arg(args, 1, "re_captures")?;
+                    let caps = match re.captures(text) {
+                        Some(caps) => caps,
+                        None => return Ok(Value::Nil),
+                    };
+                    let group = |m: Option<regex::Match>| {
+                        m.map(|m| Value::String(m.as_str().into()))
+                            .unwrap_or(Value::Nil)
+                    };
+                    let mut groups = HashMap::new();
+                    for i in 0..caps.len() {
+                        groups.insert(i.to_string(), group(caps.get(i)));
+                    }
+                    for name in re.capture_names().flatten() {
+                        groups.insert(name.to_string(), group(caps.name(name)));
+                    }
+                    Ok(Value::Map(groups))
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::call;
+    fn strs(args: &[&str]) -> Vec<Value> {
+        args.iter().map(|a| Value::String((*a).into())).collect()
+    }
+    #[test]
+    fn test_regex_builtins() {
+        assert_eq!(
+            call("re_match", &strs(&[r"^\d+$", "123"])).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            call("re_find_all", &strs(&[r"\d+", "a1b22c333"])).unwrap(),
+            Value::List(vec![
+                Value::String("1".into()),
+                Value::String("22".into()),
+                Value::String("333".into()),
+            ])
+        );
+        assert_eq!(
+            call("re_replace", &strs(&[r"(\w+)@", "bob@x", "$1 at "])).unwrap(),
+            Value::String("bob at x".into())
+        );
+        match call("re_captures", &strs(&[r"(?P<key>\w+)=(\d+)", "n=42"])).unwrap() {
+            Value::Map(caps) => {
+                assert_eq!(caps["key"], Value::String("n".into()));
+                assert_eq!(caps["2"], Value::String("42".into()));
+            }
+            other => panic!("expected map, got {}", other),
+        }
+        assert_eq!(call("re_captures", &strs(&["x", "y"])).unwrap(), Value::Nil);
+        assert!(call("re_match", &strs(&["(", "y"])).is_err());
+    }
+}
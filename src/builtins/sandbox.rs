@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+pub static LOCKED: SandboxPolicy = SandboxPolicy::locked();
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxPolicy {
+    pub read: Option<Vec<PathBuf>>,
+    pub write: Option<Vec<PathBuf>>,
+    pub net: bool,
+    pub exec: bool,
+    pub env: bool,
+}
+impl SandboxPolicy {
+    pub const fn permissive() -> Self {
+        Self {
+            read: None,
+            write: None,
+            net: true,
+            exec: true,
+            env: true,
+        }
+    }
+    pub const fn locked() -> Self {
+        Self {
+            read: Some(Vec::new()),
+            write: Some(Vec::new()),
+            net: false,
+            exec: false,
+            env: false,
+        }
+    }
+    pub fn allow_read(mut self, root: impl Into<PathBuf>) -> Self {
+        self.read.get_or_insert_with(Vec::new).push(root.into());
+        self
+    }
+    pub fn allow_write(mut self, root: impl Into<PathBuf>) -> Self {
+        self.write.get_or_insert_with(Vec::new).push(root.into());
+        self
+    }
+    pub fn allow_net(mut self, allowed: bool) -> Self {
+        self.net = allowed;
+        self
+    }
+    pub fn allow_exec(mut self, allowed: bool) -> Self {
+        self.exec = allowed;
+        self
+    }
+    pub fn allow_env(mut self, allowed: bool) -> Self {
+        self.env = allowed;
+        self
+    }
+    pub fn is_permissive(&self) -> bool {
+        *self == Self::permissive()
+    }
+    pub fn check_read(&self, func: &str, path: &str) -> Result<(), String> {
+        check_path(self.read.as_deref(), "read", func, path)
+    }
+    pub fn check_write(&self, func: &str, path: &str) -> Result<(), String> {
+        check_path(self.write.as_deref(), "write", func, path)
+    }
+    pub fn check_net(&self, func: &str) -> Result<(), String> {
+        check_flag(self.net && cfg!(feature = "net"), "network", func)
+    }
+    pub fn check_exec(&self, func: &str) -> Result<(), String> {
+        check_flag(self.exec, "process", func)
+    }
+    pub fn check_env(&self, func: &str) -> Result<(), String> {
+        check_flag(self.env, "environment", func)
+    }
+}
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::permissive()
+    }
+}
+fn check_flag(allowed: bool, access: &str, func: &str) -> Result<(), String> {
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}() is denied {} access by the sandbox",
+            func, access
+        ))
+    }
+}
+fn check_path(
+    roots: Option<&[PathBuf]>,
+    access: &str,
+    func: &str,
+    path: &str,
+) -> Result<(), String> {
+    let Some(roots) = roots else {
+        return Ok(());
+    };
+    let inside = resolve(Path::new(path)).is_some_and(|target| {
+        roots
+            .iter()
+            .filter_map(|root| resolve(root))
+            .any(|root| target.starts_with(root))
+    });
+    if inside {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}('{}') is denied {} access by the sandbox",
+            func, path, access
+        ))
+    }
+}
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let mut base = std::env::current_dir().ok()?.join(path);
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(real) = base.canonicalize() {
+            return Some(
+                rest.into_iter()
+                    .rev()
+                    .fold(real, |path, name| path.join(name)),
+            );
+        }
+        rest.push(base.file_name()?.to_os_string());
+        if !base.pop() {
+            return None;
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_policy_checks() {
+        let dir = std::env::temp_dir().join(format!("nebula_sandbox_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        let policy = SandboxPolicy::locked()
+            .allow_read(dir.join("data"))
+            .allow_env(true);
+        let inside = dir.join("data/new/file.txt").to_string_lossy().into_owned();
+        let escape = dir.join("data/new/../../x").to_string_lossy().into_owned();
+        let outside = dir.join("x").to_string_lossy().into_owned();
+        assert!(policy.check_read("stat", &inside).is_ok());
+        assert!(policy.check_read("stat", &escape).is_err());
+        assert!(policy.check_read("stat", &outside).is_err());
+        assert!(policy.check_write("mkdir", &inside).is_err());
+        assert!(policy.check_env("env").is_ok());
+        assert!(policy.check_exec("exec").unwrap_err().contains("sandbox"));
+        assert!(SandboxPolicy::default()
+            .check_write("mkdir", &outside)
+            .is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use crate::error::{ErrorCode, NebulaError};
+use crate::interp::{NativeFn, Value};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Mutex;
+thread_local! {
+    static EXIT_REQUEST: Cell<Option<i32>> = const { Cell::new(None) };
+}
+static SCRIPT_ARGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+pub fn set_script_args(args: Vec<String>) {
+    if let Ok(mut current) = SCRIPT_ARGS.lock() {
+        *current = args;
+    }
+}
+pub fn script_args() -> Vec<String> {
+    SCRIPT_ARGS
+        .lock()
+        .map(|args| args.clone())
+        .unwrap_or_default()
+}
+pub fn native_error(message: String) -> NebulaError {
+    match EXIT_REQUEST.with(|req| req.take()) {
+        Some(code) => NebulaError::Exit { code },
+        None => match coded_message(&message) {
+            Some((code, msg)) => NebulaError::Coded {
+                code,
+                msg: msg.to_string(),
+                span: None,
+            },
+            None => NebulaError::Runtime {
+                message,
+                span: None,
+            },
+        },
+    }
+}
+pub(crate) fn coded_message(message: &str) -> Option<(ErrorCode, &str)> {
+    let (code, msg) = message.strip_prefix('[')?.split_once("] ")?;
+    Some((ErrorCode::parse(code)?, msg))
+}
+pub fn callback_error(err: NebulaError) -> String {
+    if let NebulaError::Exit { code } = err {
+        EXIT_REQUEST.with(|req| req.set(Some(code)));
+    }
+    err.message()
+}
+pub fn sys_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "args",
+            NativeFn {
+                name: "args".to_string(),
+                arity: Some(0),
+                func: Rc::new(|_ctx, _args| {
+                    Ok(Value::List(
+                        script_args()
+                            .into_iter()
+                            .map(|s| Value::String(s.into()))
+                            .collect(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "exit",
+            NativeFn {
+                name: "exit".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| {
+                    let code = match args {
+                        [] => 0,
+                        [code] => code.as_integer().ok_or("exit() requires integer code")?,
+                        _ => {
+                            return Err(format!(
+                                "exit() expected 0 or 1 arguments, got {}",
+                                args.len()
+                            ))
+                        }
+                    };
+                    let code = code.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+                    EXIT_REQUEST.with(|req| req.set(Some(code)));
+                    Err(format!("exit({})", code))
+                }),
+            },
+        ),
+        (
+            "globals",
+            NativeFn {
+                name: "globals".to_string(),
+                arity: Some(0),
+                func: Rc::new(|ctx, _args| {
+                    Ok(Value::List(
+                        ctx.globals()
+                            .into_iter()
+                            .map(|s| Value::String(s.into()))
+                            .collect(),
+                    ))
+                }),
+            },
+        ),
+        (
+            "vm_stats",
+            NativeFn {
+                name: "vm_stats".to_string(),
+                arity: Some(0),
+                func: Rc::new(|ctx, _args| Ok(stats_map(ctx.engine_stats()))),
+            },
+        ),
+        (
+            "mem_stats",
+            NativeFn {
+                name: "mem_stats".to_string(),
+                arity: Some(0),
+                func: Rc::new(|ctx, _args| Ok(stats_map(ctx.memory_stats()))),
+            },
+        ),
+    ]
+}
+fn stats_map(stats: Vec<(&'static str, Value)>) -> Value {
+    Value::Map(
+        stats
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
@@ -0,0 +1,457 @@
+use crate::ext::ExtFuture;
+use crate::interp::{NativeContext, NativeFn, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+const MS_PER_DAY: i64 = 86_400_000;
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DateTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    millis: i64,
+}
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+impl DateTime {
+    fn from_millis(ts: i64) -> Self {
+        let days = ts.div_euclid(MS_PER_DAY);
+        let rem = ts.rem_euclid(MS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: rem / 3_600_000,
+            minute: rem / 60_000 % 60,
+            second: rem / 1000 % 60,
+            millis: rem % 1000,
+        }
+    }
+    fn to_millis(self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * MS_PER_DAY
+            + self.hour * 3_600_000
+            + self.minute * 60_000
+            + self.second * 1000
+            + self.millis
+    }
+    fn weekday(self) -> usize {
+        (days_from_civil(self.year, self.month, self.day) + 3).rem_euclid(7) as usize
+    }
+    fn yearday(self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) - days_from_civil(self.year, 1, 1) + 1
+    }
+    fn components(self) -> Value {
+        let mut parts = HashMap::new();
+        parts.insert("year".to_string(), Value::Integer(self.year));
+        parts.insert("month".to_string(), Value::Integer(self.month));
+        parts.insert("day".to_string(), Value::Integer(self.day));
+        parts.insert("hour".to_string(), Value::Integer(self.hour));
+        parts.insert("minute".to_string(), Value::Integer(self.minute));
+        parts.insert("second".to_string(), Value::Integer(self.second));
+        parts.insert("millis".to_string(), Value::Integer(self.millis));
+        parts.insert(
+            "weekday".to_string(),
+            Value::String(WEEKDAYS[self.weekday()].into()),
+        );
+        parts.insert("yearday".to_string(), Value::Integer(self.yearday()));
+        parts.insert(
+            "timestamp".to_string(),
+            Value::Number(self.to_millis() as f64),
+        );
+        Value::Map(parts)
+    }
+}
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+fn format(dt: DateTime, fmt: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", dt.year)),
+            Some('m') => out.push_str(&format!("{:02}", dt.month)),
+            Some('d') => out.push_str(&format!("{:02}", dt.day)),
+            Some('H') => out.push_str(&format!("{:02}", dt.hour)),
+            Some('M') => out.push_str(&format!("{:02}", dt.minute)),
+            Some('S') => out.push_str(&format!("{:02}", dt.second)),
+            Some('f') => out.push_str(&format!("{:03}", dt.millis)),
+            Some('j') => out.push_str(&format!("{:03}", dt.yearday())),
+            Some('A') => out.push_str(WEEKDAYS[dt.weekday()]),
+            Some('a') => out.push_str(&WEEKDAYS[dt.weekday()][..3]),
+            Some('B') => out.push_str(MONTHS[dt.month as usize - 1]),
+            Some('b') => out.push_str(&MONTHS[dt.month as usize - 1][..3]),
+            Some('%') => out.push('%'),
+            Some(other) => return Err(format!("date_format(): unknown directive '%{}'", other)),
+            None => return Err("date_format(): trailing '%' in format".to_string()),
+        }
+    }
+    Ok(out)
+}
+fn parse(input: &str, fmt: &str) -> Result<DateTime, String> {
+    let mut dt = DateTime {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        millis: 0,
+    };
+    let input: Vec<char> = input.chars().collect();
+    let mut pos = 0;
+    let mut fmt_chars = fmt.chars();
+    let mismatch = |pos: usize| format!("date_parse(): input does not match format at {}", pos);
+    while let Some(c) = fmt_chars.next() {
+        if c != '%' {
+            if input.get(pos) != Some(&c) {
+                return Err(mismatch(pos));
+            }
+            pos += 1;
+            continue;
+        }
+        let directive = fmt_chars
+            .next()
+            .ok_or("date_parse(): trailing '%' in format")?;
+        let (max_digits, slot) = match directive {
+            'Y' => (4, &mut dt.year),
+            'm' => (2, &mut dt.month),
+            'd' => (2, &mut dt.day),
+            'H' => (2, &mut dt.hour),
+            'M' => (2, &mut dt.minute),
+            'S' => (2, &mut dt.second),
+            'f' => (3, &mut dt.millis),
+            '%' => {
+                if input.get(pos) != Some(&'%') {
+                    return Err(mismatch(pos));
+                }
+                pos += 1;
+                continue;
+            }
+            other => return Err(format!("date_parse(): unknown directive '%{}'", other)),
+        };
+        let start = pos;
+        while pos < input.len() && pos - start < max_digits && input[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos == start {
+            return Err(mismatch(pos));
+        }
+        let digits: String = input[start..pos].iter().collect();
+        *slot = digits.parse().map_err(|_| mismatch(start))?;
+    }
+    if pos != input.len() {
+        return Err(format!(
+            "date_parse(): unexpected trailing input at {}",
+            pos
+        ));
+    }
+    if !(1..=12).contains(&dt.month)
+        || dt.day < 1
+        || dt.day > days_in_month(dt.year, dt.month)
+        || dt.hour > 23
+        || dt.minute > 59
+        || dt.second > 59
+    {
+        return Err("date_parse(): date out of range".to_string());
+    }
+    Ok(dt)
+}
+fn unit_millis(unit: &Value, func: &str) -> Result<f64, String> {
+    let unit = unit
+        .as_string()
+        .ok_or_else(|| format!("{}() requires unit string", func))?;
+    match unit {
+        "ms" | "millis" => Ok(1.0),
+        "s" | "sec" | "seconds" => Ok(1000.0),
+        "m" | "min" | "minutes" => Ok(60_000.0),
+        "h" | "hours" => Ok(3_600_000.0),
+        "d" | "days" => Ok(MS_PER_DAY as f64),
+        "w" | "weeks" => Ok(7.0 * MS_PER_DAY as f64),
+        other => Err(format!("{}(): unknown unit '{}'", func, other)),
+    }
+}
+fn timestamp_arg(value: &Value, func: &str) -> Result<i64, String> {
+    value
+        .as_number()
+        .map(|n| n as i64)
+        .ok_or_else(|| format!("{}() requires timestamp in milliseconds", func))
+}
+fn millis_arg(value: &Value, func: &str) -> Result<Duration, String> {
+    let ms = value
+        .as_number()
+        .ok_or_else(|| format!("{}() requires number (milliseconds)", func))?;
+    Ok(Duration::from_millis(ms.max(0.0) as u64))
+}
+pub fn sleep(ctx: &mut dyn NativeContext, ms: &Value) -> Result<(), String> {
+    ExtFuture::timer(millis_arg(ms, "sleep")?)
+        .wait(ctx)
+        .map(drop)
+        .map_err(|e| e.message)
+}
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+fn bench(ctx: &mut dyn NativeContext, args: &[Value]) -> Result<Value, String> {
+    let iterations = args[1]
+        .as_integer()
+        .filter(|n| *n > 0)
+        .ok_or("bench() requires a positive iteration count")?;
+    let mut samples = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        ctx.call(&args[0], &[])?;
+        samples.push(start.elapsed().as_secs_f64() * 1000.0);
+    }
+    let total: f64 = samples.iter().sum();
+    samples.sort_by(f64::total_cmp);
+    let mut stats = HashMap::new();
+    stats.insert("iterations".to_string(), Value::Integer(iterations));
+    stats.insert("total".to_string(), Value::Number(total));
+    stats.insert("min".to_string(), Value::Number(samples[0]));
+    stats.insert(
+        "mean".to_string(),
+        Value::Number(total / samples.len() as f64),
+    );
+    stats.insert("p95".to_string(), Value::Number(percentile(&samples, 95.0)));
+    stats.insert("max".to_string(), Value::Number(samples[samples.len() - 1]));
+    Ok(Value::Map(stats))
+}
+pub fn time_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "date_now",
+            NativeFn {
+                name: "date_now".to_string(),
+                arity: Some(0),
+                func: Rc::new(|_ctx, _args| Ok(DateTime::from_millis(now_millis()).components())),
+            },
+        ),
+        (
+            "date_parts",
+            NativeFn {
+                name: "date_parts".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let ts = timestamp_arg(&args[0], "date_parts")?;
+                    Ok(DateTime::from_millis(ts).components())
+                }),
+            },
+        ),
+        (
+            "date_format",
+            NativeFn {
+                name: "date_format".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let ts = timestamp_arg(&args[0], "date_format")?;
+                    let fmt = args[1]
+                        .as_string()
+                        .ok_or("date_format() requires format string")?;
+                    format(DateTime::from_millis(ts), fmt).map(|s| Value::String(s.into()))
+                }),
+            },
+        ),
+        (
+            "date_parse",
+            NativeFn {
+                name: "date_parse".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let input = args[0].as_string().ok_or("date_parse() requires string")?;
+                    let fmt = args[1]
+                        .as_string()
+                        .ok_or("date_parse() requires format string")?;
+                    Ok(Value::Number(parse(input, fmt)?.to_millis() as f64))
+                }),
+            },
+        ),
+        (
+            "duration",
+            NativeFn {
+                name: "duration".to_string(),
+                arity: Some(2),
+                func: Rc::new(|_ctx, args| {
+                    let amount = args[0].as_number().ok_or("duration() requires number")?;
+                    Ok(Value::Number(amount * unit_millis(&args[1], "duration")?))
+                }),
+            },
+        ),
+        (
+            "date_add",
+            NativeFn {
+                name: "date_add".to_string(),
+                arity: Some(3),
+                func: Rc::new(|_ctx, args| {
+                    let ts = timestamp_arg(&args[0], "date_add")?;
+                    let amount = args[1].as_number().ok_or("date_add() requires number")?;
+                    let delta = amount * unit_millis(&args[2], "date_add")?;
+                    Ok(Value::Number((ts as f64 + delta).trunc()))
+                }),
+            },
+        ),
+        (
+            "date_diff",
+            NativeFn {
+                name: "date_diff".to_string(),
+                arity: Some(3),
+                func: Rc::new(|_ctx, args| {
+                    let a = timestamp_arg(&args[0], "date_diff")?;
+                    let b = timestamp_arg(&args[1], "date_diff")?;
+                    Ok(Value::Number(
+                        (a - b) as f64 / unit_millis(&args[2], "date_diff")?,
+                    ))
+                }),
+            },
+        ),
+        (
+            "timer",
+            NativeFn {
+                name: "timer".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    Ok(Value::Future(ExtFuture::timer(millis_arg(
+                        &args[0], "timer",
+                    )?)))
+                }),
+            },
+        ),
+        (
+            "interval",
+            NativeFn {
+                name: "interval".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| {
+                    let every = millis_arg(&args[0], "interval")?;
+                    if every.is_zero() {
+                        return Err("interval() requires a positive period".to_string());
+                    }
+                    Ok(Value::Future(ExtFuture::interval(every)))
+                }),
+            },
+        ),
+        (
+            "bench",
+            NativeFn {
+                name: "bench".to_string(),
+                arity: Some(2),
+                func: Rc::new(bench),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_civil_round_trip() {
+        let dt = DateTime::from_millis(951_782_400_123);
+        assert_eq!((dt.year, dt.month, dt.day), (2000, 2, 29));
+        assert_eq!(dt.millis, 123);
+        assert_eq!(dt.to_millis(), 951_782_400_123);
+        assert_eq!(WEEKDAYS[dt.weekday()], "Tuesday");
+        let before_epoch = DateTime::from_millis(-1);
+        assert_eq!((before_epoch.year, before_epoch.hour), (1969, 23));
+    }
+    #[test]
+    fn test_format_and_parse() {
+        let dt = parse("2024-03-05 14:07:09", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(
+            format(dt, "%a %d %b %Y, %H:%M:%S.%f").unwrap(),
+            "Tue 05 Mar 2024, 14:07:09.000"
+        );
+        assert!(parse("2023-02-29", "%Y-%m-%d").is_err());
+        assert!(parse("2024-01-01x", "%Y-%m-%d").is_err());
+    }
+    #[test]
+    fn test_bench_statistics() {
+        let noop = Value::NativeFunction(NativeFn {
+            name: "noop".to_string(),
+            arity: Some(0),
+            func: Rc::new(|_ctx, _args| Ok(Value::Nil)),
+        });
+        let stats = bench(
+            &mut crate::builtins::TestContext,
+            &[noop.clone(), Value::Integer(20)],
+        );
+        let stats = match stats.unwrap() {
+            Value::Map(stats) => stats,
+            other => panic!("expected map, got {}", other),
+        };
+        assert_eq!(stats["iterations"], Value::Integer(20));
+        let min = stats["min"].as_number().unwrap();
+        let p95 = stats["p95"].as_number().unwrap();
+        assert!(min <= p95 && p95 <= stats["max"].as_number().unwrap());
+        assert!(bench(
+            &mut crate::builtins::TestContext,
+            &[noop, Value::Integer(0)]
+        )
+        .is_err());
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 50.0), 2.0);
+    }
+}
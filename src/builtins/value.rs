@@ -0,0 +1,107 @@
+use crate::interp::{NativeFn, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+fn deep_copy(value: &Value) -> Value {
+    match value {
+        Value::List(items) => Value::List(items.iter().map(deep_copy).collect()),
+        Value::Tuple(items) => Value::Tuple(items.iter().map(deep_copy).collect()),
+        Value::Set(items) => Value::Set(items.iter().map(deep_copy).collect()),
+        Value::Map(map) => Value::Map(map.iter().map(|(k, v)| (k.clone(), deep_copy(v))).collect()),
+        Value::Struct { name, fields } => Value::Struct {
+            name: name.clone(),
+            fields: fields.iter().map(deep_copy).collect(),
+        },
+        Value::Channel(queue) => Value::Channel(Rc::new(RefCell::new(
+            queue.borrow().iter().map(deep_copy).collect(),
+        ))),
+        Value::Frozen(inner) => Value::Frozen(Rc::new(deep_copy(inner))),
+        other => other.clone(),
+    }
+}
+pub fn value_builtins() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        (
+            "ready",
+            NativeFn {
+                name: "ready".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| match &args[0] {
+                    Value::Future(future) => Ok(Value::Bool(future.is_ready())),
+                    _ => Ok(Value::Bool(true)),
+                }),
+            },
+        ),
+        (
+            "clone",
+            NativeFn {
+                name: "clone".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| Ok(args[0].clone())),
+            },
+        ),
+        (
+            "copy",
+            NativeFn {
+                name: "copy".to_string(),
+                arity: None,
+                func: Rc::new(|_ctx, args| match args.len() {
+                    1 => Ok(args[0].clone()),
+                    #[cfg(feature = "io-builtins")]
+                    2 => super::fs::copy_file(_ctx, args),
+                    n => Err(format!("copy() expected 1 argument, got {}", n)),
+                }),
+            },
+        ),
+        (
+            "deep_copy",
+            NativeFn {
+                name: "deep_copy".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| Ok(deep_copy(&args[0]))),
+            },
+        ),
+        (
+            "freeze",
+            NativeFn {
+                name: "freeze".to_string(),
+                arity: Some(1),
+                func: Rc::new(|_ctx, args| match &args[0] {
+                    Value::List(_) | Value::Map(_) | Value::Set(_) => {
+                        Ok(Value::Frozen(Rc::new(args[0].clone())))
+                    }
+                    frozen @ Value::Frozen(_) => Ok(frozen.clone()),
+                    other => Err(format!(
+                        "freeze() requires list or map, got {}",
+                        other.type_name()
+                    )),
+                }),
+            },
+        ),
+    ]
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_deep_copy_detaches_shared_state() {
+        let queue = Rc::new(RefCell::new(vec![Value::Integer(1)]));
+        let nested = Value::List(vec![Value::Channel(Rc::clone(&queue))]);
+        let shallow = nested.clone();
+        let deep = deep_copy(&nested);
+        queue.borrow_mut().push(Value::Integer(2));
+        let inner_len = |v: &Value| match v {
+            Value::List(items) => match &items[0] {
+                Value::Channel(q) => q.borrow().len(),
+                other => panic!("expected channel, got {}", other),
+            },
+            other => panic!("expected list, got {}", other),
+        };
+        assert_eq!(inner_len(&shallow), 2);
+        assert_eq!(inner_len(&deep), 1);
+        let record = Value::Struct {
+            name: "P".to_string(),
+            fields: vec![Value::List(vec![Value::Integer(3)])],
+        };
+        assert_eq!(format!("{}", deep_copy(&record)), format!("{}", record));
+    }
+}
@@ -0,0 +1,54 @@
+use crate::error::Diagnostic;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::vm::Compiler;
+
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let tokens = match Lexer::new(source).tokenize() {
+        Ok(tokens) => tokens,
+        Err(errors) => return errors.iter().map(Diagnostic::from).collect(),
+    };
+    let mut parser = Parser::new(tokens);
+    let parsed = parser.parse_program();
+    let mut diagnostics = parser.take_warnings();
+    match parsed {
+        Ok(program) => {
+            let mut compiler = Compiler::new();
+            if let Err(error) = compiler.compile(&program) {
+                diagnostics.push(Diagnostic::from(&error));
+            }
+            diagnostics.extend(compiler.warnings().iter().cloned());
+        }
+        Err(error) => diagnostics.push(Diagnostic::from(&error)),
+    }
+    diagnostics.sort_by_key(|d| d.span.map_or((usize::MAX, 0), |s| (s.line, s.column)));
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Severity;
+
+    #[test]
+    fn test_check_collects_warnings_and_errors_in_order() {
+        let source = "fn f(a, a) do\n  give a\n  log(1)\nend\nfb x = (1\n";
+        let diagnostics = check(source);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.span.map(|s| s.line)))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Severity::Warning, Some(1)),
+                (Severity::Warning, Some(3)),
+                (Severity::Error, Some(5)),
+            ]
+        );
+        assert!(check("fb ok = 1\nlog(ok)\n").is_empty());
+        let lexical = check("log(1 $ 2 @)");
+        assert_eq!(lexical.len(), 2);
+        assert_eq!(lexical[0].message, "Unexpected character '$'");
+    }
+}
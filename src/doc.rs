@@ -0,0 +1,128 @@
+use crate::lexer::Span;
+use crate::parser::ast::{Item, Param, Program, Type};
+use crate::typeck::Ty;
+use serde::Serialize;
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocEntry {
+    pub name: String,
+    pub signature: String,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+pub fn document(program: &Program) -> Vec<DocEntry> {
+    let mut entries = Vec::new();
+    for item in &program.items {
+        match item {
+            Item::Function(f) => entries.push(DocEntry {
+                name: f.name.clone(),
+                signature: format!(
+                    "{}{}",
+                    if f.is_async { "async " } else { "" },
+                    signature(&f.name, &f.params, &f.return_type)
+                ),
+                doc: f.doc.clone(),
+                span: f.span,
+            }),
+            Item::Struct(s) => entries.push(DocEntry {
+                name: s.name.clone(),
+                signature: format!("struct {}", s.name),
+                doc: s.doc.clone(),
+                span: s.span,
+            }),
+            Item::Enum(e) => entries.push(DocEntry {
+                name: e.name.clone(),
+                signature: format!("enum {} {{ {} }}", e.name, e.variants.join(", ")),
+                doc: e.doc.clone(),
+                span: e.span,
+            }),
+            Item::Trait(t) => {
+                entries.push(DocEntry {
+                    name: t.name.clone(),
+                    signature: format!("trait {}", t.name),
+                    doc: t.doc.clone(),
+                    span: t.span,
+                });
+                entries.extend(t.methods.iter().map(|m| DocEntry {
+                    name: format!("{}:{}", t.name, m.name),
+                    signature: signature(&m.name, &m.params, &m.return_type),
+                    doc: m.doc.clone(),
+                    span: m.span,
+                }));
+            }
+            Item::Impl(i) => entries.extend(i.methods.iter().filter(|m| m.doc.is_some()).map(
+                |m| DocEntry {
+                    name: format!("{}:{}", i.target, m.name),
+                    signature: signature(&m.name, &m.params, &m.return_type),
+                    doc: m.doc.clone(),
+                    span: m.span,
+                },
+            )),
+            Item::TypeAlias(a) => entries.push(DocEntry {
+                name: a.name.clone(),
+                signature: format!("type {} = {}", a.name, Ty::from_ast(&a.ty)),
+                doc: a.doc.clone(),
+                span: a.span,
+            }),
+            _ => {}
+        }
+    }
+    entries
+}
+pub fn lookup<'a>(entries: &'a [DocEntry], name: &str) -> Option<&'a DocEntry> {
+    entries.iter().find(|entry| entry.name == name)
+}
+pub fn markdown(title: &str, entries: &[DocEntry]) -> String {
+    let mut out = format!("# {}\n", title);
+    for entry in entries {
+        out.push_str(&format!("\n## `{}`\n", entry.signature));
+        if let Some(doc) = &entry.doc {
+            out.push_str(&format!("\n{}\n", doc));
+        }
+    }
+    out
+}
+fn signature(name: &str, params: &[Param], return_type: &Option<Type>) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|p| {
+            let name = if p.variadic {
+                format!("...{}", p.name)
+            } else {
+                p.name.clone()
+            };
+            match &p.ty {
+                Some(ty) => format!("{}: {}", name, Ty::from_ast(ty)),
+                None => name,
+            }
+        })
+        .collect();
+    let returns = return_type
+        .as_ref()
+        .map_or(String::new(), |ty| format!(": {}", Ty::from_ast(ty)));
+    format!("fn {}({}){}", name, params.join(", "), returns)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::parse_source;
+    #[test]
+    fn test_doc_comments_attach_to_following_items() {
+        let source = "## Adds one.\n##\n## Works on any number.\nfn inc(x: nb): nb = x + 1\n# plain comment\nfn bare() = 0\nperm n = 1 ## trailing note\n## A shape.\ntrait Shape {\n  ## Area in square units.\n  fn area(self): nb\n}\n";
+        let entries = document(&parse_source(source).unwrap());
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["inc", "bare", "Shape", "Shape:area"]);
+        let inc = lookup(&entries, "inc").unwrap();
+        assert_eq!(inc.signature, "fn inc(x: nb): nb");
+        assert_eq!(
+            inc.doc.as_deref(),
+            Some("Adds one.\n\nWorks on any number.")
+        );
+        assert_eq!(lookup(&entries, "bare").unwrap().doc, None);
+        assert_eq!(
+            lookup(&entries, "Shape").unwrap().doc.as_deref(),
+            Some("A shape.")
+        );
+        let text = markdown("shapes", &entries);
+        assert!(text.contains("## `fn area(self): nb`\n\nArea in square units.\n"));
+    }
+}
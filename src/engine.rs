@@ -0,0 +1,388 @@
+use crate::builtins::{native_error, SandboxPolicy};
+use crate::error::{ErrorCode, NebulaError, NebulaResult};
+use crate::interp::{Interpreter, NativeContext, NativeFn, Value};
+use crate::lexer::Lexer;
+use crate::limits::{with_limits, ResourceLimits};
+use crate::parser::{Parser, Program};
+use crate::stream::ItemReader;
+use crate::vm::{to_value, Compiler, VM};
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    Interpreter,
+    Vm,
+}
+#[derive(Debug, Clone)]
+pub struct Script {
+    program: Arc<Program>,
+}
+impl Script {
+    pub fn parse(source: &str) -> NebulaResult<Self> {
+        Ok(Self {
+            program: Arc::new(parse(source)?),
+        })
+    }
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+}
+enum Runtime {
+    Interpreter(Box<Interpreter>),
+    Vm {
+        compiler: Box<Compiler>,
+        vm: Box<VM>,
+    },
+}
+impl Runtime {
+    fn stdio(&mut self) -> Option<&mut crate::interp::Stdio> {
+        match self {
+            Runtime::Interpreter(interpreter) => interpreter.stdio(),
+            Runtime::Vm { vm, .. } => vm.stdio(),
+        }
+    }
+    fn execute(&mut self, program: &Program) -> NebulaResult<Value> {
+        match self {
+            Runtime::Interpreter(interpreter) => interpreter.interpret(program),
+            Runtime::Vm { compiler, vm } => {
+                let chunk = compiler.compile(program)?;
+                let result =
+                    vm.run_with_functions(&chunk, compiler.global_names(), compiler.functions())?;
+                Ok(to_value(result))
+            }
+        }
+    }
+}
+pub struct ScriptEngine {
+    runtime: Runtime,
+    limits: ResourceLimits,
+    policy: Option<SandboxPolicy>,
+    natives: Vec<NativeFn>,
+}
+impl ScriptEngine {
+    pub fn new() -> Self {
+        Self::with_backend(Backend::default())
+    }
+    pub fn with_backend(backend: Backend) -> Self {
+        let runtime = match backend {
+            Backend::Interpreter => Runtime::Interpreter(Box::default()),
+            Backend::Vm => Runtime::Vm {
+                compiler: Box::default(),
+                vm: Box::default(),
+            },
+        };
+        Self {
+            runtime,
+            limits: ResourceLimits::default(),
+            policy: None,
+            natives: Vec::new(),
+        }
+    }
+    pub fn backend(&self) -> Backend {
+        match self.runtime {
+            Runtime::Interpreter(_) => Backend::Interpreter,
+            Runtime::Vm { .. } => Backend::Vm,
+        }
+    }
+    pub fn set_backend(&mut self, backend: Backend) -> NebulaResult<()> {
+        if backend == self.backend() {
+            return Ok(());
+        }
+        let globals = self.globals();
+        if let Some((name, value)) = globals.iter().find(|(_, value)| !portable(value)) {
+            return Err(NebulaError::coded(
+                ErrorCode::E030,
+                format!(
+                    "cannot carry '{}' ({}) over to the other engine",
+                    name,
+                    value.type_name()
+                ),
+            ));
+        }
+        let mut next = Self::with_backend(backend);
+        if let Some(policy) = self.policy.take() {
+            next.set_policy(policy);
+        }
+        for native in self.natives.drain(..) {
+            next.register_native(native);
+        }
+        if let (Some(from), Some(to)) = (self.runtime.stdio(), next.runtime.stdio()) {
+            std::mem::swap(from, to);
+        }
+        for (name, value) in globals {
+            next.set_global(&name, value)?;
+        }
+        next.limits = std::mem::take(&mut self.limits);
+        *self = next;
+        Ok(())
+    }
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        match &self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.global_bindings(),
+            Runtime::Vm { compiler, vm } => {
+                let mut globals: Vec<_> = compiler
+                    .global_names()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, name)| {
+                        !crate::builtins::is_builtin(name)
+                            && !self.natives.iter().any(|native| native.name == **name)
+                            && crate::ext::native_extensions().get_function(name).is_none()
+                    })
+                    .filter_map(|(index, name)| Some((name.clone(), vm.global(index)?)))
+                    .collect();
+                globals.sort_by(|a, b| a.0.cmp(&b.0));
+                globals
+            }
+        }
+    }
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        match &mut self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.set_output(output),
+            Runtime::Vm { vm, .. } => vm.set_output(output),
+        }
+    }
+    pub fn set_error(&mut self, error: Box<dyn Write>) {
+        match &mut self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.set_error(error),
+            Runtime::Vm { vm, .. } => vm.set_error(error),
+        }
+    }
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        match &mut self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.set_input(input),
+            Runtime::Vm { vm, .. } => vm.set_input(input),
+        }
+    }
+    pub fn set_policy(&mut self, policy: SandboxPolicy) {
+        self.policy = Some(policy.clone());
+        match &mut self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.set_policy(policy),
+            Runtime::Vm { compiler, vm } => {
+                compiler.set_policy(policy.clone());
+                vm.set_policy(policy);
+            }
+        }
+    }
+    pub fn register_native(&mut self, native: NativeFn) {
+        self.natives.push(native.clone());
+        match &mut self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.register_native(native),
+            Runtime::Vm { vm, .. } => vm.register_native(native),
+        }
+    }
+    pub fn eval(&mut self, source: &str) -> NebulaResult<Value> {
+        self.execute(&parse(source)?)
+    }
+    pub fn run(&mut self, script: &Script) -> NebulaResult<Value> {
+        self.execute(&script.program)
+    }
+    pub fn run_program(&mut self, program: &Program) -> NebulaResult<Value> {
+        self.execute(program)
+    }
+    pub fn run_stream<R: BufRead>(&mut self, items: &mut ItemReader<R>) -> NebulaResult<Value> {
+        let runtime = &mut self.runtime;
+        with_limits(self.limits.clone(), || {
+            let mut result = Value::Nil;
+            while let Some(program) = items.next_item()? {
+                result = runtime.execute(&program)?;
+            }
+            Ok(result)
+        })
+    }
+    fn execute(&mut self, program: &Program) -> NebulaResult<Value> {
+        let runtime = &mut self.runtime;
+        with_limits(self.limits.clone(), || runtime.execute(program))
+    }
+    pub fn call(&mut self, name: &str, args: &[Value]) -> NebulaResult<Value> {
+        let runtime = &mut self.runtime;
+        with_limits(self.limits.clone(), || match runtime {
+            Runtime::Interpreter(interpreter) => interpreter.call_global(name, args),
+            Runtime::Vm { compiler, vm } => {
+                let callee =
+                    vm_global(compiler, vm, name).ok_or_else(|| undefined(compiler, name))?;
+                vm.call(&callee, args).map_err(native_error)
+            }
+        })
+    }
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        match &self.runtime {
+            Runtime::Interpreter(interpreter) => interpreter.lookup(name),
+            Runtime::Vm { compiler, vm } => vm_global(compiler, vm, name),
+        }
+    }
+    pub fn set_global(&mut self, name: &str, value: Value) -> NebulaResult<()> {
+        match &mut self.runtime {
+            Runtime::Interpreter(interpreter) => {
+                interpreter.define(name, value).map_err(native_error)
+            }
+            Runtime::Vm { compiler, vm } => vm.set_global(compiler.declare_global(name), &value),
+        }
+    }
+}
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+fn parse(source: &str) -> NebulaResult<Program> {
+    let tokens = Lexer::new(source)
+        .tokenize()
+        .map_err(|errors| NebulaError::combine(errors, "lexical errors"))?;
+    Parser::new(tokens).parse_program()
+}
+fn portable(value: &Value) -> bool {
+    match value {
+        Value::List(items) | Value::Tuple(items) | Value::Set(items) => items.iter().all(portable),
+        Value::Map(map) => map.values().all(portable),
+        Value::Frozen(inner) => portable(inner),
+        Value::Number(_)
+        | Value::Integer(_)
+        | Value::Float(_)
+        | Value::Bool(_)
+        | Value::String(_)
+        | Value::Byte(_)
+        | Value::Char(_)
+        | Value::Nil
+        | Value::Range(_) => true,
+        _ => false,
+    }
+}
+fn vm_global(compiler: &Compiler, vm: &VM, name: &str) -> Option<Value> {
+    let index = compiler.global_names().iter().position(|n| n == name)?;
+    vm.global(index)
+}
+fn undefined(compiler: &Compiler, name: &str) -> NebulaError {
+    NebulaError::UndefinedVariable {
+        name: name.to_string(),
+        suggestion: crate::error::closest(name, compiler.global_names().iter().map(String::as_str))
+            .map(str::to_string),
+        span: None,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interp::SharedValue;
+    #[test]
+    fn test_engine_backends_share_the_api() {
+        for backend in [Backend::Interpreter, Backend::Vm] {
+            let mut engine = ScriptEngine::with_backend(backend);
+            assert_eq!(engine.backend(), backend);
+            engine.set_global("base", Value::Number(10.0)).unwrap();
+            engine
+                .eval("fn add(a, b) do\n  give a + b + base\nend\nperm total = add(1, 2)")
+                .unwrap();
+            assert_eq!(engine.get_global("total").unwrap().as_number(), Some(13.0));
+            engine.eval("total = total + 1").unwrap();
+            assert_eq!(engine.get_global("total").unwrap().as_number(), Some(14.0));
+            let sum = engine
+                .call("add", &[Value::Number(4.0), Value::Number(5.0)])
+                .unwrap();
+            assert_eq!(sum.as_number(), Some(19.0), "{:?}", backend);
+            assert!(engine.get_global("missing").is_none());
+            assert!(engine.call("ad", &[]).is_err());
+        }
+    }
+    #[test]
+    fn test_switching_backends_keeps_globals() {
+        let output = crate::interp::OutputCapture::new();
+        let mut engine = ScriptEngine::new();
+        engine.set_output(Box::new(output.clone()));
+        engine
+            .eval("fb xs = lst(1, 2)\nfb name = \"neb\"\nfb total = 40")
+            .unwrap();
+        engine.set_backend(Backend::Vm).unwrap();
+        assert_eq!(engine.backend(), Backend::Vm);
+        engine
+            .eval("fb more = total + #xs\nlog(name, more)")
+            .unwrap();
+        engine.set_backend(Backend::Interpreter).unwrap();
+        engine.eval("log(more, xs)").unwrap();
+        assert_eq!(output.take(), "neb 42\n42 lst(1, 2)\n");
+        engine.eval("fn twice(x) = x * 2").unwrap();
+        let error = engine.set_backend(Backend::Vm).unwrap_err();
+        assert!(
+            error.to_string().contains("cannot carry 'twice'"),
+            "{}",
+            error
+        );
+        assert_eq!(engine.backend(), Backend::Interpreter);
+        assert_eq!(
+            engine
+                .call("twice", &[Value::Integer(4)])
+                .unwrap()
+                .as_number(),
+            Some(8.0)
+        );
+    }
+    #[test]
+    fn test_streamed_scripts_run_item_by_item() {
+        let source = "fn add(a, b) do\n  give a + b\nend\nperm total = 0\n\
+            for i = 1, 3 do\n  total = add(total, 2)\nend\n\nperm broken = missing + 1\nlog(1)\n";
+        for backend in [Backend::Interpreter, Backend::Vm] {
+            let mut engine = ScriptEngine::with_backend(backend);
+            let mut items = ItemReader::new("gen.na", source.as_bytes());
+            let error = engine.run_stream(&mut items).unwrap_err();
+            assert_eq!(engine.get_global("total").unwrap().as_number(), Some(6.0));
+            let failed = items.current().unwrap();
+            assert_eq!(failed.first_line(), 8, "{:?}", backend);
+            assert_eq!(failed.line(9), Some("perm broken = missing + 1"));
+            assert_eq!(error.span().map(|span| span.line), Some(9), "{:?}", backend);
+            let mut items = ItemReader::new("gen.na", "perm x = total\nperm y = x + 36".as_bytes());
+            engine.run_stream(&mut items).unwrap();
+            assert_eq!(engine.get_global("y").unwrap().as_number(), Some(42.0));
+        }
+    }
+    #[test]
+    fn test_streamed_scripts_reject_forward_references() {
+        let source = "perm early = 1\nlog(double(2))\nfn double(x) = x * 2\nperm late = 2\n";
+        for backend in [Backend::Interpreter, Backend::Vm] {
+            let mut engine = ScriptEngine::with_backend(backend);
+            let mut items = ItemReader::new("gen.na", source.as_bytes());
+            let error = engine.run_stream(&mut items).unwrap_err();
+            assert_eq!(error.span().map(|span| span.line), Some(2), "{:?}", backend);
+            assert_eq!(items.current().unwrap().first_line(), 2);
+            assert!(engine.get_global("early").is_some());
+            assert!(engine.get_global("double").is_none(), "{:?}", backend);
+            assert!(engine.get_global("late").is_none());
+        }
+        let mut engine = ScriptEngine::new();
+        let mut items = ItemReader::new("gen.na", source.as_bytes());
+        let error = engine.run_stream(&mut items).unwrap_err();
+        assert_eq!(error.code(), Some(crate::error::ErrorCode::E010));
+        let mut engine = ScriptEngine::new();
+        engine.run(&Script::parse(source).unwrap()).unwrap();
+        assert!(engine.get_global("late").is_some());
+    }
+    #[test]
+    fn test_parsed_scripts_run_on_other_threads() {
+        let script = Script::parse("perm out = lst(seed, seed * 2)").unwrap();
+        let workers: Vec<_> = [Backend::Interpreter, Backend::Vm]
+            .into_iter()
+            .enumerate()
+            .map(|(seed, backend)| {
+                let script = script.clone();
+                std::thread::spawn(move || -> Result<SharedValue, String> {
+                    let mut engine = ScriptEngine::with_backend(backend);
+                    let seed = Value::Integer(seed as i64 + 1);
+                    engine.set_global("seed", seed).map_err(|e| e.to_string())?;
+                    engine.run(&script).map_err(|e| e.to_string())?;
+                    SharedValue::try_from(engine.get_global("out").unwrap_or(Value::Nil))
+                })
+            })
+            .collect();
+        let results: Vec<_> = workers
+            .into_iter()
+            .map(|worker| Value::from(worker.join().unwrap().unwrap()).to_string())
+            .collect();
+        assert_eq!(results, ["lst(1, 2)", "lst(2, 4)"]);
+    }
+}
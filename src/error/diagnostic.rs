@@ -1,57 +1,148 @@
+use super::render::Renderer;
+use super::{ErrorCode, NebulaError};
 use crate::lexer::Span;
+#[derive(Debug, Clone, PartialEq)]
 pub struct Diagnostic {
     pub message: String,
-    pub span: Span,
-    pub source: String,
+    pub span: Option<Span>,
+    pub code: Option<ErrorCode>,
     pub severity: Severity,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub causes: Vec<String>,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+impl Label {
+    pub fn primary(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            primary: true,
+        }
+    }
+    pub fn secondary(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            primary: false,
+        }
+    }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Severity {
-    Error,
-    Warning,
     Note,
+    Warning,
+    Error,
+}
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
 }
 impl Diagnostic {
-    pub fn new(message: impl Into<String>, span: Span, source: &str, severity: Severity) -> Self {
+    pub fn new(severity: Severity, message: impl Into<String>, span: Option<Span>) -> Self {
         Self {
             message: message.into(),
             span,
-            source: source.to_string(),
+            code: None,
             severity,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+            causes: Vec::new(),
         }
     }
-    pub fn format(&self) -> String {
-        let mut output = String::new();
-        let lines: Vec<&str> = self.source.lines().collect();
-        let line_num = self.span.line;
-        if line_num == 0 || line_num > lines.len() {
-            return format!("{}: {}", self.severity_str(), self.message);
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Error, message, Some(span))
+    }
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Warning, message, Some(span))
+    }
+    pub fn note(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Note, message, Some(span))
+    }
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+    pub fn with_label(mut self, label: Label) -> Self {
+        if label.primary && self.span.is_none() {
+            self.span = Some(label.span);
         }
-        let line = lines[line_num - 1];
-        let line_prefix = format!("{:>4} | ", line_num);
-        output.push_str(&format!("{}: {}\n", self.severity_str(), self.message));
-        output.push_str(&format!("  --> line {}:{}\n", line_num, self.span.column));
-        output.push_str("     |\n");
-        output.push_str(&line_prefix);
-        output.push_str(line);
-        output.push('\n');
-        let caret_offset = 7 + self.span.column.saturating_sub(1);
-        output.push_str(&format!("{}^", " ".repeat(caret_offset)));
-        if self.span.length > 1 {
-            output.push_str(&"~".repeat(self.span.length - 1));
+        self.labels.push(label);
+        self
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+    pub fn with_cause(mut self, cause: impl Into<String>) -> Self {
+        self.causes.push(cause.into());
+        self
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+    pub fn format(&self, source: &str) -> String {
+        self.render("", source)
+    }
+    pub fn render(&self, name: &str, source: &str) -> String {
+        Renderer::plain().render(self, name, source)
+    }
+}
+impl From<&NebulaError> for Diagnostic {
+    fn from(error: &NebulaError) -> Self {
+        let mut diagnostic = match error {
+            NebulaError::Diagnostic(diagnostic) => (**diagnostic).clone(),
+            NebulaError::Caused { error, .. } => Self::from(error.as_ref()),
+            _ => Self::new(Severity::Error, error.message(), error.span().copied()),
+        };
+        if diagnostic.code.is_none() {
+            diagnostic.code = error.code();
         }
-        output
+        diagnostic
+            .causes
+            .extend(error.causes().map(NebulaError::message));
+        diagnostic
     }
-    fn severity_str(&self) -> &'static str {
-        match self.severity {
-            Severity::Error => "error",
-            Severity::Warning => "warning",
-            Severity::Note => "note",
+}
+impl From<NebulaError> for Diagnostic {
+    fn from(error: NebulaError) -> Self {
+        match error {
+            NebulaError::Diagnostic(diagnostic) => *diagnostic,
+            error => Self::from(&error),
         }
     }
 }
 impl std::fmt::Display for Diagnostic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.format())
+        write!(f, "{}: {}", self.severity.as_str(), self.message)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_format_warning_with_snippet() {
+        let span = Span::new(11, 3, 2, 1);
+        let diagnostic = Diagnostic::warning("unreachable statement", span);
+        let text = diagnostic.format("perm a = 1\nlog(a)\n");
+        assert!(text.starts_with("warning: unreachable statement"));
+        assert!(text.ends_with("2 | log(a)\n  | ^^^"));
+        assert!(Severity::Warning < Severity::Error);
     }
 }
@@ -1,4 +1,11 @@
+mod diagnostic;
+mod render;
+mod suggest;
+use crate::interp::Value;
 use crate::lexer::Span;
+pub use diagnostic::{Diagnostic, Label, Severity};
+pub use render::Renderer;
+pub use suggest::{closest, did_you_mean, edit_distance, suggest};
 use thiserror::Error;
 pub type NebulaResult<T> = Result<T, NebulaError>;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,15 +23,64 @@ pub enum ErrorCode {
     E030,
     E031,
     E032,
+    E033,
     E040,
     E050,
     E060,
     E061,
+    E062,
+    E063,
     E070,
     E071,
+    E072,
     E080,
+    E090,
+    E091,
+    E092,
+    E093,
+    E094,
+    E095,
+    E096,
+    W001,
 }
 impl ErrorCode {
+    pub const ALL: [ErrorCode; 32] = [
+        ErrorCode::E001,
+        ErrorCode::E002,
+        ErrorCode::E003,
+        ErrorCode::E004,
+        ErrorCode::E010,
+        ErrorCode::E011,
+        ErrorCode::E012,
+        ErrorCode::E013,
+        ErrorCode::E020,
+        ErrorCode::E021,
+        ErrorCode::E030,
+        ErrorCode::E031,
+        ErrorCode::E032,
+        ErrorCode::E033,
+        ErrorCode::E040,
+        ErrorCode::E050,
+        ErrorCode::E060,
+        ErrorCode::E061,
+        ErrorCode::E062,
+        ErrorCode::E063,
+        ErrorCode::E070,
+        ErrorCode::E071,
+        ErrorCode::E072,
+        ErrorCode::E080,
+        ErrorCode::E090,
+        ErrorCode::E091,
+        ErrorCode::E092,
+        ErrorCode::E093,
+        ErrorCode::E094,
+        ErrorCode::E095,
+        ErrorCode::E096,
+        ErrorCode::W001,
+    ];
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == code)
+    }
     pub fn as_str(&self) -> &'static str {
         match self {
             ErrorCode::E001 => "E001",
@@ -40,13 +96,25 @@ impl ErrorCode {
             ErrorCode::E030 => "E030",
             ErrorCode::E031 => "E031",
             ErrorCode::E032 => "E032",
+            ErrorCode::E033 => "E033",
             ErrorCode::E040 => "E040",
             ErrorCode::E050 => "E050",
             ErrorCode::E060 => "E060",
             ErrorCode::E061 => "E061",
+            ErrorCode::E062 => "E062",
+            ErrorCode::E063 => "E063",
             ErrorCode::E070 => "E070",
             ErrorCode::E071 => "E071",
+            ErrorCode::E072 => "E072",
             ErrorCode::E080 => "E080",
+            ErrorCode::E090 => "E090",
+            ErrorCode::E091 => "E091",
+            ErrorCode::E092 => "E092",
+            ErrorCode::E093 => "E093",
+            ErrorCode::E094 => "E094",
+            ErrorCode::E095 => "E095",
+            ErrorCode::E096 => "E096",
+            ErrorCode::W001 => "W001",
         }
     }
     pub fn message(&self) -> &'static str {
@@ -64,13 +132,25 @@ impl ErrorCode {
             ErrorCode::E030 => "type mismatch",
             ErrorCode::E031 => "not a number",
             ErrorCode::E032 => "not iterable",
+            ErrorCode::E033 => "frozen value",
             ErrorCode::E040 => "divide by zero",
             ErrorCode::E050 => "stack overflow",
             ErrorCode::E060 => "file not found",
             ErrorCode::E061 => "io failed",
+            ErrorCode::E062 => "module not found",
+            ErrorCode::E063 => "module item not found",
             ErrorCode::E070 => "execution timeout",
             ErrorCode::E071 => "iteration limit",
+            ErrorCode::E072 => "memory limit",
             ErrorCode::E080 => "extension error",
+            ErrorCode::E090 => "incompatible types",
+            ErrorCode::E091 => "invalid operands",
+            ErrorCode::E092 => "invalid iteration",
+            ErrorCode::E093 => "non-exhaustive match",
+            ErrorCode::E094 => "impossible pattern",
+            ErrorCode::E095 => "unknown member",
+            ErrorCode::E096 => "trait not satisfied",
+            ErrorCode::W001 => "deprecated",
         }
     }
 }
@@ -86,20 +166,44 @@ pub enum NebulaError {
     Lexer { message: String, span: Span },
     #[error("Parse error at {span}: {message}")]
     Parse { message: String, span: Span },
-    #[error("Type error at {span}: {message}")]
-    Type { message: String, span: Span },
+    #[error("[{code}] Type error at {span}: {message}")]
+    Type {
+        code: ErrorCode,
+        message: String,
+        span: Span,
+    },
     #[error("Runtime error: {message}")]
-    Runtime { message: String },
+    Runtime { message: String, span: Option<Span> },
+    #[error("Uncaught error: {}", value.repr())]
+    Thrown { value: Value, span: Option<Span> },
     #[error("Undefined variable: {name}")]
-    UndefinedVariable { name: String },
+    UndefinedVariable {
+        name: String,
+        suggestion: Option<String>,
+        span: Option<Span>,
+    },
     #[error("Index out of bounds: {index} (length: {length})")]
-    IndexOutOfBounds { index: i64, length: usize },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+        span: Option<Span>,
+    },
     #[error("Division by zero")]
-    DivisionByZero,
+    DivisionByZero { span: Option<Span> },
     #[error("Invalid operation: {message}")]
-    InvalidOperation { message: String },
+    InvalidOperation { message: String, span: Option<Span> },
     #[error("IO error: {message}")]
     Io { message: String },
+    #[error("Exit with code {code}")]
+    Exit { code: i32 },
+    #[error("{0}")]
+    Diagnostic(Box<Diagnostic>),
+    #[error("{error}")]
+    Caused {
+        error: Box<NebulaError>,
+        #[source]
+        cause: Box<NebulaError>,
+    },
 }
 impl NebulaError {
     pub fn coded(code: ErrorCode, detail: impl Into<String>) -> Self {
@@ -134,6 +238,73 @@ impl NebulaError {
             NebulaError::Lexer { span, .. } => Some(span),
             NebulaError::Parse { span, .. } => Some(span),
             NebulaError::Type { span, .. } => Some(span),
+            NebulaError::Runtime { span, .. }
+            | NebulaError::Thrown { span, .. }
+            | NebulaError::UndefinedVariable { span, .. }
+            | NebulaError::IndexOutOfBounds { span, .. }
+            | NebulaError::DivisionByZero { span }
+            | NebulaError::InvalidOperation { span, .. } => span.as_ref(),
+            NebulaError::Diagnostic(diagnostic) => diagnostic.span.as_ref(),
+            NebulaError::Caused { error, .. } => error.span(),
+            _ => None,
+        }
+    }
+    pub fn caused_by(self, cause: NebulaError) -> Self {
+        NebulaError::Caused {
+            error: Box::new(self),
+            cause: Box::new(cause),
+        }
+    }
+    pub fn cause(&self) -> Option<&NebulaError> {
+        match self {
+            NebulaError::Caused { cause, .. } => Some(cause),
+            _ => None,
+        }
+    }
+    pub fn causes(&self) -> impl Iterator<Item = &NebulaError> {
+        std::iter::successors(self.cause(), |error| error.cause())
+    }
+    pub fn combine(mut errors: Vec<NebulaError>, what: &str) -> Self {
+        if errors.len() == 1 {
+            return errors.remove(0);
+        }
+        let mut diagnostic =
+            Diagnostic::new(Severity::Error, format!("{} {}", errors.len(), what), None);
+        for error in &errors {
+            match error.span() {
+                Some(span) => {
+                    diagnostic = diagnostic.with_label(Label::primary(*span, error.message()))
+                }
+                None => diagnostic = diagnostic.with_note(error.message()),
+            }
+        }
+        NebulaError::Diagnostic(Box::new(diagnostic))
+    }
+    pub fn with_span(mut self, at: Span) -> Self {
+        if let NebulaError::Caused { error, cause } = self {
+            return NebulaError::Caused {
+                error: Box::new(error.with_span(at)),
+                cause,
+            };
+        }
+        match &mut self {
+            NebulaError::Coded { span, .. }
+            | NebulaError::Runtime { span, .. }
+            | NebulaError::Thrown { span, .. }
+            | NebulaError::UndefinedVariable { span, .. }
+            | NebulaError::IndexOutOfBounds { span, .. }
+            | NebulaError::DivisionByZero { span }
+            | NebulaError::InvalidOperation { span, .. } => {
+                span.get_or_insert(at);
+            }
+            _ => {}
+        }
+        self
+    }
+    pub fn payload(&self) -> Option<&Value> {
+        match self {
+            NebulaError::Thrown { value, .. } => Some(value),
+            NebulaError::Caused { error, .. } => error.payload(),
             _ => None,
         }
     }
@@ -143,22 +314,34 @@ impl NebulaError {
             NebulaError::Lexer { message, .. } => message.clone(),
             NebulaError::Parse { message, .. } => message.clone(),
             NebulaError::Type { message, .. } => message.clone(),
-            NebulaError::Runtime { message } => message.clone(),
-            NebulaError::UndefinedVariable { name } => format!("variable not found: {}", name),
-            NebulaError::IndexOutOfBounds { index, length } => {
+            NebulaError::Runtime { message, .. } => message.clone(),
+            NebulaError::Thrown { value, .. } => value.repr(),
+            NebulaError::UndefinedVariable {
+                name, suggestion, ..
+            } => format!(
+                "variable not found: {}{}",
+                name,
+                did_you_mean(suggestion.as_deref())
+            ),
+            NebulaError::IndexOutOfBounds { index, length, .. } => {
                 format!("out of bounds: {} (len {})", index, length)
             }
-            NebulaError::DivisionByZero => "divide by zero".to_string(),
-            NebulaError::InvalidOperation { message } => message.clone(),
+            NebulaError::DivisionByZero { .. } => "divide by zero".to_string(),
+            NebulaError::InvalidOperation { message, .. } => message.clone(),
             NebulaError::Io { message } => message.clone(),
+            NebulaError::Exit { code } => format!("exit({})", code),
+            NebulaError::Diagnostic(diagnostic) => diagnostic.message.clone(),
+            NebulaError::Caused { error, .. } => error.message(),
         }
     }
     pub fn code(&self) -> Option<ErrorCode> {
         match self {
-            NebulaError::Coded { code, .. } => Some(*code),
+            NebulaError::Coded { code, .. } | NebulaError::Type { code, .. } => Some(*code),
             NebulaError::UndefinedVariable { .. } => Some(ErrorCode::E010),
             NebulaError::IndexOutOfBounds { .. } => Some(ErrorCode::E020),
-            NebulaError::DivisionByZero => Some(ErrorCode::E040),
+            NebulaError::DivisionByZero { .. } => Some(ErrorCode::E040),
+            NebulaError::Diagnostic(diagnostic) => diagnostic.code,
+            NebulaError::Caused { error, .. } => error.code(),
             _ => None,
         }
     }
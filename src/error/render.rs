@@ -0,0 +1,274 @@
+use super::{Diagnostic, Label, Severity};
+use crate::lexer::{display_width, expand_tabs, SourceFile, SourceMap};
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    color: bool,
+}
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "color"), allow(dead_code))]
+enum Style {
+    Bold,
+    Gutter,
+    Primary,
+    Severity(Severity),
+}
+struct Placed<'a> {
+    label: &'a Label,
+    start_line: usize,
+    column: usize,
+    start_col: usize,
+    end_line: usize,
+    end_col: usize,
+}
+impl Renderer {
+    pub fn plain() -> Self {
+        Self { color: false }
+    }
+    pub fn colored() -> Self {
+        Self { color: true }
+    }
+    pub fn render(&self, diagnostic: &Diagnostic, name: &str, source: &str) -> String {
+        self.render_in(diagnostic, &SourceMap::single(name, source))
+    }
+    pub fn render_in(&self, diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+        let severity = diagnostic.severity.as_str();
+        let title = match diagnostic.code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity.to_string(),
+        };
+        let mut out = format!(
+            "{}: {}",
+            self.paint(&title, Style::Severity(diagnostic.severity)),
+            self.paint(&diagnostic.message, Style::Bold)
+        );
+        let labels = labels(diagnostic);
+        let anchor = labels.iter().find(|label| label.primary).or(labels.first());
+        let file = anchor.and_then(|label| sources.file(label.span));
+        let placed: Vec<Placed> = match (anchor, file) {
+            (Some(anchor), Some(file)) => labels
+                .iter()
+                .filter(|label| label.span.file == anchor.span.file)
+                .filter_map(|label| place(label, file))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let last_line = placed.iter().map(|p| p.end_line).max().unwrap_or(0);
+        let width = last_line.max(1).to_string().len();
+        let pad = " ".repeat(width);
+        let gutter = self.paint("|", Style::Gutter);
+        if let (Some(first), Some(file)) = (
+            placed.iter().find(|p| p.label.primary).or(placed.first()),
+            file,
+        ) {
+            let name = file.name();
+            let location = if name.is_empty() {
+                format!("{}:{}", first.start_line, first.column)
+            } else {
+                format!("{}:{}:{}", name, first.start_line, first.column)
+            };
+            out.push_str(&format!(
+                "\n{}{} {}\n{} {}",
+                pad,
+                self.paint("-->", Style::Gutter),
+                location,
+                pad,
+                gutter
+            ));
+            let mut shown: Vec<usize> = placed
+                .iter()
+                .flat_map(|p| p.start_line..=p.end_line)
+                .collect();
+            shown.sort_unstable();
+            shown.dedup();
+            let mut previous = None;
+            for line in shown {
+                if previous.is_some_and(|prev| line > prev + 1) {
+                    out.push_str(&format!("\n{}", self.paint("...", Style::Gutter)));
+                }
+                previous = Some(line);
+                let text = expand_tabs(file.line(line).unwrap_or_default());
+                out.push_str(&format!(
+                    "\n{} {} {}",
+                    self.paint(&format!("{:>width$}", line), Style::Gutter),
+                    gutter,
+                    text
+                ));
+                for p in placed
+                    .iter()
+                    .filter(|p| p.start_line <= line && line <= p.end_line)
+                {
+                    out.push_str(&format!(
+                        "\n{} {} {}",
+                        pad,
+                        gutter,
+                        self.underline(p, line, &text)
+                    ));
+                }
+            }
+        }
+        for cause in &diagnostic.causes {
+            out.push_str(&format!(
+                "\n{} {} caused by: {}",
+                pad,
+                self.paint("=", Style::Gutter),
+                cause
+            ));
+        }
+        for note in &diagnostic.notes {
+            out.push_str(&format!(
+                "\n{} {} note: {}",
+                pad,
+                self.paint("=", Style::Gutter),
+                note
+            ));
+        }
+        if let Some(help) = &diagnostic.help {
+            out.push_str(&format!(
+                "\n{} {} help: {}",
+                pad,
+                self.paint("=", Style::Gutter),
+                help
+            ));
+        }
+        out
+    }
+    fn underline(&self, placed: &Placed, line: usize, text: &str) -> String {
+        let indent = display_width(&text[..text.len() - text.trim_start().len()]) + 1;
+        let line_end = display_width(text) + 1;
+        let from = if line == placed.start_line {
+            placed.start_col
+        } else {
+            indent
+        };
+        let to = if line == placed.end_line {
+            placed.end_col
+        } else {
+            line_end
+        };
+        let marker = if placed.label.primary { "^" } else { "-" };
+        let mut row = format!(
+            "{}{}",
+            " ".repeat(from.saturating_sub(1)),
+            marker.repeat(to.saturating_sub(from).max(1))
+        );
+        if line == placed.end_line && !placed.label.message.is_empty() {
+            row.push(' ');
+            row.push_str(&placed.label.message);
+        }
+        if placed.label.primary {
+            self.paint(&row, Style::Primary)
+        } else {
+            self.paint(&row, Style::Gutter)
+        }
+    }
+    fn paint(&self, text: &str, style: Style) -> String {
+        if self.color {
+            style.apply(text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+impl Style {
+    #[cfg(feature = "color")]
+    fn apply(self, text: &str) -> String {
+        use colored::Colorize;
+        let styled = match self {
+            Style::Bold => text.bold(),
+            Style::Gutter => text.blue().bold(),
+            Style::Primary | Style::Severity(Severity::Error) => text.red().bold(),
+            Style::Severity(Severity::Warning) => text.yellow().bold(),
+            Style::Severity(Severity::Note) => text.cyan().bold(),
+        };
+        styled.to_string()
+    }
+    #[cfg(not(feature = "color"))]
+    fn apply(self, text: &str) -> String {
+        text.to_string()
+    }
+}
+fn labels(diagnostic: &Diagnostic) -> Vec<Label> {
+    let mut labels = diagnostic.labels.clone();
+    if let Some(span) = diagnostic.span {
+        if !labels.iter().any(|label| label.primary) {
+            labels.insert(0, Label::primary(span, ""));
+        }
+    }
+    labels
+}
+fn place<'a>(label: &'a Label, file: &SourceFile) -> Option<Placed<'a>> {
+    let span = label.span;
+    if span.line == 0 || span.start > file.source().len() {
+        return None;
+    }
+    let start = file.position(span.start);
+    let end = file.position(span.end());
+    Some(Placed {
+        label,
+        start_line: start.line,
+        column: start.column,
+        start_col: start.display_column,
+        end_line: end.line,
+        end_col: end.display_column,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::lexer::Span;
+    #[test]
+    fn test_render_primary_and_secondary_labels() {
+        let source = "fn main() do\n  log(1)\n\n\n  log(2)\n";
+        let diagnostic = Diagnostic::new(Severity::Error, "unclosed block", None)
+            .with_code(ErrorCode::E003)
+            .with_label(Label::primary(Span::new(33, 0, 6, 1), "file ends here"))
+            .with_label(Label::secondary(
+                Span::new(10, 2, 1, 11),
+                "block opened here",
+            ))
+            .with_help("add 'end' to close the block");
+        let text = Renderer::plain().render(&diagnostic, "main.na", source);
+        let expected = [
+            "error[E003]: unclosed block",
+            " --> main.na:6:1",
+            "  |",
+            "1 | fn main() do",
+            "  |           -- block opened here",
+            "...",
+            "6 | ",
+            "  | ^ file ends here",
+            "  = help: add 'end' to close the block",
+        ];
+        assert_eq!(text, expected.join("\n"));
+    }
+    #[test]
+    fn test_render_multiline_label() {
+        let source = "perm x = \"a\n  b\"\n";
+        let span = Span::new(9, 7, 1, 10);
+        let diagnostic = Diagnostic::error("bad string", span)
+            .with_label(Label::primary(span, "spans two lines"))
+            .with_note("strings may not contain raw newlines");
+        let text = Renderer::plain().render(&diagnostic, "", source);
+        assert!(
+            text.contains("1 | perm x = \"a\n  |          ^^\n2 |   b\"\n  |   ^^ spans two lines")
+        );
+        assert!(text.ends_with("  = note: strings may not contain raw newlines"));
+    }
+    #[test]
+    fn test_render_aligns_carets_after_tabs_and_wide_chars() {
+        let source = "fn f() do\r\n\tlog(\"🚀\", 名前)\r\nend\r\n";
+        let start = source.find('名').unwrap();
+        let span = Span::new(start, '名'.len_utf8() * 2, 2, 10);
+        let diagnostic = Diagnostic::error("undefined variable", span);
+        let text = Renderer::plain().render(&diagnostic, "tabs.na", source);
+        let expected = [
+            "error: undefined variable",
+            " --> tabs.na:2:11",
+            "  |",
+            "2 |     log(\"🚀\", 名前)",
+            "  |               ^^^^",
+        ];
+        assert_eq!(text, expected.join("\n"));
+    }
+}
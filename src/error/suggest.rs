@@ -0,0 +1,67 @@
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    d[0] = (0..=b.len()).collect();
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let length = name.chars().count();
+    if length < 2 {
+        return None;
+    }
+    let limit = (length / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name && candidate.chars().count() > 1)
+        .filter_map(|candidate| {
+            let distance = edit_distance(name, candidate);
+            if distance <= limit {
+                Some((0, distance, candidate))
+            } else if candidate.len() >= 3 && name.starts_with(candidate) {
+                Some((1, distance, candidate))
+            } else {
+                None
+            }
+        })
+        .min()
+        .map(|(_, _, candidate)| candidate)
+}
+pub fn did_you_mean(suggestion: Option<&str>) -> String {
+    suggestion
+        .map(|s| format!(" (did you mean '{}'?)", s))
+        .unwrap_or_default()
+}
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    did_you_mean(closest(name, candidates))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_closest_name() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        let names = ["count", "counter", "log", "len"];
+        assert_eq!(closest("cuont", names), Some("count"));
+        assert_eq!(closest("lgo", names), Some("log"));
+        assert_eq!(closest("zzzzzz", names), None);
+        assert_eq!(closest("lenght", names), Some("len"));
+        assert_eq!(closest("x", ["E", "y"]), None);
+        assert_eq!(closest("ab", ["a", "b"]), None);
+        assert_eq!(suggest("lgo", names), " (did you mean 'log'?)");
+    }
+}
@@ -1,5 +1,21 @@
+pub mod abi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 use crate::error::{ErrorCode, NebulaError, NebulaResult};
-use crate::interp::Value;
+use crate::interp::{NativeContext, Value};
+use crate::lexer::Span;
+use crate::parser::ast::{Item, Program, StmtKind, Use};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+pub const EXTENSION_ABI: u32 = 3;
+pub const EXTENSION_INIT: &str = "nebula_extension_init";
+pub type ExtensionInit = unsafe extern "C" fn(abi: u32) -> *const abi::ExtensionVTable;
 pub type ExtResult<T> = Result<T, ExtError>;
 #[derive(Debug, Clone)]
 pub struct ExtError {
@@ -11,22 +27,77 @@ impl ExtError {
             message: msg.into(),
         }
     }
+    pub(crate) fn raise(self) -> NebulaError {
+        match crate::builtins::native_error(self.message.clone()) {
+            exit @ NebulaError::Exit { .. } => exit,
+            _ => self.into(),
+        }
+    }
 }
 impl From<ExtError> for NebulaError {
     fn from(e: ExtError) -> Self {
-        NebulaError::coded(ErrorCode::E080, e.message)
+        match crate::builtins::coded_message(&e.message) {
+            Some((code @ (ErrorCode::E070 | ErrorCode::E072), msg)) => NebulaError::Coded {
+                code,
+                msg: msg.to_string(),
+                span: None,
+            },
+            _ => NebulaError::coded(ErrorCode::E080, e.message),
+        }
     }
 }
 pub struct ExtensionContext<'a> {
     pub fn_name: &'a str,
     pub argc: usize,
+    host: Option<&'a mut dyn NativeContext>,
 }
 impl<'a> ExtensionContext<'a> {
     pub fn new(fn_name: &'a str, argc: usize) -> Self {
-        Self { fn_name, argc }
+        Self {
+            fn_name,
+            argc,
+            host: None,
+        }
+    }
+    pub fn with_host(fn_name: &'a str, argc: usize, host: &'a mut dyn NativeContext) -> Self {
+        Self {
+            fn_name,
+            argc,
+            host: Some(host),
+        }
+    }
+    fn host(&mut self) -> ExtResult<&mut (dyn NativeContext + 'a)> {
+        let fn_name = self.fn_name;
+        self.host
+            .as_deref_mut()
+            .ok_or_else(|| ExtError::new(format!("{}: no script is attached", fn_name)))
+    }
+    pub fn call(&mut self, callee: &Value, args: &[Value]) -> ExtResult<Value> {
+        self.host()?.call(callee, args).map_err(ExtError::new)
+    }
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.host.as_ref()?.lookup(name)
+    }
+    pub fn define(&mut self, name: &str, value: Value) -> ExtResult<()> {
+        self.host()?.define(name, value).map_err(ExtError::new)
+    }
+    pub fn policy(&self) -> &crate::builtins::SandboxPolicy {
+        match &self.host {
+            Some(host) => host.policy(),
+            None => &crate::builtins::LOCKED,
+        }
+    }
+    pub fn limits(&self) -> crate::limits::ResourceLimits {
+        crate::limits::limits()
+    }
+    pub fn check_limits(&self) -> ExtResult<()> {
+        crate::limits::check().map_err(|e| ExtError::new(e.to_string()))
+    }
+    pub fn charge(&self, bytes: usize) -> ExtResult<()> {
+        crate::limits::charge(bytes).map_err(|e| ExtError::new(e.to_string()))
     }
 }
-pub type NativeFn = fn(&ExtensionContext, &[Value]) -> ExtResult<Value>;
+pub type NativeFn = Arc<dyn Fn(&mut ExtensionContext, &[Value]) -> ExtResult<Value> + Send + Sync>;
 #[derive(Clone)]
 pub struct ExtFunction {
     pub name: String,
@@ -35,20 +106,26 @@ pub struct ExtFunction {
     pub func: NativeFn,
 }
 impl ExtFunction {
-    pub fn new(name: impl Into<String>, func: NativeFn) -> Self {
+    pub fn new<F>(name: impl Into<String>, func: F) -> Self
+    where
+        F: Fn(&mut ExtensionContext, &[Value]) -> ExtResult<Value> + Send + Sync + 'static,
+    {
         Self {
             name: name.into(),
             min_args: 0,
             max_args: None,
-            func,
+            func: Arc::new(func),
         }
     }
-    pub fn with_arity(name: impl Into<String>, arity: usize, func: NativeFn) -> Self {
+    pub fn with_arity<F>(name: impl Into<String>, arity: usize, func: F) -> Self
+    where
+        F: Fn(&mut ExtensionContext, &[Value]) -> ExtResult<Value> + Send + Sync + 'static,
+    {
         Self {
             name: name.into(),
             min_args: arity,
             max_args: Some(arity),
-            func,
+            func: Arc::new(func),
         }
     }
     pub fn validate_args(&self, argc: usize) -> ExtResult<()> {
@@ -68,10 +145,336 @@ impl ExtFunction {
         }
         Ok(())
     }
+    pub fn call(&self, args: &[Value]) -> NebulaResult<Value> {
+        self.validate_args(args.len())?;
+        let mut ctx = ExtensionContext::new(&self.name, args.len());
+        (self.func)(&mut ctx, args).map_err(|e| e.into())
+    }
+    pub fn call_with(&self, host: &mut dyn NativeContext, args: &[Value]) -> NebulaResult<Value> {
+        self.validate_args(args.len())?;
+        crate::limits::check()?;
+        let mut ctx = ExtensionContext::with_host(&self.name, args.len(), host);
+        (self.func)(&mut ctx, args).map_err(ExtError::raise)
+    }
+}
+impl std::fmt::Debug for ExtFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+type Settle = Box<dyn FnOnce() -> ExtResult<Value> + Send>;
+type Job = Box<dyn FnOnce() + Send>;
+fn workers() -> &'static Mutex<mpsc::Sender<Job>> {
+    static WORKERS: OnceLock<Mutex<mpsc::Sender<Job>>> = OnceLock::new();
+    WORKERS.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let count = std::thread::available_parallelism().map_or(4, |n| n.get().clamp(2, 8));
+        for index in 0..count {
+            let receiver = Arc::clone(&receiver);
+            std::thread::Builder::new()
+                .name(format!("nebula-ext-{}", index))
+                .spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        Ok(job) => {
+                            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                        }
+                        Err(_) => return,
+                    }
+                })
+                .expect("failed to start extension worker");
+        }
+        Mutex::new(sender)
+    })
+}
+struct Alarm {
+    at: Instant,
+    every: Option<Duration>,
+    sender: SyncSender<Settle>,
+}
+fn alarms() -> &'static Mutex<mpsc::Sender<Alarm>> {
+    static ALARMS: OnceLock<Mutex<mpsc::Sender<Alarm>>> = OnceLock::new();
+    ALARMS.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Alarm>();
+        std::thread::Builder::new()
+            .name("nebula-timer".to_string())
+            .spawn(move || {
+                let mut alarms: Vec<Alarm> = Vec::new();
+                loop {
+                    let received = match alarms.iter().map(|alarm| alarm.at).min() {
+                        Some(at) => {
+                            receiver.recv_timeout(at.saturating_duration_since(Instant::now()))
+                        }
+                        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    match received {
+                        Ok(alarm) => alarms.push(alarm),
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                    let now = Instant::now();
+                    alarms.retain_mut(|alarm| {
+                        if alarm.at > now {
+                            return true;
+                        }
+                        let fired = alarm.sender.try_send(Box::new(|| Ok(Value::Nil)));
+                        match alarm.every {
+                            Some(every) if !matches!(fired, Err(TrySendError::Disconnected(_))) => {
+                                alarm.at += every;
+                                true
+                            }
+                            _ => false,
+                        }
+                    });
+                }
+            })
+            .expect("failed to start timer thread");
+        Mutex::new(sender)
+    })
+}
+thread_local! {
+    static TASKS: RefCell<VecDeque<ExtFuture>> = const { RefCell::new(VecDeque::new()) };
+}
+pub(crate) fn run_tasks(ctx: &mut dyn NativeContext) -> NebulaResult<()> {
+    while let Some(task) = TASKS.with(|tasks| tasks.borrow_mut().pop_front()) {
+        let failed = match task.start(ctx) {
+            Err(e) => Some(e.raise()),
+            Ok(()) => match &*task.0.borrow() {
+                FutureState::Failed(message) => {
+                    Some(crate::builtins::native_error(message.clone()))
+                }
+                _ => None,
+            },
+        };
+        if let Some(error) = failed {
+            drop_tasks();
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+pub(crate) fn drop_tasks() {
+    TASKS.with(|tasks| tasks.borrow_mut().clear());
+}
+enum FutureState {
+    Pending(Receiver<Settle>),
+    Interval {
+        receiver: Receiver<Settle>,
+        ticks: i64,
+        due: bool,
+    },
+    Task(Value, Vec<Value>),
+    Running,
+    Failed(String),
+    Done(Result<Value, String>),
+}
+#[derive(Clone)]
+pub struct ExtFuture(Rc<RefCell<FutureState>>);
+impl ExtFuture {
+    pub fn spawn<T: Send + 'static>(
+        work: impl FnOnce() -> ExtResult<T> + Send + 'static,
+        finish: fn(T) -> Value,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel::<Settle>();
+        let job: Job = Box::new(move || {
+            let result = work();
+            let _ = sender.send(Box::new(move || result.map(finish)));
+        });
+        let _ = workers().lock().map(|workers| workers.send(job));
+        Self::new(FutureState::Pending(receiver))
+    }
+    pub fn timer(after: Duration) -> Self {
+        Self::new(FutureState::Pending(Self::alarm(after, None)))
+    }
+    pub fn interval(every: Duration) -> Self {
+        Self::new(FutureState::Interval {
+            receiver: Self::alarm(every, Some(every)),
+            ticks: 0,
+            due: false,
+        })
+    }
+    pub fn task(callee: Value, args: Vec<Value>) -> Self {
+        let future = Self::new(FutureState::Task(callee, args));
+        TASKS.with(|tasks| tasks.borrow_mut().push_back(future.clone()));
+        future
+    }
+    pub fn resolved(value: Value) -> Self {
+        Self::new(FutureState::Done(Ok(value)))
+    }
+    fn new(state: FutureState) -> Self {
+        Self(Rc::new(RefCell::new(state)))
+    }
+    fn alarm(after: Duration, every: Option<Duration>) -> Receiver<Settle> {
+        let (sender, receiver) = mpsc::sync_channel::<Settle>(1);
+        let alarm = Alarm {
+            at: Instant::now() + after,
+            every,
+            sender,
+        };
+        let _ = alarms().lock().map(|alarms| alarms.send(alarm));
+        receiver
+    }
+    pub fn is_ready(&self) -> bool {
+        self.settle(false)
+    }
+    pub fn wait(&self, ctx: &mut dyn NativeContext) -> ExtResult<Value> {
+        while !self.settle(false) {
+            match TASKS.with(|tasks| tasks.borrow_mut().pop_front()) {
+                Some(task) => task.start(ctx)?,
+                None => {
+                    self.settle(true);
+                    break;
+                }
+            }
+        }
+        match &mut *self.0.borrow_mut() {
+            FutureState::Done(result) => result.clone().map_err(ExtError::new),
+            FutureState::Failed(message) => Err(ExtError::new(message.clone())),
+            FutureState::Interval { ticks, due, .. } if *due => {
+                *due = false;
+                *ticks += 1;
+                Ok(Value::Integer(*ticks))
+            }
+            FutureState::Running => Err(ExtError::new("task awaits itself")),
+            FutureState::Pending(_) | FutureState::Interval { .. } | FutureState::Task(..) => {
+                Err(ExtError::new(
+                    NebulaError::coded(ErrorCode::E070, "deadline exceeded awaiting future")
+                        .to_string(),
+                ))
+            }
+        }
+    }
+    pub(crate) fn join(&self, ctx: &mut dyn NativeContext) -> NebulaResult<Value> {
+        self.wait(ctx).map_err(|e| match &*self.0.borrow() {
+            FutureState::Failed(_) => crate::builtins::native_error(e.message),
+            _ => e.raise(),
+        })
+    }
+    fn start(&self, ctx: &mut dyn NativeContext) -> ExtResult<()> {
+        let state = std::mem::replace(&mut *self.0.borrow_mut(), FutureState::Running);
+        let (callee, args) = match state {
+            FutureState::Task(callee, args) => (callee, args),
+            state => {
+                *self.0.borrow_mut() = state;
+                return Ok(());
+            }
+        };
+        let result = ctx.call(&callee, &args).and_then(|value| match value {
+            Value::Future(future) => future.wait(ctx).map_err(|e| e.message),
+            value => Ok(value),
+        });
+        match result {
+            Ok(value) => *self.0.borrow_mut() = FutureState::Done(Ok(value)),
+            Err(message) => {
+                let error = crate::builtins::native_error(message.clone());
+                *self.0.borrow_mut() = FutureState::Failed(message);
+                if let NebulaError::Exit { .. } = error {
+                    return Err(ExtError::new(crate::builtins::callback_error(error)));
+                }
+            }
+        }
+        Ok(())
+    }
+    fn settle(&self, block: bool) -> bool {
+        let mut state = self.0.borrow_mut();
+        let receiver = match &mut *state {
+            FutureState::Pending(receiver) => receiver,
+            FutureState::Interval { receiver, due, .. } => {
+                if !*due {
+                    *due = Self::receive(receiver, block).is_ok();
+                }
+                return *due;
+            }
+            FutureState::Task(..) | FutureState::Running => return false,
+            FutureState::Failed(_) | FutureState::Done(_) => return true,
+        };
+        let result = match Self::receive(receiver, block) {
+            Ok(finish) => finish().map_err(|e| e.message),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => Err("extension task panicked".to_string()),
+        };
+        *state = FutureState::Done(result);
+        true
+    }
+    fn receive(receiver: &Receiver<Settle>, block: bool) -> Result<Settle, TryRecvError> {
+        match (block, crate::limits::remaining()) {
+            (true, Some(timeout)) => receiver.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => TryRecvError::Empty,
+                RecvTimeoutError::Disconnected => TryRecvError::Disconnected,
+            }),
+            (true, None) => receiver.recv().map_err(|_| TryRecvError::Disconnected),
+            (false, _) => receiver.try_recv(),
+        }
+    }
+}
+impl std::fmt::Debug for ExtFuture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &*self.0.borrow() {
+            FutureState::Interval { due: true, .. }
+            | FutureState::Failed(_)
+            | FutureState::Done(_) => {
+                write!(f, "<future ready>")
+            }
+            _ => write!(f, "<future pending>"),
+        }
+    }
+}
+struct ExternData {
+    owner: String,
+    type_name: String,
+    data: Box<dyn Any>,
+}
+#[derive(Clone)]
+pub struct Extern(Rc<ExternData>);
+impl Extern {
+    pub fn new<T: Any>(owner: &str, type_name: &str, data: T) -> Self {
+        Self(Rc::new(ExternData {
+            owner: owner.to_string(),
+            type_name: type_name.to_string(),
+            data: Box::new(data),
+        }))
+    }
+    pub fn owner(&self) -> &str {
+        &self.0.owner
+    }
+    pub fn type_name(&self) -> &str {
+        &self.0.type_name
+    }
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.data.downcast_ref()
+    }
+    pub fn ptr_eq(&self, other: &Extern) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+impl std::fmt::Debug for Extern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}>", self.0.type_name)
+    }
 }
 pub trait Extension: Send + Sync {
     fn name(&self) -> &str;
     fn functions(&self) -> Vec<ExtFunction>;
+    fn module(&self) -> Option<&str> {
+        None
+    }
+    fn call_method(
+        &self,
+        _ctx: &mut ExtensionContext,
+        handle: &Extern,
+        method: &str,
+        _args: &[Value],
+    ) -> ExtResult<Value> {
+        Err(ExtError::new(format!(
+            "No method '{}' on {}",
+            method,
+            handle.type_name()
+        )))
+    }
     fn on_load(&self) -> ExtResult<()> {
         Ok(())
     }
@@ -79,36 +482,107 @@ pub trait Extension: Send + Sync {
         Ok(())
     }
 }
+struct Library {
+    path: PathBuf,
+    functions: Vec<String>,
+}
 pub struct ExtensionRegistry {
-    extensions: Vec<Box<dyn Extension>>,
+    extensions: Vec<Arc<dyn Extension>>,
     functions: std::collections::HashMap<String, ExtFunction>,
+    modules: std::collections::HashMap<String, Vec<String>>,
+    libraries: Vec<Library>,
 }
 impl ExtensionRegistry {
     pub fn new() -> Self {
         Self {
             extensions: Vec::new(),
             functions: std::collections::HashMap::new(),
+            modules: std::collections::HashMap::new(),
+            libraries: Vec::new(),
+        }
+    }
+    pub fn load_library(&mut self, name: &str) -> ExtResult<Vec<String>> {
+        let path = library_path(name);
+        if let Some(library) = self.libraries.iter().find(|l| l.path == path) {
+            return Ok(library.functions.clone());
         }
+        let fail = |message: String| ExtError::new(format!("{}: {}", path.display(), message));
+        let extension = if path.extension().is_some_and(|ext| ext == "wasm") {
+            load_wasm(&path).map_err(fail)?
+        } else {
+            load_shared(&path).map_err(fail)?
+        };
+        let functions: Vec<_> = exported(extension.as_ref())
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        self.register(extension)?;
+        self.libraries.push(Library {
+            path,
+            functions: functions.clone(),
+        });
+        Ok(functions)
     }
     pub fn register(&mut self, ext: Box<dyn Extension>) -> ExtResult<()> {
+        let functions = exported(ext.as_ref());
+        if let Some(module) = ext.module() {
+            if self.modules.contains_key(module) {
+                return Err(ExtError::new(format!(
+                    "extension module '{}' is already registered",
+                    module
+                )));
+            }
+            if crate::builtins::is_builtin(module) {
+                return Err(ExtError::new(format!(
+                    "extension module '{}' collides with the builtin of the same name",
+                    module
+                )));
+            }
+        }
+        if let Some(func) = functions
+            .iter()
+            .find(|f| self.functions.contains_key(&f.name))
+        {
+            return Err(ExtError::new(format!(
+                "extension function '{}' is already registered",
+                func.name
+            )));
+        }
         ext.on_load()?;
-        for func in ext.functions() {
+        if let Some(module) = ext.module() {
+            let names = functions.iter().map(|f| f.name.clone()).collect();
+            self.modules.insert(module.to_string(), names);
+        }
+        for func in functions {
             self.functions.insert(func.name.clone(), func);
         }
-        self.extensions.push(ext);
+        self.extensions.push(Arc::from(ext));
         Ok(())
     }
+    pub fn extension(&self, name: &str) -> Option<Arc<dyn Extension>> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.name() == name)
+            .cloned()
+    }
     pub fn get_function(&self, name: &str) -> Option<&ExtFunction> {
         self.functions.get(name)
     }
+    pub fn module_functions(&self, module: &str) -> Option<Vec<ExtFunction>> {
+        let names = self.modules.get(module)?;
+        Some(
+            names
+                .iter()
+                .filter_map(|name| self.functions.get(name).cloned())
+                .collect(),
+        )
+    }
     pub fn call(&self, name: &str, args: &[Value]) -> NebulaResult<Value> {
         let func = self
             .functions
             .get(name)
             .ok_or_else(|| NebulaError::coded(ErrorCode::E010, name))?;
-        func.validate_args(args.len())?;
-        let ctx = ExtensionContext::new(name, args.len());
-        (func.func)(&ctx, args).map_err(|e| e.into())
+        func.call(args)
     }
 }
 impl Default for ExtensionRegistry {
@@ -116,11 +590,188 @@ impl Default for ExtensionRegistry {
         Self::new()
     }
 }
+pub fn native_extensions() -> MutexGuard<'static, ExtensionRegistry> {
+    static REGISTRY: OnceLock<Mutex<ExtensionRegistry>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+pub fn load_native(
+    name: &str,
+    span: Span,
+    policy: &crate::builtins::SandboxPolicy,
+) -> NebulaResult<Vec<ExtFunction>> {
+    let allowed = if library_path(name)
+        .extension()
+        .is_some_and(|ext| ext == "wasm")
+    {
+        policy.check_read("use native", name)
+    } else {
+        policy.check_exec("use native")
+    };
+    allowed.map_err(|message| NebulaError::coded_at(ErrorCode::E080, message, span))?;
+    let mut registry = native_extensions();
+    let names = registry
+        .load_library(name)
+        .map_err(|e| NebulaError::coded_at(ErrorCode::E080, e.message, span))?;
+    Ok(names
+        .iter()
+        .filter_map(|name| registry.get_function(name).cloned())
+        .collect())
+}
+pub fn call_extern_method(
+    host: &mut dyn NativeContext,
+    handle: &Extern,
+    method: &str,
+    args: &[Value],
+) -> NebulaResult<Value> {
+    let owner = native_extensions()
+        .extension(handle.owner())
+        .ok_or_else(|| {
+            NebulaError::coded(
+                ErrorCode::E080,
+                format!(
+                    "{}: extension '{}' is not loaded",
+                    handle.type_name(),
+                    handle.owner()
+                ),
+            )
+        })?;
+    let name = format!("{}:{}", handle.type_name(), method);
+    let mut ctx = ExtensionContext::with_host(&name, args.len(), host);
+    Ok(owner.call_method(&mut ctx, handle, method, args)?)
+}
+pub fn import_module(program: &Program, module: &Use) -> NebulaResult<Option<Vec<ExtFunction>>> {
+    let Some(mut functions) = native_extensions().module_functions(&module.path) else {
+        return Ok(None);
+    };
+    let prefix = format!("{}.", module.path);
+    let mut bound = vec![module.binding().to_string()];
+    if !module.items.is_empty() {
+        if let Some(missing) = module.items.iter().find(|name| {
+            !functions
+                .iter()
+                .any(|f| f.name.strip_prefix(&prefix) == Some(name.as_str()))
+        }) {
+            return Err(NebulaError::coded_at(
+                ErrorCode::E063,
+                format!("'{}' in module {}", missing, module.path),
+                module.span,
+            ));
+        }
+        functions.retain(|f| {
+            f.name
+                .strip_prefix(&prefix)
+                .is_some_and(|name| module.items.iter().any(|item| item == name))
+        });
+        bound = module.items.clone();
+    }
+    let globals = declared_globals(program);
+    let collision = bound.iter().find_map(|name| {
+        if crate::builtins::is_builtin(name) {
+            Some((name, "builtin"))
+        } else if globals.contains(name) {
+            Some((name, "global"))
+        } else {
+            None
+        }
+    });
+    let Some((name, kind)) = collision else {
+        return Ok(Some(functions));
+    };
+    Err(NebulaError::coded_at(
+        ErrorCode::E080,
+        format!(
+            "extension module '{}' binds '{}', which collides with the {} of the same name",
+            module.path, name, kind
+        ),
+        module.span,
+    ))
+}
+pub(crate) fn declared_globals(program: &Program) -> Vec<String> {
+    program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(f) => Some(f.name.clone()),
+            Item::Struct(s) => Some(s.name.clone()),
+            Item::Statement(stmt) => match &stmt.kind {
+                StmtKind::Var { name, .. } | StmtKind::Const { name, .. } => Some(name.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+fn exported(ext: &dyn Extension) -> Vec<ExtFunction> {
+    let mut functions = ext.functions();
+    if let Some(module) = ext.module() {
+        for func in &mut functions {
+            func.name = format!("{}.{}", module, func.name);
+        }
+    }
+    functions
+}
+#[macro_export]
+macro_rules! export_extension {
+    ($extension:expr) => {
+        #[no_mangle]
+        pub extern "C" fn nebula_extension_init(
+            abi: u32,
+        ) -> *const $crate::ext::abi::ExtensionVTable {
+            static EXPORTED: ::std::sync::OnceLock<$crate::ext::abi::Exported> =
+                ::std::sync::OnceLock::new();
+            if abi != $crate::ext::EXTENSION_ABI {
+                return ::std::ptr::null();
+            }
+            EXPORTED
+                .get_or_init(|| $crate::ext::abi::Exported::new(Box::new($extension)))
+                .vtable()
+        }
+    };
+}
+#[cfg(feature = "ext-dynamic")]
+fn load_shared(path: &Path) -> Result<Box<dyn Extension>, String> {
+    // SAFETY: loading a library runs its initialisers; `use native` is gated on the exec
+    // permission because a shared library is trusted native code.
+    let library = unsafe { libloading::Library::new(path) }.map_err(|e| e.to_string())?;
+    // SAFETY: `ExtensionInit` is the signature `export_extension!` gives this symbol; a library
+    // exporting the name with another type is a broken extension we cannot detect.
+    let vtable = unsafe {
+        let init = library
+            .get::<ExtensionInit>(EXTENSION_INIT.as_bytes())
+            .map_err(|e| format!("missing symbol '{}': {}", EXTENSION_INIT, e))?;
+        init(EXTENSION_ABI)
+    };
+    // SAFETY: the vtable lives in the library's statics, and `SharedExtension` keeps `library`
+    // loaded for as long as the extension or any of its functions are alive.
+    Ok(Box::new(unsafe { abi::SharedExtension::load(vtable, library) }?))
+}
+#[cfg(not(feature = "ext-dynamic"))]
+fn load_shared(_path: &Path) -> Result<Box<dyn Extension>, String> {
+    Err("native extensions require building with the 'ext-dynamic' feature".to_string())
+}
+#[cfg(feature = "wasm")]
+fn load_wasm(path: &Path) -> Result<Box<dyn Extension>, String> {
+    Ok(Box::new(wasm::WasmExtension::load(path)?))
+}
+#[cfg(not(feature = "wasm"))]
+fn load_wasm(_path: &Path) -> Result<Box<dyn Extension>, String> {
+    Err("WebAssembly extensions require building with the 'wasm' feature".to_string())
+}
+fn library_path(name: &str) -> PathBuf {
+    let path = PathBuf::from(name);
+    if path.extension().is_some() {
+        return path;
+    }
+    PathBuf::from(format!("{}{}", name, std::env::consts::DLL_SUFFIX))
+}
 #[cfg(test)]
 mod tests {
     use super::*;
-    fn test_add(_ctx: &ExtensionContext, args: &[Value]) -> ExtResult<Value> {
-        let a = args.get(0).and_then(|v| v.as_number()).unwrap_or(0.0);
+    fn test_add(_ctx: &mut ExtensionContext, args: &[Value]) -> ExtResult<Value> {
+        let a = args.first().and_then(|v| v.as_number()).unwrap_or(0.0);
         let b = args.get(1).and_then(|v| v.as_number()).unwrap_or(0.0);
         Ok(Value::Number(a + b))
     }
@@ -130,4 +781,63 @@ mod tests {
         assert!(func.validate_args(2).is_ok());
         assert!(func.validate_args(1).is_err());
     }
+    struct Shapes(&'static str);
+    impl Extension for Shapes {
+        fn name(&self) -> &str {
+            "shapes"
+        }
+        fn functions(&self) -> Vec<ExtFunction> {
+            vec![ExtFunction::with_arity("add", 2, test_add)]
+        }
+        fn module(&self) -> Option<&str> {
+            Some(self.0)
+        }
+    }
+    #[test]
+    fn test_module_registration() {
+        let mut registry = ExtensionRegistry::new();
+        registry.register(Box::new(Shapes("geo"))).unwrap();
+        let functions = registry.module_functions("geo").unwrap();
+        assert_eq!(functions[0].name, "geo.add");
+        assert!(registry.get_function("add").is_none());
+        assert!(registry.register(Box::new(Shapes("geo"))).is_err());
+        let builtin = registry.register(Box::new(Shapes("len"))).unwrap_err();
+        assert!(builtin.message.contains("collides with the builtin"));
+    }
+    #[test]
+    fn test_future_settles_off_thread() {
+        let future = ExtFuture::spawn(|| Ok(21), |n: i64| Value::Integer(n * 2));
+        let ctx = &mut crate::builtins::TestContext;
+        assert_eq!(future.wait(ctx).unwrap(), Value::Integer(42));
+        assert!(future.is_ready());
+        let failed = ExtFuture::spawn(|| Err::<(), _>(ExtError::new("refused")), |_| Value::Nil);
+        assert_eq!(failed.wait(ctx).unwrap_err().message, "refused");
+        assert!(ExtFuture::resolved(Value::Nil).is_ready());
+    }
+    #[test]
+    fn test_load_library_errors() {
+        let mut registry = ExtensionRegistry::new();
+        let missing = registry.load_library("no_such_nebula_ext").unwrap_err();
+        assert!(missing.message.starts_with(&format!(
+            "no_such_nebula_ext{}: ",
+            std::env::consts::DLL_SUFFIX
+        )));
+        #[cfg(not(feature = "ext-dynamic"))]
+        assert!(missing
+            .message
+            .ends_with("require building with the 'ext-dynamic' feature"));
+        #[cfg(all(target_os = "linux", feature = "ext-dynamic"))]
+        {
+            let libc = registry.load_library("libc.so.6").unwrap_err();
+            assert!(libc
+                .message
+                .contains("missing symbol 'nebula_extension_init'"));
+        }
+        #[cfg(not(feature = "wasm"))]
+        assert!(registry
+            .load_library("plugin.wasm")
+            .unwrap_err()
+            .message
+            .ends_with("require building with the 'wasm' feature"));
+    }
 }
@@ -0,0 +1,378 @@
+use super::{ExtError, ExtFunction, ExtResult, Extension, EXTENSION_ABI};
+use crate::interp::Value;
+use serde_json::{json, Map, Number};
+use std::ffi::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, OnceLock};
+pub type AbiDescribe = unsafe extern "C" fn(state: *const c_void, out_len: *mut usize) -> *mut u8;
+pub type AbiCall = unsafe extern "C" fn(
+    state: *const c_void,
+    name: *const u8,
+    name_len: usize,
+    input: *const u8,
+    input_len: usize,
+    out_len: *mut usize,
+) -> *mut u8;
+pub type AbiFree = unsafe extern "C" fn(ptr: *mut u8, len: usize);
+#[repr(C)]
+pub struct ExtensionVTable {
+    pub abi: u32,
+    pub state: *const c_void,
+    pub describe: AbiDescribe,
+    pub call: AbiCall,
+    pub free: AbiFree,
+}
+// SAFETY: `state` only ever points at a `'static` `Exported`, whose extension is `Send + Sync`,
+// and the function pointers are plain code addresses.
+unsafe impl Send for ExtensionVTable {}
+// SAFETY: as above; the table itself is never mutated after it is built.
+unsafe impl Sync for ExtensionVTable {}
+pub struct Exported {
+    extension: Box<dyn Extension>,
+    functions: Vec<ExtFunction>,
+    vtable: OnceLock<ExtensionVTable>,
+}
+impl Exported {
+    pub fn new(extension: Box<dyn Extension>) -> Self {
+        Self {
+            functions: extension.functions(),
+            extension,
+            vtable: OnceLock::new(),
+        }
+    }
+    pub fn vtable(&'static self) -> *const ExtensionVTable {
+        self.vtable.get_or_init(|| ExtensionVTable {
+            abi: EXTENSION_ABI,
+            state: self as *const Self as *const c_void,
+            describe: describe_exported,
+            call: call_exported,
+            free: free_buffer,
+        })
+    }
+    fn describe(&self) -> serde_json::Value {
+        let functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|f| json!({"name": f.name, "min": f.min_args, "max": f.max_args}))
+            .collect();
+        json!({
+            "name": self.extension.name(),
+            "module": self.extension.module(),
+            "functions": functions,
+        })
+    }
+    fn call(&self, name: &[u8], input: &[u8]) -> Result<Value, String> {
+        let func = self
+            .functions
+            .iter()
+            .find(|f| f.name.as_bytes() == name)
+            .ok_or_else(|| format!("no function '{}'", String::from_utf8_lossy(name)))?;
+        let args = match serde_json::from_slice(input).map_err(|e| e.to_string())? {
+            serde_json::Value::Array(items) => items.into_iter().map(from_json).collect::<Vec<_>>(),
+            _ => return Err("arguments must be a JSON array".to_string()),
+        };
+        func.validate_args(args.len()).map_err(|e| e.message)?;
+        let mut ctx = super::ExtensionContext::new(&func.name, args.len());
+        (func.func)(&mut ctx, &args).map_err(|e| e.message)
+    }
+}
+/// # Safety
+///
+/// `out_len` must be non-null and point at a `usize` the caller may write.
+unsafe fn leak(bytes: Vec<u8>, out_len: *mut usize) -> *mut u8 {
+    let bytes = bytes.into_boxed_slice();
+    // SAFETY: guaranteed by the caller.
+    unsafe { *out_len = bytes.len() };
+    Box::into_raw(bytes) as *mut u8
+}
+unsafe extern "C" fn describe_exported(state: *const c_void, out_len: *mut usize) -> *mut u8 {
+    if state.is_null() || out_len.is_null() {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: `state` is the pointer this library put in its own vtable, which points at a
+    // `'static` `Exported`.
+    let exported = unsafe { &*(state as *const Exported) };
+    match catch_unwind(AssertUnwindSafe(|| exported.describe().to_string())) {
+        // SAFETY: `out_len` was checked above and the host passes a live `usize`.
+        Ok(description) => unsafe { leak(description.into_bytes(), out_len) },
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+unsafe extern "C" fn call_exported(
+    state: *const c_void,
+    name: *const u8,
+    name_len: usize,
+    input: *const u8,
+    input_len: usize,
+    out_len: *mut usize,
+) -> *mut u8 {
+    if state.is_null() || name.is_null() || input.is_null() || out_len.is_null() {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: `state` comes from this library's vtable, and the host keeps `name` and `input`
+    // alive and unmodified for `name_len`/`input_len` bytes until the call returns.
+    let (exported, name, input) = unsafe {
+        (
+            &*(state as *const Exported),
+            std::slice::from_raw_parts(name, name_len),
+            std::slice::from_raw_parts(input, input_len),
+        )
+    };
+    let result = catch_unwind(AssertUnwindSafe(|| exported.call(name, input)))
+        .unwrap_or_else(|_| Err("extension panicked".to_string()));
+    // SAFETY: `out_len` was checked above and the host passes a live `usize`.
+    unsafe { leak(encode_reply(result), out_len) }
+}
+unsafe extern "C" fn free_buffer(ptr: *mut u8, len: usize) {
+    if !ptr.is_null() {
+        // SAFETY: `ptr` and `len` were produced by `leak` in this same library, so they describe
+        // a boxed slice allocated by this library's allocator that has not been freed yet.
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+    }
+}
+struct Shared {
+    vtable: &'static ExtensionVTable,
+    _library: Box<dyn Send + Sync>,
+}
+impl Shared {
+    fn request(&self, name: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+        let mut len = 0;
+        // SAFETY: the vtable was validated in `SharedExtension::load` and `_library` keeps the
+        // code and the exported state mapped for as long as `self` lives. Both byte ranges are
+        // borrowed for the whole call and `len` is a live local.
+        let reply = unsafe {
+            (self.vtable.call)(
+                self.vtable.state,
+                name.as_ptr(),
+                name.len(),
+                input.as_ptr(),
+                input.len(),
+                &mut len,
+            )
+        };
+        self.take(reply, len)
+            .ok_or_else(|| format!("{}: extension returned no reply", name))
+    }
+    fn take(&self, ptr: *mut u8, len: usize) -> Option<Vec<u8>> {
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: a non-null reply is a buffer of `len` bytes the library allocated for us; we
+        // copy it out before handing it back to the library's own `free`, exactly once.
+        unsafe {
+            let bytes = std::slice::from_raw_parts(ptr, len).to_vec();
+            (self.vtable.free)(ptr, len);
+            Some(bytes)
+        }
+    }
+}
+pub struct SharedExtension {
+    name: String,
+    module: Option<String>,
+    functions: Vec<(String, usize, Option<usize>)>,
+    shared: Arc<Shared>,
+}
+impl SharedExtension {
+    /// # Safety
+    ///
+    /// `vtable` must be null or point at an `ExtensionVTable` that stays valid, together with
+    /// everything it points at, for as long as `library` is alive.
+    pub unsafe fn load(
+        vtable: *const ExtensionVTable,
+        library: impl Send + Sync + 'static,
+    ) -> Result<Self, String> {
+        // SAFETY: the caller guarantees a non-null `vtable` outlives `library`, which we keep
+        // alongside it in `Shared`, so extending the borrow to `'static` never dangles.
+        let vtable: &'static ExtensionVTable = match unsafe { vtable.as_ref() } {
+            Some(vtable) => vtable,
+            None => {
+                return Err(format!(
+                    "extension was built for a different ABI than {}",
+                    EXTENSION_ABI
+                ))
+            }
+        };
+        if vtable.abi != EXTENSION_ABI {
+            return Err(format!(
+                "library targets ABI {} but the host provides {}",
+                vtable.abi, EXTENSION_ABI
+            ));
+        }
+        let shared = Shared {
+            vtable,
+            _library: Box::new(library),
+        };
+        let mut len = 0;
+        // SAFETY: same invariants as `Shared::request`; `len` is a live local.
+        let reply = unsafe { (vtable.describe)(vtable.state, &mut len) };
+        let description = shared
+            .take(reply, len)
+            .ok_or_else(|| "extension did not describe itself".to_string())?;
+        let description: serde_json::Value =
+            serde_json::from_slice(&description).map_err(|e| e.to_string())?;
+        let text = |key: &str| description[key].as_str().map(str::to_string);
+        let functions = description["functions"]
+            .as_array()
+            .ok_or_else(|| "extension description lists no functions".to_string())?
+            .iter()
+            .filter_map(|f| {
+                let min = f["min"].as_u64()? as usize;
+                let max = f["max"].as_u64().map(|max| max as usize);
+                Some((f["name"].as_str()?.to_string(), min, max))
+            })
+            .collect();
+        Ok(Self {
+            name: text("name").unwrap_or_default(),
+            module: text("module"),
+            functions,
+            shared: Arc::new(shared),
+        })
+    }
+}
+impl Extension for SharedExtension {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+    fn functions(&self) -> Vec<ExtFunction> {
+        self.functions
+            .iter()
+            .map(|(name, min, max)| {
+                let shared = Arc::clone(&self.shared);
+                let export = name.clone();
+                let mut func = ExtFunction::new(name.clone(), move |_ctx, args| {
+                    let input = serde_json::to_vec(&to_json(&Value::List(args.to_vec()))?)
+                        .map_err(|e| ExtError::new(e.to_string()))?;
+                    let reply = shared.request(&export, &input).map_err(ExtError::new)?;
+                    decode_reply(&reply).map_err(ExtError::new)
+                });
+                func.min_args = *min;
+                func.max_args = *max;
+                func
+            })
+            .collect()
+    }
+}
+pub fn encode_reply(result: Result<Value, String>) -> Vec<u8> {
+    let reply = match result.and_then(|value| to_json(&value).map_err(|e| e.message)) {
+        Ok(value) => json!({ "ok": value }),
+        Err(message) => json!({ "error": message }),
+    };
+    reply.to_string().into_bytes()
+}
+pub fn decode_reply(bytes: &[u8]) -> Result<Value, String> {
+    match serde_json::from_slice(bytes).map_err(|e| e.to_string())? {
+        serde_json::Value::Object(mut reply) => match (reply.remove("ok"), reply.remove("error")) {
+            (Some(value), None) => Ok(from_json(value)),
+            (None, Some(error)) => Err(match error {
+                serde_json::Value::String(message) => message,
+                other => other.to_string(),
+            }),
+            _ => Err("reply must contain exactly one of 'ok' or 'error'".to_string()),
+        },
+        _ => Err("reply must be a JSON object".to_string()),
+    }
+}
+pub fn to_json(value: &Value) -> ExtResult<serde_json::Value> {
+    Ok(match value {
+        Value::Nil => serde_json::Value::Null,
+        Value::Bool(b) => json!(b),
+        Value::Integer(i) => json!(i),
+        Value::Byte(b) => json!(b),
+        Value::Number(n) | Value::Float(n) => Number::from_f64(*n)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::String(s) => json!(s),
+        Value::Char(c) => json!(c.to_string()),
+        Value::List(items) | Value::Tuple(items) | Value::Set(items) => {
+            serde_json::Value::Array(items.iter().map(to_json).collect::<ExtResult<_>>()?)
+        }
+        Value::Range(range) => json!(range.iter().collect::<Vec<_>>()),
+        Value::Map(map) => serde_json::Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), to_json(v)?)))
+                .collect::<ExtResult<Map<_, _>>>()?,
+        ),
+        Value::Frozen(inner) => to_json(inner)?,
+        other => {
+            return Err(ExtError::new(format!(
+                "cannot pass {} across the extension ABI",
+                other.type_name()
+            )))
+        }
+    })
+}
+pub fn from_json(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(b) => Value::Bool(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::String(s.into()),
+        serde_json::Value::Array(items) => Value::List(items.into_iter().map(from_json).collect()),
+        serde_json::Value::Object(map) => {
+            Value::Map(map.into_iter().map(|(k, v)| (k, from_json(v))).collect())
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    #[test]
+    fn test_json_round_trip() {
+        let value = Value::Map(HashMap::from([
+            (
+                "xs".to_string(),
+                Value::List(vec![Value::Integer(1), Value::Number(2.5), Value::Nil]),
+            ),
+            ("name".to_string(), Value::String("neb".into())),
+        ]));
+        let back = from_json(to_json(&value).unwrap());
+        assert_eq!(back.repr(), value.repr());
+        assert!(to_json(&Value::Channel(Default::default())).is_err());
+    }
+    struct Calc;
+    impl Extension for Calc {
+        fn name(&self) -> &str {
+            "calc"
+        }
+        fn functions(&self) -> Vec<ExtFunction> {
+            vec![
+                ExtFunction::with_arity("sum", 1, |_ctx, args| match &args[0] {
+                    Value::List(items) => Ok(Value::Integer(
+                        items.iter().filter_map(Value::as_number).sum::<f64>() as i64,
+                    )),
+                    _ => Err(ExtError::new("sum expects a list")),
+                }),
+                ExtFunction::new("boom", |_ctx, _args| panic!("boom")),
+            ]
+        }
+        fn module(&self) -> Option<&str> {
+            Some("calc")
+        }
+    }
+    #[test]
+    fn test_vtable_round_trip() {
+        static EXPORTED: OnceLock<Exported> = OnceLock::new();
+        let vtable = EXPORTED
+            .get_or_init(|| Exported::new(Box::new(Calc)))
+            .vtable();
+        let shared = unsafe { SharedExtension::load(vtable, ()) }.unwrap();
+        assert_eq!(shared.name(), "calc");
+        assert_eq!(shared.module(), Some("calc"));
+        let functions = shared.functions();
+        let list = Value::List(vec![Value::Integer(2), Value::Number(3.0)]);
+        assert_eq!(functions[0].call(&[list]).unwrap(), Value::Integer(5));
+        assert!(functions[0].call(&[]).is_err());
+        let err = functions[0].call(&[Value::Nil]).unwrap_err();
+        assert!(err.to_string().contains("sum expects a list"), "{}", err);
+        let err = functions[1].call(&[]).unwrap_err();
+        assert!(err.to_string().contains("extension panicked"), "{}", err);
+        assert!(unsafe { SharedExtension::load(std::ptr::null(), ()) }.is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use super::abi::{decode_reply, to_json};
+use super::{ExtError, ExtFunction, ExtResult, Extension, ExtensionContext, EXTENSION_ABI};
+use crate::interp::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, TypedFunc};
+pub const FUNCTION_PREFIX: &str = "nebula_fn_";
+pub const FUEL_PER_CALL: u64 = 100_000_000;
+struct WasmInstance {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+#[derive(Clone)]
+struct WasmExport {
+    instance: Arc<Mutex<WasmInstance>>,
+    func: TypedFunc<(i32, i32), i64>,
+}
+fn exports() -> MutexGuard<'static, HashMap<String, WasmExport>> {
+    static EXPORTS: OnceLock<Mutex<HashMap<String, WasmExport>>> = OnceLock::new();
+    EXPORTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+pub struct WasmExtension {
+    name: String,
+    functions: Vec<String>,
+}
+impl WasmExtension {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let fail = |e: wasmtime::Error| e.to_string();
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(fail)?;
+        let module = Module::from_file(&engine, path).map_err(fail)?;
+        if module.imports().next().is_some() {
+            return Err("sandboxed modules may not import host functions".to_string());
+        }
+        let mut store = Store::new(&engine, ());
+        store.set_fuel(FUEL_PER_CALL).map_err(fail)?;
+        let instance = Instance::new(&mut store, &module, &[]).map_err(fail)?;
+        let abi = instance
+            .get_typed_func::<(), i32>(&mut store, "nebula_abi")
+            .map_err(fail)?
+            .call(&mut store, ())
+            .map_err(fail)?;
+        if abi as u32 != EXTENSION_ABI {
+            return Err(format!(
+                "module targets ABI {} but the host provides {}",
+                abi, EXTENSION_ABI
+            ));
+        }
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| "module does not export 'memory'".to_string())?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "nebula_alloc")
+            .map_err(fail)?;
+        let mut functions = Vec::new();
+        for export in module.exports() {
+            if let Some(name) = export.name().strip_prefix(FUNCTION_PREFIX) {
+                let func = instance
+                    .get_typed_func::<(i32, i32), i64>(&mut store, export.name())
+                    .map_err(fail)?;
+                functions.push((name.to_string(), func));
+            }
+        }
+        let instance = Arc::new(Mutex::new(WasmInstance {
+            store,
+            memory,
+            alloc,
+        }));
+        let mut table = exports();
+        let mut names = Vec::new();
+        for (name, func) in functions {
+            table.insert(
+                name.clone(),
+                WasmExport {
+                    instance: Arc::clone(&instance),
+                    func,
+                },
+            );
+            names.push(name);
+        }
+        Ok(Self {
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            functions: names,
+        })
+    }
+}
+impl Extension for WasmExtension {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn functions(&self) -> Vec<ExtFunction> {
+        self.functions
+            .iter()
+            .map(|name| ExtFunction::new(name.clone(), call_export))
+            .collect()
+    }
+}
+fn call_export(ctx: &mut ExtensionContext, args: &[Value]) -> ExtResult<Value> {
+    let fail = |message: String| ExtError::new(format!("{}: {}", ctx.fn_name, message));
+    let export = exports()
+        .get(ctx.fn_name)
+        .cloned()
+        .ok_or_else(|| fail("wasm export is not loaded".to_string()))?;
+    let input = serde_json::to_vec(&to_json(&Value::List(args.to_vec()))?)
+        .map_err(|e| fail(e.to_string()))?;
+    let mut guard = export
+        .instance
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let WasmInstance {
+        store,
+        memory,
+        alloc,
+    } = &mut *guard;
+    store
+        .set_fuel(FUEL_PER_CALL)
+        .map_err(|e| fail(e.to_string()))?;
+    let ptr = alloc
+        .call(&mut *store, input.len() as i32)
+        .map_err(|e| fail(e.to_string()))?;
+    memory
+        .write(&mut *store, ptr as u32 as usize, &input)
+        .map_err(|e| fail(e.to_string()))?;
+    let packed = export
+        .func
+        .call(&mut *store, (ptr, input.len() as i32))
+        .map_err(|e| fail(e.to_string()))? as u64;
+    let mut output = vec![0; (packed & 0xffff_ffff) as usize];
+    memory
+        .read(&*store, (packed >> 32) as usize, &mut output)
+        .map_err(|e| fail(e.to_string()))?;
+    decode_reply(&output).map_err(fail)
+}
@@ -0,0 +1,182 @@
+use super::Value;
+use serde::de::{self, DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Nil => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::Number(n) | Value::Float(n)
+                if n.fract() == 0.0 && n.abs() <= MAX_EXACT_INTEGER =>
+            {
+                serializer.serialize_i64(*n as i64)
+            }
+            Value::Number(n) | Value::Float(n) => serializer.serialize_f64(*n),
+            Value::Byte(b) => serializer.serialize_u8(*b),
+            Value::Char(c) => serializer.serialize_char(*c),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::List(items) | Value::Tuple(items) | Value::Set(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Value::Struct { fields, .. } => {
+                let mut seq = serializer.serialize_seq(Some(fields.len()))?;
+                for field in fields {
+                    seq.serialize_element(field)?;
+                }
+                seq.end()
+            }
+            Value::Range(range) => serializer.collect_seq(range.iter()),
+            Value::Map(map) => {
+                let mut keys: Vec<_> = map.keys().collect();
+                keys.sort();
+                let mut out = serializer.serialize_map(Some(keys.len()))?;
+                for key in keys {
+                    out.serialize_entry(key, &map[key])?;
+                }
+                out.end()
+            }
+            Value::Frozen(inner) => inner.serialize(serializer),
+            other => Err(ser::Error::custom(format!(
+                "cannot serialize a {} value",
+                other.type_name()
+            ))),
+        }
+    }
+}
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+struct ValueVisitor;
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a nil, bool, number, string, list or map")
+    }
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(v as f64))
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(v as f64))
+    }
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Number(v))
+    }
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Value, E> {
+        Ok(Value::Char(v))
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.into()))
+    }
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v.into()))
+    }
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::List(items))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut map = HashMap::with_capacity(access.size_hint().unwrap_or(0));
+        while let Some((key, value)) = access.next_entry::<String, Value>()? {
+            map.insert(key, value);
+        }
+        Ok(Value::Map(map))
+    }
+}
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(f64::NAN)),
+            serde_json::Value::String(s) => Value::String(s.into()),
+            serde_json::Value::Array(items) => {
+                Value::List(items.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(map) => {
+                Value::Map(map.into_iter().map(|(k, v)| (k, Value::from(v))).collect())
+            }
+        }
+    }
+}
+impl TryFrom<&Value> for serde_json::Value {
+    type Error = String;
+    fn try_from(value: &Value) -> Result<Self, String> {
+        serde_json::to_value(value).map_err(|e| e.to_string())
+    }
+}
+pub fn to_script<T: Serialize + ?Sized>(value: &T) -> Result<Value, String> {
+    serde_json::to_value(value)
+        .map(Value::from)
+        .map_err(|e| e.to_string())
+}
+pub fn from_script<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .map_err(|e| e.to_string())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        retries: u32,
+        ratio: f64,
+        tags: Vec<String>,
+        parent: Option<Box<Config>>,
+    }
+    #[test]
+    fn test_struct_round_trip() {
+        let config = Config {
+            name: "neb".to_string(),
+            retries: 3,
+            ratio: 0.5,
+            tags: vec!["a".to_string()],
+            parent: None,
+        };
+        let value = to_script(&config).unwrap();
+        let Value::Map(map) = &value else {
+            panic!("expected a map, got {:?}", value);
+        };
+        assert_eq!(map["retries"], Value::Number(3.0));
+        assert_eq!(map["parent"], Value::Nil);
+        assert_eq!(from_script::<Config>(&value).unwrap(), config);
+        assert!(from_script::<Config>(&Value::Number(1.0)).is_err());
+    }
+    #[test]
+    fn test_json_conversion() {
+        let json = serde_json::json!({"xs": [1, 2.5, null], "ok": true});
+        let value = Value::from(json.clone());
+        assert_eq!(serde_json::Value::try_from(&value).unwrap(), json);
+        let frozen = Value::Frozen(std::rc::Rc::new(Value::Integer(7)));
+        assert_eq!(serde_json::to_string(&frozen).unwrap(), "7");
+        assert!(serde_json::to_string(&Value::Channel(Default::default())).is_err());
+    }
+}
@@ -35,6 +35,15 @@ impl Environment {
             None
         }
     }
+    pub fn with<R>(&self, name: &str, f: impl FnOnce(&Value) -> R) -> Option<R> {
+        if let Some(value) = self.values.get(name) {
+            Some(f(value))
+        } else if let Some(parent) = &self.parent {
+            parent.borrow().with(name, f)
+        } else {
+            None
+        }
+    }
     pub fn assign(&mut self, name: &str, value: Value) -> bool {
         if self.values.contains_key(name) {
             self.values.insert(name.to_string(), value);
@@ -48,6 +57,13 @@ impl Environment {
     pub fn is_defined_locally(&self, name: &str) -> bool {
         self.values.contains_key(name)
     }
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().cloned().collect();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.borrow().names());
+        }
+        names
+    }
     pub fn locals(&self) -> &HashMap<String, Value> {
         &self.values
     }
@@ -1,10 +1,16 @@
 use super::env::Environment;
-use super::value::{FunctionValue, LambdaValue, NativeFn, Value};
+use super::stdio::{with_stdio, Stdio};
+use super::value::{FunctionValue, LambdaValue, NativeContext, NativeFn, RangeValue, Value};
+use crate::builtins::SandboxPolicy;
 use crate::error::{ErrorCode, NebulaError, NebulaResult};
+use crate::ext::ExtFuture;
+use crate::lexer::Span;
 use crate::parser::ast::*;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::{BufRead, Write};
 use std::rc::Rc;
+use std::sync::Arc;
 enum ControlFlow {
     Return(Value),
     Break,
@@ -15,19 +21,66 @@ enum EvalError {
     Error(NebulaError),
     Control(ControlFlow),
 }
+impl EvalError {
+    fn with_span(self, span: Span) -> Self {
+        match self {
+            EvalError::Error(e) => EvalError::Error(e.with_span(span)),
+            control => control,
+        }
+    }
+}
 impl From<NebulaError> for EvalError {
     fn from(e: NebulaError) -> Self {
         EvalError::Error(e)
     }
 }
 const MAX_RECURSION_DEPTH: usize = 50;
+fn frozen_error(name: &str, value: &Value) -> NebulaError {
+    NebulaError::coded(
+        ErrorCode::E033,
+        format!("cannot modify frozen {} '{}'", value.type_name(), name),
+    )
+}
+fn raised(value: Value) -> NebulaError {
+    match value.into_thawed() {
+        Value::String(message) => NebulaError::Runtime {
+            message: message.to_string(),
+            span: None,
+        },
+        value => NebulaError::Thrown { value, span: None },
+    }
+}
+fn undefined_error(env: &Environment, name: &str) -> NebulaError {
+    let names = env.names();
+    NebulaError::UndefinedVariable {
+        name: name.to_string(),
+        suggestion: crate::error::closest(name, names.iter().map(String::as_str))
+            .map(str::to_string),
+        span: None,
+    }
+}
+fn builtin_method(receiver: &Value, method: &str, args: &[Value]) -> EvalResult {
+    crate::builtins::call_method(receiver, method, args).map_err(|message| {
+        NebulaError::Runtime {
+            message,
+            span: None,
+        }
+        .into()
+    })
+}
 const MAX_ITERATIONS: usize = 1_000_000;
 pub struct Interpreter {
     global: Rc<RefCell<Environment>>,
     current: Rc<RefCell<Environment>>,
     structs: HashMap<String, Vec<String>>,
+    methods: HashMap<String, HashMap<String, Value>>,
     recursion_depth: usize,
     iteration_count: usize,
+    caught: Vec<(Value, NebulaError)>,
+    stdio: Stdio,
+    policy: SandboxPolicy,
+    frames: Vec<Rc<RefCell<Environment>>>,
+    usage: crate::limits::ResourceUsage,
 }
 impl Interpreter {
     pub fn new() -> Self {
@@ -39,11 +92,11 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "log".to_string(),
                     arity: None,
-                    func: |args| {
+                    func: Rc::new(|ctx, args| {
                         let output: Vec<_> = args.iter().map(|a| a.to_display_string()).collect();
-                        println!("{}", output.join(" "));
+                        with_stdio(ctx, |io| io.write(&format!("{}\n", output.join(" "))))?;
                         Ok(Value::Nil)
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -51,13 +104,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "get".to_string(),
                     arity: Some(0),
-                    func: |_args| {
-                        let mut line = String::new();
-                        std::io::stdin()
-                            .read_line(&mut line)
-                            .map_err(|e| e.to_string())?;
-                        Ok(Value::String(line.trim().to_string()))
-                    },
+                    func: Rc::new(|ctx, _args| {
+                        let line = with_stdio(ctx, Stdio::read_line)?.unwrap_or_default();
+                        Ok(Value::String(line.trim().into()))
+                    }),
                 }),
             );
             env.define(
@@ -65,7 +115,7 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "typeof".to_string(),
                     arity: Some(1),
-                    func: |args| Ok(Value::String(args[0].type_name().to_string())),
+                    func: Rc::new(|_ctx, args| Ok(Value::String(args[0].type_name().into()))),
                 }),
             );
             env.define(
@@ -73,10 +123,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "sqrt".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("sqrt requires number")?;
                         Ok(Value::Number(n.sqrt()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -84,12 +134,12 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "abs".to_string(),
                     arity: Some(1),
-                    func: |args| match &args[0] {
+                    func: Rc::new(|_ctx, args| match &args[0] {
                         Value::Number(n) => Ok(Value::Number(n.abs())),
                         Value::Integer(n) => Ok(Value::Integer(n.abs())),
                         Value::Float(f) => Ok(Value::Float(f.abs())),
                         _ => Err("abs requires number".to_string()),
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -97,10 +147,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "sin".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("sin requires number")?;
                         Ok(Value::Number(n.sin()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -108,10 +158,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "cos".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("cos requires number")?;
                         Ok(Value::Number(n.cos()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -119,10 +169,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "tan".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("tan requires number")?;
                         Ok(Value::Number(n.tan()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -130,10 +180,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "floor".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("floor requires number")?;
                         Ok(Value::Number(n.floor()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -141,10 +191,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "ceil".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("ceil requires number")?;
                         Ok(Value::Number(n.ceil()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -152,10 +202,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "round".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("round requires number")?;
                         Ok(Value::Number(n.round()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -163,11 +213,11 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "pow".to_string(),
                     arity: Some(2),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let base = args[0].as_number().ok_or("pow requires number")?;
                         let exp = args[1].as_number().ok_or("pow requires number")?;
                         Ok(Value::Number(base.powf(exp)))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -175,10 +225,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "exp".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("exp requires number")?;
                         Ok(Value::Number(n.exp()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -186,10 +236,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "ln".to_string(),
                     arity: Some(1),
-                    func: |args| {
+                    func: Rc::new(|_ctx, args| {
                         let n = args[0].as_number().ok_or("ln requires number")?;
                         Ok(Value::Number(n.ln()))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -197,31 +247,17 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "len".to_string(),
                     arity: Some(1),
-                    func: |args| match &args[0] {
-                        Value::String(s) => Ok(Value::Integer(s.len() as i64)),
+                    func: Rc::new(|_ctx, args| match &args[0] {
+                        Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
                         Value::List(l) => Ok(Value::Integer(l.len() as i64)),
                         Value::Map(m) => Ok(Value::Integer(m.len() as i64)),
                         Value::Tuple(t) => Ok(Value::Integer(t.len() as i64)),
+                        Value::Range(r) => Ok(Value::Integer(r.len() as i64)),
                         _ => Err(format!(
                             "len() requires collection or string, got {}",
                             args[0].type_name()
                         )),
-                    },
-                }),
-            );
-            env.define(
-                "rnd".to_string(),
-                Value::NativeFunction(NativeFn {
-                    name: "rnd".to_string(),
-                    arity: Some(0),
-                    func: |_args| {
-                        use std::time::{SystemTime, UNIX_EPOCH};
-                        let seed = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .subsec_nanos() as f64;
-                        Ok(Value::Number((seed / 1_000_000_000.0) % 1.0))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -229,12 +265,12 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "dbg".to_string(),
                     arity: None,
-                    func: |args| {
+                    func: Rc::new(|ctx, args| {
                         for arg in args {
-                            eprintln!("[dbg] {:?}", arg);
+                            with_stdio(ctx, |io| io.write_error(&format!("[dbg] {:?}\n", arg)))?;
                         }
                         Ok(Value::Nil)
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -242,7 +278,9 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "chan".to_string(),
                     arity: Some(0),
-                    func: |_args| Ok(Value::Channel(Rc::new(RefCell::new(Vec::new())))),
+                    func: Rc::new(|_ctx, _args| {
+                        Ok(Value::Channel(Rc::new(RefCell::new(Vec::new()))))
+                    }),
                 }),
             );
             env.define(
@@ -250,14 +288,14 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "now".to_string(),
                     arity: Some(0),
-                    func: |_args| {
+                    func: Rc::new(|_ctx, _args| {
                         use std::time::{SystemTime, UNIX_EPOCH};
                         let ms = SystemTime::now()
                             .duration_since(UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_millis() as f64;
                         Ok(Value::Number(ms))
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -265,15 +303,10 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "sleep".to_string(),
                     arity: Some(1),
-                    func: |args| {
-                        let ms = args[0]
-                            .as_number()
-                            .ok_or("sleep requires number (milliseconds)")?;
-                        if ms > 0.0 {
-                            std::thread::sleep(std::time::Duration::from_millis(ms as u64));
-                        }
+                    func: Rc::new(|ctx, args| {
+                        crate::builtins::sleep(ctx, &args[0])?;
                         Ok(Value::Nil)
-                    },
+                    }),
                 }),
             );
             env.define(
@@ -281,7 +314,9 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "str".to_string(),
                     arity: Some(1),
-                    func: |args| Ok(Value::String(args[0].to_display_string())),
+                    func: Rc::new(|_ctx, args| {
+                        Ok(Value::String(args[0].to_display_string().into()))
+                    }),
                 }),
             );
             env.define(
@@ -289,7 +324,7 @@ impl Interpreter {
                 Value::NativeFunction(NativeFn {
                     name: "num".to_string(),
                     arity: Some(1),
-                    func: |args| match &args[0] {
+                    func: Rc::new(|_ctx, args| match &args[0] {
                         Value::Number(n) => Ok(Value::Number(*n)),
                         Value::Integer(n) => Ok(Value::Number(*n as f64)),
                         Value::Float(f) => Ok(Value::Number(*f)),
@@ -299,23 +334,49 @@ impl Interpreter {
                             .map_err(|_| format!("Cannot convert '{}' to number", s)),
                         Value::Bool(b) => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
                         _ => Err(format!("Cannot convert {} to number", args[0].type_name())),
-                    },
+                    }),
                 }),
             );
+            for (name, native) in crate::builtins::stdlib() {
+                env.define(name.to_string(), Value::NativeFunction(native));
+            }
+            for (name, value) in crate::builtins::math_constants() {
+                env.define(name.to_string(), value);
+            }
         }
         let current = Rc::clone(&global);
         Self {
             global,
             current,
             structs: HashMap::new(),
+            methods: HashMap::new(),
             recursion_depth: 0,
             iteration_count: 0,
+            caught: Vec::new(),
+            stdio: Stdio::default(),
+            policy: SandboxPolicy::default(),
+            frames: Vec::new(),
+            usage: crate::limits::ResourceUsage::default(),
         }
     }
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.stdio.set_output(output);
+    }
+    pub fn set_error(&mut self, error: Box<dyn Write>) {
+        self.stdio.set_error(error);
+    }
+    pub fn set_policy(&mut self, policy: SandboxPolicy) {
+        self.policy = policy;
+    }
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.stdio.set_input(input);
+    }
     pub fn reset_scope(&mut self) {
         self.current = Rc::clone(&self.global);
+        self.frames.clear();
     }
     pub fn interpret(&mut self, program: &Program) -> NebulaResult<Value> {
+        self.usage = crate::limits::usage();
         let mut result = Value::Nil;
         for item in &program.items {
             match item {
@@ -326,6 +387,42 @@ impl Interpreter {
                 Item::Function(f) => {
                     self.define_function(f);
                 }
+                Item::Impl(imp) => {
+                    let methods = imp
+                        .methods
+                        .iter()
+                        .map(|f| (f.name.clone(), self.function_value(f)))
+                        .collect::<Vec<_>>();
+                    self.methods
+                        .entry(imp.target.clone())
+                        .or_default()
+                        .extend(methods);
+                }
+                Item::Use(module) if module.native => {
+                    for func in crate::ext::load_native(&module.path, module.span, &self.policy)? {
+                        self.global
+                            .borrow_mut()
+                            .define(func.name.clone(), Value::Extension(func));
+                    }
+                }
+                Item::Use(module) => {
+                    if let Some(functions) = crate::ext::import_module(program, module)? {
+                        let prefix = format!("{}.", module.path);
+                        let mut members: Vec<_> = functions
+                            .into_iter()
+                            .map(|f| (f.name[prefix.len()..].to_string(), Value::Extension(f)))
+                            .collect();
+                        let mut global = self.global.borrow_mut();
+                        if module.items.is_empty() {
+                            let members = members.into_iter().collect();
+                            global.define(module.binding().to_string(), Value::Map(members));
+                        } else {
+                            for (name, value) in members.drain(..) {
+                                global.define(name, value);
+                            }
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -333,43 +430,91 @@ impl Interpreter {
             if let Item::Statement(stmt) = item {
                 match self.eval_stmt(stmt) {
                     Ok(v) => result = v,
-                    Err(EvalError::Error(e)) => return Err(e),
+                    Err(EvalError::Error(e)) => {
+                        crate::ext::drop_tasks();
+                        return Err(e);
+                    }
                     Err(EvalError::Control(_)) => {}
                 }
             }
         }
+        crate::ext::run_tasks(self)?;
         Ok(result)
     }
+    pub fn global_bindings(&self) -> Vec<(String, Value)> {
+        let constants = crate::builtins::math_constants();
+        let global = self.global.borrow();
+        let mut bindings: Vec<_> = global
+            .locals()
+            .iter()
+            .filter(|(name, value)| {
+                !matches!(value, Value::NativeFunction(_))
+                    && !constants.iter().any(|(c, _)| c == name)
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+    pub fn register_native(&mut self, native: NativeFn) {
+        self.global
+            .borrow_mut()
+            .define(native.name.clone(), Value::NativeFunction(native));
+    }
+    pub fn call_global(&mut self, name: &str, args: &[Value]) -> NebulaResult<Value> {
+        let callee = self
+            .global
+            .borrow()
+            .get(name)
+            .ok_or_else(|| undefined_error(&self.global.borrow(), name))?;
+        let depth = self.recursion_depth;
+        let result = self.call_value(&callee, args);
+        self.recursion_depth = depth;
+        self.reset_scope();
+        match result {
+            Ok(value) | Err(EvalError::Control(ControlFlow::Return(value))) => Ok(value),
+            Err(EvalError::Error(e)) => Err(e),
+            Err(EvalError::Control(_)) => Err(NebulaError::Runtime {
+                message: format!("control flow escaped {}()", name),
+                span: None,
+            }),
+        }
+    }
     fn define_function(&mut self, f: &Function) {
-        let func = FunctionValue {
+        let func = self.function_value(f);
+        self.current.borrow_mut().define(f.name.clone(), func);
+    }
+    fn function_value(&self, f: &Function) -> Value {
+        Value::Function(Rc::new(FunctionValue {
             name: f.name.clone(),
-            params: f.params.clone(),
-            body: f.body.clone(),
+            params: Arc::clone(&f.params),
+            body: Arc::clone(&f.body),
             closure: Rc::clone(&self.current),
             is_async: f.is_async,
-        };
-        self.current
-            .borrow_mut()
-            .define(f.name.clone(), Value::Function(Rc::new(func)));
+        }))
     }
     fn eval_stmt(&mut self, stmt: &Stmt) -> EvalResult {
+        self.eval_stmt_kind(&stmt.kind)
+            .map_err(|e| e.with_span(stmt.span))
+    }
+    fn eval_stmt_kind(&mut self, stmt: &StmtKind) -> EvalResult {
         match stmt {
-            Stmt::Var { name, value, .. } => {
+            StmtKind::Var { name, value, .. } => {
                 let val = self.eval_expr(value)?;
                 self.current.borrow_mut().define(name.clone(), val);
                 Ok(Value::Nil)
             }
-            Stmt::Const { name, value, .. } => {
+            StmtKind::Const { name, value, .. } => {
                 let val = self.eval_expr(value)?;
                 self.current.borrow_mut().define(name.clone(), val);
                 Ok(Value::Nil)
             }
-            Stmt::Assignment { target, value } => {
+            StmtKind::Assignment { target, value } => {
                 let val = self.eval_expr(value)?;
                 self.assign_target(target, val)?;
                 Ok(Value::Nil)
             }
-            Stmt::CompoundAssignment { target, op, value } => {
+            StmtKind::CompoundAssignment { target, op, value } => {
                 let current_val = self.eval_expr(target)?;
                 let rhs = self.eval_expr(value)?;
                 let new_val = match op {
@@ -381,7 +526,7 @@ impl Interpreter {
                 self.assign_target(target, new_val)?;
                 Ok(Value::Nil)
             }
-            Stmt::If {
+            StmtKind::If {
                 condition,
                 then_block,
                 elif_branches,
@@ -404,8 +549,9 @@ impl Interpreter {
                     }
                 }
             }
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 loop {
+                    crate::limits::poll()?;
                     self.iteration_count += 1;
                     if self.iteration_count > MAX_ITERATIONS {
                         return Err(NebulaError::coded(ErrorCode::E071, "while loop").into());
@@ -423,7 +569,7 @@ impl Interpreter {
                 }
                 Ok(Value::Nil)
             }
-            Stmt::For {
+            StmtKind::For {
                 var,
                 start,
                 end,
@@ -433,17 +579,20 @@ impl Interpreter {
                 let start_val = self.eval_expr(start)?.as_integer().ok_or(EvalError::Error(
                     NebulaError::Runtime {
                         message: "for loop start must be integer".to_string(),
+                        span: None,
                     },
                 ))?;
                 let end_val = self.eval_expr(end)?.as_integer().ok_or(EvalError::Error(
                     NebulaError::Runtime {
                         message: "for loop end must be integer".to_string(),
+                        span: None,
                     },
                 ))?;
                 let step_val = if let Some(s) = step {
                     self.eval_expr(s)?.as_integer().ok_or(EvalError::Error(
                         NebulaError::Runtime {
                             message: "for loop step must be integer".to_string(),
+                            span: None,
                         },
                     ))?
                 } else {
@@ -451,6 +600,7 @@ impl Interpreter {
                 };
                 let mut i = start_val;
                 while (step_val > 0 && i <= end_val) || (step_val < 0 && i >= end_val) {
+                    crate::limits::poll()?;
                     self.iteration_count += 1;
                     if self.iteration_count > MAX_ITERATIONS {
                         return Err(NebulaError::coded(ErrorCode::E071, "for loop").into());
@@ -480,28 +630,29 @@ impl Interpreter {
                 }
                 Ok(Value::Nil)
             }
-            Stmt::Each {
+            StmtKind::Each {
                 var,
                 iterator,
                 body,
             } => {
-                let iter_val = self.eval_expr(iterator)?;
-                let items: Vec<Value> = match iter_val {
-                    Value::Range(start, end, inclusive) => {
-                        let end = if inclusive { end + 1 } else { end };
-                        (start..end).map(Value::Integer).collect()
+                let iter_val = self.eval_expr(iterator)?.into_thawed();
+                let items: Box<dyn Iterator<Item = Value>> = match iter_val {
+                    Value::Range(range) => Box::new(range.iter().map(Value::Integer)),
+                    Value::List(arr) => Box::new(arr.into_iter()),
+                    Value::String(s) => {
+                        Box::new(s.chars().map(Value::Char).collect::<Vec<_>>().into_iter())
                     }
-                    Value::List(arr) => arr,
-                    Value::String(s) => s.chars().map(Value::Char).collect(),
-                    Value::Map(m) => m.keys().map(|k| Value::String(k.clone())).collect(),
+                    Value::Map(m) => Box::new(m.into_keys().map(|s| Value::String(s.into()))),
                     _ => {
                         return Err(NebulaError::InvalidOperation {
                             message: format!("Cannot iterate over {}", iter_val.type_name()),
+                            span: None,
                         }
                         .into());
                     }
                 };
                 for item in items {
+                    crate::limits::poll()?;
                     self.push_scope();
                     self.current.borrow_mut().define(var.clone(), item);
                     match self.eval_block_inner(body) {
@@ -523,7 +674,7 @@ impl Interpreter {
                 }
                 Ok(Value::Nil)
             }
-            Stmt::Match { value, arms } => {
+            StmtKind::Match { value, arms } => {
                 let val = self.eval_expr(value)?;
                 for arm in arms {
                     if self.match_pattern(&arm.pattern, &val) {
@@ -531,11 +682,12 @@ impl Interpreter {
                     }
                 }
                 Err(NebulaError::Runtime {
-                    message: "Non-exhaustive match".to_string(),
+                    message: format!("Non-exhaustive match: no arm matches {}", val.repr()),
+                    span: Some(value.span),
                 }
                 .into())
             }
-            Stmt::Try {
+            StmtKind::Try {
                 try_block,
                 catch_var,
                 catch_block,
@@ -543,15 +695,22 @@ impl Interpreter {
             } => {
                 let result = self.eval_block(try_block);
                 let final_result = match result {
-                    Err(EvalError::Error(e)) if catch_block.is_some() => {
+                    Err(EvalError::Error(e))
+                        if catch_block.is_some() && !matches!(e, NebulaError::Exit { .. }) =>
+                    {
                         self.push_scope();
+                        let caught = match e.payload() {
+                            Some(value) => value.clone(),
+                            None => Value::String(format!("{}", e).into()),
+                        };
                         if let Some(var) = catch_var {
-                            let err_msg = format!("{}", e);
                             self.current
                                 .borrow_mut()
-                                .define(var.clone(), Value::String(err_msg));
+                                .define(var.clone(), caught.clone());
                         }
+                        self.caught.push((caught, e));
                         let catch_result = self.eval_block_inner(catch_block.as_ref().unwrap());
+                        self.caught.pop();
                         self.pop_scope();
                         catch_result
                     }
@@ -562,7 +721,7 @@ impl Interpreter {
                 }
                 final_result
             }
-            Stmt::Return(expr) => {
+            StmtKind::Return(expr) => {
                 let value = if let Some(e) = expr {
                     self.eval_expr(e)?
                 } else {
@@ -570,42 +729,48 @@ impl Interpreter {
                 };
                 Err(EvalError::Control(ControlFlow::Return(value)))
             }
-            Stmt::Break => Err(EvalError::Control(ControlFlow::Break)),
-            Stmt::Continue => Err(EvalError::Control(ControlFlow::Continue)),
-            Stmt::Expression(expr) => self.eval_expr(expr),
+            StmtKind::Break => Err(EvalError::Control(ControlFlow::Break)),
+            StmtKind::Continue => Err(EvalError::Control(ControlFlow::Continue)),
+            StmtKind::Expression(expr) => self.eval_expr(expr),
         }
     }
     fn match_pattern(&self, pattern: &Pattern, value: &Value) -> bool {
         match pattern {
             Pattern::Wildcard => true,
             Pattern::Binding(_) => true,
+            Pattern::Nil => matches!(value, Value::Nil),
             Pattern::Literal(lit) => match (lit, value) {
                 (Literal::Integer(a), Value::Integer(b)) => a == b,
                 (Literal::Integer(a), Value::Number(b)) => *a as f64 == *b,
                 (Literal::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
                 (Literal::Float(a), Value::Number(b)) => (a - b).abs() < f64::EPSILON,
                 (Literal::Bool(a), Value::Bool(b)) => a == b,
-                (Literal::String(a), Value::String(b)) => a == b,
+                (Literal::String(a), Value::String(b)) => **a == **b,
                 _ => false,
             },
         }
     }
     fn assign_target(&mut self, target: &Expr, value: Value) -> EvalResult {
-        match target {
-            Expr::Variable(name) => {
+        match &target.kind {
+            ExprKind::Variable(name) => {
                 if !self.current.borrow_mut().assign(name, value) {
-                    return Err(NebulaError::UndefinedVariable { name: name.clone() }.into());
+                    return Err(undefined_error(&self.current.borrow(), name).into());
                 }
                 Ok(Value::Nil)
             }
-            Expr::Index { array, index } => {
-                if let Expr::Variable(arr_name) = array.as_ref() {
+            ExprKind::Index { array, index } => {
+                if let ExprKind::Variable(arr_name) = &array.kind {
                     let idx = self.eval_expr(index)?.as_integer().ok_or(EvalError::Error(
                         NebulaError::InvalidOperation {
                             message: "Index must be integer".to_string(),
+                            span: None,
                         },
                     ))?;
-                    if let Some(Value::List(mut arr)) = self.current.borrow().get(arr_name) {
+                    let current = self.current.borrow().get(arr_name);
+                    if let Some(frozen @ Value::Frozen(_)) = &current {
+                        return Err(frozen_error(arr_name, frozen).into());
+                    }
+                    if let Some(Value::List(mut arr)) = current {
                         if idx >= 0 && (idx as usize) < arr.len() {
                             arr[idx as usize] = value;
                             self.current.borrow_mut().assign(arr_name, Value::List(arr));
@@ -613,6 +778,7 @@ impl Interpreter {
                             return Err(NebulaError::IndexOutOfBounds {
                                 index: idx,
                                 length: arr.len(),
+                                span: None,
                             }
                             .into());
                         }
@@ -620,9 +786,13 @@ impl Interpreter {
                 }
                 Ok(Value::Nil)
             }
-            Expr::Field { object, field } => {
-                if let Expr::Variable(obj_name) = object.as_ref() {
-                    if let Some(Value::Map(mut m)) = self.current.borrow().get(obj_name) {
+            ExprKind::Field { object, field } => {
+                if let ExprKind::Variable(obj_name) = &object.kind {
+                    let current = self.current.borrow().get(obj_name);
+                    if let Some(frozen @ Value::Frozen(_)) = &current {
+                        return Err(frozen_error(obj_name, frozen).into());
+                    }
+                    if let Some(Value::Map(mut m)) = current {
                         m.insert(field.clone(), value);
                         self.current.borrow_mut().assign(obj_name, Value::Map(m));
                     }
@@ -631,6 +801,7 @@ impl Interpreter {
             }
             _ => Err(NebulaError::InvalidOperation {
                 message: "Invalid assignment target".to_string(),
+                span: None,
             }
             .into()),
         }
@@ -649,73 +820,54 @@ impl Interpreter {
         Ok(result)
     }
     fn eval_expr(&mut self, expr: &Expr) -> EvalResult {
+        self.eval_expr_kind(&expr.kind)
+            .map_err(|e| e.with_span(expr.span))
+    }
+    fn eval_expr_kind(&mut self, expr: &ExprKind) -> EvalResult {
         match expr {
-            Expr::Literal(lit) => Ok(self.eval_literal(lit)),
-            Expr::Variable(name) => self
+            ExprKind::Literal(lit) => Ok(self.eval_literal(lit)),
+            ExprKind::Variable(name) => self
                 .current
                 .borrow()
                 .get(name)
-                .ok_or_else(|| NebulaError::UndefinedVariable { name: name.clone() }.into()),
-            Expr::Binary { left, op, right } => {
+                .ok_or_else(|| undefined_error(&self.current.borrow(), name).into()),
+            ExprKind::Binary { left, op, right }
+                if matches!(left.kind, ExprKind::Binary { .. }) =>
+            {
+                self.eval_chain(*op, left, right)
+            }
+            ExprKind::Binary { left, op, right } => {
                 let lhs = self.eval_expr(left)?;
-                let rhs = self.eval_expr(right)?;
-                self.eval_binary_op(*op, &lhs, &rhs)
+                self.eval_operand(lhs, *op, right)
             }
-            Expr::Unary { op, operand } => {
+            ExprKind::Unary { op, operand } => {
                 let val = self.eval_expr(operand)?;
                 self.eval_unary_op(*op, &val)
             }
-            Expr::Call { callee, args } => {
+            ExprKind::Call { callee, args } => {
                 let callee_val = self.eval_expr(callee)?;
-                let arg_vals: Result<Vec<_>, _> = args.iter().map(|a| self.eval_expr(a)).collect();
-                let arg_vals = arg_vals?;
-                match callee_val {
-                    Value::Function(func) => self.call_function(&func, &arg_vals),
-                    Value::Lambda(lambda) => self.call_lambda(&lambda, &arg_vals),
-                    Value::NativeFunction(nf) => {
-                        if let Some(arity) = nf.arity {
-                            if arg_vals.len() != arity {
-                                return Err(NebulaError::InvalidOperation {
-                                    message: format!(
-                                        "{}() expected {} arguments, got {}",
-                                        nf.name,
-                                        arity,
-                                        arg_vals.len()
-                                    ),
-                                }
-                                .into());
-                            }
-                        }
-                        (nf.func)(&arg_vals)
-                            .map_err(|msg| NebulaError::Runtime { message: msg }.into())
-                    }
-                    _ => Err(NebulaError::InvalidOperation {
-                        message: format!("Cannot call {}", callee_val.type_name()),
-                    }
-                    .into()),
-                }
+                let arg_vals = self.eval_args(args)?;
+                self.call_value(&callee_val, &arg_vals)
             }
-            Expr::MethodCall {
+            ExprKind::MethodCall {
                 receiver,
                 method,
                 args,
             } => {
-                let recv_val = self.eval_expr(receiver)?;
-                let arg_vals: Result<Vec<_>, _> = args.iter().map(|a| self.eval_expr(a)).collect();
-                let arg_vals = arg_vals?;
-                self.call_method(&recv_val, method, &arg_vals)
+                let value = self.eval_method_call(receiver, method, args)?;
+                self.allocated(value)
             }
-            Expr::Field { object, field } => {
+            ExprKind::Field { object, field } => {
                 let obj = self.eval_expr(object)?;
-                self.get_field(&obj, field)
+                self.get_field(obj.thawed(), field)
             }
-            Expr::Index { array, index } => {
+            ExprKind::Index { array, index } => {
                 let arr = self.eval_expr(array)?;
                 let idx = self.eval_expr(index)?;
-                self.get_index(&arr, &idx)
+                self.get_index(arr.thawed(), &idx)
             }
-            Expr::Slice { array, start, end } => {
-                let arr = self.eval_expr(array)?;
+            ExprKind::Slice { array, start, end } => {
+                let arr = self.eval_expr(array)?.into_thawed();
                 let start_idx = start
                     .as_ref()
                     .map(|e| self.eval_expr(e))
@@ -742,15 +894,16 @@ impl Interpreter {
                             .map(|i| i as usize)
                             .unwrap_or(chars.len())
                             .min(chars.len());
-                        Ok(Value::String(chars[s..e].iter().collect()))
+                        Ok(Value::String(chars[s..e].iter().collect::<String>().into()))
                     }
                     _ => Err(NebulaError::InvalidOperation {
                         message: format!("Cannot slice {}", arr.type_name()),
+                        span: None,
                     }
                     .into()),
                 }
             }
-            Expr::Ternary {
+            ExprKind::Ternary {
                 condition,
                 then_expr,
                 else_expr,
@@ -762,35 +915,42 @@ impl Interpreter {
                     self.eval_expr(else_expr)
                 }
             }
-            Expr::Lambda { params, body } => {
+            ExprKind::Lambda { params, body } => {
                 let lambda = LambdaValue {
-                    params: params.clone(),
-                    body: (**body).clone(),
+                    params: Arc::clone(params),
+                    body: Arc::clone(body),
                     closure: Rc::clone(&self.current),
                 };
                 Ok(Value::Lambda(Rc::new(lambda)))
             }
-            Expr::List(elements) => {
+            ExprKind::List(elements) => {
                 let vals: Result<Vec<_>, _> = elements.iter().map(|e| self.eval_expr(e)).collect();
-                Ok(Value::List(vals?))
+                self.allocated(Value::List(vals?))
             }
-            Expr::Map(pairs) => {
+            ExprKind::Map(pairs) => {
                 let mut map = HashMap::new();
                 for (key, value) in pairs {
                     let k = match self.eval_expr(key)? {
-                        Value::String(s) => s,
+                        Value::String(s) => s.to_string(),
                         other => other.to_display_string(),
                     };
                     let v = self.eval_expr(value)?;
                     map.insert(k, v);
                 }
-                Ok(Value::Map(map))
+                self.allocated(Value::Map(map))
             }
-            Expr::Tuple(elements) => {
+            ExprKind::Tuple(elements) => {
                 let vals: Result<Vec<_>, _> = elements.iter().map(|e| self.eval_expr(e)).collect();
-                Ok(Value::Tuple(vals?))
+                self.allocated(Value::Tuple(vals?))
+            }
+            ExprKind::Interpolation(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    text.push_str(&self.eval_expr(part)?.to_display_string());
+                }
+                self.allocated(Value::String(text.into()))
             }
-            Expr::Range {
+            ExprKind::Range {
                 start,
                 end,
                 inclusive,
@@ -798,40 +958,43 @@ impl Interpreter {
                 let s = self.eval_expr(start)?.as_integer().ok_or(EvalError::Error(
                     NebulaError::InvalidOperation {
                         message: "Range start must be integer".to_string(),
+                        span: None,
                     },
                 ))?;
                 let e = self.eval_expr(end)?.as_integer().ok_or(EvalError::Error(
                     NebulaError::InvalidOperation {
                         message: "Range end must be integer".to_string(),
+                        span: None,
                     },
                 ))?;
-                Ok(Value::Range(s, e, *inclusive))
+                Ok(Value::Range(RangeValue::new(s, e, *inclusive)))
             }
-            Expr::StructInit { name, args } => {
-                let arg_vals: Result<Vec<_>, _> = args.iter().map(|e| self.eval_expr(e)).collect();
-                Ok(Value::Struct {
-                    name: name.clone(),
-                    fields: arg_vals?,
-                })
-            }
-            Expr::Length(operand) => {
-                let val = self.eval_expr(operand)?;
+            ExprKind::StructInit { name, args } => Ok(Value::Struct {
+                name: name.clone(),
+                fields: self.eval_args(args)?,
+            }),
+            ExprKind::Length(operand) => {
+                let val = self.eval_expr(operand)?.into_thawed();
                 match val {
                     Value::List(arr) => Ok(Value::Integer(arr.len() as i64)),
-                    Value::String(s) => Ok(Value::Integer(s.len() as i64)),
+                    Value::String(s) => Ok(Value::Integer(s.chars().count() as i64)),
                     Value::Map(m) => Ok(Value::Integer(m.len() as i64)),
+                    Value::Tuple(t) => Ok(Value::Integer(t.len() as i64)),
+                    Value::Range(r) => Ok(Value::Integer(r.len() as i64)),
                     _ => Err(NebulaError::InvalidOperation {
                         message: format!("Cannot get length of {}", val.type_name()),
+                        span: None,
                     }
                     .into()),
                 }
             }
-            Expr::Append { list, value } => {
-                let mut arr = match self.eval_expr(list)? {
+            ExprKind::Append { list, value } => {
+                let mut arr = match self.eval_expr(list)?.into_thawed() {
                     Value::List(a) => a,
                     other => {
                         return Err(NebulaError::InvalidOperation {
                             message: format!("Cannot append to {}", other.type_name()),
+                            span: None,
                         }
                         .into())
                     }
@@ -840,13 +1003,32 @@ impl Interpreter {
                 arr.push(val);
                 Ok(Value::List(arr))
             }
-            Expr::Await(operand) => self.eval_expr(operand),
-            Expr::Spawn(operand) => self.eval_expr(operand),
-            Expr::Error(msg) => {
-                let message = self.eval_expr(msg)?.to_display_string();
-                Err(NebulaError::Runtime { message }.into())
+            ExprKind::Await(operand) => match self.eval_expr(operand)? {
+                Value::Future(future) => Ok(future.join(self)?),
+                value => Ok(value),
+            },
+            ExprKind::Spawn(operand) => {
+                let (callee, args) = match &operand.kind {
+                    ExprKind::Call { callee, args } => {
+                        (self.eval_expr(callee)?, self.eval_args(args)?)
+                    }
+                    _ => (self.eval_expr(operand)?, Vec::new()),
+                };
+                Ok(Value::Future(ExtFuture::task(callee, args)))
+            }
+            ExprKind::Error { value, cause } => {
+                let error = raised(self.eval_expr(value)?);
+                let Some(cause) = cause else {
+                    return Err(error.into());
+                };
+                let cause = self.eval_expr(cause)?;
+                let cause = match self.caught.iter().rev().find(|(value, _)| *value == cause) {
+                    Some((_, original)) => original.clone(),
+                    None => raised(cause),
+                };
+                Err(error.caused_by(cause).into())
             }
-            Expr::Assert { condition, message } => {
+            ExprKind::Assert { condition, message } => {
                 let cond = self.eval_expr(condition)?;
                 if !cond.is_truthy() {
                     let msg = if let Some(m) = message {
@@ -854,11 +1036,15 @@ impl Interpreter {
                     } else {
                         "Assertion failed".to_string()
                     };
-                    return Err(NebulaError::Runtime { message: msg }.into());
+                    return Err(NebulaError::Runtime {
+                        message: msg,
+                        span: None,
+                    }
+                    .into());
                 }
                 Ok(Value::Nil)
             }
-            Expr::Send { channel, value } => {
+            ExprKind::Send { channel, value } => {
                 if let Value::Channel(ch) = self.eval_expr(channel)? {
                     let val = self.eval_expr(value)?;
                     ch.borrow_mut().push(val);
@@ -866,46 +1052,80 @@ impl Interpreter {
                 } else {
                     Err(NebulaError::InvalidOperation {
                         message: "Send requires channel".to_string(),
+                        span: None,
                     }
                     .into())
                 }
             }
-            Expr::Receive(channel) => {
+            ExprKind::Receive(channel) => {
                 if let Value::Channel(ch) = self.eval_expr(channel)? {
                     ch.borrow_mut().pop().ok_or(
                         NebulaError::Runtime {
                             message: "Channel empty".to_string(),
+                            span: None,
                         }
                         .into(),
                     )
                 } else {
                     Err(NebulaError::InvalidOperation {
                         message: "Receive requires channel".to_string(),
+                        span: None,
                     }
                     .into())
                 }
             }
-            Expr::Borrow(operand) => self.eval_expr(operand),
-            Expr::Cast { ty, value } => {
+            ExprKind::Borrow(operand) => self.eval_expr(operand),
+            ExprKind::Cast { ty, value } => {
                 let val = self.eval_expr(value)?;
                 self.cast_value(ty, val)
             }
-            Expr::TypeOf(operand) => {
+            ExprKind::TypeOf(operand) => {
                 let val = self.eval_expr(operand)?;
-                Ok(Value::String(val.type_name().to_string()))
+                Ok(Value::String(val.type_name().into()))
             }
-            Expr::Block(stmts) => self.eval_block(stmts),
-            Expr::Nil => Ok(Value::Nil),
+            ExprKind::Block(stmts) => self.eval_block(stmts),
+            ExprKind::Nil => Ok(Value::Nil),
         }
     }
     fn eval_literal(&self, lit: &Literal) -> Value {
         match lit {
             Literal::Integer(n) => Value::Number(*n as f64),
             Literal::Float(f) => Value::Number(*f),
-            Literal::String(s) => Value::String(s.clone()),
+            Literal::String(s) => Value::String(s.as_str().into()),
             Literal::Bool(b) => Value::Bool(*b),
         }
     }
+    fn eval_chain(&mut self, op: BinaryOp, left: &Expr, right: &Expr) -> EvalResult {
+        let mut links = vec![(op, right, None)];
+        let mut left = left;
+        while let ExprKind::Binary {
+            left: inner,
+            op,
+            right,
+        } = &left.kind
+        {
+            links.push((*op, right, Some(left.span)));
+            left = inner;
+        }
+        let mut value = self.eval_expr(left)?;
+        for (op, right, span) in links.into_iter().rev() {
+            value = match (self.eval_operand(value, op, right), span) {
+                (Err(e), Some(span)) => return Err(e.with_span(span)),
+                (result, _) => result?,
+            };
+        }
+        Ok(value)
+    }
+    fn eval_operand(&mut self, lhs: Value, op: BinaryOp, right: &Expr) -> EvalResult {
+        match (op, lhs) {
+            (BinaryOp::Coalesce, Value::Nil) => self.eval_expr(right),
+            (BinaryOp::Coalesce, value) => Ok(value),
+            (op, lhs) => {
+                let rhs = self.eval_expr(right)?;
+                self.eval_binary_op(op, lhs.thawed(), rhs.thawed())
+            }
+        }
+    }
     fn eval_binary_op(&self, op: BinaryOp, lhs: &Value, rhs: &Value) -> EvalResult {
         match op {
             BinaryOp::Add => self.add(lhs, rhs),
@@ -927,17 +1147,26 @@ impl Interpreter {
             BinaryOp::BitXor => self.bitxor(lhs, rhs),
             BinaryOp::Shl => self.shl(lhs, rhs),
             BinaryOp::Shr => self.shr(lhs, rhs),
+            BinaryOp::Coalesce if matches!(lhs, Value::Nil) => Ok(rhs.clone()),
+            BinaryOp::Coalesce => Ok(lhs.clone()),
         }
     }
     fn add(&self, lhs: &Value, rhs: &Value) -> EvalResult {
         match (lhs, rhs) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
             (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
-            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
-            (Value::String(a), other) => Ok(Value::String(format!("{}{}", a, other))),
-            (other, Value::String(b)) => Ok(Value::String(format!("{}{}", other, b))),
+            (Value::String(a), Value::String(b)) => {
+                self.allocated(Value::String(format!("{}{}", a, b).into()))
+            }
+            (Value::String(a), other) => {
+                self.allocated(Value::String(format!("{}{}", a, other).into()))
+            }
+            (other, Value::String(b)) => {
+                self.allocated(Value::String(format!("{}{}", other, b).into()))
+            }
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot add {} and {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -954,6 +1183,7 @@ impl Interpreter {
                     lhs.type_name(),
                     rhs.type_name()
                 ),
+                span: None,
             }
             .into()),
         }
@@ -970,6 +1200,7 @@ impl Interpreter {
                     lhs.type_name(),
                     rhs.type_name()
                 ),
+                span: None,
             }
             .into()),
         }
@@ -978,20 +1209,21 @@ impl Interpreter {
         match (lhs, rhs) {
             (Value::Number(a), Value::Number(b)) => {
                 if *b == 0.0 {
-                    Err(NebulaError::DivisionByZero.into())
+                    Err(NebulaError::DivisionByZero { span: None }.into())
                 } else {
                     Ok(Value::Number(a / b))
                 }
             }
             (Value::Integer(a), Value::Integer(b)) => {
                 if *b == 0 {
-                    Err(NebulaError::DivisionByZero.into())
+                    Err(NebulaError::DivisionByZero { span: None }.into())
                 } else {
                     Ok(Value::Integer(a / b))
                 }
             }
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot divide {} by {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1001,7 +1233,7 @@ impl Interpreter {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a % b)),
             (Value::Integer(a), Value::Integer(b)) => {
                 if *b == 0 {
-                    Err(NebulaError::DivisionByZero.into())
+                    Err(NebulaError::DivisionByZero { span: None }.into())
                 } else {
                     Ok(Value::Integer(a % b))
                 }
@@ -1010,6 +1242,7 @@ impl Interpreter {
             (Value::Integer(a), Value::Number(b)) => Ok(Value::Number((*a as f64) % b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot modulo {} and {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1019,11 +1252,13 @@ impl Interpreter {
             .as_number()
             .ok_or(EvalError::Error(NebulaError::InvalidOperation {
                 message: "Power requires numbers".to_string(),
+                span: None,
             }))?;
         let exp = rhs
             .as_number()
             .ok_or(EvalError::Error(NebulaError::InvalidOperation {
                 message: "Power requires numbers".to_string(),
+                span: None,
             }))?;
         Ok(Value::Number(base.powf(exp)))
     }
@@ -1034,6 +1269,7 @@ impl Interpreter {
             (Value::String(a), Value::String(b)) => Ok(Value::Bool(a < b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot compare {} and {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1045,6 +1281,7 @@ impl Interpreter {
             (Value::String(a), Value::String(b)) => Ok(Value::Bool(a > b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot compare {} and {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1056,6 +1293,7 @@ impl Interpreter {
             (Value::String(a), Value::String(b)) => Ok(Value::Bool(a <= b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot compare {} and {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1067,6 +1305,7 @@ impl Interpreter {
             (Value::String(a), Value::String(b)) => Ok(Value::Bool(a >= b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot compare {} and {}", lhs.type_name(), rhs.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1076,6 +1315,7 @@ impl Interpreter {
             (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a & b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: "Bitwise AND requires integers".to_string(),
+                span: None,
             }
             .into()),
         }
@@ -1085,6 +1325,7 @@ impl Interpreter {
             (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a | b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: "Bitwise OR requires integers".to_string(),
+                span: None,
             }
             .into()),
         }
@@ -1094,6 +1335,7 @@ impl Interpreter {
             (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a ^ b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: "Bitwise XOR requires integers".to_string(),
+                span: None,
             }
             .into()),
         }
@@ -1103,6 +1345,7 @@ impl Interpreter {
             (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a << b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: "Shift requires integers".to_string(),
+                span: None,
             }
             .into()),
         }
@@ -1112,6 +1355,7 @@ impl Interpreter {
             (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a >> b)),
             _ => Err(NebulaError::InvalidOperation {
                 message: "Shift requires integers".to_string(),
+                span: None,
             }
             .into()),
         }
@@ -1123,6 +1367,7 @@ impl Interpreter {
                 Value::Integer(n) => Ok(Value::Integer(-n)),
                 _ => Err(NebulaError::InvalidOperation {
                     message: format!("Cannot negate {}", val.type_name()),
+                    span: None,
                 }
                 .into()),
             },
@@ -1131,23 +1376,71 @@ impl Interpreter {
                 Value::Integer(n) => Ok(Value::Integer(!n)),
                 _ => Err(NebulaError::InvalidOperation {
                     message: format!("Cannot bitwise NOT {}", val.type_name()),
+                    span: None,
                 }
                 .into()),
             },
         }
     }
+    fn call_value(&mut self, callee: &Value, args: &[Value]) -> EvalResult {
+        match callee {
+            Value::Function(func) => self.call_function(func, args),
+            Value::Lambda(lambda) => self.call_lambda(lambda, args),
+            Value::NativeFunction(nf) => {
+                if let Some(arity) = nf.arity {
+                    if args.len() != arity {
+                        return Err(NebulaError::InvalidOperation {
+                            message: format!(
+                                "{}() expected {} arguments, got {}",
+                                nf.name,
+                                arity,
+                                args.len()
+                            ),
+                            span: None,
+                        }
+                        .into());
+                    }
+                }
+                let thawed: Vec<_>;
+                let args = if args.iter().any(Value::is_frozen) {
+                    thawed = args.iter().map(|a| a.thawed().clone()).collect();
+                    &thawed
+                } else {
+                    args
+                };
+                let result = (nf.func)(self, args).map_err(crate::builtins::native_error)?;
+                self.allocated(result)
+            }
+            Value::Extension(func) => {
+                let thawed: Vec<_> = args.iter().map(|a| a.thawed().clone()).collect();
+                Ok(func.call_with(self, &thawed)?)
+            }
+            _ => Err(NebulaError::InvalidOperation {
+                message: format!("Cannot call {}", callee.type_name()),
+                span: None,
+            }
+            .into()),
+        }
+    }
     fn call_function(&mut self, func: &FunctionValue, args: &[Value]) -> EvalResult {
+        crate::limits::poll()?;
         self.recursion_depth += 1;
         if self.recursion_depth > MAX_RECURSION_DEPTH {
             self.recursion_depth -= 1;
             return Err(NebulaError::Runtime {
                 message: format!("Maximum recursion depth ({}) exceeded", MAX_RECURSION_DEPTH),
+                span: None,
             }
             .into());
         }
-        let prev = Rc::clone(&self.current);
         let new_env = Environment::with_parent(Rc::clone(&func.closure));
-        self.current = Rc::new(RefCell::new(new_env));
+        self.enter_frame(new_env);
+        let result = self.run_function(func, args);
+        self.leave_frame();
+        self.recursion_depth -= 1;
+        result
+    }
+    fn run_function(&mut self, func: &FunctionValue, args: &[Value]) -> EvalResult {
         for (i, param) in func.params.iter().enumerate() {
             let value = if i < args.len() {
                 args[i].clone()
@@ -1160,29 +1453,35 @@ impl Interpreter {
             };
             self.current.borrow_mut().define(param.name.clone(), value);
         }
-        let result = match &func.body {
+        match &*func.body {
             FunctionBody::Expression(expr) => self.eval_expr(expr),
             FunctionBody::Block(stmts) => {
-                let mut res = Value::Nil;
+                let mut res = Ok(Value::Nil);
                 for stmt in stmts {
                     match self.eval_stmt(stmt) {
-                        Ok(v) => res = v,
+                        Ok(v) => res = Ok(v),
                         Err(EvalError::Control(ControlFlow::Return(value))) => {
-                            self.current = prev;
-                            return Ok(value);
+                            res = Ok(value);
+                            break;
                         }
                         Err(e) => {
-                            self.current = prev;
-                            return Err(e);
+                            res = Err(e);
+                            break;
                         }
                     }
                 }
-                Ok(res)
+                res
             }
-        };
-        self.current = prev;
-        self.recursion_depth -= 1;
-        result
+        }
+    }
+    fn enter_frame(&mut self, env: Environment) {
+        let caller = std::mem::replace(&mut self.current, Rc::new(RefCell::new(env)));
+        self.frames.push(caller);
+    }
+    fn leave_frame(&mut self) {
+        if let Some(caller) = self.frames.pop() {
+            self.current = caller;
+        }
     }
     fn call_lambda(&mut self, lambda: &LambdaValue, args: &[Value]) -> EvalResult {
         self.recursion_depth += 1;
@@ -1190,63 +1489,86 @@ impl Interpreter {
             self.recursion_depth -= 1;
             return Err(NebulaError::Runtime {
                 message: format!("Maximum recursion depth ({}) exceeded", MAX_RECURSION_DEPTH),
+                span: None,
             }
             .into());
         }
-        let prev = Rc::clone(&self.current);
-        let new_env = Environment::with_parent(Rc::clone(&lambda.closure));
-        self.current = Rc::new(RefCell::new(new_env));
+        self.enter_frame(Environment::with_parent(Rc::clone(&lambda.closure)));
         for (i, param) in lambda.params.iter().enumerate() {
             let value = args.get(i).cloned().unwrap_or(Value::Nil);
             self.current.borrow_mut().define(param.clone(), value);
         }
-        let result = self.eval_expr(&lambda.body);
-        self.current = prev;
+        let result = match self.eval_expr(&lambda.body) {
+            Err(EvalError::Control(ControlFlow::Return(value))) => Ok(value),
+            result => result,
+        };
+        self.leave_frame();
         self.recursion_depth -= 1;
         result
     }
-    fn call_method(&mut self, receiver: &Value, method: &str, args: &[Value]) -> EvalResult {
-        match (receiver, method) {
-            (Value::List(arr), "len") => Ok(Value::Integer(arr.len() as i64)),
-            (Value::List(arr), "push") if !args.is_empty() => {
-                let mut new_arr = arr.clone();
-                for arg in args {
-                    new_arr.push(arg.clone());
+    fn eval_args(&mut self, args: &[Expr]) -> Result<Vec<Value>, EvalError> {
+        args.iter().map(|a| self.eval_expr(a)).collect()
+    }
+    fn eval_method_call(&mut self, receiver: &Expr, method: &str, args: &[Expr]) -> EvalResult {
+        let plain_args = args
+            .iter()
+            .all(|a| matches!(a.kind, ExprKind::Literal(_) | ExprKind::Variable(_)));
+        if let ExprKind::Variable(name) = &receiver.kind {
+            if plain_args {
+                let arg_vals = self.eval_args(args)?;
+                if let Some(result) = self.call_builtin_method_on(name, method, &arg_vals) {
+                    return result;
                 }
-                Ok(Value::List(new_arr))
-            }
-            (Value::List(arr), "pop") => {
-                let mut new_arr = arr.clone();
-                let val = new_arr.pop().unwrap_or(Value::Nil);
-                Ok(val)
-            }
-            (Value::String(s), "len") => Ok(Value::Integer(s.len() as i64)),
-            (Value::String(s), "upper") => Ok(Value::String(s.to_uppercase())),
-            (Value::String(s), "lower") => Ok(Value::String(s.to_lowercase())),
-            (Value::String(s), "trim") => Ok(Value::String(s.trim().to_string())),
-            (Value::String(s), "split") if !args.is_empty() => {
-                let sep = args[0].to_display_string();
-                let parts: Vec<_> = s
-                    .split(&sep)
-                    .map(|p| Value::String(p.to_string()))
-                    .collect();
-                Ok(Value::List(parts))
+                let recv_val = self.eval_expr(receiver)?.into_thawed();
+                return self.call_method(&recv_val, method, &arg_vals);
             }
-            (Value::Map(m), "keys") => Ok(Value::List(
-                m.keys().map(|k| Value::String(k.clone())).collect(),
-            )),
-            (Value::Map(m), "values") => Ok(Value::List(m.values().cloned().collect())),
-            _ => Err(NebulaError::Runtime {
-                message: format!("No method '{}' on {}", method, receiver.type_name()),
+        }
+        let recv_val = self.eval_expr(receiver)?.into_thawed();
+        let arg_vals = self.eval_args(args)?;
+        self.call_method(&recv_val, method, &arg_vals)
+    }
+    fn call_builtin_method_on(
+        &self,
+        name: &str,
+        method: &str,
+        args: &[Value],
+    ) -> Option<EvalResult> {
+        self.current
+            .borrow()
+            .with(name, |receiver| {
+                let receiver = receiver.thawed();
+                let user_method = match receiver {
+                    Value::Extern(_) => true,
+                    Value::Struct { name, .. } => self
+                        .methods
+                        .get(name)
+                        .is_some_and(|m| m.contains_key(method)),
+                    _ => false,
+                };
+                (!user_method).then(|| builtin_method(receiver, method, args))
+            })
+            .flatten()
+    }
+    fn call_method(&mut self, receiver: &Value, method: &str, args: &[Value]) -> EvalResult {
+        if let Value::Extern(handle) = receiver {
+            return Ok(crate::ext::call_extern_method(self, handle, method, args)?);
+        }
+        if let Value::Struct { name, .. } = receiver {
+            if let Some(func) = self.methods.get(name).and_then(|m| m.get(method)).cloned() {
+                let call_args: Vec<_> = std::iter::once(receiver.clone())
+                    .chain(args.iter().cloned())
+                    .collect();
+                return self.call_value(&func, &call_args);
             }
-            .into()),
         }
+        builtin_method(receiver, method, args)
     }
     fn get_field(&self, obj: &Value, field: &str) -> EvalResult {
         match obj {
             Value::Map(m) => m.get(field).cloned().ok_or_else(|| {
                 NebulaError::Runtime {
                     message: format!("Key '{}' not found", field),
+                    span: None,
                 }
                 .into()
             }),
@@ -1256,6 +1578,7 @@ impl Interpreter {
                         return fields.get(idx).cloned().ok_or_else(|| {
                             NebulaError::Runtime {
                                 message: format!("Field '{}' not found", field),
+                                span: None,
                             }
                             .into()
                         });
@@ -1263,6 +1586,7 @@ impl Interpreter {
                 }
                 Err(NebulaError::Runtime {
                     message: format!("Field '{}' not found on {}", field, name),
+                    span: None,
                 }
                 .into())
             }
@@ -1272,18 +1596,21 @@ impl Interpreter {
                         NebulaError::IndexOutOfBounds {
                             index: idx as i64,
                             length: elements.len(),
+                            span: None,
                         }
                         .into()
                     })
                 } else {
                     Err(NebulaError::Runtime {
                         message: format!("Invalid tuple index: {}", field),
+                        span: None,
                     }
                     .into())
                 }
             }
             _ => Err(NebulaError::Runtime {
                 message: format!("Cannot access field on {}", obj.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1295,11 +1622,13 @@ impl Interpreter {
                     idx.as_integer()
                         .ok_or(EvalError::Error(NebulaError::InvalidOperation {
                             message: "Index must be integer".to_string(),
+                            span: None,
                         }))?;
                 if i < 0 || i as usize >= list.len() {
                     Err(NebulaError::IndexOutOfBounds {
                         index: i,
                         length: list.len(),
+                        span: None,
                     }
                     .into())
                 } else {
@@ -1311,12 +1640,14 @@ impl Interpreter {
                     idx.as_integer()
                         .ok_or(EvalError::Error(NebulaError::InvalidOperation {
                             message: "Index must be integer".to_string(),
+                            span: None,
                         }))?;
                 let chars: Vec<_> = s.chars().collect();
                 if i < 0 || i as usize >= chars.len() {
                     Err(NebulaError::IndexOutOfBounds {
                         index: i,
                         length: chars.len(),
+                        span: None,
                     }
                     .into())
                 } else {
@@ -1328,12 +1659,14 @@ impl Interpreter {
                 m.get(&key).cloned().ok_or_else(|| {
                     NebulaError::Runtime {
                         message: format!("Key '{}' not found", key),
+                        span: None,
                     }
                     .into()
                 })
             }
             _ => Err(NebulaError::InvalidOperation {
                 message: format!("Cannot index {} with {}", arr.type_name(), idx.type_name()),
+                span: None,
             }
             .into()),
         }
@@ -1345,6 +1678,7 @@ impl Interpreter {
                     .as_number()
                     .ok_or(EvalError::Error(NebulaError::InvalidOperation {
                         message: "Cannot convert to number".to_string(),
+                        span: None,
                     }))?;
                 Ok(Value::Number(n))
             }
@@ -1353,13 +1687,22 @@ impl Interpreter {
                     val.as_integer()
                         .ok_or(EvalError::Error(NebulaError::InvalidOperation {
                             message: "Cannot convert to integer".to_string(),
+                            span: None,
                         }))?;
                 Ok(Value::Integer(n))
             }
-            Type::Wrd => Ok(Value::String(val.to_display_string())),
+            Type::Wrd => Ok(Value::String(val.to_display_string().into())),
             _ => Ok(val),
         }
     }
+    fn allocated(&self, value: Value) -> EvalResult {
+        crate::limits::charge_value(&value, || self.live_bytes())?;
+        Ok(value)
+    }
+    fn live_bytes(&self) -> usize {
+        let roots = self.frames.iter().chain([&self.global, &self.current]);
+        crate::limits::live_bytes(roots.cloned())
+    }
     fn push_scope(&mut self) {
         let new_env = Environment::with_parent(Rc::clone(&self.current));
         self.current = Rc::new(RefCell::new(new_env));
@@ -1371,6 +1714,51 @@ impl Interpreter {
         }
     }
 }
+impl NativeContext for Interpreter {
+    fn call(&mut self, callee: &Value, args: &[Value]) -> Result<Value, String> {
+        match self.call_value(callee, args) {
+            Ok(value) => Ok(value),
+            Err(EvalError::Error(e)) => Err(crate::builtins::callback_error(e)),
+            Err(EvalError::Control(_)) => Err("control flow escaped callback".to_string()),
+        }
+    }
+    fn globals(&self) -> Vec<String> {
+        self.global_bindings()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+    fn engine_stats(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("engine", Value::String("interp".into())),
+            ("frames", Value::Integer(self.recursion_depth as i64)),
+            ("iterations", Value::Integer(self.iteration_count as i64)),
+        ]
+    }
+    fn memory_stats(&self) -> Vec<(&'static str, Value)> {
+        let allocations = crate::limits::usage()
+            .allocations
+            .saturating_sub(self.usage.allocations);
+        vec![
+            ("engine", Value::String("interp".into())),
+            ("allocations", Value::Integer(allocations as i64)),
+            ("live_bytes", Value::Integer(self.live_bytes() as i64)),
+        ]
+    }
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.current.borrow().get(name)
+    }
+    fn define(&mut self, name: &str, value: Value) -> Result<(), String> {
+        self.global.borrow_mut().define(name.to_string(), value);
+        Ok(())
+    }
+    fn stdio(&mut self) -> Option<&mut Stdio> {
+        Some(&mut self.stdio)
+    }
+    fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+}
 impl Default for Interpreter {
     fn default() -> Self {
         Self::new()
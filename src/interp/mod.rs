@@ -1,6 +1,14 @@
+mod convert;
 mod env;
 mod eval;
+mod shared;
+mod stdio;
 mod value;
+pub use convert::{from_script, to_script};
 pub use env::Environment;
 pub use eval::Interpreter;
-pub use value::{FunctionValue, LambdaValue, NativeFn, Value};
+pub use shared::SharedValue;
+pub use stdio::{with_stdio, OutputCapture, Stdio};
+pub use value::{
+    FunctionValue, LambdaValue, NativeContext, NativeFn, NativeFunc, RangeValue, Value,
+};
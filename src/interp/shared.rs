@@ -0,0 +1,156 @@
+use super::{RangeValue, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedValue {
+    Number(f64),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    String(Arc<str>),
+    Byte(u8),
+    Char(char),
+    Nil,
+    List(Arc<[SharedValue]>),
+    Map(Arc<HashMap<String, SharedValue>>),
+    Tuple(Arc<[SharedValue]>),
+    Set(Arc<[SharedValue]>),
+    Range(RangeValue),
+    Struct {
+        name: Arc<str>,
+        fields: Arc<[SharedValue]>,
+    },
+}
+impl SharedValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SharedValue::Number(_) => "nb",
+            SharedValue::Integer(_) => "int",
+            SharedValue::Float(_) => "fl",
+            SharedValue::Bool(_) => "bool",
+            SharedValue::String(_) => "wrd",
+            SharedValue::Byte(_) => "by",
+            SharedValue::Char(_) => "chr",
+            SharedValue::Nil => "nil",
+            SharedValue::List(_) => "lst",
+            SharedValue::Map(_) => "map",
+            SharedValue::Tuple(_) => "tup",
+            SharedValue::Set(_) => "set",
+            SharedValue::Range(_) => "range",
+            SharedValue::Struct { .. } => "struct",
+        }
+    }
+}
+impl TryFrom<&Value> for SharedValue {
+    type Error = String;
+    fn try_from(value: &Value) -> Result<Self, String> {
+        let items = |items: &[Value]| {
+            items
+                .iter()
+                .map(SharedValue::try_from)
+                .collect::<Result<Arc<[_]>, _>>()
+        };
+        Ok(match value {
+            Value::Number(n) => SharedValue::Number(*n),
+            Value::Integer(i) => SharedValue::Integer(*i),
+            Value::Float(f) => SharedValue::Float(*f),
+            Value::Bool(b) => SharedValue::Bool(*b),
+            Value::String(s) => SharedValue::String(Arc::from(&**s)),
+            Value::Byte(b) => SharedValue::Byte(*b),
+            Value::Char(c) => SharedValue::Char(*c),
+            Value::Nil => SharedValue::Nil,
+            Value::List(list) => SharedValue::List(items(list)?),
+            Value::Tuple(tuple) => SharedValue::Tuple(items(tuple)?),
+            Value::Set(set) => SharedValue::Set(items(set)?),
+            Value::Map(map) => SharedValue::Map(Arc::new(
+                map.iter()
+                    .map(|(k, v)| Ok((k.clone(), SharedValue::try_from(v)?)))
+                    .collect::<Result<_, String>>()?,
+            )),
+            Value::Range(range) => SharedValue::Range(*range),
+            Value::Struct { name, fields } => SharedValue::Struct {
+                name: name.as_str().into(),
+                fields: items(fields)?,
+            },
+            Value::Frozen(inner) => SharedValue::try_from(inner.as_ref())?,
+            other => {
+                return Err(format!(
+                    "a {} value cannot be shared between threads",
+                    other.type_name()
+                ))
+            }
+        })
+    }
+}
+impl TryFrom<Value> for SharedValue {
+    type Error = String;
+    fn try_from(value: Value) -> Result<Self, String> {
+        SharedValue::try_from(&value)
+    }
+}
+impl From<&SharedValue> for Value {
+    fn from(value: &SharedValue) -> Self {
+        let items = |items: &[SharedValue]| items.iter().map(Value::from).collect();
+        match value {
+            SharedValue::Number(n) => Value::Number(*n),
+            SharedValue::Integer(i) => Value::Integer(*i),
+            SharedValue::Float(f) => Value::Float(*f),
+            SharedValue::Bool(b) => Value::Bool(*b),
+            SharedValue::String(s) => Value::String(Rc::from(&**s)),
+            SharedValue::Byte(b) => Value::Byte(*b),
+            SharedValue::Char(c) => Value::Char(*c),
+            SharedValue::Nil => Value::Nil,
+            SharedValue::List(list) => Value::List(items(list)),
+            SharedValue::Tuple(tuple) => Value::Tuple(items(tuple)),
+            SharedValue::Set(set) => Value::Set(items(set)),
+            SharedValue::Map(map) => {
+                Value::Map(map.iter().map(|(k, v)| (k.clone(), v.into())).collect())
+            }
+            SharedValue::Range(range) => Value::Range(*range),
+            SharedValue::Struct { name, fields } => Value::Struct {
+                name: name.to_string(),
+                fields: items(fields),
+            },
+        }
+    }
+}
+impl From<SharedValue> for Value {
+    fn from(value: SharedValue) -> Self {
+        Value::from(&value)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn is_send_sync<T: Send + Sync>() {}
+    #[test]
+    fn test_shared_values_round_trip_across_threads() {
+        is_send_sync::<SharedValue>();
+        let value = Value::Map(HashMap::from([
+            (
+                "xs".to_string(),
+                Value::List(vec![Value::Integer(1), Value::String("two".into())]),
+            ),
+            (
+                "p".to_string(),
+                Value::Frozen(std::rc::Rc::new(Value::Struct {
+                    name: "P".into(),
+                    fields: vec![Value::Bool(true), Value::Nil],
+                })),
+            ),
+        ]));
+        let shared = SharedValue::try_from(&value).unwrap();
+        let back = std::thread::spawn(move || shared.clone()).join().unwrap();
+        let Value::Map(map) = Value::from(back) else {
+            panic!("expected a map");
+        };
+        assert_eq!(map["xs"].to_string(), "lst(1, two)");
+        assert_eq!(map["p"].type_name(), "struct");
+        let channel = Value::Channel(Default::default());
+        assert_eq!(
+            SharedValue::try_from(&channel).unwrap_err(),
+            "a chan value cannot be shared between threads"
+        );
+    }
+}
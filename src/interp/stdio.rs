@@ -0,0 +1,101 @@
+use super::NativeContext;
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read, Write};
+use std::rc::Rc;
+#[derive(Default)]
+pub struct Stdio {
+    output: Option<Box<dyn Write>>,
+    error: Option<Box<dyn Write>>,
+    input: Option<Box<dyn BufRead>>,
+}
+impl Stdio {
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Some(output);
+    }
+    pub fn set_error(&mut self, error: Box<dyn Write>) {
+        self.error = Some(error);
+    }
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = Some(input);
+    }
+    pub fn write(&mut self, text: &str) -> Result<(), String> {
+        match &mut self.output {
+            Some(output) => output.write_all(text.as_bytes()),
+            None => io::stdout().write_all(text.as_bytes()),
+        }
+        .map_err(|e| e.to_string())
+    }
+    pub fn write_error(&mut self, text: &str) -> Result<(), String> {
+        match &mut self.error {
+            Some(error) => error.write_all(text.as_bytes()),
+            None => io::stderr().write_all(text.as_bytes()),
+        }
+        .map_err(|e| e.to_string())
+    }
+    pub fn flush(&mut self) -> Result<(), String> {
+        match &mut self.output {
+            Some(output) => output.flush(),
+            None => io::stdout().flush(),
+        }
+        .and_then(|()| match &mut self.error {
+            Some(error) => error.flush(),
+            None => io::stderr().flush(),
+        })
+        .map_err(|e| e.to_string())
+    }
+    pub fn read_line(&mut self) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        let read = match &mut self.input {
+            Some(input) => input.read_line(&mut line),
+            None => io::stdin().read_line(&mut line),
+        }
+        .map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+    pub fn read_all(&mut self) -> Result<String, String> {
+        let mut text = String::new();
+        match &mut self.input {
+            Some(input) => input.read_to_string(&mut text),
+            None => io::stdin().read_to_string(&mut text),
+        }
+        .map_err(|e| e.to_string())?;
+        Ok(text)
+    }
+}
+pub fn with_stdio<T>(ctx: &mut dyn NativeContext, f: impl FnOnce(&mut Stdio) -> T) -> T {
+    match ctx.stdio() {
+        Some(stdio) => f(stdio),
+        None => f(&mut Stdio::default()),
+    }
+}
+#[derive(Debug, Clone, Default)]
+pub struct OutputCapture(Rc<RefCell<Vec<u8>>>);
+impl OutputCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&std::mem::take(&mut *self.0.borrow_mut())).into_owned()
+    }
+}
+impl Write for OutputCapture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
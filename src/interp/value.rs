@@ -3,13 +3,14 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
+use std::sync::Arc;
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
     Integer(i64),
     Float(f64),
     Bool(bool),
-    String(String),
+    String(Rc<str>),
     Byte(u8),
     Char(char),
     Nil,
@@ -17,32 +18,125 @@ pub enum Value {
     Map(HashMap<String, Value>),
     Tuple(Vec<Value>),
     Set(Vec<Value>),
-    Range(i64, i64, bool),
+    Range(RangeValue),
     Function(Rc<FunctionValue>),
     Lambda(Rc<LambdaValue>),
     NativeFunction(NativeFn),
+    Extension(crate::ext::ExtFunction),
+    Future(crate::ext::ExtFuture),
+    Extern(crate::ext::Extern),
+    VmFunction { name: String, handle: usize },
     Struct { name: String, fields: Vec<Value> },
     Channel(Rc<RefCell<Vec<Value>>>),
+    Frozen(Rc<Value>),
 }
 #[derive(Debug, Clone)]
 pub struct FunctionValue {
     pub name: String,
-    pub params: Vec<Param>,
-    pub body: crate::parser::ast::FunctionBody,
+    pub params: Arc<Vec<Param>>,
+    pub body: Arc<crate::parser::ast::FunctionBody>,
     pub closure: Rc<RefCell<super::Environment>>,
     pub is_async: bool,
 }
 #[derive(Debug, Clone)]
 pub struct LambdaValue {
-    pub params: Vec<String>,
-    pub body: crate::parser::ast::Expr,
+    pub params: Arc<Vec<String>>,
+    pub body: Arc<crate::parser::ast::Expr>,
     pub closure: Rc<RefCell<super::Environment>>,
 }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeValue {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+impl RangeValue {
+    pub fn new(start: i64, end: i64, inclusive: bool) -> Self {
+        Self {
+            start,
+            end,
+            step: 1,
+            inclusive,
+        }
+    }
+    pub fn in_bounds(&self, n: i64) -> bool {
+        match (self.step > 0, self.inclusive) {
+            (true, true) => n <= self.end,
+            (true, false) => n < self.end,
+            (false, true) => n >= self.end,
+            (false, false) => n > self.end,
+        }
+    }
+    pub fn iter(self) -> impl Iterator<Item = i64> {
+        std::iter::successors(Some(self.start), move |n| n.checked_add(self.step))
+            .take_while(move |n| self.in_bounds(*n))
+    }
+    pub fn len(&self) -> usize {
+        if !self.in_bounds(self.start) {
+            return 0;
+        }
+        let span = (self.end as i128 - self.start as i128).abs();
+        let step = (self.step as i128).abs();
+        let count = if self.inclusive {
+            span / step + 1
+        } else {
+            (span + step - 1) / step
+        };
+        count.min(i64::MAX as i128) as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn contains(&self, n: i64) -> bool {
+        let offset = n as i128 - self.start as i128;
+        (n == self.start || (offset.signum() == (self.step as i128).signum()))
+            && offset % self.step as i128 == 0
+            && self.in_bounds(n)
+    }
+}
+pub trait NativeContext {
+    fn call(&mut self, callee: &Value, args: &[Value]) -> Result<Value, String>;
+    fn globals(&self) -> Vec<String> {
+        Vec::new()
+    }
+    fn engine_stats(&self) -> Vec<(&'static str, Value)> {
+        Vec::new()
+    }
+    fn memory_stats(&self) -> Vec<(&'static str, Value)> {
+        Vec::new()
+    }
+    fn lookup(&self, _name: &str) -> Option<Value> {
+        None
+    }
+    fn define(&mut self, name: &str, _value: Value) -> Result<(), String> {
+        Err(format!("cannot define '{}' here", name))
+    }
+    fn stdio(&mut self) -> Option<&mut super::Stdio> {
+        None
+    }
+    fn policy(&self) -> &crate::builtins::SandboxPolicy {
+        &crate::builtins::LOCKED
+    }
+}
+pub type NativeFunc = Rc<dyn Fn(&mut dyn NativeContext, &[Value]) -> Result<Value, String>>;
 #[derive(Clone)]
 pub struct NativeFn {
     pub name: String,
     pub arity: Option<usize>,
-    pub func: fn(&[Value]) -> Result<Value, String>,
+    pub func: NativeFunc,
+}
+impl NativeFn {
+    pub fn new<F>(name: impl Into<String>, arity: Option<usize>, func: F) -> Self
+    where
+        F: Fn(&mut dyn NativeContext, &[Value]) -> Result<Value, String> + 'static,
+    {
+        Self {
+            name: name.into(),
+            arity,
+            func: Rc::new(func),
+        }
+    }
 }
 impl fmt::Debug for NativeFn {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -64,12 +158,34 @@ impl Value {
             Value::Map(_) => "map",
             Value::Tuple(_) => "tup",
             Value::Set(_) => "set",
-            Value::Range(_, _, _) => "range",
+            Value::Range(_) => "range",
             Value::Function(_) => "fn",
             Value::Lambda(_) => "fn",
             Value::NativeFunction(_) => "fn",
+            Value::Extension(_) => "fn",
+            Value::Future(_) => "future",
+            Value::Extern(_) => "extern",
+            Value::VmFunction { .. } => "fn",
             Value::Struct { .. } => "struct",
             Value::Channel(_) => "chan",
+            Value::Frozen(inner) => inner.type_name(),
+        }
+    }
+    pub fn is_frozen(&self) -> bool {
+        matches!(self, Value::Frozen(_))
+    }
+    pub fn thawed(&self) -> &Value {
+        match self {
+            Value::Frozen(inner) => inner.thawed(),
+            other => other,
+        }
+    }
+    pub fn into_thawed(self) -> Value {
+        match self {
+            Value::Frozen(inner) => Rc::try_unwrap(inner)
+                .unwrap_or_else(|shared| (*shared).clone())
+                .into_thawed(),
+            other => other,
         }
     }
     pub fn is_truthy(&self) -> bool {
@@ -81,6 +197,7 @@ impl Value {
             Value::Float(f) => *f != 0.0,
             Value::String(s) => !s.is_empty(),
             Value::List(arr) => !arr.is_empty(),
+            Value::Frozen(inner) => inner.is_truthy(),
             _ => true,
         }
     }
@@ -113,11 +230,34 @@ impl Value {
         }
     }
     pub fn to_display_string(&self) -> String {
-        match self {
-            Value::String(s) => s.clone(),
+        match self.thawed() {
+            Value::String(s) => s.to_string(),
             other => format!("{}", other),
         }
     }
+    pub fn repr(&self) -> String {
+        fn seq(prefix: &str, items: &[Value]) -> String {
+            let parts: Vec<_> = items.iter().map(Value::repr).collect();
+            format!("{}({})", prefix, parts.join(", "))
+        }
+        match self.thawed() {
+            Value::String(s) => format!("{:?}", s),
+            Value::Char(c) => format!("{:?}", c),
+            Value::List(items) => seq("lst", items),
+            Value::Tuple(items) => seq("", items),
+            Value::Set(items) => seq("set", items),
+            Value::Map(map) => {
+                let mut keys: Vec<_> = map.keys().collect();
+                keys.sort();
+                let parts: Vec<_> = keys
+                    .into_iter()
+                    .map(|k| format!("{:?}: {}", k, map[k].repr()))
+                    .collect();
+                format!("map({})", parts.join(", "))
+            }
+            other => other.to_string(),
+        }
+    }
 }
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -149,11 +289,13 @@ impl fmt::Display for Value {
             }
             Value::Map(m) => {
                 write!(f, "map(")?;
-                for (i, (k, v)) in m.iter().enumerate() {
+                let mut keys: Vec<_> = m.keys().collect();
+                keys.sort();
+                for (i, k) in keys.into_iter().enumerate() {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "\"{}\": {}", k, v)?;
+                    write!(f, "\"{}\": {}", k, m[k])?;
                 }
                 write!(f, ")")
             }
@@ -177,16 +319,23 @@ impl fmt::Display for Value {
                 }
                 write!(f, ")")
             }
-            Value::Range(start, end, inclusive) => {
-                if *inclusive {
-                    write!(f, "{}..{}", start, end)
+            Value::Range(r) if r.step != 1 => {
+                write!(f, "range({}, {}, {})", r.start, r.end, r.step)
+            }
+            Value::Range(r) => {
+                if r.inclusive {
+                    write!(f, "{}..{}", r.start, r.end)
                 } else {
-                    write!(f, "{}..<{}", start, end)
+                    write!(f, "{}..<{}", r.start, r.end)
                 }
             }
             Value::Function(func) => write!(f, "<fn {}>", func.name),
             Value::Lambda(_) => write!(f, "<lambda>"),
             Value::NativeFunction(nf) => write!(f, "<native fn {}>", nf.name),
+            Value::Extension(func) => write!(f, "<native fn {}>", func.name),
+            Value::Future(future) => write!(f, "{:?}", future),
+            Value::Extern(handle) => write!(f, "{:?}", handle),
+            Value::VmFunction { name, .. } => write!(f, "<fn {}>", name),
             Value::Struct { name, fields } => {
                 write!(f, "{}(", name)?;
                 for (i, v) in fields.iter().enumerate() {
@@ -198,12 +347,15 @@ impl fmt::Display for Value {
                 write!(f, ")")
             }
             Value::Channel(_) => write!(f, "<chan>"),
+            Value::Frozen(inner) => write!(f, "{}", inner),
         }
     }
 }
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (Value::Frozen(a), b) => a.as_ref() == b,
+            (a, Value::Frozen(b)) => a == b.as_ref(),
             (Value::Number(a), Value::Number(b)) => a == b,
             (Value::Integer(a), Value::Integer(b)) => a == b,
             (Value::Float(a), Value::Float(b)) => a == b,
@@ -212,6 +364,12 @@ impl PartialEq for Value {
             (Value::Nil, Value::Nil) => true,
             (Value::List(a), Value::List(b)) => a == b,
             (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Set(a), Value::Set(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::Byte(a), Value::Byte(b)) => a == b,
+            (Value::Range(a), Value::Range(b)) => a == b,
+            (Value::Extern(a), Value::Extern(b)) => a.ptr_eq(b),
             (Value::Number(a), Value::Integer(b)) => *a == *b as f64,
             (Value::Integer(a), Value::Number(b)) => *a as f64 == *b,
             _ => false,
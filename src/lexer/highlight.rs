@@ -0,0 +1,65 @@
+use super::{Lexer, TokenKind};
+use colored::{ColoredString, Colorize};
+fn paint(kind: &TokenKind, text: &str) -> Option<ColoredString> {
+    let painted = match kind {
+        TokenKind::Integer(_) | TokenKind::Float(_) => text.yellow(),
+        TokenKind::String(_) | TokenKind::Interpolated(_) => text.green(),
+        TokenKind::On | TokenKind::Off | TokenKind::Empty => text.cyan(),
+        TokenKind::Nb
+        | TokenKind::Int
+        | TokenKind::Fl
+        | TokenKind::Wrd
+        | TokenKind::By
+        | TokenKind::Chr
+        | TokenKind::Any
+        | TokenKind::Void
+        | TokenKind::Lst
+        | TokenKind::Map
+        | TokenKind::Tup
+        | TokenKind::Set => text.blue(),
+        TokenKind::Error(_) => text.red().underline(),
+        TokenKind::Identifier(_) => return None,
+        _ if TokenKind::keyword_from_str(text).is_some() => text.purple().bold(),
+        _ => return None,
+    };
+    Some(painted)
+}
+pub fn highlight(source: &str) -> String {
+    let mut out = String::with_capacity(source.len() * 2);
+    let mut cursor = 0;
+    for token in Lexer::new(source) {
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+        let start = token.span.start.min(source.len());
+        let end = token.span.end().min(source.len());
+        if start < cursor {
+            continue;
+        }
+        out.push_str(&source[cursor..start]);
+        let text = &source[start..end];
+        match paint(&token.kind, text) {
+            Some(painted) => out.push_str(&painted.to_string()),
+            None => out.push_str(text),
+        }
+        cursor = end;
+    }
+    out.push_str(&source[cursor..]);
+    out
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_highlight_preserves_text() {
+        colored::control::set_override(true);
+        let source = "perm x = lst(1, \"two\") # note";
+        let painted = highlight(source);
+        assert!(painted.contains(&"perm".purple().bold().to_string()));
+        assert!(painted.contains(&"\"two\"".green().to_string()));
+        assert!(painted.ends_with(" # note"));
+        colored::control::set_override(false);
+        assert_eq!(highlight(source), source);
+        colored::control::unset_override();
+    }
+}
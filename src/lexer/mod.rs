@@ -1,4 +1,10 @@
+#[cfg(feature = "color")]
+mod highlight;
 mod scanner;
+mod source;
 mod token;
+#[cfg(feature = "color")]
+pub use highlight::highlight;
 pub use scanner::Lexer;
-pub use token::{Span, Token, TokenKind};
+pub use source::{display_width, expand_tabs, FileId, Position, SourceFile, SourceMap, TAB_WIDTH};
+pub use token::{Span, StringPart, Token, TokenKind};
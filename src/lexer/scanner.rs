@@ -1,26 +1,60 @@
-use super::token::{Span, Token, TokenKind};
+use super::source::FileId;
+use super::token::{Span, StringPart, Token, TokenKind};
+use crate::error::NebulaError;
 pub struct Lexer<'src> {
-    #[allow(dead_code)]
     source: &'src str,
-    chars: Vec<char>,
     current: usize,
     start: usize,
     line: usize,
     column: usize,
+    start_line: usize,
     start_column: usize,
     emitted_eof: bool,
+    error_span: Option<Span>,
+    expects_operand: bool,
+    file: FileId,
 }
 impl<'src> Lexer<'src> {
     pub fn new(source: &'src str) -> Self {
         Self {
             source,
-            chars: source.chars().collect(),
             current: 0,
             start: 0,
             line: 1,
             column: 1,
+            start_line: 1,
             start_column: 1,
             emitted_eof: false,
+            error_span: None,
+            expects_operand: false,
+            file: FileId::default(),
+        }
+    }
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
+    pub fn with_first_line(mut self, line: usize) -> Self {
+        self.line = line.max(1);
+        self.start_line = self.line;
+        self
+    }
+    pub fn tokenize(self) -> Result<Vec<Token>, Vec<NebulaError>> {
+        let tokens: Vec<Token> = self.collect();
+        let errors: Vec<NebulaError> = tokens
+            .iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Error(message) => Some(NebulaError::Lexer {
+                    message: message.clone(),
+                    span: token.span,
+                }),
+                _ => None,
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
         }
     }
     pub fn scan_token(&mut self) -> Option<Token> {
@@ -30,10 +64,18 @@ impl<'src> Lexer<'src> {
                 return None;
             }
             self.emitted_eof = true;
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             return Some(self.make_token(TokenKind::Eof));
         }
         self.start = self.current;
+        self.start_line = self.line;
         self.start_column = self.column;
+        if self.starts_doc_comment() {
+            let kind = self.scan_doc_comment();
+            return Some(self.make_token(kind));
+        }
         let c = self.advance();
         let kind = match c {
             '(' => TokenKind::LeftParen,
@@ -45,7 +87,13 @@ impl<'src> Lexer<'src> {
             ',' => TokenKind::Comma,
             ';' => TokenKind::Semicolon,
             '~' => TokenKind::Tilde,
-            '?' => TokenKind::Question,
+            '?' => {
+                if self.match_char('?') {
+                    TokenKind::QuestionQuestion
+                } else {
+                    TokenKind::Question
+                }
+            }
             '+' => {
                 if self.match_char('=') {
                     TokenKind::PlusAssign
@@ -135,11 +183,7 @@ impl<'src> Lexer<'src> {
                 }
             }
             '#' => TokenKind::Hash,
-            '\n' => {
-                self.line += 1;
-                self.column = 1;
-                TokenKind::Newline
-            }
+            '\n' => TokenKind::Newline,
             '"' => self.scan_string('"'),
             '\'' => {
                 if self.peek() == '\'' && self.peek_next() == Some('\'') {
@@ -155,7 +199,27 @@ impl<'src> Lexer<'src> {
             c if c.is_alphabetic() || c == '_' => self.scan_identifier(c),
             _ => TokenKind::Error(format!("Unexpected character '{}'", c)),
         };
-        Some(self.make_token(kind))
+        self.expects_operand = !matches!(
+            kind,
+            TokenKind::Newline
+                | TokenKind::Integer(_)
+                | TokenKind::Float(_)
+                | TokenKind::String(_)
+                | TokenKind::Interpolated(_)
+                | TokenKind::Identifier(_)
+                | TokenKind::On
+                | TokenKind::Off
+                | TokenKind::Empty
+                | TokenKind::RightParen
+                | TokenKind::RightBracket
+                | TokenKind::RightBrace
+                | TokenKind::End
+        );
+        let mut token = self.make_token(kind);
+        if let Some(span) = self.error_span.take() {
+            token.span = span;
+        }
+        Some(token)
     }
     fn skip_whitespace_and_comments(&mut self) {
         loop {
@@ -166,7 +230,8 @@ impl<'src> Lexer<'src> {
                 ' ' | '\t' | '\r' => {
                     self.advance();
                 }
-                '#' => {
+                '#' if self.starts_doc_comment() => break,
+                '#' if self.hash_starts_comment() => {
                     while !self.is_at_end() && self.peek() != '\n' {
                         self.advance();
                     }
@@ -175,50 +240,72 @@ impl<'src> Lexer<'src> {
             }
         }
     }
+    fn hash_starts_comment(&self) -> bool {
+        let attached = self.peek_next().is_some_and(|next| !next.is_whitespace());
+        !(self.expects_operand && attached)
+    }
+    fn starts_doc_comment(&self) -> bool {
+        let indent = self.source[..self.current]
+            .rsplit('\n')
+            .next()
+            .unwrap_or("");
+        self.source[self.current..].starts_with("##")
+            && self.hash_starts_comment()
+            && indent.trim().is_empty()
+    }
+    fn scan_doc_comment(&mut self) -> TokenKind {
+        self.advance();
+        self.advance();
+        while !self.is_at_end() && self.peek() != '\n' {
+            self.advance();
+        }
+        let text = &self.source[self.start + 2..self.current];
+        let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+        TokenKind::DocComment(text.to_string())
+    }
     fn scan_block_comment(&mut self) -> TokenKind {
         while !self.is_at_end() {
             if self.peek() == '\''
                 && self.peek_next() == Some('\'')
-                && self.current + 2 < self.chars.len()
-                && self.chars[self.current + 2] == '\''
+                && self.source[self.current..].starts_with("'''")
             {
                 self.advance();
                 self.advance();
                 self.advance();
                 return self.scan_token().map(|t| t.kind).unwrap_or(TokenKind::Eof);
             }
-            if self.peek() == '\n' {
-                self.line += 1;
-                self.column = 0;
-            }
             self.advance();
         }
         TokenKind::Error("Unterminated block comment".into())
     }
     fn scan_string(&mut self, quote: char) -> TokenKind {
         let mut value = String::new();
+        let mut parts = Vec::new();
+        let mut invalid = None;
         while !self.is_at_end() && self.peek() != quote {
             let c = self.advance();
-            if c == '\n' {
-                self.line += 1;
-                self.column = 1;
-            }
-            if c == '\\' && !self.is_at_end() {
-                let escaped = self.advance();
-                match escaped {
-                    'n' => value.push('\n'),
-                    't' => value.push('\t'),
-                    'r' => value.push('\r'),
-                    '\\' => value.push('\\'),
-                    '"' => value.push('"'),
-                    '\'' => value.push('\''),
-                    '0' => value.push('\0'),
-                    _ => {
-                        return TokenKind::Error(format!(
-                            "Invalid escape sequence '\\{}'",
-                            escaped
-                        ));
+            if c == '$' && self.peek() == '{' {
+                self.advance();
+                match self.scan_interpolation() {
+                    Ok(tokens) => {
+                        if !value.is_empty() {
+                            parts.push(StringPart::Text(std::mem::take(&mut value)));
+                        }
+                        parts.push(StringPart::Code(tokens));
                     }
+                    Err(message) => return TokenKind::Error(message),
+                }
+            } else if c == '\\' && !self.is_at_end() {
+                let (start, line, column) =
+                    (self.current - c.len_utf8(), self.line, self.column - 1);
+                match self.scan_escape() {
+                    Ok(escaped) => value.push(escaped),
+                    Err(message) if invalid.is_none() => {
+                        let span = Span::new(start, self.current - start, line, column)
+                            .with_file(self.file);
+                        invalid = Some((message, span));
+                    }
+                    Err(_) => {}
                 }
             } else {
                 value.push(c);
@@ -228,17 +315,132 @@ impl<'src> Lexer<'src> {
             return TokenKind::Error("Unterminated string".into());
         }
         self.advance();
-        TokenKind::String(value)
+        if let Some((message, span)) = invalid {
+            self.error_span = Some(span);
+            return TokenKind::Error(message);
+        }
+        if parts.is_empty() {
+            return TokenKind::String(value);
+        }
+        if !value.is_empty() {
+            parts.push(StringPart::Text(value));
+        }
+        TokenKind::Interpolated(parts)
+    }
+    fn scan_escape(&mut self) -> Result<char, String> {
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            '$' => Ok('$'),
+            '0' => Ok('\0'),
+            'x' => {
+                let digits = self.hex_digits(2);
+                if digits.len() != 2 {
+                    return Err(format!("Expected two hex digits in '\\x{}'", digits));
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if byte.is_ascii() => Ok(byte as char),
+                    _ => Err(format!(
+                        "'\\x{}' is above 0x7F; use '\\u{{{}}}' instead",
+                        digits, digits
+                    )),
+                }
+            }
+            'u' => {
+                if !self.match_char('{') {
+                    return Err("Expected '{' after '\\u'".into());
+                }
+                let digits = self.hex_digits(6);
+                if !self.match_char('}') {
+                    return Err(format!("Unterminated unicode escape '\\u{{{}'", digits));
+                }
+                if digits.is_empty() {
+                    return Err("Empty unicode escape '\\u{}'".into());
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("'\\u{{{}}}' is not a valid unicode character", digits))
+            }
+            other => Err(format!("Invalid escape sequence '\\{}'", other)),
+        }
+    }
+    fn hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max && !self.is_at_end() && self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+        digits
+    }
+    fn scan_interpolation(&mut self) -> Result<Vec<Token>, String> {
+        let (open, line, column) = (self.current, self.line, self.column);
+        self.skip_interpolation()?;
+        let mut inner = Lexer {
+            source: &self.source[..self.current - 1],
+            current: open,
+            start: open,
+            line,
+            column,
+            start_line: line,
+            start_column: column,
+            emitted_eof: false,
+            error_span: None,
+            expects_operand: true,
+            file: self.file,
+        };
+        let tokens: Vec<Token> = inner
+            .by_ref()
+            .take_while(|token| token.kind != TokenKind::Eof)
+            .collect();
+        if let Some(TokenKind::Error(message)) = tokens
+            .iter()
+            .map(|token| &token.kind)
+            .find(|kind| matches!(kind, TokenKind::Error(_)))
+        {
+            return Err(format!("{} in interpolation", message));
+        }
+        if tokens.iter().all(|token| token.kind == TokenKind::Newline) {
+            return Err("Empty interpolation '${}'".into());
+        }
+        Ok(tokens)
+    }
+    fn skip_interpolation(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        while !self.is_at_end() {
+            match self.advance() {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(()),
+                '}' => depth -= 1,
+                quote @ ('"' | '\'' | '`') => self.skip_quoted(quote)?,
+                _ => {}
+            }
+        }
+        Err("Unterminated interpolation '${'".into())
+    }
+    fn skip_quoted(&mut self, quote: char) -> Result<(), String> {
+        while !self.is_at_end() {
+            match self.advance() {
+                c if c == quote => return Ok(()),
+                '\\' if quote != '`' && !self.is_at_end() => {
+                    self.advance();
+                }
+                '$' if quote != '`' && self.peek() == '{' => {
+                    self.advance();
+                    self.skip_interpolation()?;
+                }
+                _ => {}
+            }
+        }
+        Err("Unterminated string in interpolation".into())
     }
     fn scan_raw_string(&mut self) -> TokenKind {
         let mut value = String::new();
         while !self.is_at_end() && self.peek() != '`' {
-            let c = self.advance();
-            if c == '\n' {
-                self.line += 1;
-                self.column = 1;
-            }
-            value.push(c);
+            value.push(self.advance());
         }
         if self.is_at_end() {
             return TokenKind::Error("Unterminated raw string".into());
@@ -264,81 +466,111 @@ impl<'src> Lexer<'src> {
                 _ => {}
             }
         }
-        while !self.is_at_end() && self.peek().is_ascii_digit() {
-            self.advance();
-        }
+        self.scan_digits(char::is_ascii_digit);
         if !self.is_at_end() && self.peek() == '.' {
             if let Some(next) = self.peek_next() {
                 if next.is_ascii_digit() {
                     self.advance();
-                    while !self.is_at_end() && self.peek().is_ascii_digit() {
-                        self.advance();
-                    }
+                    self.scan_digits(char::is_ascii_digit);
                     if !self.is_at_end() && (self.peek() == 'e' || self.peek() == 'E') {
                         self.advance();
                         if !self.is_at_end() && (self.peek() == '+' || self.peek() == '-') {
                             self.advance();
                         }
-                        while !self.is_at_end() && self.peek().is_ascii_digit() {
-                            self.advance();
-                        }
+                        self.scan_digits(char::is_ascii_digit);
+                    }
+                    if let Some(error) = self.misplaced_separator() {
+                        return error;
                     }
                     let lexeme = self.current_lexeme();
-                    return match lexeme.parse::<f64>() {
+                    return match lexeme.replace('_', "").parse::<f64>() {
                         Ok(n) => TokenKind::Float(n),
                         Err(_) => TokenKind::Error(format!("Invalid float literal: {}", lexeme)),
                     };
                 }
             }
         }
+        if let Some(error) = self.misplaced_separator() {
+            return error;
+        }
         let lexeme = self.current_lexeme();
-        match lexeme.parse::<i64>() {
+        match lexeme.replace('_', "").parse::<i64>() {
             Ok(n) => TokenKind::Integer(n),
             Err(_) => TokenKind::Error(format!("Invalid integer literal: {}", lexeme)),
         }
     }
     fn scan_hex(&mut self) -> TokenKind {
         let start = self.current;
-        while !self.is_at_end() && self.peek().is_ascii_hexdigit() {
-            self.advance();
+        self.scan_digits(char::is_ascii_hexdigit);
+        if let Some(error) = self.misplaced_separator() {
+            return error;
         }
         if self.current == start {
             return TokenKind::Error("Expected hex digits after '0x'".into());
         }
-        let hex_str: String = self.chars[start..self.current].iter().collect();
-        match i64::from_str_radix(&hex_str, 16) {
+        let hex_str = &self.source[start..self.current];
+        match i64::from_str_radix(&hex_str.replace('_', ""), 16) {
             Ok(n) => TokenKind::Integer(n),
             Err(_) => TokenKind::Error(format!("Invalid hex literal: 0x{}", hex_str)),
         }
     }
     fn scan_binary(&mut self) -> TokenKind {
         let start = self.current;
-        while !self.is_at_end() && (self.peek() == '0' || self.peek() == '1') {
-            self.advance();
+        self.scan_digits(|c| matches!(c, '0' | '1'));
+        if let Some(error) = self.misplaced_separator() {
+            return error;
         }
         if self.current == start {
             return TokenKind::Error("Expected binary digits after '0b'".into());
         }
-        let bin_str: String = self.chars[start..self.current].iter().collect();
-        match i64::from_str_radix(&bin_str, 2) {
+        let bin_str = &self.source[start..self.current];
+        match i64::from_str_radix(&bin_str.replace('_', ""), 2) {
             Ok(n) => TokenKind::Integer(n),
             Err(_) => TokenKind::Error(format!("Invalid binary literal: 0b{}", bin_str)),
         }
     }
     fn scan_octal(&mut self) -> TokenKind {
         let start = self.current;
-        while !self.is_at_end() && ('0'..='7').contains(&self.peek()) {
-            self.advance();
+        self.scan_digits(|c| matches!(c, '0'..='7'));
+        if let Some(error) = self.misplaced_separator() {
+            return error;
         }
         if self.current == start {
             return TokenKind::Error("Expected octal digits after '0o'".into());
         }
-        let oct_str: String = self.chars[start..self.current].iter().collect();
-        match i64::from_str_radix(&oct_str, 8) {
+        let oct_str = &self.source[start..self.current];
+        match i64::from_str_radix(&oct_str.replace('_', ""), 8) {
             Ok(n) => TokenKind::Integer(n),
             Err(_) => TokenKind::Error(format!("Invalid octal literal: 0o{}", oct_str)),
         }
     }
+    fn scan_digits(&mut self, is_digit: fn(&char) -> bool) {
+        while !self.is_at_end() {
+            let c = self.peek();
+            let separator = c == '_'
+                && self.source[..self.current]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|prev| is_digit(&prev))
+                && self.peek_next().is_some_and(|next| is_digit(&next));
+            if !is_digit(&c) && !separator {
+                break;
+            }
+            self.advance();
+        }
+    }
+    fn misplaced_separator(&mut self) -> Option<TokenKind> {
+        if self.is_at_end() || self.peek() != '_' {
+            return None;
+        }
+        while !self.is_at_end() && (self.peek().is_alphanumeric() || self.peek() == '_') {
+            self.advance();
+        }
+        Some(TokenKind::Error(format!(
+            "Misplaced '_' in numeric literal: {}",
+            self.current_lexeme()
+        )))
+    }
     fn scan_identifier(&mut self, first: char) -> TokenKind {
         let mut ident = String::new();
         ident.push(first);
@@ -352,26 +584,26 @@ impl<'src> Lexer<'src> {
         }
     }
     fn is_at_end(&self) -> bool {
-        self.current >= self.chars.len()
+        self.current >= self.source.len()
+    }
+    fn rest(&self) -> std::str::Chars<'src> {
+        self.source[self.current..].chars()
     }
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.chars[self.current]
-        }
+        self.rest().next().unwrap_or('\0')
     }
     fn peek_next(&self) -> Option<char> {
-        if self.current + 1 >= self.chars.len() {
-            None
-        } else {
-            Some(self.chars[self.current + 1])
-        }
+        self.rest().nth(1)
     }
     fn advance(&mut self) -> char {
-        let c = self.chars[self.current];
-        self.current += 1;
-        self.column += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         c
     }
     fn match_char(&mut self, expected: char) -> bool {
@@ -383,27 +615,24 @@ impl<'src> Lexer<'src> {
         }
     }
     fn current_lexeme(&self) -> String {
-        self.chars[self.start..self.current].iter().collect()
+        self.source[self.start..self.current].to_string()
     }
     fn make_token(&self, kind: TokenKind) -> Token {
         let lexeme = self.current_lexeme();
         let span = Span::new(
             self.start,
             self.current - self.start,
-            self.line,
+            self.start_line,
             self.start_column,
-        );
+        )
+        .with_file(self.file);
         Token::new(kind, span, lexeme)
     }
 }
 impl<'src> Iterator for Lexer<'src> {
     type Item = Token;
     fn next(&mut self) -> Option<Self::Item> {
-        let token = self.scan_token()?;
-        if token.kind == TokenKind::Eof && self.emitted_eof {
-            return None;
-        }
-        Some(token)
+        self.scan_token()
     }
 }
 #[cfg(test)]
@@ -434,4 +663,187 @@ mod tests {
         assert!(matches!(tokens[1].kind, TokenKind::Off));
         assert!(matches!(tokens[2].kind, TokenKind::Empty));
     }
+    #[test]
+    fn test_tokenize_reports_every_bad_character() {
+        let errors = Lexer::new("perm a = 1 @ 2\nlog(a $ ¤)")
+            .tokenize()
+            .unwrap_err();
+        let spans: Vec<_> = errors
+            .iter()
+            .filter_map(|e| e.span().map(|s| (s.line, s.column)))
+            .collect();
+        assert_eq!(spans, [(1, 12), (2, 7), (2, 9)]);
+        assert_eq!(errors[1].message(), "Unexpected character '$'");
+    }
+    #[test]
+    fn test_interpolated_strings() {
+        let tokens: Vec<_> = Lexer::new("\"count: ${n + 1}\"").collect();
+        let TokenKind::Interpolated(parts) = &tokens[0].kind else {
+            panic!("expected an interpolated string, got {:?}", tokens[0].kind);
+        };
+        assert_eq!(parts[0], StringPart::Text("count: ".to_string()));
+        let StringPart::Code(code) = &parts[1] else {
+            panic!("expected code, got {:?}", parts[1]);
+        };
+        let kinds: Vec<_> = code.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Identifier("n".to_string()),
+                TokenKind::Plus,
+                TokenKind::Integer(1)
+            ]
+        );
+        assert_eq!((code[0].span.start, code[0].span.column), (10, 11));
+        assert_eq!(tokens[1].kind, TokenKind::Eof);
+        let nested: Vec<_> = Lexer::new("\"${m{\"}\": \"${1}\"}} \\${x}\"").collect();
+        let TokenKind::Interpolated(parts) = &nested[0].kind else {
+            panic!("expected an interpolated string, got {:?}", nested[0].kind);
+        };
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1], StringPart::Text(" ${x}".to_string()));
+        assert_eq!(
+            Lexer::new("\"plain $ {}\"").next().unwrap().kind,
+            TokenKind::String("plain $ {}".to_string())
+        );
+        let error = |source| Lexer::new(source).tokenize().unwrap_err()[0].message();
+        assert_eq!(error("\"${}\""), "Empty interpolation '${}'");
+        assert_eq!(error("\"${a + 1\""), "Unterminated string in interpolation");
+        assert_eq!(
+            error("\"${a @ 1}\""),
+            "Unexpected character '@' in interpolation"
+        );
+    }
+    #[test]
+    fn test_numeric_separators() {
+        let kinds = |source| {
+            Lexer::new(source)
+                .map(|token| token.kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("1_000_000 0xFF_FF 0b1010_1010 0o7_7 1_000.000_5"),
+            [
+                TokenKind::Integer(1_000_000),
+                TokenKind::Integer(0xFFFF),
+                TokenKind::Integer(0b1010_1010),
+                TokenKind::Integer(0o77),
+                TokenKind::Float(1000.0005),
+                TokenKind::Eof
+            ]
+        );
+        let error = |source| Lexer::new(source).tokenize().unwrap_err()[0].message();
+        assert_eq!(error("1__0"), "Misplaced '_' in numeric literal: 1__0");
+        assert_eq!(error("100_"), "Misplaced '_' in numeric literal: 100_");
+        assert_eq!(error("0x_FF"), "Misplaced '_' in numeric literal: 0x_FF");
+        assert_eq!(error("1_.5"), "Misplaced '_' in numeric literal: 1_");
+    }
+    #[test]
+    fn test_hex_and_unicode_escapes() {
+        let kind = |source| Lexer::new(source).next().unwrap().kind;
+        assert_eq!(
+            kind("\"\\x41\\u{1F680}\\u{e9}\""),
+            TokenKind::String("A\u{1F680}\u{e9}".to_string())
+        );
+        let errors = Lexer::new("log(\"ok \\u{D800} \\q\")\nlog('\\x9')")
+            .tokenize()
+            .unwrap_err();
+        let located: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                let span = e.span().unwrap();
+                (e.message(), span.line, span.column, span.length)
+            })
+            .collect();
+        assert_eq!(
+            located,
+            [
+                (
+                    "'\\u{D800}' is not a valid unicode character".to_string(),
+                    1,
+                    9,
+                    8
+                ),
+                ("Expected two hex digits in '\\x9'".to_string(), 2, 6, 3)
+            ]
+        );
+        let error = |source| Lexer::new(source).tokenize().unwrap_err()[0].message();
+        assert_eq!(
+            error("\"\\xFF\""),
+            "'\\xFF' is above 0x7F; use '\\u{FF}' instead"
+        );
+        assert_eq!(error("\"\\u{41\""), "Unterminated unicode escape '\\u{41'");
+        assert_eq!(error("\"\\u41\""), "Expected '{' after '\\u'");
+    }
+    #[test]
+    fn test_hash_is_length_only_where_an_operand_is_expected() {
+        let kinds = |source| {
+            Lexer::new(source)
+                .map(|token| token.kind)
+                .filter(|kind| !matches!(kind, TokenKind::Newline | TokenKind::Eof))
+                .collect::<Vec<_>>()
+        };
+        let xs = || TokenKind::Identifier("xs".to_string());
+        assert_eq!(
+            kinds("#xs\nperm n = #xs # count\nlog(#xs)#done\nn = n #xs"),
+            [
+                TokenKind::Perm,
+                TokenKind::Identifier("n".to_string()),
+                TokenKind::Assign,
+                TokenKind::Hash,
+                xs(),
+                TokenKind::Identifier("log".to_string()),
+                TokenKind::LeftParen,
+                TokenKind::Hash,
+                xs(),
+                TokenKind::RightParen,
+                TokenKind::Identifier("n".to_string()),
+                TokenKind::Assign,
+                TokenKind::Identifier("n".to_string()),
+            ]
+        );
+        assert_eq!(kinds("log(1 + # note\n2)").len(), 6);
+    }
+    #[test]
+    fn test_spans_are_byte_offsets() {
+        let source = "perm s = \"h\u{e9}llo \u{1F30C}\"\nlog(s)";
+        let tokens: Vec<_> = Lexer::new(source).collect();
+        let string = &tokens[3];
+        assert_eq!((string.span.start, string.span.length), (9, 13));
+        assert_eq!(&source[string.span.start..string.span.end()], string.lexeme);
+        let log = &tokens[5];
+        assert_eq!((log.span.start, log.span.line, log.span.column), (23, 2, 1));
+        assert_eq!(&source[log.span.start..log.span.end()], "log");
+    }
+    #[test]
+    fn test_doc_comments_are_tokens_only_at_line_start() {
+        let kinds: Vec<_> = Lexer::new("## Doc.\n  ##indented\nlog(#x) ## not doc\n")
+            .map(|t| t.kind)
+            .collect();
+        assert_eq!(kinds[0], TokenKind::DocComment("Doc.".into()));
+        assert_eq!(kinds[2], TokenKind::DocComment("indented".into()));
+        assert_eq!(
+            kinds[5..8],
+            [
+                TokenKind::LeftParen,
+                TokenKind::Hash,
+                TokenKind::Identifier("x".into())
+            ]
+        );
+        assert!(!kinds[3..]
+            .iter()
+            .any(|k| matches!(k, TokenKind::DocComment(_))));
+    }
+    #[test]
+    fn test_line_accounting_across_crlf_and_multiline_literals() {
+        let source = "perm s = `a\r\nb`\r\n'''c\r\n'''\tlog(\"x\ny\")\r\nend";
+        let tokens: Vec<_> = Lexer::new(source).collect();
+        let at = |lexeme: &str| {
+            let token = tokens.iter().find(|t| t.lexeme == lexeme).unwrap();
+            (token.span.line, token.span.column)
+        };
+        assert_eq!(at("log"), (4, 5));
+        assert_eq!(at(")"), (5, 3));
+        assert_eq!(at("end"), (6, 1));
+    }
 }
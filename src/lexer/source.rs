@@ -0,0 +1,226 @@
+use super::token::Span;
+use serde::Serialize;
+use std::fmt;
+use unicode_width::UnicodeWidthChar;
+pub const TAB_WIDTH: usize = 4;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord, Serialize)]
+pub struct FileId(pub u32);
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub byte_column: usize,
+    pub display_column: usize,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    name: String,
+    source: String,
+    line_starts: Vec<usize>,
+    first_line: usize,
+}
+impl SourceFile {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name: name.into(),
+            source,
+            line_starts,
+            first_line: 1,
+        }
+    }
+    pub fn with_first_line(mut self, line: usize) -> Self {
+        self.first_line = line.max(1);
+        self
+    }
+    pub fn first_line(&self) -> usize {
+        self.first_line
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+    pub fn line_index(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = self.clamp(offset);
+        let line = self.line_index(offset);
+        let before = &self.source[self.line_starts[line]..offset];
+        Position {
+            line: line + self.first_line,
+            column: before.chars().count() + 1,
+            byte_column: before.len() + 1,
+            display_column: display_width(before) + 1,
+        }
+    }
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let position = self.position(offset);
+        (position.line, position.column)
+    }
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let index = line.checked_sub(self.first_line)?;
+        let start = *self.line_starts.get(index)?;
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+    pub fn slice(&self, span: Span) -> Option<&str> {
+        self.source.get(span.start..span.end())
+    }
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+pub fn display_width(text: &str) -> usize {
+    text.chars().fold(0, |width, c| match c {
+        '\t' => width + TAB_WIDTH - width % TAB_WIDTH,
+        c => width + c.width().unwrap_or(0),
+    })
+}
+pub fn expand_tabs(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\t' {
+            let width = display_width(&out);
+            out.extend(std::iter::repeat_n(' ', TAB_WIDTH - width % TAB_WIDTH));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn single(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let mut map = Self::new();
+        map.add(name, source);
+        map
+    }
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        self.add_file(SourceFile::new(name, source))
+    }
+    pub fn add_file(&mut self, file: SourceFile) -> FileId {
+        self.files.push(file);
+        FileId(self.files.len() as u32 - 1)
+    }
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> {
+        self.files.get(file.0 as usize)
+    }
+    pub fn file(&self, span: Span) -> Option<&SourceFile> {
+        self.get(span.file)
+    }
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files
+            .iter()
+            .position(|file| file.name == name)
+            .map(|index| FileId(index as u32))
+    }
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (FileId(index as u32), file))
+    }
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+    pub fn location(&self, span: Span) -> Option<String> {
+        let file = self.file(span)?;
+        let (line, column) = file.line_col(span.start);
+        Some(if file.name.is_empty() {
+            format!("{}:{}", line, column)
+        } else {
+            format!("{}:{}:{}", file.name, line, column)
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    #[test]
+    fn test_source_map_resolves_byte_offsets_per_file() {
+        let mut map = SourceMap::new();
+        let main = map.add("main.na", "log(1)\r\nperm é = \"ü\"\n");
+        let util = map.add("util.na", "fn f() do\nend");
+        assert_eq!((main, util), (FileId(0), FileId(1)));
+        let file = map.get(main).unwrap();
+        assert_eq!(file.line_count(), 3);
+        assert_eq!(file.line(2), Some("perm é = \"ü\""));
+        assert_eq!(file.line(1), Some("log(1)"));
+        assert_eq!(file.line(4), None);
+        let tokens: Vec<_> = Lexer::new(file.source()).with_file(main).collect();
+        let string = tokens.iter().find(|t| t.lexeme == "\"ü\"").unwrap().span;
+        assert_eq!(file.line_col(string.start), (2, 10));
+        assert_eq!(file.slice(string), Some("\"ü\""));
+        let end = Lexer::new(map.get(util).unwrap().source())
+            .with_file(util)
+            .find(|t| t.lexeme == "end")
+            .unwrap()
+            .span;
+        assert_eq!(end.file, util);
+        assert_eq!(map.location(end).as_deref(), Some("util.na:2:1"));
+        assert_eq!(map.find("util.na"), Some(util));
+        assert!(map.location(Span::default().with_file(FileId(7))).is_none());
+        let shifted = map.add_file(SourceFile::new("big.na", "a\nbc").with_first_line(40));
+        let file = map.get(shifted).unwrap();
+        assert_eq!(file.line_col(3), (41, 2));
+        assert_eq!((file.line(41), file.line(1)), (Some("bc"), None));
+        let bc = Lexer::new(file.source())
+            .with_first_line(40)
+            .nth(2)
+            .unwrap();
+        assert_eq!((bc.lexeme.as_str(), bc.span.line), ("bc", 41));
+    }
+    #[test]
+    fn test_positions_track_bytes_chars_and_display_width() {
+        let file = SourceFile::new("", "\tlog(\"🚀\") x\r\nab\t\"日本\" y\r\n");
+        let x = file.source().find('x').unwrap();
+        let position = file.position(x);
+        assert_eq!((position.line, position.column), (1, 11));
+        assert_eq!(position.byte_column, 14);
+        assert_eq!(position.display_column, 15);
+        let y = file.source().find('y').unwrap();
+        let position = file.position(y);
+        assert_eq!(
+            (position.line, position.column, position.byte_column),
+            (2, 9, 13)
+        );
+        assert_eq!(position.display_column, 12);
+        assert_eq!(file.line(2), Some("ab\t\"日本\" y"));
+        assert_eq!(expand_tabs("ab\tc\t"), "ab  c   ");
+        assert_eq!(display_width("\t🚀é"), 7);
+    }
+}
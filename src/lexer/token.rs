@@ -1,10 +1,13 @@
+use super::source::FileId;
+use serde::Serialize;
 use std::fmt;
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
 pub struct Span {
     pub start: usize,
     pub length: usize,
     pub line: usize,
     pub column: usize,
+    pub file: FileId,
 }
 impl Span {
     pub fn new(start: usize, length: usize, line: usize, column: usize) -> Self {
@@ -13,11 +16,21 @@ impl Span {
             length,
             line,
             column,
+            file: FileId::default(),
         }
     }
+    pub fn with_file(self, file: FileId) -> Self {
+        Self { file, ..self }
+    }
     pub fn end(&self) -> usize {
         self.start + self.length
     }
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            length: other.end().max(self.end()) - self.start,
+            ..*self
+        }
+    }
 }
 impl fmt::Display for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -40,10 +53,17 @@ impl Token {
     }
 }
 #[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Token>),
+}
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     Integer(i64),
     Float(f64),
     String(String),
+    Interpolated(Vec<StringPart>),
+    DocComment(String),
     On,
     Off,
     Empty,
@@ -80,6 +100,7 @@ pub enum TokenKind {
     Struct,
     Enum,
     Trait,
+    Impl,
     Type,
     Mod,
     Use,
@@ -136,6 +157,7 @@ pub enum TokenKind {
     DotDotLess,
     Hash,
     Question,
+    QuestionQuestion,
     LeftArrow,
     SendArrow,
     Newline,
@@ -145,7 +167,7 @@ pub enum TokenKind {
 impl TokenKind {
     pub fn keyword_from_str(s: &str) -> Option<TokenKind> {
         match s {
-            "perm" => Some(TokenKind::Perm),
+            "perm" | "fb" | "cn" => Some(TokenKind::Perm),
             "give" => Some(TokenKind::Give),
             "nb" => Some(TokenKind::Nb),
             "int" => Some(TokenKind::Int),
@@ -159,12 +181,12 @@ impl TokenKind {
             "map" => Some(TokenKind::Map),
             "tup" => Some(TokenKind::Tup),
             "set" => Some(TokenKind::Set),
-            "on" => Some(TokenKind::On),
-            "off" => Some(TokenKind::Off),
-            "empty" => Some(TokenKind::Empty),
+            "on" | "yes" => Some(TokenKind::On),
+            "off" | "no" => Some(TokenKind::Off),
+            "empty" | "nil" => Some(TokenKind::Empty),
             "fn" | "function" => Some(TokenKind::Function),
             "if" => Some(TokenKind::If),
-            "elsif" => Some(TokenKind::Elsif),
+            "elsif" | "elif" => Some(TokenKind::Elsif),
             "else" => Some(TokenKind::Else),
             "do" => Some(TokenKind::Do),
             "end" => Some(TokenKind::End),
@@ -178,6 +200,7 @@ impl TokenKind {
             "struct" => Some(TokenKind::Struct),
             "enum" => Some(TokenKind::Enum),
             "trait" => Some(TokenKind::Trait),
+            "impl" => Some(TokenKind::Impl),
             "type" => Some(TokenKind::Type),
             "mod" => Some(TokenKind::Mod),
             "use" => Some(TokenKind::Use),
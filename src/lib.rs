@@ -1,13 +1,27 @@
 pub mod builtins;
+pub mod check;
+pub mod doc;
+pub mod engine;
 pub mod error;
 pub mod ext;
 pub mod interp;
 pub mod lexer;
+pub mod limits;
 pub mod parser;
+pub mod project;
+pub mod stream;
+pub mod testing;
+pub mod typeck;
 pub mod vm;
-pub use error::{ErrorCode, NebulaError, NebulaResult};
+pub use builtins::SandboxPolicy;
+pub use check::check;
+pub use engine::{Backend, Script, ScriptEngine};
+pub use error::{Diagnostic, ErrorCode, Label, NebulaError, NebulaResult, Renderer, Severity};
 pub use ext::{ExtFunction, Extension, ExtensionContext, ExtensionRegistry};
-pub use interp::{Environment, Interpreter, Value};
-pub use lexer::{Lexer, Span, Token, TokenKind};
+pub use interp::{Environment, Interpreter, SharedValue, Value};
+pub use lexer::{FileId, Lexer, SourceMap, Span, Token, TokenKind};
+pub use limits::{ResourceLimits, ResourceUsage};
 pub use parser::{Parser, Program};
+pub use stream::ItemReader;
+pub use typeck::{TypeChecker, TypeMode};
 pub use vm::{Chunk, Compiler, OpCode, VM};
@@ -0,0 +1,289 @@
+use crate::error::{ErrorCode, NebulaError, NebulaResult};
+use crate::interp::{Environment, Value};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::mem::size_of;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+thread_local! {
+    static STATE: RefCell<LimitState> = RefCell::new(LimitState::default());
+}
+pub const CHECK_INTERVAL: u32 = 1024;
+#[derive(Clone, Default)]
+pub struct ResourceLimits {
+    pub max_heap_bytes: Option<usize>,
+    pub max_allocations: Option<usize>,
+    pub deadline: Option<Instant>,
+    interrupt: Option<Rc<dyn Fn() -> bool>>,
+}
+impl ResourceLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn max_heap_bytes(mut self, bytes: usize) -> Self {
+        self.max_heap_bytes = Some(bytes);
+        self
+    }
+    pub fn max_allocations(mut self, count: usize) -> Self {
+        self.max_allocations = Some(count);
+        self
+    }
+    pub fn deadline(mut self, at: Instant) -> Self {
+        self.deadline = Some(at);
+        self
+    }
+    pub fn timeout(self, after: Duration) -> Self {
+        self.deadline(Instant::now() + after)
+    }
+    pub fn interrupt(mut self, should_stop: impl Fn() -> bool + 'static) -> Self {
+        self.interrupt = Some(Rc::new(should_stop));
+        self
+    }
+    pub fn is_unlimited(&self) -> bool {
+        self.max_heap_bytes.is_none()
+            && self.max_allocations.is_none()
+            && self.deadline.is_none()
+            && self.interrupt.is_none()
+    }
+}
+impl fmt::Debug for ResourceLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResourceLimits")
+            .field("max_heap_bytes", &self.max_heap_bytes)
+            .field("max_allocations", &self.max_allocations)
+            .field("deadline", &self.deadline)
+            .field("interrupt", &self.interrupt.is_some())
+            .finish()
+    }
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub allocations: usize,
+    pub heap_bytes: usize,
+}
+#[derive(Default)]
+struct LimitState {
+    limits: ResourceLimits,
+    usage: ResourceUsage,
+    ticks: u32,
+}
+impl LimitState {
+    fn check_memory(&self) -> NebulaResult<()> {
+        let over = |used: usize, limit: Option<usize>| limit.is_some_and(|max| used > max);
+        if over(self.usage.heap_bytes, self.limits.max_heap_bytes) {
+            return Err(NebulaError::coded(
+                ErrorCode::E072,
+                format!(
+                    "{} heap bytes exceeds the limit of {}",
+                    self.usage.heap_bytes,
+                    self.limits.max_heap_bytes.unwrap_or(0)
+                ),
+            ));
+        }
+        if over(self.usage.allocations, self.limits.max_allocations) {
+            return Err(NebulaError::coded(
+                ErrorCode::E072,
+                format!(
+                    "{} allocations exceeds the limit of {}",
+                    self.usage.allocations,
+                    self.limits.max_allocations.unwrap_or(0)
+                ),
+            ));
+        }
+        Ok(())
+    }
+    fn check_time(&self) -> NebulaResult<()> {
+        if self.limits.deadline.is_some_and(|at| Instant::now() >= at) {
+            return Err(NebulaError::coded(ErrorCode::E070, "deadline exceeded"));
+        }
+        if self.limits.interrupt.as_ref().is_some_and(|stop| stop()) {
+            return Err(NebulaError::coded(ErrorCode::E070, "interrupted by host"));
+        }
+        Ok(())
+    }
+}
+pub fn set_limits(limits: ResourceLimits) {
+    STATE.with(|state| {
+        *state.borrow_mut() = LimitState {
+            limits,
+            ..LimitState::default()
+        }
+    });
+}
+pub fn limits() -> ResourceLimits {
+    STATE.with(|state| state.borrow().limits.clone())
+}
+pub fn usage() -> ResourceUsage {
+    STATE.with(|state| state.borrow().usage)
+}
+pub fn with_limits<T>(limits: ResourceLimits, run: impl FnOnce() -> T) -> T {
+    let previous = STATE.with(|state| {
+        std::mem::replace(
+            &mut *state.borrow_mut(),
+            LimitState {
+                limits,
+                ..LimitState::default()
+            },
+        )
+    });
+    let result = run();
+    STATE.with(|state| *state.borrow_mut() = previous);
+    result
+}
+pub fn record(bytes: usize) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.usage.allocations += 1;
+        state.usage.heap_bytes += bytes;
+    });
+}
+pub fn charge(bytes: usize) -> NebulaResult<()> {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.usage.allocations += 1;
+        state.usage.heap_bytes += bytes;
+        state.check_memory()
+    })
+}
+pub fn charge_value(value: &Value, live: impl FnOnce() -> usize) -> NebulaResult<()> {
+    let bytes = match value {
+        Value::String(_)
+        | Value::List(_)
+        | Value::Tuple(_)
+        | Value::Set(_)
+        | Value::Map(_)
+        | Value::Struct { .. } => value_bytes(value),
+        _ => return Ok(()),
+    };
+    let checked = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.usage.allocations += 1;
+        state.usage.heap_bytes += bytes;
+        let over = state
+            .limits
+            .max_heap_bytes
+            .is_some_and(|max| state.usage.heap_bytes > max);
+        (!over).then(|| state.check_memory())
+    });
+    if let Some(result) = checked {
+        return result;
+    }
+    let measured = live() + bytes;
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.usage.heap_bytes = measured;
+        state.check_memory()
+    })
+}
+pub fn poll() -> NebulaResult<()> {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.check_memory()?;
+        state.ticks += 1;
+        if state.ticks < CHECK_INTERVAL {
+            return Ok(());
+        }
+        state.ticks = 0;
+        state.check_time()
+    })
+}
+pub fn check() -> NebulaResult<()> {
+    STATE.with(|state| {
+        let state = state.borrow();
+        state.check_memory()?;
+        state.check_time()
+    })
+}
+pub fn remaining() -> Option<Duration> {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .limits
+            .deadline
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    })
+}
+pub fn value_bytes(value: &Value) -> usize {
+    size_of::<Value>()
+        + match value {
+            Value::String(s) => s.len(),
+            Value::List(items) | Value::Tuple(items) | Value::Set(items) => {
+                items.len() * size_of::<Value>()
+            }
+            Value::Map(map) => map
+                .keys()
+                .map(|key| key.len() + size_of::<String>() + size_of::<Value>())
+                .sum(),
+            Value::Struct { fields, .. } => fields.len() * size_of::<Value>(),
+            _ => 0,
+        }
+}
+pub fn live_bytes(roots: impl IntoIterator<Item = Rc<RefCell<Environment>>>) -> usize {
+    let mut pending: Vec<_> = roots.into_iter().collect();
+    let mut seen = HashSet::new();
+    let mut total = 0;
+    while let Some(env) = pending.pop() {
+        if !seen.insert(Rc::as_ptr(&env)) {
+            continue;
+        }
+        let Ok(env) = env.try_borrow() else {
+            continue;
+        };
+        for (name, value) in env.locals() {
+            total += name.len() + size_of::<String>() + reachable_bytes(value, &mut pending);
+        }
+        pending.extend(env.parent());
+    }
+    total
+}
+fn reachable_bytes(value: &Value, envs: &mut Vec<Rc<RefCell<Environment>>>) -> usize {
+    let mut slots = |items: &mut dyn Iterator<Item = &Value>| -> usize {
+        items
+            .map(|item| reachable_bytes(item, envs) - size_of::<Value>())
+            .sum()
+    };
+    let nested = match value {
+        Value::List(items) | Value::Tuple(items) | Value::Set(items) => slots(&mut items.iter()),
+        Value::Map(map) => slots(&mut map.values()),
+        Value::Struct { fields, .. } => slots(&mut fields.iter()),
+        Value::Channel(queue) => queue.try_borrow().map_or(0, |queue| {
+            queue.iter().map(|item| reachable_bytes(item, envs)).sum()
+        }),
+        Value::Frozen(inner) => reachable_bytes(inner, envs),
+        Value::Function(func) => {
+            envs.push(Rc::clone(&func.closure));
+            0
+        }
+        Value::Lambda(lambda) => {
+            envs.push(Rc::clone(&lambda.closure));
+            0
+        }
+        _ => 0,
+    };
+    value_bytes(value) + nested
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_limits_are_scoped_and_enforced() {
+        let budget = ResourceLimits::new().max_allocations(2);
+        let result = with_limits(budget, || {
+            charge(8)?;
+            charge(8)?;
+            assert_eq!(usage().heap_bytes, 16);
+            charge(8)
+        });
+        assert_eq!(result.unwrap_err().code(), Some(ErrorCode::E072));
+        assert_eq!(usage(), ResourceUsage::default());
+        assert!(limits().is_unlimited());
+        let expired = ResourceLimits::new().deadline(Instant::now());
+        let err = with_limits(expired, check).unwrap_err();
+        assert!(err.message().contains("deadline exceeded"));
+        let stopped = with_limits(ResourceLimits::new().interrupt(|| true), || {
+            (0..CHECK_INTERVAL).try_for_each(|_| poll())
+        });
+        assert!(stopped.unwrap_err().message().contains("interrupted"));
+    }
+}
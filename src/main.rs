@@ -1,11 +1,33 @@
+use std::collections::HashSet;
 use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+use clap::{ArgAction, CommandFactory, FromArgMatches};
+use clap_complete::Shell;
 use colored::Colorize;
-use nebula::{Compiler, Interpreter, Lexer, NebulaError, Parser, Value, VM};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use std::borrow::Cow;
+use nebula::project::{self, Dependency, Project};
+use nebula::parser::ast::{Item, StmtKind};
+use nebula::typeck::{NumericHints, Ty};
+use nebula::{
+    testing, Backend, Chunk, Compiler, Diagnostic, ErrorCode, FileId, Interpreter, ItemReader,
+    Lexer, NebulaError, Parser, Program, Renderer, SandboxPolicy, ScriptEngine, Severity,
+    SourceMap, Span, TypeChecker, TypeMode, Value, VM,
+};
 
 #[cfg(windows)]
 fn enable_ansi_support() {
@@ -31,6 +53,13 @@ fn enable_ansi_support() {
 #[cfg(not(windows))]
 fn enable_ansi_support() {}
 
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+static DENY_WARNINGS: AtomicBool = AtomicBool::new(false);
+static ALLOW_DEPRECATED: AtomicBool = AtomicBool::new(false);
+static TYPED: AtomicBool = AtomicBool::new(false);
+static STRICT_TYPES: AtomicBool = AtomicBool::new(false);
+static POLICY: OnceLock<SandboxPolicy> = OnceLock::new();
+
 const BANNER: &str = r#"
 ▀█▄    ▀█▀         ▀██                ▀██          
  █▀█    █    ▄▄▄▄   ██ ▄▄▄  ▄▄▄ ▄▄▄    ██    ▄▄▄▄  
@@ -39,124 +68,463 @@ const BANNER: &str = r#"
 ▄█▄    ▀█   ▀█▄▄▄▀  ▀█▄▄▄▀   ▀█▄▄▀█▄  ▄██▄  ▀█▄▄▀█▀  
 "#;
 
+struct ReplHelper;
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(nebula::lexer::highlight(line))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        true
+    }
+}
+
+impl Helper for ReplHelper {}
+
+struct Options {
+    use_vm: bool,
+    policy: SandboxPolicy,
+    file_path: Option<String>,
+    inline_source: Option<String>,
+    script_args: Vec<String>,
+    test_dir: Option<String>,
+    check: bool,
+    project: Option<ProjectCommand>,
+    preload: Vec<String>,
+    quiet: bool,
+    no_color: bool,
+    timing: bool,
+    watch: bool,
+    stream: bool,
+    dump: Option<Dump>,
+    doc: Option<String>,
+}
+
+enum ProjectCommand {
+    New(String),
+    Add(String, Dependency),
+    Fetch,
+    Run,
+}
+
+#[derive(Clone, Copy)]
+enum Dump {
+    Tokens,
+    Ast,
+    AstJson,
+    Pretty,
+}
+
 fn main() {
     enable_ansi_support();
     
-    let args: Vec<String> = env::args().collect();
-    let (use_vm, file_path) = parse_args(&args);
+    let options = parse_args();
+    if options.no_color || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        colored::control::set_override(false);
+    }
+    if options.check {
+        check_sources(&options);
+    }
+    nebula::builtins::set_script_args(options.script_args.clone());
+    let _ = POLICY.set(options.policy.clone());
+
+    if let Some(dir) = options.test_dir {
+        run_test_suite(&dir);
+    }
+
+    match options.project {
+        Some(ProjectCommand::New(name)) => new_project(&name),
+        Some(ProjectCommand::Add(name, dep)) => add_dependency(&name, dep),
+        Some(ProjectCommand::Fetch) => fetch_dependencies(),
+        Some(ProjectCommand::Run) => run_project(options.use_vm, !options.quiet),
+        None => {}
+    }
 
-    match file_path {
-        None => run_repl(use_vm),
-        Some(path) => run_file(&path, use_vm),
+    if let Some(path) = &options.doc {
+        document(path);
+    }
+
+    if let Some(dump) = options.dump {
+        let (name, source) = program_source(&options);
+        dump_source(&name, &source, dump);
+        return;
+    }
+
+    if options.watch {
+        match options.file_path.as_deref() {
+            Some(path) if path != "-" => watch_file(path, options.use_vm, options.quiet),
+            _ => {
+                eprintln!("{} --watch requires a script file", "[ERROR]".bold().red());
+                process::exit(64);
+            }
+        }
+    }
+
+    if options.timing {
+        let (name, source) = program_source(&options);
+        run_timed(&name, &source, options.use_vm);
+        return;
+    }
+
+    if let Some(source) = options.inline_source {
+        run_source("<eval>", &source, options.use_vm, false);
+        return;
+    }
+
+    match options.file_path.as_deref() {
+        None => run_repl(options.use_vm, &options.preload),
+        Some(path) if options.stream => stream_file(path, options.use_vm, !options.quiet),
+        Some(path) => run_file(path, options.use_vm, !options.quiet),
     }
 }
 
-fn parse_args(args: &[String]) -> (bool, Option<String>) {
-    let mut use_vm = false;
-    let mut file_path = None;
+#[derive(clap::Parser)]
+#[command(
+    name = "nebula",
+    version = "1.0.0",
+    about = "A high-performance programming language with Python ergonomics",
+    disable_version_flag = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+    #[arg(short = 'v', long, action = ArgAction::Version, help = "Show version info")]
+    version: Option<bool>,
+    #[arg(long, global = true, help = "Use bytecode VM (35x faster)")]
+    vm: bool,
+    #[arg(long, global = true, help = "Deny filesystem, network, process and environment access")]
+    sandbox: bool,
+    #[arg(long, global = true, value_name = "DIR", help = "Sandbox: allow reading files under DIR")]
+    allow_read: Vec<PathBuf>,
+    #[arg(long, global = true, value_name = "DIR", help = "Sandbox: allow writing files under DIR")]
+    allow_write: Vec<PathBuf>,
+    #[arg(long, global = true, help = "Sandbox: allow network access")]
+    allow_net: bool,
+    #[arg(long, global = true, help = "Sandbox: allow running processes and loading native libraries")]
+    allow_exec: bool,
+    #[arg(long, global = true, help = "Sandbox: allow reading and setting environment variables")]
+    allow_env: bool,
+    #[arg(short, long, global = true, help = "Suppress the timing banner and status output")]
+    quiet: bool,
+    #[arg(long, global = true, help = "Disable colored output (also honors NO_COLOR)")]
+    no_color: bool,
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human, help = "How errors are printed on stderr")]
+    error_format: ErrorFormat,
+    #[arg(long, global = true, help = "Treat warnings as errors")]
+    deny_warnings: bool,
+    #[arg(long, global = true, help = "Silence warnings about deprecated syntax and builtins")]
+    allow_deprecated: bool,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "gradual",
+        help = "Type-check before running; gradual only enforces annotations"
+    )]
+    typed: Option<TypedMode>,
+    #[arg(long, help = "Print per-phase timings (lex, parse, compile, execute)")]
+    time: bool,
+    #[arg(long, help = "Re-run the script when it or its used modules change")]
+    watch: bool,
+    #[arg(long, help = "Parse scripts and report errors without running")]
+    check: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["typed", "deny_warnings", "time", "watch", "check", "dump", "eval"],
+        help = "Read, parse and run the script one top-level item at a time"
+    )]
+    stream: bool,
+    #[arg(long, group = "dump", help = "Print the token stream instead of running")]
+    tokens: bool,
+    #[arg(long, group = "dump", help = "Print the parsed AST instead of running")]
+    ast: bool,
+    #[arg(long, group = "dump", help = "Print the parsed AST as JSON")]
+    ast_json: bool,
+    #[arg(long, group = "dump", help = "Print the parsed program in canonical form")]
+    pretty: bool,
+    #[arg(short = 'e', long = "eval", value_name = "CODE", help = "Run code given on the command line")]
+    eval: Option<String>,
+    #[arg(
+        value_name = "SCRIPT [ARGS]",
+        trailing_var_arg = true,
+        help = "Script to run ('-' reads stdin), followed by arguments for args()"
+    )]
+    program: Vec<String>,
+}
 
-    for arg in args.iter().skip(1) {
-        if arg == "--vm" {
-            use_vm = true;
-        } else if arg == "--help" || arg == "-h" {
-            print_usage();
-            process::exit(0);
-        } else if arg == "--version" || arg == "-v" {
-            println!("Nebula 1.0.0");
-            process::exit(0);
-        } else if arg.starts_with('-') {
-            eprintln!("{} Unknown flag: {}", "[ERROR]".bold().red(), arg);
-            print_usage();
-            process::exit(64);
+#[derive(clap::Subcommand)]
+enum CliCommand {
+    #[command(about = "Start the REPL")]
+    Repl {
+        #[arg(long, value_name = "FILE", help = "Evaluate a file before the prompt")]
+        load: Vec<String>,
+    },
+    #[command(about = "Run test_* functions in test files")]
+    Test {
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+    #[command(about = "Create a new project with a nebula.toml manifest")]
+    New { name: String },
+    #[command(about = "Run the current project's entry script")]
+    Run {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    #[command(about = "Add a path or git dependency to nebula.toml and fetch it")]
+    Add {
+        name: String,
+        #[arg(long, conflicts_with = "git", required_unless_present = "git")]
+        path: Option<String>,
+        #[arg(long)]
+        git: Option<String>,
+        #[arg(long, requires = "git", help = "Branch, tag or commit to check out")]
+        rev: Option<String>,
+    },
+    #[command(about = "Fetch every dependency into vendor/")]
+    Fetch,
+    #[command(about = "Print the doc comments of a script's items as Markdown")]
+    Doc { file: String },
+    #[command(about = "Print a shell completion script")]
+    Completions { shell: Shell },
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum TypedMode {
+    Gradual,
+    Strict,
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+fn sandbox_policy(cli: &Cli) -> SandboxPolicy {
+    let restricted = cli.sandbox
+        || !cli.allow_read.is_empty()
+        || !cli.allow_write.is_empty()
+        || cli.allow_net
+        || cli.allow_exec
+        || cli.allow_env;
+    if !restricted {
+        return SandboxPolicy::permissive();
+    }
+    let mut policy = SandboxPolicy::locked()
+        .allow_net(cli.allow_net)
+        .allow_exec(cli.allow_exec)
+        .allow_env(cli.allow_env);
+    for dir in &cli.allow_read {
+        policy = policy.allow_read(dir);
+    }
+    for dir in &cli.allow_write {
+        policy = policy.allow_write(dir);
+    }
+    policy
+}
+
+fn parse_args() -> Options {
+    let command = Cli::command().before_help(BANNER.cyan().to_string());
+    let cli = match command
+        .try_get_matches()
+        .and_then(|matches| Cli::from_arg_matches(&matches))
+    {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            process::exit(if e.use_stderr() { 64 } else { 0 });
+        }
+    };
+
+    JSON_ERRORS.store(cli.error_format == ErrorFormat::Json, Ordering::Relaxed);
+    DENY_WARNINGS.store(cli.deny_warnings, Ordering::Relaxed);
+    ALLOW_DEPRECATED.store(cli.allow_deprecated, Ordering::Relaxed);
+    TYPED.store(cli.typed.is_some(), Ordering::Relaxed);
+    STRICT_TYPES.store(cli.typed == Some(TypedMode::Strict), Ordering::Relaxed);
+
+    let policy = sandbox_policy(&cli);
+    let mut program = cli.program.into_iter();
+    let mut options = Options {
+        use_vm: cli.vm,
+        policy,
+        file_path: None,
+        inline_source: cli.eval,
+        script_args: Vec::new(),
+        test_dir: None,
+        check: cli.check,
+        project: None,
+        preload: Vec::new(),
+        quiet: cli.quiet,
+        no_color: cli.no_color,
+        timing: cli.time,
+        watch: cli.watch,
+        stream: cli.stream,
+        dump: if cli.tokens {
+            Some(Dump::Tokens)
+        } else if cli.ast {
+            Some(Dump::Ast)
+        } else if cli.ast_json {
+            Some(Dump::AstJson)
+        } else if cli.pretty {
+            Some(Dump::Pretty)
         } else {
-            file_path = Some(arg.clone());
+            None
+        },
+        doc: None,
+    };
+    if options.inline_source.is_none() {
+        options.file_path = program.next();
+    }
+    options.script_args = program.collect();
+
+    match cli.command {
+        Some(CliCommand::Repl { load }) => options.preload = load,
+        Some(CliCommand::Test { dir }) => options.test_dir = Some(dir),
+        Some(CliCommand::New { name }) => options.project = Some(ProjectCommand::New(name)),
+        Some(CliCommand::Add {
+            name,
+            path,
+            git,
+            rev,
+        }) => options.project = Some(ProjectCommand::Add(name, Dependency { path, git, rev })),
+        Some(CliCommand::Fetch) => options.project = Some(ProjectCommand::Fetch),
+        Some(CliCommand::Run { args }) => {
+            options.project = Some(ProjectCommand::Run);
+            options.script_args = args;
         }
+        Some(CliCommand::Doc { file }) => options.doc = Some(file),
+        Some(CliCommand::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "nebula", &mut io::stdout());
+            process::exit(0);
+        }
+        None => {}
     }
 
-    (use_vm, file_path)
+    options
 }
 
-fn print_usage() {
-    println!("{}", BANNER.cyan());
+struct ReplState {
+    engine: ScriptEngine,
+    session: Vec<String>,
+    sources: SourceMap,
+}
 
-    println!();
-    println!("{}", "USAGE:".bold().white());
-    println!(
-        "  {} {}              Start REPL",
-        "nebula".cyan(),
-        "".dimmed()
-    );
-    println!(
-        "  {} {}  Run script (interpreter)",
-        "nebula".cyan(),
-        "<script.na>".green()
-    );
-    println!(
-        "  {} {} {} Run script (fast VM)",
-        "nebula".cyan(),
-        "--vm".yellow(),
-        "<script>".green()
-    );
-    println!();
-    println!("{}", "OPTIONS:".bold().white());
-    println!("  {}    Use bytecode VM (35x faster)", "--vm".yellow());
-    println!("  {}     Show version info", "--version".yellow());
-    println!("  {}  Show this message", "--help".yellow());
+enum Command {
+    Handled,
+    NotACommand,
 }
 
-fn run_repl(use_vm: bool) {
+fn run_repl(use_vm: bool, preload: &[String]) {
     println!("{}", BANNER.cyan());
-    let mode = if use_vm {
-        "VM".green()
-    } else {
-        "Interpreter".blue()
-    };
     println!(
         "  {} {} {}",
         "Nebula".purple().bold(),
         "v1.0".dimmed(),
-        mode
+        mode_label(use_vm)
+    );
+    println!(
+        "  Type {} for commands, {} to quit\n",
+        "':help'".dimmed(),
+        "'exit'".dimmed()
     );
-    println!("  Type {} to quit\n", "'exit'".dimmed());
 
-    let mut interpreter = Interpreter::new();
-    let mut input = String::new();
+    let mut state = ReplState {
+        engine: engine(use_vm),
+        session: Vec::new(),
+        sources: SourceMap::new(),
+    };
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("{} Cannot start line editor: {}", "[ERROR]".bold().red(), e);
+            process::exit(74);
+        }
+    };
+    editor.set_helper(Some(ReplHelper));
+
+    if let Some(rc) = home_file(".nebularc").filter(|path| path.is_file()) {
+        if let Err(message) = load_file(&mut state, &rc.to_string_lossy()) {
+            repl_error(&message);
+        }
+    }
+    for path in preload {
+        match load_file(&mut state, path) {
+            Ok(source) => {
+                state.session.push(source);
+                println!("{} {}", "  Loaded".green(), path.yellow());
+            }
+            Err(message) => repl_error(&message),
+        }
+    }
+
+    let history = home_file(".nebula_history");
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+    let prompt = format!("{} ", "λ".purple().bold());
 
     loop {
-        print!("{} ", "λ".purple().bold());
-        let _ = io::stdout().flush();
+        let input = match editor.readline(&prompt) {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => {
+                println!("{}", "✨ Goodbye.".cyan());
+                break;
+            }
+            Err(e) => {
+                eprintln!("{} {}", "[ERROR]".bold().red(), e);
+                break;
+            }
+        };
 
-        input.clear();
-        if io::stdin().read_line(&mut input).is_err() {
-            break;
+        let line = input.trim();
+        if line.is_empty() {
+            continue;
         }
+        let _ = editor.add_history_entry(line);
 
-        let line = input.trim();
         if line == "exit" || line == "quit" {
             println!("{}", "✨ Goodbye.".cyan());
             break;
         }
 
-        if line.is_empty() {
+        if let Command::Handled = handle_command(line, &mut state) {
             continue;
         }
 
         let start = Instant::now();
-        let result = if use_vm {
-            run_vm(line)
-        } else {
-            run_interpreter(line, &mut interpreter)
-        };
+        let name = format!("<repl:{}>", state.sources.len() + 1);
+        let result = eval_in(&mut state, &name, line);
 
         match result {
             Ok(value) => {
+                state.session.push(line.to_string());
                 if !matches!(value, Value::Nil) {
                     println!("{} {}", "=>".dimmed(), format!("{}", value).green());
                 }
             }
-            Err(e) => {
-                println!("{} {}", "[ERROR]".bold().red(), e.message().red());
+            Err(NebulaError::Exit { code }) => {
+                if let Some(path) = &history {
+                    let _ = editor.save_history(path);
+                }
+                process::exit(code)
             }
+            Err(e) => repl_error(&describe(&state, &e)),
         }
 
         let elapsed = start.elapsed();
@@ -164,137 +532,945 @@ fn run_repl(use_vm: bool) {
             println!("{}", format!("  ⏱ {}ms", elapsed.as_millis()).dimmed());
         }
     }
+
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
+}
+
+fn mode_label(use_vm: bool) -> colored::ColoredString {
+    if use_vm {
+        "VM".green()
+    } else {
+        "Interpreter".blue()
+    }
+}
+
+fn eval_in(state: &mut ReplState, name: &str, source: &str) -> Result<Value, NebulaError> {
+    let file = state.sources.add(name, source);
+    let program = project::parse_file(&state.sources, file)?;
+    state.engine.run_program(&program)
+}
+
+fn infer_type(state: &mut ReplState, source: &str) -> Result<Ty, NebulaError> {
+    let file = state.sources.add("<repl:type>", source);
+    let program = project::parse_file(&state.sources, file)?;
+    let not_expression = || NebulaError::coded(ErrorCode::E004, ":type expects one expression");
+    let [Item::Statement(stmt)] = program.items.as_slice() else {
+        return Err(not_expression());
+    };
+    let StmtKind::Expression(expr) = &stmt.kind else {
+        return Err(not_expression());
+    };
+    let mut checker = TypeChecker::new();
+    let session = Parser::new(Lexer::new(&state.session.join("\n")).collect()).parse_program();
+    if session.map_or(true, |session| checker.check_program(&session).is_err()) {
+        checker = TypeChecker::new();
+        for (name, _) in state.engine.globals() {
+            checker.define(&name, Ty::Any);
+        }
+    }
+    checker.infer(expr)
+}
+
+fn describe(state: &ReplState, error: &NebulaError) -> String {
+    Renderer::plain().render_in(&Diagnostic::from(error), &state.sources)
+}
+
+fn handle_command(line: &str, state: &mut ReplState) -> Command {
+    let Some(command) = line.strip_prefix(':') else {
+        return Command::NotACommand;
+    };
+    let (name, arg) = match command.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command, ""),
+    };
+
+    match (name, arg) {
+        ("help", _) => print_repl_help(),
+        ("env", _) => {
+            let bindings = state.engine.globals();
+            if bindings.is_empty() {
+                println!("{}", "  (no variables defined)".dimmed());
+            }
+            for (name, value) in bindings {
+                println!("  {} {} {}", name.cyan(), ":".dimmed(), value.type_name().yellow());
+            }
+        }
+        ("type", "") => repl_error("usage: :type <expr>"),
+        ("type", expr) => match infer_type(state, expr) {
+            Ok(ty) => println!("  {}", ty.to_string().yellow()),
+            Err(e) => repl_error(&describe(state, &e)),
+        },
+        ("load", "") => repl_error("usage: :load <file.na>"),
+        ("load", path) => match load_file(state, path) {
+            Ok(source) => {
+                state.session.push(source);
+                println!("{} {}", "  Loaded".green(), path.yellow());
+            }
+            Err(message) => repl_error(&message),
+        },
+        ("save", "") => repl_error("usage: :save <session.na>"),
+        ("save", path) => {
+            let mut contents = state.session.join("\n");
+            contents.push('\n');
+            match fs::write(path, contents) {
+                Ok(()) => println!(
+                    "{} {} lines to {}",
+                    "  Saved".green(),
+                    state.session.len(),
+                    path.yellow()
+                ),
+                Err(e) => repl_error(&format!("Cannot write '{}': {}", path, e)),
+            }
+        }
+        ("clear", _) => {
+            state.engine = engine(state.engine.backend() == Backend::Vm);
+            state.session.clear();
+            state.sources.clear();
+            println!("{}", "  Session cleared".dimmed());
+        }
+        ("mode", "vm" | "interp") => {
+            let backend = if arg == "vm" {
+                Backend::Vm
+            } else {
+                Backend::Interpreter
+            };
+            match state.engine.set_backend(backend) {
+                Ok(()) => println!("  Switched to {}", mode_label(arg == "vm")),
+                Err(e) => repl_error(&format!("{}; use :clear to start over", e)),
+            }
+        }
+        ("mode", "") => println!("  {}", mode_label(state.engine.backend() == Backend::Vm)),
+        ("mode", other) => repl_error(&format!("unknown mode '{}' (use vm or interp)", other)),
+        _ => repl_error(&format!("unknown command ':{}' (try :help)", name)),
+    }
+    Command::Handled
+}
+
+fn repl_error(message: &str) {
+    eprintln!("{} {}", "[ERROR]".bold().red(), message.red());
 }
 
-fn run_file(path: &str, use_vm: bool) {
-    let source = match fs::read_to_string(path) {
+fn print_repl_help() {
+    let commands = [
+        (":help", "Show this message"),
+        (":env", "List defined variables with their types"),
+        (":type <expr>", "Show the inferred type of an expression"),
+        (":load <file>", "Run a file in the current session"),
+        (":save <file>", "Write the lines entered so far to a file"),
+        (":clear", "Forget all variables and session lines"),
+        (":mode vm|interp", "Switch execution engine"),
+        ("exit", "Leave the REPL"),
+    ];
+    for (command, description) in commands {
+        println!("  {:<18} {}", command.yellow(), description);
+    }
+}
+
+fn load_file(state: &mut ReplState, path: &str) -> Result<String, String> {
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+    match eval_in(state, path, &source) {
+        Ok(_) => Ok(source),
+        Err(NebulaError::Exit { code }) => Err(format!("'{}' exited with code {}", path, code)),
+        Err(e) => Err(describe(state, &e)),
+    }
+}
+
+fn home_file(name: &str) -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(name))
+}
+
+fn read_source(path: &str) -> String {
+    if path == "-" {
+        let mut source = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut source) {
+            eprintln!("{} Cannot read stdin: {}", "[FILE ERROR]".bold().red(), e);
+            process::exit(66);
+        }
+        return source;
+    }
+
+    match fs::read_to_string(path) {
         Ok(s) => s,
+        Err(e) => unreadable(path, e),
+    }
+}
+
+fn unreadable(path: &str, error: io::Error) -> ! {
+    eprintln!(
+        "{} Cannot read '{}': {}",
+        "[FILE ERROR]".bold().red(),
+        path.yellow(),
+        error
+    );
+    process::exit(66);
+}
+
+fn program_source(options: &Options) -> (String, String) {
+    match (&options.inline_source, options.file_path.as_deref()) {
+        (Some(source), _) => ("<eval>".to_string(), source.clone()),
+        (None, Some(path)) => (source_name(path).to_string(), read_source(path)),
+        (None, None) => {
+            eprintln!("{} No script given", "[ERROR]".bold().red());
+            process::exit(64);
+        }
+    }
+}
+
+fn source_name(path: &str) -> &str {
+    if path == "-" {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn run_file(path: &str, use_vm: bool, report_time: bool) {
+    let source = read_source(path);
+    run_source(source_name(path), &source, use_vm, report_time && path != "-");
+}
+
+fn dump_source(name: &str, source: &str, dump: Dump) {
+    let mut out = io::stdout().lock();
+    if let Dump::Tokens = dump {
+        return finish_dump(dump_tokens(source, &mut out));
+    }
+
+    let program = match parse(source) {
+        Ok(program) => program,
         Err(e) => {
-            eprintln!(
-                "{} Cannot read '{}': {}",
-                "[FILE ERROR]".bold().red(),
-                path.yellow(),
-                e
-            );
-            process::exit(66);
+            report_error(&SourceMap::single(name, source), &e);
+            process::exit(70);
         }
     };
 
+    let written = match dump {
+        Dump::AstJson => match serde_json::to_string_pretty(&program) {
+            Ok(json) => writeln!(out, "{}", json),
+            Err(e) => {
+                eprintln!("{} Cannot encode AST: {}", "[ERROR]".bold().red(), e);
+                process::exit(70);
+            }
+        },
+        Dump::Pretty => write!(out, "{}", nebula::parser::pretty(&program)),
+        _ => writeln!(out, "{:#?}", program),
+    };
+    finish_dump(written.and_then(|()| out.flush()));
+}
+
+fn finish_dump(written: io::Result<()>) {
+    match written {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("{} Cannot write output: {}", "[ERROR]".bold().red(), e);
+            process::exit(74);
+        }
+        _ => {}
+    }
+}
+
+fn run_source(name: &str, source: &str, use_vm: bool, report_time: bool) {
     let start = Instant::now();
+    let sources = SourceMap::single(name, source);
+
+    let result = execute(&sources, use_vm);
+    finish_run(&sources, result, start.elapsed(), report_time);
+}
 
-    let result = if use_vm {
-        run_vm(&source)
+fn stream_file(path: &str, use_vm: bool, report_time: bool) {
+    let start = Instant::now();
+    let input: Box<dyn BufRead> = if path == "-" {
+        Box::new(io::stdin().lock())
     } else {
-        let mut interpreter = Interpreter::new();
-        run_interpreter(&source, &mut interpreter)
+        match fs::File::open(path) {
+            Ok(file) => Box::new(io::BufReader::new(file)),
+            Err(e) => unreadable(path, e),
+        }
     };
+    let backend = if use_vm { Backend::Vm } else { Backend::Interpreter };
 
-    let elapsed = start.elapsed();
+    let mut items = ItemReader::new(source_name(path), input);
+    let mut engine = ScriptEngine::with_backend(backend);
+    engine.set_policy(policy());
+    let result = engine.run_stream(&mut items);
+    let mut sources = SourceMap::new();
+    if let Some(file) = items.current() {
+        sources.add_file(file.clone());
+    }
+    finish_run(&sources, result, start.elapsed(), report_time && path != "-");
+}
 
+fn finish_run(
+    sources: &SourceMap,
+    result: Result<Value, NebulaError>,
+    elapsed: Duration,
+    report_time: bool,
+) {
     match result {
         Ok(_) => {
-            println!(
-                "{}",
-                format!("✨ Executed in {:.3}s", elapsed.as_secs_f64()).cyan()
-            );
+            if report_time {
+                eprintln!(
+                    "{}",
+                    format!("✨ Executed in {:.3}s", elapsed.as_secs_f64()).cyan()
+                );
+            }
         }
+        Err(NebulaError::Exit { code }) => process::exit(code),
         Err(e) => {
-            report_error(&source, &e);
+            report_error(sources, &e);
             process::exit(70);
         }
     }
 }
 
-fn run_interpreter(source: &str, interpreter: &mut Interpreter) -> Result<Value, NebulaError> {
-    let lexer = Lexer::new(source);
-    let tokens: Vec<_> = lexer.collect();
+fn run_timed(name: &str, source: &str, use_vm: bool) {
+    let sources = SourceMap::single(name, source);
+    let mut phases: Vec<(&str, Duration, String)> = Vec::new();
+    let total = Instant::now();
+
+    let result = (|| {
+        let start = Instant::now();
+        let tokens = Lexer::new(source).tokenize().map_err(lexical_errors)?;
+        phases.push(("lex", start.elapsed(), format!("{} tokens", tokens.len())));
+
+        let start = Instant::now();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program()?;
+        phases.push(("parse", start.elapsed(), format!("{} items", program.items.len())));
+        report_warnings(&sources, parser.warnings())?;
 
-    for token in &tokens {
-        if let nebula::TokenKind::Error(msg) = &token.kind {
-            return Err(NebulaError::Lexer {
-                message: msg.clone(),
-                span: token.span,
-            });
+        let mut hints = NumericHints::new();
+        if TYPED.load(Ordering::Relaxed) {
+            let start = Instant::now();
+            let (warnings, numeric) = type_check(&program)?;
+            phases.push((
+                "typeck",
+                start.elapsed(),
+                format!("{} specialized ops", numeric.len()),
+            ));
+            report_warnings(&sources, &warnings)?;
+            hints = numeric;
         }
+
+        if !use_vm {
+            let start = Instant::now();
+            let mut interpreter = interpreter();
+            let result = interpreter.interpret(&program);
+            phases.push(("execute", start.elapsed(), "interpreter".to_string()));
+            return result;
+        }
+
+        let start = Instant::now();
+        let mut compiler = compiler();
+        compiler.set_numeric_hints(hints);
+        let chunk = compiler.compile(&program)?;
+        let functions = compiler.functions();
+        phases.push((
+            "compile",
+            start.elapsed(),
+            format!("{} bytes, {} functions", chunk.len(), functions.len()),
+        ));
+        report_warnings(&sources, compiler.warnings())?;
+
+        let start = Instant::now();
+        let mut vm = vm();
+        let result = vm.run_with_functions(&chunk, compiler.global_names(), functions);
+        phases.push((
+            "execute",
+            start.elapsed(),
+            format!("{} instructions", vm.instruction_count()),
+        ));
+        result.map(nebula::vm::to_value)
+    })();
+
+    let total = total.elapsed();
+    eprintln!("{}", "── timing ──".dimmed());
+    for (phase, elapsed, detail) in &phases {
+        eprintln!(
+            "  {:<8} {:>10.3}ms  {}",
+            phase,
+            elapsed.as_secs_f64() * 1000.0,
+            detail.dimmed()
+        );
     }
+    eprintln!("  {:<8} {:>10.3}ms", "total".bold(), total.as_secs_f64() * 1000.0);
 
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse_program()?;
+    match result {
+        Ok(_) => {}
+        Err(NebulaError::Exit { code }) => process::exit(code),
+        Err(e) => {
+            report_error(&sources, &e);
+            process::exit(70);
+        }
+    }
+}
 
-    interpreter.interpret(&program)
+fn execute(sources: &SourceMap, use_vm: bool) -> Result<Value, NebulaError> {
+    let (program, hints) = parse_reporting(sources, FileId::default())?;
+    run_program(sources, &program, hints, use_vm)
 }
 
-fn run_vm(source: &str) -> Result<Value, NebulaError> {
-    let lexer = Lexer::new(source);
-    let tokens: Vec<_> = lexer.collect();
+fn new_project(name: &str) -> ! {
+    let dir = Path::new(name);
+    if let Err(e) = project::scaffold(dir) {
+        eprintln!(
+            "{} Cannot create project '{}': {}",
+            "[FILE ERROR]".bold().red(),
+            name.yellow(),
+            e
+        );
+        process::exit(73);
+    }
+    println!("{} {}", "Created project".green(), name.yellow());
+    println!("  {} {} && nebula run", "cd".dimmed(), name);
+    process::exit(0);
+}
 
-    for token in &tokens {
-        if let nebula::TokenKind::Error(msg) = &token.kind {
-            return Err(NebulaError::Lexer {
-                message: msg.clone(),
-                span: token.span,
-            });
+fn open_project() -> Project {
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let Some(root) = Project::find_root(&cwd) else {
+        eprintln!(
+            "{} No {} found in this directory or its parents",
+            "[ERROR]".bold().red(),
+            project::MANIFEST_FILE
+        );
+        process::exit(66);
+    };
+    match Project::load(&root) {
+        Ok(project) => project,
+        Err(e) => {
+            eprintln!("{} {}", "[ERROR]".bold().red(), e.message());
+            process::exit(65);
         }
     }
+}
 
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse_program()?;
+fn add_dependency(name: &str, dep: Dependency) -> ! {
+    let mut project = open_project();
+    match project.add_dependency(name, dep) {
+        Ok(dest) => {
+            println!("{} {} {}", "Added".green(), name.yellow(), format!("→ {}", dest.display()).dimmed());
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("{} {}", "[ERROR]".bold().red(), e.message());
+            process::exit(74);
+        }
+    }
+}
 
-    let mut compiler = Compiler::new();
-    let chunk = compiler.compile(&program)?;
-    let global_names = compiler.global_names();
-    let functions = compiler.functions();
+fn fetch_dependencies() -> ! {
+    let project = open_project();
+    for name in project.manifest.dependencies.keys() {
+        match project.fetch(name) {
+            Ok(_) => println!("{} {}", "Fetched".green(), name.yellow()),
+            Err(e) => {
+                eprintln!("{} {}: {}", "[ERROR]".bold().red(), name, e.message());
+                process::exit(74);
+            }
+        }
+    }
+    process::exit(0);
+}
 
-    let mut vm = VM::new();
-    let result = vm.run_with_functions(&chunk, global_names, functions)?;
-
-    Ok(nanbox_to_value(result))
-}
-
-fn nanbox_to_value(nb: nebula::vm::NanBoxed) -> Value {
-    if nb.is_nil() {
-        Value::Nil
-    } else if nb.is_bool() {
-        Value::Bool(nb.as_bool())
-    } else if nb.is_number() {
-        Value::Number(nb.as_number())
-    } else if nb.is_integer() {
-        Value::Integer(nb.as_integer())
-    } else if nb.is_ptr() {
-        let obj = unsafe { &*nb.as_ptr() };
-        match &obj.data {
-            nebula::vm::HeapData::String(s) => Value::String(s.to_string()),
-            nebula::vm::HeapData::List(items) => {
-                Value::List(items.iter().map(|v| nanbox_to_value(*v)).collect())
+fn run_project(use_vm: bool, report_time: bool) -> ! {
+    let project = open_project();
+    match project.fetch_missing() {
+        Ok(fetched) => {
+            for name in fetched {
+                eprintln!("{} {}", "Fetched".green(), name.yellow());
             }
-            nebula::vm::HeapData::Map(map) => Value::Map(
-                map.iter()
-                    .map(|(k, v)| (k.to_string(), nanbox_to_value(*v)))
-                    .collect(),
+        }
+        Err(e) => {
+            eprintln!("{} {}", "[ERROR]".bold().red(), e.message());
+            process::exit(74);
+        }
+    }
+
+    let entry = project.entry_path();
+    let name = entry.display().to_string();
+    let mut sources = SourceMap::single(&name, read_source(&name));
+    let start = Instant::now();
+    let result = parse_reporting(&sources, FileId::default())
+        .and_then(|(program, hints)| {
+            let program =
+                project::load_modules(program, &project.module_paths(), &mut sources, &policy())?;
+            run_program(&sources, &program, hints, use_vm)
+        });
+
+    match result {
+        Ok(_) => {
+            if report_time {
+                eprintln!(
+                    "{}",
+                    format!("✨ Executed in {:.3}s", start.elapsed().as_secs_f64()).cyan()
+                );
+            }
+            process::exit(0);
+        }
+        Err(NebulaError::Exit { code }) => process::exit(code),
+        Err(e) => {
+            report_error(&sources, &e);
+            process::exit(70);
+        }
+    }
+}
+
+fn watch_file(path: &str, use_vm: bool, quiet: bool) -> ! {
+    let mut run = 0;
+    loop {
+        run += 1;
+        let header = format!("── run {} · {} ──", run, path);
+        eprintln!("{}", header.dimmed());
+
+        let source = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!(
+                "{} Cannot read '{}': {}",
+                "[FILE ERROR]".bold().red(),
+                path.yellow(),
+                e
+            );
+            String::new()
+        });
+
+        let start = Instant::now();
+        let sources = SourceMap::single(path, source.as_str());
+        match execute(&sources, use_vm) {
+            Ok(_) if quiet => {}
+            Ok(_) => eprintln!(
+                "{}",
+                format!("✨ Executed in {:.3}s", start.elapsed().as_secs_f64()).cyan()
             ),
-            nebula::vm::HeapData::Function(f) => Value::String(format!("<fn {}>", f.name)),
+            Err(NebulaError::Exit { code }) => {
+                eprintln!("{}", format!("exited with code {}", code).dimmed())
+            }
+            Err(e) => report_error(&sources, &e),
+        }
+
+        let files = watched_files(Path::new(path), &source);
+        if !quiet {
+            eprintln!("{}", format!("watching {} file(s) for changes...", files.len()).dimmed());
+        }
+        wait_for_change(&files);
+    }
+}
+
+fn watched_files(script: &Path, source: &str) -> Vec<PathBuf> {
+    let search = [script.parent().unwrap_or(Path::new("")).to_path_buf()];
+    let mut files = vec![script.to_path_buf()];
+    let mut visited = HashSet::new();
+    visited.insert(script.canonicalize().unwrap_or_else(|_| script.to_path_buf()));
+    collect_modules(source, &search, &mut visited, &mut files);
+    files
+}
+
+fn collect_modules(
+    source: &str,
+    search: &[PathBuf],
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) {
+    let Ok(program) = parse(source) else {
+        return;
+    };
+    for item in &program.items {
+        let Item::Use(module) = item else {
+            continue;
+        };
+        if module.native {
+            continue;
+        }
+        let path = project::resolve_module(&module.path, search)
+            .unwrap_or_else(|| search[0].join(project::module_file(&module.path)));
+        if !visited.insert(path.canonicalize().unwrap_or_else(|_| path.clone())) {
+            continue;
+        }
+        files.push(path.clone());
+        if let Ok(source) = fs::read_to_string(&path) {
+            collect_modules(&source, search, visited, files);
+        }
+    }
+}
+
+fn wait_for_change(files: &[PathBuf]) {
+    let snapshot = || -> Vec<Option<SystemTime>> {
+        files
+            .iter()
+            .map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+            .collect()
+    };
+
+    let initial = snapshot();
+    loop {
+        thread::sleep(Duration::from_millis(200));
+        if snapshot() != initial {
+            break;
+        }
+    }
+
+    let mut settled = snapshot();
+    loop {
+        thread::sleep(Duration::from_millis(100));
+        let current = snapshot();
+        if current == settled {
+            return;
+        }
+        settled = current;
+    }
+}
+
+fn document(path: &str) -> ! {
+    let source = read_source(path);
+    match parse(&source) {
+        Ok(program) => {
+            let entries = nebula::doc::document(&program);
+            print!("{}", nebula::doc::markdown(source_name(path), &entries));
+            process::exit(0);
+        }
+        Err(e) => {
+            report_error(&SourceMap::single(source_name(path), source), &e);
+            process::exit(70);
         }
-    } else {
-        Value::Nil
     }
 }
 
-fn report_error(source: &str, error: &NebulaError) {
-    eprintln!("{}", "[COSMIC FRACTURE]".bold().red());
-    eprintln!("{}", error.message().red());
+fn dump_tokens(source: &str, out: &mut impl Write) -> io::Result<()> {
+    for token in Lexer::new(source) {
+        writeln!(
+            out,
+            "{:>4}:{:<4} {:>5}+{:<3} {:<28} {:?}",
+            token.span.line,
+            token.span.column,
+            token.span.start,
+            token.span.length,
+            format!("{:?}", token.kind),
+            token.lexeme
+        )?;
+    }
+    out.flush()
+}
 
-    if let Some(span) = error.span() {
-        let lines: Vec<_> = source.lines().collect();
-        if span.line > 0 && span.line <= lines.len() {
-            let line_content = lines[span.line - 1];
-            eprintln!("  {} line {}", "-->".cyan(), span.line);
-            eprintln!("   {}", "|".cyan());
-            eprintln!("{:3} {} {}", span.line, "|".cyan(), line_content);
+fn run_test_suite(dir: &str) -> ! {
+    let files = match testing::discover(Path::new(dir)) {
+        Ok(files) => files,
+        Err(e) => {
             eprintln!(
-                "   {} {}^",
-                "|".cyan(),
-                " ".repeat(span.column.saturating_sub(1))
+                "{} Cannot read '{}': {}",
+                "[FILE ERROR]".bold().red(),
+                dir.yellow(),
+                e
             );
+            process::exit(66);
+        }
+    };
+
+    let start = Instant::now();
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for file in &files {
+        let display = file.display().to_string();
+        println!("{}", display.bold());
+
+        let outcomes = fs::read_to_string(file)
+            .map_err(|e| NebulaError::Io {
+                message: e.to_string(),
+            })
+            .and_then(|source| parse(&source))
+            .and_then(|program| testing::run_tests(&program, policy()));
+
+        let outcomes = match outcomes {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                println!("  {} {}", "ERROR".bold().red(), e.message().red());
+                failed += 1;
+                continue;
+            }
+        };
+
+        for outcome in outcomes {
+            let ms = format!("({:.2}ms)", outcome.elapsed.as_secs_f64() * 1000.0);
+            match &outcome.error {
+                None => {
+                    passed += 1;
+                    println!("  {} {} {}", "PASS".green(), outcome.name, ms.dimmed());
+                }
+                Some(e) => {
+                    failed += 1;
+                    println!("  {} {} {}", "FAIL".bold().red(), outcome.name, ms.dimmed());
+                    for line in e.message().lines() {
+                        println!("       {}", line.red());
+                    }
+                }
+            }
+        }
+    }
+
+    let summary = format!(
+        "{} passed, {} failed in {:.3}s",
+        passed,
+        failed,
+        start.elapsed().as_secs_f64()
+    );
+    if failed > 0 {
+        println!("\n{}", summary.bold().red());
+        process::exit(1);
+    }
+    println!("\n{}", summary.bold().green());
+    process::exit(0);
+}
+
+fn check_sources(options: &Options) -> ! {
+    let mut sources = Vec::new();
+    if let Some(source) = &options.inline_source {
+        sources.push(("<eval>".to_string(), Ok(source.clone())));
+    }
+    for path in options.file_path.iter().chain(&options.script_args) {
+        let source = if path == "-" {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source).map(|_| source)
+        } else {
+            fs::read_to_string(path)
+        };
+        sources.push((path.clone(), source));
+    }
+    if sources.is_empty() {
+        eprintln!("{} --check requires a script", "[ERROR]".bold().red());
+        process::exit(64);
+    }
+
+    let mut failed = false;
+    for (name, source) in sources {
+        let source = match source {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!(
+                    "{} Cannot read '{}': {}",
+                    "[FILE ERROR]".bold().red(),
+                    name.yellow(),
+                    e
+                );
+                failed = true;
+                continue;
+            }
+        };
+        let sources = SourceMap::single(source_name(&name), source.as_str());
+        match diagnostics(&source, FileId::default()) {
+            Ok((program, mut warnings)) => {
+                match type_check(&program) {
+                    Ok((typeck_warnings, _)) => warnings.extend(typeck_warnings),
+                    Err(error) => {
+                        failed = true;
+                        report_error(&sources, &error);
+                        continue;
+                    }
+                }
+                warnings.extend(compile_warnings(&program));
+                let denied = report_warnings(&sources, &warnings).is_err();
+                failed |= denied;
+                if denied && !JSON_ERRORS.load(Ordering::Relaxed) {
+                    eprintln!("{} {}", "denied".red(), name);
+                } else if !denied && !options.quiet {
+                    println!("{} {}", "ok".green(), name);
+                }
+            }
+            Err(errors) => {
+                failed = true;
+                if !JSON_ERRORS.load(Ordering::Relaxed) {
+                    eprintln!("{}", name.bold());
+                }
+                for error in &errors {
+                    report_error(&sources, error);
+                }
+            }
         }
     }
+    process::exit(if failed { 1 } else { 0 });
+}
+
+fn diagnostics(
+    source: &str,
+    file: FileId,
+) -> Result<(Program, Vec<Diagnostic>), Vec<NebulaError>> {
+    let tokens = Lexer::new(source).with_file(file).tokenize()?;
+    let mut parser = Parser::new(tokens);
+    let program = parser.parse_program().map_err(|e| vec![e])?;
+    Ok((program, parser.take_warnings()))
+}
+
+fn compile_warnings(program: &Program) -> Vec<Diagnostic> {
+    let mut compiler = compiler();
+    let _ = compiler.compile(program);
+    compiler.warnings().to_vec()
+}
+
+fn parse(source: &str) -> Result<Program, NebulaError> {
+    parse_with_warnings(source).map(|(program, _)| program)
+}
+
+fn parse_with_warnings(source: &str) -> Result<(Program, Vec<Diagnostic>), NebulaError> {
+    diagnostics(source, FileId::default()).map_err(lexical_errors)
+}
+
+fn lexical_errors(errors: Vec<NebulaError>) -> NebulaError {
+    NebulaError::combine(errors, "lexical errors")
+}
+
+fn parse_reporting(
+    sources: &SourceMap,
+    file: FileId,
+) -> Result<(Program, NumericHints), NebulaError> {
+    let source = sources.get(file).map_or("", |file| file.source());
+    let (program, warnings) = diagnostics(source, file).map_err(lexical_errors)?;
+    report_warnings(sources, &warnings)?;
+    let (typeck_warnings, hints) = type_check(&program)?;
+    report_warnings(sources, &typeck_warnings)?;
+    Ok((program, hints))
+}
+
+fn type_check(program: &Program) -> Result<(Vec<Diagnostic>, NumericHints), NebulaError> {
+    if !TYPED.load(Ordering::Relaxed) {
+        return Ok((Vec::new(), NumericHints::new()));
+    }
+    let mode = if STRICT_TYPES.load(Ordering::Relaxed) {
+        TypeMode::Strict
+    } else {
+        TypeMode::Gradual
+    };
+    let mut checker = TypeChecker::with_mode(mode);
+    checker.check_program(program)?;
+    Ok((checker.warnings().to_vec(), checker.numeric_hints().clone()))
+}
+
+fn run_program(
+    sources: &SourceMap,
+    program: &Program,
+    hints: NumericHints,
+    use_vm: bool,
+) -> Result<Value, NebulaError> {
+    if !use_vm {
+        return interpreter().interpret(program);
+    }
+
+    let mut compiler = compiler();
+    compiler.set_numeric_hints(hints);
+    let chunk = compiler.compile(program)?;
+    report_warnings(sources, compiler.warnings())?;
+    run_compiled(&compiler, &chunk)
+}
+
+fn policy() -> SandboxPolicy {
+    POLICY.get().cloned().unwrap_or_default()
+}
+
+fn interpreter() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_policy(policy());
+    interpreter
+}
+
+fn engine(use_vm: bool) -> ScriptEngine {
+    let mut engine = ScriptEngine::with_backend(if use_vm {
+        Backend::Vm
+    } else {
+        Backend::Interpreter
+    });
+    engine.set_policy(policy());
+    engine
+}
+
+fn compiler() -> Compiler {
+    let mut compiler = Compiler::new();
+    compiler.set_policy(policy());
+    compiler
+}
+
+fn vm() -> VM {
+    let mut vm = VM::new();
+    vm.set_policy(policy());
+    vm
+}
+
+fn run_compiled(compiler: &Compiler, chunk: &Chunk) -> Result<Value, NebulaError> {
+    let global_names = compiler.global_names();
+    let functions = compiler.functions();
+
+    let mut vm = vm();
+    let result = vm.run_with_functions(chunk, global_names, functions)?;
+
+    Ok(nebula::vm::to_value(result))
+}
+
+fn report_warnings(sources: &SourceMap, warnings: &[Diagnostic]) -> Result<(), NebulaError> {
+    let allow_deprecated = ALLOW_DEPRECATED.load(Ordering::Relaxed);
+    let warnings: Vec<_> = warnings
+        .iter()
+        .filter(|w| !(allow_deprecated && w.code == Some(ErrorCode::W001)))
+        .collect();
+    for warning in &warnings {
+        report_diagnostic(sources, warning);
+    }
+    if warnings.is_empty() || !DENY_WARNINGS.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    Err(NebulaError::Runtime {
+        message: format!("{} warning(s) denied by --deny-warnings", warnings.len()),
+        span: None,
+    })
+}
+
+fn report_error(sources: &SourceMap, error: &NebulaError) {
+    report_diagnostic(sources, &Diagnostic::from(error));
+}
+
+fn report_diagnostic(sources: &SourceMap, diagnostic: &Diagnostic) {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        let span = diagnostic.span;
+        let width = |span: Span| {
+            sources
+                .file(span)
+                .and_then(|file| file.slice(span))
+                .map_or(span.length, |text| text.chars().count())
+        };
+        let name = span
+            .and_then(|span| sources.file(span))
+            .or(sources.get(FileId::default()))
+            .map_or("", |file| file.name());
+        let labels: Vec<_> = diagnostic
+            .labels
+            .iter()
+            .map(|label| {
+                serde_json::json!({
+                    "primary": label.primary,
+                    "message": label.message,
+                    "line": label.span.line,
+                    "column": label.span.column,
+                    "length": width(label.span),
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "severity": diagnostic.severity.as_str(),
+            "code": diagnostic.code.map(|code| code.as_str()),
+            "message": diagnostic.message,
+            "file": name,
+            "line": span.map(|s| s.line),
+            "column": span.map(|s| s.column),
+            "length": span.map(width),
+            "labels": labels,
+            "notes": diagnostic.notes,
+            "help": diagnostic.help,
+            "causes": diagnostic.causes,
+        });
+        eprintln!("{}", json);
+        return;
+    }
+
+    if diagnostic.severity == Severity::Error {
+        eprintln!("{}", "[COSMIC FRACTURE]".bold().red());
+    }
+    eprintln!("{}", Renderer::colored().render_in(diagnostic, sources));
 }
@@ -1,75 +1,127 @@
 use crate::lexer::Span;
-#[derive(Debug, Clone)]
+use serde::Serialize;
+use std::sync::Arc;
+#[derive(Debug, Clone, Serialize)]
 pub struct Program {
     pub items: Vec<Item>,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Item {
     Function(Function),
     Struct(Struct),
     Enum(Enum),
+    Trait(Trait),
+    Impl(Impl),
     TypeAlias(TypeAlias),
     Module(Module),
     Use(Use),
-    Statement(Stmt),
+    Statement(Box<Stmt>),
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Function {
     pub name: String,
-    pub params: Vec<Param>,
+    pub params: Arc<Vec<Param>>,
     pub return_type: Option<Type>,
-    pub body: FunctionBody,
+    pub body: Arc<FunctionBody>,
     pub is_async: bool,
+    pub doc: Option<String>,
     pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum FunctionBody {
     Expression(Expr),
     Block(Vec<Stmt>),
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Param {
     pub name: String,
     pub ty: Option<Type>,
     pub default: Option<Expr>,
     pub variadic: bool,
+    pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Struct {
     pub name: String,
     pub fields: Vec<Field>,
+    pub doc: Option<String>,
     pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Field {
     pub name: String,
     pub ty: Type,
+    pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Enum {
     pub name: String,
     pub variants: Vec<String>,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct Trait {
+    pub name: String,
+    pub methods: Vec<MethodSig>,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodSig {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Option<Type>,
+    pub doc: Option<String>,
     pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+pub struct Impl {
+    pub trait_name: Option<String>,
+    pub target: String,
+    pub methods: Vec<Function>,
+    pub span: Span,
+}
+#[derive(Debug, Clone, Serialize)]
 pub struct TypeAlias {
     pub name: String,
     pub ty: Type,
+    pub doc: Option<String>,
     pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Module {
     pub name: String,
     pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Use {
     pub path: String,
     pub alias: Option<String>,
+    pub items: Vec<String>,
+    pub native: bool,
+    pub span: Span,
+}
+impl Use {
+    pub fn binding(&self) -> &str {
+        match &self.alias {
+            Some(alias) => alias,
+            None => self.path.rsplit('.').next().unwrap_or(&self.path),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct Stmt {
+    pub kind: StmtKind,
     pub span: Span,
 }
-#[derive(Debug, Clone)]
-pub enum Stmt {
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+pub enum StmtKind {
     Var {
         name: String,
         ty: Option<Type>,
@@ -126,26 +178,38 @@ pub enum Stmt {
     Continue,
     Expression(Expr),
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub enum CompoundOp {
     Add,
     Sub,
     Mul,
     Div,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MatchArm {
     pub pattern: Pattern,
     pub body: Expr,
+    pub span: Span,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Pattern {
     Wildcard,
     Binding(String),
     Literal(Literal),
+    Nil,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
 }
-#[derive(Debug, Clone)]
-pub enum Expr {
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+pub enum ExprKind {
     Literal(Literal),
     Variable(String),
     Binary {
@@ -185,12 +249,13 @@ pub enum Expr {
         else_expr: Box<Expr>,
     },
     Lambda {
-        params: Vec<String>,
-        body: Box<Expr>,
+        params: Arc<Vec<String>>,
+        body: Arc<Expr>,
     },
     List(Vec<Expr>),
     Map(Vec<(Expr, Expr)>),
     Tuple(Vec<Expr>),
+    Interpolation(Vec<Expr>),
     Range {
         start: Box<Expr>,
         end: Box<Expr>,
@@ -207,7 +272,10 @@ pub enum Expr {
     },
     Await(Box<Expr>),
     Spawn(Box<Expr>),
-    Error(Box<Expr>),
+    Error {
+        value: Box<Expr>,
+        cause: Option<Box<Expr>>,
+    },
     Assert {
         condition: Box<Expr>,
         message: Option<Box<Expr>>,
@@ -226,14 +294,14 @@ pub enum Expr {
     Block(Vec<Stmt>),
     Nil,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Literal {
     Integer(i64),
     Float(f64),
     String(String),
     Bool(bool),
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum BinaryOp {
     Add,
     Sub,
@@ -254,6 +322,7 @@ pub enum BinaryOp {
     BitXor,
     Shl,
     Shr,
+    Coalesce,
 }
 impl BinaryOp {
     pub fn as_str(&self) -> &'static str {
@@ -277,16 +346,17 @@ impl BinaryOp {
             BinaryOp::BitXor => "^|",
             BinaryOp::Shl => "<<",
             BinaryOp::Shr => ">>",
+            BinaryOp::Coalesce => "??",
         }
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum UnaryOp {
     Neg,
     Not,
     BitNot,
 }
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Type {
     Nb,
     Int,
@@ -304,6 +374,7 @@ pub enum Type {
     Set(Option<Box<Type>>),
     Optional(Box<Type>),
     Named(String),
+    Union(Vec<Type>),
 }
 impl Type {
     pub fn is_numeric(&self) -> bool {
@@ -0,0 +1,72 @@
+use crate::error::{Diagnostic, ErrorCode};
+use crate::lexer::{Span, Token, TokenKind};
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deprecated {
+    Syntax(TokenKind),
+    Builtin,
+}
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    pub what: Deprecated,
+    pub name: &'static str,
+    pub replacement: &'static str,
+}
+pub const DEPRECATIONS: &[Deprecation] = &[
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::Elsif),
+        name: "elif",
+        replacement: "elsif",
+    },
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::Perm),
+        name: "perm",
+        replacement: "fb",
+    },
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::Function),
+        name: "function",
+        replacement: "fn",
+    },
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::On),
+        name: "on",
+        replacement: "yes",
+    },
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::Off),
+        name: "off",
+        replacement: "no",
+    },
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::Empty),
+        name: "empty",
+        replacement: "nil",
+    },
+    Deprecation {
+        what: Deprecated::Syntax(TokenKind::Arrow),
+        name: "->",
+        replacement: "give",
+    },
+];
+impl Deprecation {
+    pub fn for_token(token: &Token) -> Option<&'static Deprecation> {
+        DEPRECATIONS.iter().find(|d| {
+            d.name == token.lexeme
+                && matches!(&d.what, Deprecated::Syntax(kind) if *kind == token.kind)
+        })
+    }
+    pub fn for_builtin(name: &str) -> Option<&'static Deprecation> {
+        DEPRECATIONS
+            .iter()
+            .find(|d| d.what == Deprecated::Builtin && d.name == name)
+    }
+    pub fn warning(&self, span: Span) -> Diagnostic {
+        let what = match self.what {
+            Deprecated::Syntax(_) => "syntax",
+            Deprecated::Builtin => "builtin",
+        };
+        Diagnostic::warning(format!("{} '{}' is deprecated", what, self.name), span)
+            .with_code(ErrorCode::W001)
+            .with_help(format!("use '{}' instead", self.replacement))
+    }
+}
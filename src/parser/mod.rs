@@ -1,17 +1,66 @@
 pub mod ast;
+mod deprecated;
 mod expr;
+pub mod pretty;
 mod stmt;
 mod types;
-use crate::error::{NebulaError, NebulaResult};
-use crate::lexer::{Token, TokenKind};
+pub mod visit;
+use crate::error::{Diagnostic, ErrorCode, Label, NebulaError, NebulaResult, Severity};
+use crate::lexer::{Span, StringPart, Token, TokenKind};
 pub use ast::*;
+pub use deprecated::{Deprecated, Deprecation, DEPRECATIONS};
+pub use pretty::pretty;
+use std::collections::HashMap;
+use std::sync::Arc;
+pub use visit::{Visitor, VisitorMut};
+const MAX_NESTING: usize = 100;
+const MAX_CHAIN: usize = 1000;
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    depth: usize,
+    in_header: bool,
+    warnings: Vec<Diagnostic>,
+    docs: HashMap<usize, String>,
 }
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        let mut docs = HashMap::new();
+        let mut pending: Vec<String> = Vec::new();
+        let mut kept = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            if let TokenKind::DocComment(text) = &token.kind {
+                pending.push(text.clone());
+                continue;
+            }
+            if token.kind != TokenKind::Newline && !pending.is_empty() {
+                docs.insert(kept.len(), std::mem::take(&mut pending).join("\n"));
+            }
+            kept.push(token);
+        }
+        let mut tokens = kept;
+        if tokens.last().map(|t| &t.kind) != Some(&TokenKind::Eof) {
+            let span = tokens.last().map_or(Span::new(0, 0, 1, 1), |last| {
+                let span = last.span;
+                let width = last.lexeme.chars().count();
+                Span::new(span.end(), 0, span.line, span.column + width).with_file(span.file)
+            });
+            tokens.push(Token::new(TokenKind::Eof, span, ""));
+        }
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            in_header: false,
+            warnings: Vec::new(),
+            docs,
+        }
+    }
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
     }
     pub fn parse_program(&mut self) -> NebulaResult<Program> {
         let mut items = Vec::new();
@@ -28,47 +77,70 @@ impl Parser {
             TokenKind::Function | TokenKind::Async => self.parse_function().map(Item::Function),
             TokenKind::Struct => self.parse_struct().map(Item::Struct),
             TokenKind::Enum => self.parse_enum().map(Item::Enum),
+            TokenKind::Trait => self.parse_trait().map(Item::Trait),
+            TokenKind::Impl => self.parse_impl().map(Item::Impl),
             TokenKind::Type => self.parse_type_alias().map(Item::TypeAlias),
             TokenKind::Mod => self.parse_module().map(Item::Module),
             TokenKind::Use => self.parse_use().map(Item::Use),
             _ => {
                 let stmt = self.parse_statement()?;
-                Ok(Item::Statement(stmt))
+                Ok(Item::Statement(Box::new(stmt)))
             }
         }
     }
+    fn take_doc(&mut self) -> Option<String> {
+        self.docs.remove(&self.current)
+    }
     fn parse_function(&mut self) -> NebulaResult<Function> {
+        let doc = self.take_doc();
         let is_async = self.match_token(&TokenKind::Async);
         let start_span = self.expect(TokenKind::Function)?.span;
         let name = self.expect_identifier()?;
         self.expect(TokenKind::LeftParen)?;
         let params = self.parse_params()?;
         self.expect(TokenKind::RightParen)?;
-        let return_type = None;
+        let return_type = self.parse_return_type()?;
         let body = if self.match_token(&TokenKind::Assign) {
             FunctionBody::Expression(self.parse_expression()?)
         } else {
-            self.expect(TokenKind::Do)?;
+            let opener = self.expect(TokenKind::Do)?.span;
             let stmts = self.parse_block_until_end()?;
-            self.expect(TokenKind::End)?;
+            self.expect_end(opener)?;
             FunctionBody::Block(stmts)
         };
         Ok(Function {
             name,
-            params,
+            params: Arc::new(params),
             return_type,
-            body,
+            body: Arc::new(body),
             is_async,
+            doc,
             span: start_span,
         })
     }
+    fn parse_return_type(&mut self) -> NebulaResult<Option<Type>> {
+        if self.check(&TokenKind::Colon) || self.check(&TokenKind::Arrow) {
+            self.current += 1;
+            Ok(Some(self.parse_type()?))
+        } else {
+            Ok(None)
+        }
+    }
     fn parse_params(&mut self) -> NebulaResult<Vec<Param>> {
         let mut params = Vec::new();
         if !self.check(&TokenKind::RightParen) {
             loop {
+                let start = self.peek().span;
                 let variadic =
                     self.match_token(&TokenKind::DotDot) && self.match_token(&TokenKind::Dot);
+                let name_span = self.peek().span;
                 let name = self.expect_identifier()?;
+                if params.iter().any(|p: &Param| p.name == name) {
+                    self.warnings.push(Diagnostic::warning(
+                        format!("duplicate parameter '{}' shadows an earlier one", name),
+                        name_span,
+                    ));
+                }
                 let ty = if self.match_token(&TokenKind::Colon) {
                     Some(self.parse_type()?)
                 } else {
@@ -84,6 +156,7 @@ impl Parser {
                     ty,
                     default,
                     variadic,
+                    span: self.span_from(start),
                 });
                 if !self.match_token(&TokenKind::Comma) {
                     break;
@@ -93,6 +166,7 @@ impl Parser {
         Ok(params)
     }
     fn parse_struct(&mut self) -> NebulaResult<Struct> {
+        let doc = self.take_doc();
         let start_span = self.expect(TokenKind::Struct)?.span;
         let name = self.expect_identifier()?;
         self.expect(TokenKind::LeftBrace)?;
@@ -102,12 +176,14 @@ impl Parser {
             if self.check(&TokenKind::RightBrace) {
                 break;
             }
+            let start = self.peek().span;
             let field_name = self.expect_identifier()?;
             self.expect(TokenKind::Colon)?;
             let field_type = self.parse_type()?;
             fields.push(Field {
                 name: field_name,
                 ty: field_type,
+                span: self.span_from(start),
             });
             self.match_token(&TokenKind::Comma);
             self.skip_newlines();
@@ -116,10 +192,12 @@ impl Parser {
         Ok(Struct {
             name,
             fields,
+            doc,
             span: start_span,
         })
     }
     fn parse_enum(&mut self) -> NebulaResult<Enum> {
+        let doc = self.take_doc();
         let start_span = self.expect(TokenKind::Enum)?.span;
         let name = self.expect_identifier()?;
         self.expect(TokenKind::LeftBrace)?;
@@ -137,10 +215,75 @@ impl Parser {
         Ok(Enum {
             name,
             variants,
+            doc,
+            span: start_span,
+        })
+    }
+    fn parse_trait(&mut self) -> NebulaResult<Trait> {
+        let doc = self.take_doc();
+        let start_span = self.expect(TokenKind::Trait)?.span;
+        let name = self.expect_identifier()?;
+        self.expect(TokenKind::LeftBrace)?;
+        let mut methods = Vec::new();
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            self.skip_newlines();
+            if self.check(&TokenKind::RightBrace) {
+                break;
+            }
+            let method_doc = self.take_doc();
+            let span = self.expect(TokenKind::Function)?.span;
+            let method = self.expect_identifier()?;
+            self.expect(TokenKind::LeftParen)?;
+            let params = self.parse_params()?;
+            self.expect(TokenKind::RightParen)?;
+            methods.push(MethodSig {
+                name: method,
+                params,
+                return_type: self.parse_return_type()?,
+                doc: method_doc,
+                span,
+            });
+            self.match_token(&TokenKind::Comma);
+            self.skip_newlines();
+        }
+        self.expect(TokenKind::RightBrace)?;
+        Ok(Trait {
+            name,
+            methods,
+            doc,
+            span: start_span,
+        })
+    }
+    fn parse_impl(&mut self) -> NebulaResult<Impl> {
+        let start_span = self.expect(TokenKind::Impl)?.span;
+        let name = self.expect_identifier()?;
+        let (trait_name, target) = if self.match_token(&TokenKind::For) {
+            (Some(name), self.expect_identifier()?)
+        } else {
+            (None, name)
+        };
+        let close = if self.match_token(&TokenKind::Do) {
+            TokenKind::End
+        } else {
+            self.expect(TokenKind::LeftBrace)?;
+            TokenKind::RightBrace
+        };
+        let mut methods = Vec::new();
+        self.skip_newlines();
+        while !self.check(&close) && !self.is_at_end() {
+            methods.push(self.parse_function()?);
+            self.skip_newlines();
+        }
+        self.expect(close)?;
+        Ok(Impl {
+            trait_name,
+            target,
+            methods,
             span: start_span,
         })
     }
     fn parse_type_alias(&mut self) -> NebulaResult<TypeAlias> {
+        let doc = self.take_doc();
         let start_span = self.expect(TokenKind::Type)?.span;
         let name = self.expect_identifier()?;
         self.expect(TokenKind::Assign)?;
@@ -148,6 +291,7 @@ impl Parser {
         Ok(TypeAlias {
             name,
             ty,
+            doc,
             span: start_span,
         })
     }
@@ -161,8 +305,35 @@ impl Parser {
     }
     fn parse_use(&mut self) -> NebulaResult<Use> {
         let start_span = self.expect(TokenKind::Use)?.span;
-        let path = self.expect_identifier()?;
-        let alias = if self.match_token(&TokenKind::As) {
+        let native = matches!(&self.peek().kind, TokenKind::Identifier(word) if word == "native")
+            && self.check_next(&TokenKind::String(String::new()));
+        if native {
+            self.advance();
+        }
+        let mut path = match &self.peek().kind {
+            TokenKind::String(path) if native => {
+                let path = path.clone();
+                self.advance();
+                path
+            }
+            _ => self.expect_identifier()?,
+        };
+        let mut items = Vec::new();
+        while !native && self.match_token(&TokenKind::Dot) {
+            if self.match_token(&TokenKind::LeftBrace) {
+                items = self.parse_bracketed(TokenKind::RightBrace, Self::expect_identifier)?;
+                if items.is_empty() {
+                    return Err(NebulaError::Parse {
+                        message: format!("Expected at least one name to import from '{}'", path),
+                        span: self.previous().span,
+                    });
+                }
+                break;
+            }
+            path.push('.');
+            path.push_str(&self.expect_identifier()?);
+        }
+        let alias = if !native && items.is_empty() && self.match_token(&TokenKind::As) {
             Some(self.expect_identifier()?)
         } else {
             None
@@ -170,11 +341,14 @@ impl Parser {
         Ok(Use {
             path,
             alias,
+            items,
+            native,
             span: start_span,
         })
     }
     fn parse_block_until_end(&mut self) -> NebulaResult<Vec<Stmt>> {
         let mut statements = Vec::new();
+        let mut unreachable_warned = false;
         self.skip_newlines();
         while !self.check(&TokenKind::End)
             && !self.check(&TokenKind::Elsif)
@@ -183,51 +357,67 @@ impl Parser {
             && !self.check(&TokenKind::Finally)
             && !self.is_at_end()
         {
-            statements.push(self.parse_statement()?);
+            let stmt = self.parse_statement()?;
+            if !unreachable_warned
+                && matches!(
+                    statements.last().map(|s: &Stmt| &s.kind),
+                    Some(StmtKind::Return(_) | StmtKind::Break | StmtKind::Continue)
+                )
+            {
+                self.warnings
+                    .push(Diagnostic::warning("unreachable statement", stmt.span));
+                unreachable_warned = true;
+            }
+            statements.push(stmt);
             self.skip_newlines();
         }
         Ok(statements)
     }
     pub fn parse_statement(&mut self) -> NebulaResult<Stmt> {
+        self.nested(Self::parse_statement_inner)
+    }
+    fn parse_statement_inner(&mut self) -> NebulaResult<Stmt> {
         self.skip_newlines();
-        match &self.peek().kind {
-            TokenKind::Perm => self.parse_const(),
-            TokenKind::Give => self.parse_return(),
-            TokenKind::If => self.parse_if(),
-            TokenKind::While => self.parse_while(),
-            TokenKind::For => self.parse_for(),
-            TokenKind::Each => self.parse_each(),
-            TokenKind::Match => self.parse_match(),
-            TokenKind::Try => self.parse_try(),
-            TokenKind::Arrow => self.parse_return(),
+        let start = self.peek().span;
+        let kind = match &self.peek().kind {
+            TokenKind::Perm => self.parse_const()?,
+            TokenKind::Give => self.parse_return()?,
+            TokenKind::If => self.parse_if()?,
+            TokenKind::While => self.parse_while()?,
+            TokenKind::For => self.parse_for()?,
+            TokenKind::Each => self.parse_each()?,
+            TokenKind::Match => self.parse_match()?,
+            TokenKind::Try => self.parse_try()?,
+            TokenKind::Arrow => self.parse_return()?,
             TokenKind::Break => {
                 self.advance();
-                Ok(Stmt::Break)
+                StmtKind::Break
             }
             TokenKind::Continue => {
                 self.advance();
-                Ok(Stmt::Continue)
+                StmtKind::Continue
             }
             _ => {
                 let expr = self.parse_expression()?;
                 if self.match_token(&TokenKind::Assign) {
                     let value = self.parse_expression()?;
-                    Ok(Stmt::Assignment {
+                    StmtKind::Assignment {
                         target: expr,
                         value,
-                    })
+                    }
                 } else if let Some(op) = self.match_compound_assign() {
                     let value = self.parse_expression()?;
-                    Ok(Stmt::CompoundAssignment {
+                    StmtKind::CompoundAssignment {
                         target: expr,
                         op,
                         value,
-                    })
+                    }
                 } else {
-                    Ok(Stmt::Expression(expr))
+                    StmtKind::Expression(expr)
                 }
             }
-        }
+        };
+        Ok(Stmt::new(kind, self.span_from(start)))
     }
     fn match_compound_assign(&mut self) -> Option<CompoundOp> {
         match &self.peek().kind {
@@ -250,7 +440,7 @@ impl Parser {
             _ => None,
         }
     }
-    fn parse_const(&mut self) -> NebulaResult<Stmt> {
+    fn parse_const(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::Perm)?;
         let name = self.expect_identifier()?;
         let ty = if self.match_token(&TokenKind::Colon) {
@@ -260,16 +450,16 @@ impl Parser {
         };
         self.expect(TokenKind::Assign)?;
         let value = self.parse_expression()?;
-        Ok(Stmt::Const { name, ty, value })
+        Ok(StmtKind::Const { name, ty, value })
     }
-    fn parse_if(&mut self) -> NebulaResult<Stmt> {
+    fn parse_if(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::If)?;
-        let condition = self.parse_expression()?;
-        self.expect(TokenKind::Do)?;
+        let condition = self.parse_header()?;
+        let opener = self.expect(TokenKind::Do)?.span;
         let then_block = self.parse_block_until_end()?;
         let mut elif_branches = Vec::new();
         while self.match_token(&TokenKind::Elsif) {
-            let elif_cond = self.parse_expression()?;
+            let elif_cond = self.parse_header()?;
             self.expect(TokenKind::Do)?;
             let elif_body = self.parse_block_until_end()?;
             elif_branches.push((elif_cond, elif_body));
@@ -279,38 +469,38 @@ impl Parser {
         } else {
             None
         };
-        self.expect(TokenKind::End)?;
-        Ok(Stmt::If {
+        self.expect_end(opener)?;
+        Ok(StmtKind::If {
             condition,
             then_block,
             elif_branches,
             else_block,
         })
     }
-    fn parse_while(&mut self) -> NebulaResult<Stmt> {
+    fn parse_while(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::While)?;
-        let condition = self.parse_expression()?;
-        self.expect(TokenKind::Do)?;
+        let condition = self.parse_header()?;
+        let opener = self.expect(TokenKind::Do)?.span;
         let body = self.parse_block_until_end()?;
-        self.expect(TokenKind::End)?;
-        Ok(Stmt::While { condition, body })
+        self.expect_end(opener)?;
+        Ok(StmtKind::While { condition, body })
     }
-    fn parse_for(&mut self) -> NebulaResult<Stmt> {
+    fn parse_for(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::For)?;
         let var = self.expect_identifier()?;
         self.expect(TokenKind::Assign)?;
-        let start = self.parse_expression()?;
+        let start = self.parse_header()?;
         self.expect(TokenKind::Comma)?;
-        let end = self.parse_expression()?;
+        let end = self.parse_header()?;
         let step = if self.match_token(&TokenKind::Comma) {
-            Some(self.parse_expression()?)
+            Some(self.parse_header()?)
         } else {
             None
         };
-        self.expect(TokenKind::Do)?;
+        let opener = self.expect(TokenKind::Do)?.span;
         let body = self.parse_block_until_end()?;
-        self.expect(TokenKind::End)?;
-        Ok(Stmt::For {
+        self.expect_end(opener)?;
+        Ok(StmtKind::For {
             var,
             start,
             end,
@@ -318,35 +508,40 @@ impl Parser {
             body,
         })
     }
-    fn parse_each(&mut self) -> NebulaResult<Stmt> {
+    fn parse_each(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::Each)?;
         let var = self.expect_identifier()?;
         self.expect(TokenKind::In)?;
-        let iterator = self.parse_expression()?;
-        self.expect(TokenKind::Do)?;
+        let iterator = self.parse_header()?;
+        let opener = self.expect(TokenKind::Do)?.span;
         let body = self.parse_block_until_end()?;
-        self.expect(TokenKind::End)?;
-        Ok(Stmt::Each {
+        self.expect_end(opener)?;
+        Ok(StmtKind::Each {
             var,
             iterator,
             body,
         })
     }
-    fn parse_match(&mut self) -> NebulaResult<Stmt> {
+    fn parse_match(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::Match)?;
-        let value = self.parse_expression()?;
-        self.expect(TokenKind::Do)?;
+        let value = self.parse_header()?;
+        let opener = self.expect(TokenKind::Do)?.span;
         self.skip_newlines();
         let mut arms = Vec::new();
         while !self.check(&TokenKind::End) && !self.is_at_end() {
+            let start = self.peek().span;
             let pattern = self.parse_pattern()?;
             self.expect(TokenKind::FatArrow)?;
             let body = self.parse_expression()?;
-            arms.push(MatchArm { pattern, body });
+            arms.push(MatchArm {
+                pattern,
+                body,
+                span: self.span_from(start),
+            });
             self.skip_newlines();
         }
-        self.expect(TokenKind::End)?;
-        Ok(Stmt::Match { value, arms })
+        self.expect_end(opener)?;
+        Ok(StmtKind::Match { value, arms })
     }
     fn parse_pattern(&mut self) -> NebulaResult<Pattern> {
         match &self.peek().kind {
@@ -382,15 +577,19 @@ impl Parser {
                 self.advance();
                 Ok(Pattern::Literal(Literal::Bool(false)))
             }
+            TokenKind::Empty => {
+                self.advance();
+                Ok(Pattern::Nil)
+            }
             _ => Err(NebulaError::Parse {
                 message: "Expected pattern".to_string(),
                 span: self.peek().span,
             }),
         }
     }
-    fn parse_try(&mut self) -> NebulaResult<Stmt> {
+    fn parse_try(&mut self) -> NebulaResult<StmtKind> {
         self.expect(TokenKind::Try)?;
-        self.expect(TokenKind::Do)?;
+        let opener = self.expect(TokenKind::Do)?.span;
         let try_block = self.parse_block_until_end()?;
         let (catch_var, catch_block) = if self.match_token(&TokenKind::Catch) {
             let var = self.expect_identifier()?;
@@ -406,15 +605,15 @@ impl Parser {
         } else {
             None
         };
-        self.expect(TokenKind::End)?;
-        Ok(Stmt::Try {
+        self.expect_end(opener)?;
+        Ok(StmtKind::Try {
             try_block,
             catch_var,
             catch_block,
             finally_block,
         })
     }
-    fn parse_return(&mut self) -> NebulaResult<Stmt> {
+    fn parse_return(&mut self) -> NebulaResult<StmtKind> {
         if self.check(&TokenKind::Arrow) {
             self.advance();
         } else {
@@ -426,265 +625,251 @@ impl Parser {
             } else {
                 Some(self.parse_expression()?)
             };
-        Ok(Stmt::Return(value))
+        Ok(StmtKind::Return(value))
     }
     pub fn parse_expression(&mut self) -> NebulaResult<Expr> {
-        self.parse_ternary()
+        self.nested(Self::parse_ternary)
+    }
+    fn nested<T>(&mut self, parse: fn(&mut Self) -> NebulaResult<T>) -> NebulaResult<T> {
+        self.deepen()?;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+    fn deepen(&mut self) -> NebulaResult<()> {
+        if self.depth >= MAX_NESTING {
+            return Err(NebulaError::coded_at(
+                ErrorCode::E050,
+                format!("expression too deeply nested (over {} levels)", MAX_NESTING),
+                self.peek().span,
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+    fn lengthen(&self, links: &mut usize) -> NebulaResult<()> {
+        *links += 1;
+        if *links > MAX_CHAIN {
+            return Err(NebulaError::coded_at(
+                ErrorCode::E050,
+                format!("expression chain too long (over {} operators)", MAX_CHAIN),
+                self.peek().span,
+            ));
+        }
+        Ok(())
     }
     fn parse_ternary(&mut self) -> NebulaResult<Expr> {
-        let expr = self.parse_or()?;
+        let expr = self.parse_coalesce()?;
         if self.match_token(&TokenKind::Question) {
             let then_expr = self.parse_expression()?;
             self.expect(TokenKind::Colon)?;
             let else_expr = self.parse_expression()?;
-            return Ok(Expr::Ternary {
-                condition: Box::new(expr),
-                then_expr: Box::new(then_expr),
-                else_expr: Box::new(else_expr),
-            });
+            let span = expr.span.to(else_expr.span);
+            return Ok(Expr::new(
+                ExprKind::Ternary {
+                    condition: Box::new(expr),
+                    then_expr: Box::new(then_expr),
+                    else_expr: Box::new(else_expr),
+                },
+                span,
+            ));
         }
         Ok(expr)
     }
+    fn parse_coalesce(&mut self) -> NebulaResult<Expr> {
+        self.chain(Self::parse_or, |p| {
+            p.match_token(&TokenKind::QuestionQuestion)
+                .then_some(BinaryOp::Coalesce)
+        })
+    }
     fn parse_or(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_and()?;
-        while self.check(&TokenKind::Pipe) && !self.check_next(&TokenKind::Pipe) {
-            self.advance();
-            let right = self.parse_and()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op: BinaryOp::Or,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_and, |p| {
+            if p.check(&TokenKind::Pipe) && !p.check_next(&TokenKind::Pipe) {
+                p.advance();
+                return Some(BinaryOp::Or);
+            }
+            None
+        })
     }
     fn parse_and(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_not()?;
-        while self.check(&TokenKind::Ampersand) {
-            self.advance();
-            let right = self.parse_not()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op: BinaryOp::And,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_not, |p| {
+            p.match_token(&TokenKind::Ampersand)
+                .then_some(BinaryOp::And)
+        })
     }
     fn parse_not(&mut self) -> NebulaResult<Expr> {
+        let start = self.peek().span;
         if self.match_token(&TokenKind::Bang) {
-            let operand = self.parse_not()?;
-            Ok(Expr::Unary {
-                op: UnaryOp::Not,
-                operand: Box::new(operand),
-            })
+            let operand = self.nested(Self::parse_not)?;
+            Ok(unary(UnaryOp::Not, operand, start))
         } else {
             self.parse_comparison()
         }
     }
     fn parse_comparison(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_bitor()?;
-        loop {
-            let op = match &self.peek().kind {
-                TokenKind::Equal => BinaryOp::Eq,
-                TokenKind::NotEqual => BinaryOp::Ne,
-                TokenKind::Less => BinaryOp::Lt,
-                TokenKind::Greater => BinaryOp::Gt,
-                TokenKind::LessEqual => BinaryOp::Le,
-                TokenKind::GreaterEqual => BinaryOp::Ge,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_bitor()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_bitor, |p| {
+            p.match_operator(&[
+                (TokenKind::Equal, BinaryOp::Eq),
+                (TokenKind::NotEqual, BinaryOp::Ne),
+                (TokenKind::Less, BinaryOp::Lt),
+                (TokenKind::Greater, BinaryOp::Gt),
+                (TokenKind::LessEqual, BinaryOp::Le),
+                (TokenKind::GreaterEqual, BinaryOp::Ge),
+            ])
+        })
     }
     fn parse_bitor(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_bitxor()?;
-        while self.match_token(&TokenKind::Pipe) {
-            let right = self.parse_bitxor()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op: BinaryOp::BitOr,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_bitxor, |p| {
+            p.match_token(&TokenKind::Pipe).then_some(BinaryOp::BitOr)
+        })
     }
     fn parse_bitxor(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_bitand()?;
-        while self.match_token(&TokenKind::CaretPipe) {
-            let right = self.parse_bitand()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op: BinaryOp::BitXor,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_bitand, |p| {
+            p.match_token(&TokenKind::CaretPipe)
+                .then_some(BinaryOp::BitXor)
+        })
     }
     fn parse_bitand(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_shift()?;
-        while self.match_token(&TokenKind::Ampersand) {
-            let right = self.parse_shift()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op: BinaryOp::BitAnd,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_shift, |p| {
+            p.match_token(&TokenKind::Ampersand)
+                .then_some(BinaryOp::BitAnd)
+        })
     }
     fn parse_shift(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_range()?;
-        loop {
-            let op = match &self.peek().kind {
-                TokenKind::ShiftLeft => BinaryOp::Shl,
-                TokenKind::ShiftRight => BinaryOp::Shr,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_range()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_range, |p| {
+            p.match_operator(&[
+                (TokenKind::ShiftLeft, BinaryOp::Shl),
+                (TokenKind::ShiftRight, BinaryOp::Shr),
+            ])
+        })
     }
     fn parse_range(&mut self) -> NebulaResult<Expr> {
         let left = self.parse_additive()?;
-        if self.match_token(&TokenKind::DotDot) {
-            let right = self.parse_additive()?;
-            return Ok(Expr::Range {
-                start: Box::new(left),
-                end: Box::new(right),
-                inclusive: true,
-            });
-        }
-        if self.match_token(&TokenKind::DotDotLess) {
-            let right = self.parse_additive()?;
-            return Ok(Expr::Range {
+        let inclusive = if self.match_token(&TokenKind::DotDot) {
+            true
+        } else if self.match_token(&TokenKind::DotDotLess) {
+            false
+        } else {
+            return Ok(left);
+        };
+        let right = self.parse_additive()?;
+        let span = left.span.to(right.span);
+        Ok(Expr::new(
+            ExprKind::Range {
                 start: Box::new(left),
                 end: Box::new(right),
-                inclusive: false,
-            });
-        }
-        Ok(left)
+                inclusive,
+            },
+            span,
+        ))
     }
     fn parse_additive(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_multiplicative()?;
-        loop {
-            let op = match &self.peek().kind {
-                TokenKind::Plus => BinaryOp::Add,
-                TokenKind::Minus => BinaryOp::Sub,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_multiplicative()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
-        }
-        Ok(left)
+        self.chain(Self::parse_multiplicative, |p| {
+            p.match_operator(&[
+                (TokenKind::Plus, BinaryOp::Add),
+                (TokenKind::Minus, BinaryOp::Sub),
+            ])
+        })
     }
     fn parse_multiplicative(&mut self) -> NebulaResult<Expr> {
-        let mut left = self.parse_power()?;
-        loop {
-            let op = match &self.peek().kind {
-                TokenKind::Star => BinaryOp::Mul,
-                TokenKind::Slash => BinaryOp::Div,
-                TokenKind::Percent => BinaryOp::Mod,
-                _ => break,
-            };
-            self.advance();
-            let right = self.parse_power()?;
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+        self.chain(Self::parse_power, |p| {
+            p.match_operator(&[
+                (TokenKind::Star, BinaryOp::Mul),
+                (TokenKind::Slash, BinaryOp::Div),
+                (TokenKind::Percent, BinaryOp::Mod),
+            ])
+        })
+    }
+    fn chain(
+        &mut self,
+        operand: fn(&mut Self) -> NebulaResult<Expr>,
+        operator: fn(&mut Self) -> Option<BinaryOp>,
+    ) -> NebulaResult<Expr> {
+        let mut links = 0;
+        let mut left = operand(self)?;
+        while let Some(op) = operator(self) {
+            self.lengthen(&mut links)?;
+            let right = operand(self)?;
+            left = binary(left, op, right);
         }
         Ok(left)
     }
+    fn match_operator(&mut self, operators: &[(TokenKind, BinaryOp)]) -> Option<BinaryOp> {
+        let kind = &self.peek().kind;
+        let op = operators.iter().find(|(token, _)| token == kind)?.1;
+        self.advance();
+        Some(op)
+    }
     fn parse_power(&mut self) -> NebulaResult<Expr> {
         let left = self.parse_unary()?;
         if self.match_token(&TokenKind::Caret) {
-            let right = self.parse_power()?;
-            return Ok(Expr::Binary {
-                left: Box::new(left),
-                op: BinaryOp::Pow,
-                right: Box::new(right),
-            });
+            let right = self.nested(Self::parse_power)?;
+            return Ok(binary(left, BinaryOp::Pow, right));
         }
         Ok(left)
     }
     fn parse_unary(&mut self) -> NebulaResult<Expr> {
-        match &self.peek().kind {
+        let start = self.peek().span;
+        let wrap: fn(Box<Expr>) -> ExprKind = match &self.peek().kind {
             TokenKind::Minus => {
                 self.advance();
-                let operand = self.parse_unary()?;
-                Ok(Expr::Unary {
-                    op: UnaryOp::Neg,
-                    operand: Box::new(operand),
-                })
+                let operand = self.nested(Self::parse_unary)?;
+                return Ok(unary(UnaryOp::Neg, operand, start));
             }
             TokenKind::Tilde => {
                 self.advance();
-                let operand = self.parse_unary()?;
-                Ok(Expr::Unary {
-                    op: UnaryOp::BitNot,
-                    operand: Box::new(operand),
-                })
-            }
-            TokenKind::Hash => {
-                self.advance();
-                let operand = self.parse_unary()?;
-                Ok(Expr::Length(Box::new(operand)))
-            }
-            TokenKind::Ampersand => {
-                self.advance();
-                let operand = self.parse_unary()?;
-                Ok(Expr::Borrow(Box::new(operand)))
-            }
-            TokenKind::LeftArrow => {
-                self.advance();
-                let operand = self.parse_unary()?;
-                Ok(Expr::Receive(Box::new(operand)))
-            }
-            TokenKind::Await => {
-                self.advance();
-                let operand = self.parse_unary()?;
-                Ok(Expr::Await(Box::new(operand)))
+                let operand = self.nested(Self::parse_unary)?;
+                return Ok(unary(UnaryOp::BitNot, operand, start));
             }
+            TokenKind::Hash => ExprKind::Length,
+            TokenKind::Ampersand => ExprKind::Borrow,
+            TokenKind::LeftArrow => ExprKind::Receive,
+            TokenKind::Await => ExprKind::Await,
             TokenKind::Spawn => {
                 self.advance();
                 let operand = self.parse_postfix()?;
-                Ok(Expr::Spawn(Box::new(operand)))
+                let span = start.to(operand.span);
+                return Ok(Expr::new(ExprKind::Spawn(Box::new(operand)), span));
             }
-            _ => self.parse_postfix(),
-        }
+            _ => return self.parse_postfix(),
+        };
+        self.advance();
+        let operand = self.nested(Self::parse_unary)?;
+        let span = start.to(operand.span);
+        Ok(Expr::new(wrap(Box::new(operand)), span))
     }
     fn parse_postfix(&mut self) -> NebulaResult<Expr> {
+        let depth = self.depth;
         let mut expr = self.parse_primary()?;
         loop {
+            if matches!(
+                self.peek().kind,
+                TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::Dot
+            ) || self.check(&TokenKind::Colon) && self.is_method_call()
+            {
+                self.deepen()?;
+            }
             match &self.peek().kind {
                 TokenKind::LeftParen => {
+                    if let ExprKind::Variable(name) = &expr.kind {
+                        if let Some(deprecation) = Deprecation::for_builtin(name) {
+                            self.warnings.push(deprecation.warning(expr.span));
+                        }
+                    }
                     self.advance();
-                    let args = self.parse_args()?;
+                    let mut args = self.parse_args()?;
                     self.expect(TokenKind::RightParen)?;
-                    expr = Expr::Call {
-                        callee: Box::new(expr),
-                        args,
-                    };
+                    if self.check_trailing_block() {
+                        args.push(self.parse_trailing_block()?);
+                    }
+                    let span = self.span_from(expr.span);
+                    expr = Expr::new(
+                        ExprKind::Call {
+                            callee: Box::new(expr),
+                            args,
+                        },
+                        span,
+                    );
                 }
                 TokenKind::LeftBracket => {
                     self.advance();
@@ -700,46 +885,99 @@ impl Parser {
                             Some(Box::new(self.parse_expression()?))
                         };
                         self.expect(TokenKind::RightBracket)?;
-                        expr = Expr::Slice {
-                            array: Box::new(expr),
-                            start,
-                            end,
-                        };
+                        let span = self.span_from(expr.span);
+                        expr = Expr::new(
+                            ExprKind::Slice {
+                                array: Box::new(expr),
+                                start,
+                                end,
+                            },
+                            span,
+                        );
                     } else {
                         self.expect(TokenKind::RightBracket)?;
                         if let Some(index) = start {
-                            expr = Expr::Index {
-                                array: Box::new(expr),
-                                index,
-                            };
+                            let span = self.span_from(expr.span);
+                            expr = Expr::new(
+                                ExprKind::Index {
+                                    array: Box::new(expr),
+                                    index,
+                                },
+                                span,
+                            );
                         }
                     }
                 }
                 TokenKind::Dot => {
                     self.advance();
                     let field = self.expect_identifier()?;
-                    expr = Expr::Field {
-                        object: Box::new(expr),
-                        field,
-                    };
+                    let span = self.span_from(expr.span);
+                    expr = Expr::new(
+                        ExprKind::Field {
+                            object: Box::new(expr),
+                            field,
+                        },
+                        span,
+                    );
                 }
-                TokenKind::Colon if self.is_next_identifier() => {
+                TokenKind::Colon if self.is_method_call() => {
                     self.advance();
                     let method = self.expect_identifier()?;
                     self.expect(TokenKind::LeftParen)?;
-                    let args = self.parse_args()?;
+                    let mut args = self.parse_args()?;
                     self.expect(TokenKind::RightParen)?;
-                    expr = Expr::MethodCall {
-                        receiver: Box::new(expr),
-                        method,
-                        args,
-                    };
+                    if self.check_trailing_block() {
+                        args.push(self.parse_trailing_block()?);
+                    }
+                    let span = self.span_from(expr.span);
+                    expr = Expr::new(
+                        ExprKind::MethodCall {
+                            receiver: Box::new(expr),
+                            method,
+                            args,
+                        },
+                        span,
+                    );
                 }
                 _ => break,
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
+    fn parse_header(&mut self) -> NebulaResult<Expr> {
+        let outer = std::mem::replace(&mut self.in_header, true);
+        let expr = self.parse_expression();
+        self.in_header = outer;
+        expr
+    }
+    fn check_trailing_block(&self) -> bool {
+        !self.in_header && self.check(&TokenKind::Do)
+    }
+    fn parse_trailing_block(&mut self) -> NebulaResult<Expr> {
+        let start = self.peek().span;
+        let opener = self.expect(TokenKind::Do)?.span;
+        let mut params = Vec::new();
+        if self.match_token(&TokenKind::Pipe) {
+            if !self.check(&TokenKind::Pipe) {
+                params.push(self.expect_identifier()?);
+                while self.match_token(&TokenKind::Comma) {
+                    params.push(self.expect_identifier()?);
+                }
+            }
+            self.expect(TokenKind::Pipe)?;
+        }
+        let body = self.parse_block_until_end()?;
+        self.expect_end(opener)?;
+        let span = self.span_from(start);
+        Ok(Expr::new(
+            ExprKind::Lambda {
+                params: Arc::new(params),
+                body: Arc::new(Expr::new(ExprKind::Block(body), span)),
+            },
+            span,
+        ))
+    }
     fn check_next(&self, kind: &TokenKind) -> bool {
         if self.current + 1 >= self.tokens.len() {
             false
@@ -748,15 +986,14 @@ impl Parser {
                 == std::mem::discriminant(kind)
         }
     }
-    fn is_next_identifier(&self) -> bool {
-        if self.current + 1 >= self.tokens.len() {
-            false
-        } else {
-            matches!(
-                &self.tokens[self.current + 1].kind,
-                TokenKind::Identifier(_)
-            )
-        }
+    fn is_method_call(&self) -> bool {
+        matches!(
+            self.tokens.get(self.current + 1).map(|t| &t.kind),
+            Some(TokenKind::Identifier(_))
+        ) && matches!(
+            self.tokens.get(self.current + 2).map(|t| &t.kind),
+            Some(TokenKind::LeftParen)
+        )
     }
     fn parse_args(&mut self) -> NebulaResult<Vec<Expr>> {
         let mut args = Vec::new();
@@ -770,31 +1007,72 @@ impl Parser {
         }
         Ok(args)
     }
+    fn parse_bracketed<T>(
+        &mut self,
+        close: TokenKind,
+        mut item: impl FnMut(&mut Self) -> NebulaResult<T>,
+    ) -> NebulaResult<Vec<T>> {
+        let mut items = Vec::new();
+        self.skip_newlines();
+        while !self.check(&close) && !self.is_at_end() {
+            items.push(item(self)?);
+            self.skip_newlines();
+            if !self.match_token(&TokenKind::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
     fn parse_primary(&mut self) -> NebulaResult<Expr> {
+        let start = self.peek().span;
+        if self.check(&TokenKind::LeftParen) {
+            return self.parse_group(start);
+        }
+        if self.check_next(&TokenKind::FatArrow) {
+            if let TokenKind::Identifier(name) = self.peek().kind.clone() {
+                self.advance();
+                self.advance();
+                return self.parse_lambda(vec![name], start);
+            }
+        }
+        let kind = self.parse_atom(start)?;
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
+    fn parse_atom(&mut self, start: Span) -> NebulaResult<ExprKind> {
         match self.peek().kind.clone() {
             TokenKind::Integer(n) => {
                 self.advance();
-                Ok(Expr::Literal(Literal::Integer(n)))
+                Ok(ExprKind::Literal(Literal::Integer(n)))
             }
             TokenKind::Float(n) => {
                 self.advance();
-                Ok(Expr::Literal(Literal::Float(n)))
+                Ok(ExprKind::Literal(Literal::Float(n)))
             }
             TokenKind::String(s) => {
                 self.advance();
-                Ok(Expr::Literal(Literal::String(s)))
+                Ok(ExprKind::Literal(Literal::String(s)))
+            }
+            TokenKind::Interpolated(parts) => {
+                self.advance();
+                self.parse_interpolation(parts, start)
             }
             TokenKind::On => {
                 self.advance();
-                Ok(Expr::Literal(Literal::Bool(true)))
+                Ok(ExprKind::Literal(Literal::Bool(true)))
             }
             TokenKind::Off => {
                 self.advance();
-                Ok(Expr::Literal(Literal::Bool(false)))
+                Ok(ExprKind::Literal(Literal::Bool(false)))
             }
             TokenKind::Empty => {
                 self.advance();
-                Ok(Expr::Nil)
+                Ok(ExprKind::Nil)
+            }
+            TokenKind::Any if self.check_next(&TokenKind::LeftParen) => {
+                self.advance();
+                Ok(ExprKind::Variable("any".to_string()))
             }
             TokenKind::Identifier(name) => {
                 self.advance();
@@ -808,16 +1086,31 @@ impl Parser {
                     self.advance();
                     let args = self.parse_args()?;
                     self.expect(TokenKind::RightParen)?;
-                    return Ok(Expr::StructInit { name, args });
+                    return Ok(ExprKind::StructInit { name, args });
                 }
-                Ok(Expr::Variable(name))
+                Ok(ExprKind::Variable(name))
             }
             TokenKind::Lst => {
                 self.advance();
                 self.expect(TokenKind::LeftParen)?;
                 let elements = self.parse_args()?;
                 self.expect(TokenKind::RightParen)?;
-                Ok(Expr::List(elements))
+                Ok(ExprKind::List(elements))
+            }
+            TokenKind::LeftBracket => {
+                self.advance();
+                let elements =
+                    self.parse_bracketed(TokenKind::RightBracket, Self::parse_expression)?;
+                Ok(ExprKind::List(elements))
+            }
+            TokenKind::LeftBrace => {
+                self.advance();
+                let pairs = self.parse_bracketed(TokenKind::RightBrace, |parser| {
+                    let key = parser.parse_expression()?;
+                    parser.expect(TokenKind::Colon)?;
+                    Ok((key, parser.parse_expression()?))
+                })?;
+                Ok(ExprKind::Map(pairs))
             }
             TokenKind::Map => {
                 self.advance();
@@ -826,6 +1119,20 @@ impl Parser {
                 if !self.check(&TokenKind::RightParen) {
                     loop {
                         let key = self.parse_expression()?;
+                        if pairs.is_empty() && !self.check(&TokenKind::Colon) {
+                            let mut args = vec![key];
+                            while self.match_token(&TokenKind::Comma) {
+                                args.push(self.parse_expression()?);
+                            }
+                            self.expect(TokenKind::RightParen)?;
+                            return Ok(ExprKind::Call {
+                                callee: Box::new(Expr::new(
+                                    ExprKind::Variable("map".to_string()),
+                                    start,
+                                )),
+                                args,
+                            });
+                        }
                         self.expect(TokenKind::Colon)?;
                         let value = self.parse_expression()?;
                         pairs.push((key, value));
@@ -835,21 +1142,35 @@ impl Parser {
                     }
                 }
                 self.expect(TokenKind::RightParen)?;
-                Ok(Expr::Map(pairs))
+                Ok(ExprKind::Map(pairs))
             }
             TokenKind::Tup => {
                 self.advance();
                 self.expect(TokenKind::LeftParen)?;
                 let elements = self.parse_args()?;
                 self.expect(TokenKind::RightParen)?;
-                Ok(Expr::Tuple(elements))
+                Ok(ExprKind::Tuple(elements))
             }
             TokenKind::Err => {
                 self.advance();
                 self.expect(TokenKind::LeftParen)?;
-                let msg = self.parse_expression()?;
+                let value = Box::new(self.parse_expression()?);
+                let cause = if self.match_token(&TokenKind::Comma) {
+                    let label_span = self.peek().span;
+                    if self.expect_identifier()? != "cause" {
+                        return Err(NebulaError::Parse {
+                            message: "Expected 'cause:' as the second argument of err()"
+                                .to_string(),
+                            span: label_span,
+                        });
+                    }
+                    self.expect(TokenKind::Colon)?;
+                    Some(Box::new(self.parse_expression()?))
+                } else {
+                    None
+                };
                 self.expect(TokenKind::RightParen)?;
-                Ok(Expr::Error(Box::new(msg)))
+                Ok(ExprKind::Error { value, cause })
             }
             TokenKind::Assert => {
                 self.advance();
@@ -861,7 +1182,7 @@ impl Parser {
                     None
                 };
                 self.expect(TokenKind::RightParen)?;
-                Ok(Expr::Assert {
+                Ok(ExprKind::Assert {
                     condition: Box::new(condition),
                     message,
                 })
@@ -871,65 +1192,100 @@ impl Parser {
                 self.expect(TokenKind::LeftParen)?;
                 let value = self.parse_expression()?;
                 self.expect(TokenKind::RightParen)?;
-                Ok(Expr::Cast {
+                Ok(ExprKind::Cast {
                     ty,
                     value: Box::new(value),
                 })
             }
-            TokenKind::LeftParen => {
-                self.advance();
-                let first = self.parse_expression()?;
-                if self.match_token(&TokenKind::Comma) {
-                    let mut elements = vec![first];
-                    loop {
-                        elements.push(self.parse_expression()?);
-                        if !self.match_token(&TokenKind::Comma) {
-                            break;
-                        }
-                    }
-                    self.expect(TokenKind::RightParen)?;
-                    if self.match_token(&TokenKind::FatArrow) {
-                        let params: Result<Vec<_>, _> = elements
-                            .iter()
-                            .map(|e| {
-                                if let Expr::Variable(name) = e {
-                                    Ok(name.clone())
-                                } else {
-                                    Err(NebulaError::Parse {
-                                        message: "Lambda parameters must be identifiers"
-                                            .to_string(),
-                                        span: self.peek().span,
-                                    })
-                                }
-                            })
-                            .collect();
-                        let body = self.parse_expression()?;
-                        return Ok(Expr::Lambda {
-                            params: params?,
-                            body: Box::new(body),
-                        });
-                    }
-                    return Ok(Expr::Tuple(elements));
-                }
-                self.expect(TokenKind::RightParen)?;
-                if self.match_token(&TokenKind::FatArrow) {
-                    if let Expr::Variable(name) = first {
-                        let body = self.parse_expression()?;
-                        return Ok(Expr::Lambda {
-                            params: vec![name],
-                            body: Box::new(body),
-                        });
-                    }
-                }
-                Ok(first)
-            }
             _ => Err(NebulaError::Parse {
                 message: format!("Unexpected token: {:?}", self.peek().kind),
                 span: self.peek().span,
             }),
         }
     }
+    fn parse_interpolation(
+        &mut self,
+        parts: Vec<StringPart>,
+        span: Span,
+    ) -> NebulaResult<ExprKind> {
+        let mut pieces = Vec::new();
+        for part in parts {
+            let tokens = match part {
+                StringPart::Text(value) => {
+                    let text = ExprKind::Literal(Literal::String(value));
+                    pieces.push(Expr::new(text, span));
+                    continue;
+                }
+                StringPart::Code(tokens) => tokens,
+            };
+            let mut inner = Parser::new(tokens);
+            inner.depth = self.depth;
+            inner.skip_newlines();
+            let expr = inner.parse_expression()?;
+            inner.skip_newlines();
+            if !inner.is_at_end() {
+                return Err(NebulaError::Parse {
+                    message: format!("Unexpected token in interpolation: {:?}", inner.peek().kind),
+                    span: inner.peek().span,
+                });
+            }
+            self.warnings.extend(inner.take_warnings());
+            pieces.push(expr);
+        }
+        Ok(ExprKind::Interpolation(pieces))
+    }
+    fn parse_group(&mut self, start: Span) -> NebulaResult<Expr> {
+        self.expect(TokenKind::LeftParen)?;
+        if self.match_token(&TokenKind::RightParen) {
+            self.expect(TokenKind::FatArrow)?;
+            return self.parse_lambda(Vec::new(), start);
+        }
+        let mut elements = vec![self.parse_expression()?];
+        let tuple = self.check(&TokenKind::Comma);
+        while self.match_token(&TokenKind::Comma) {
+            elements.push(self.parse_expression()?);
+        }
+        self.expect(TokenKind::RightParen)?;
+        if self.match_token(&TokenKind::FatArrow) {
+            let params = elements
+                .into_iter()
+                .map(|e| match e.kind {
+                    ExprKind::Variable(name) => Ok(name),
+                    _ => Err(NebulaError::Parse {
+                        message: "Lambda parameters must be identifiers".to_string(),
+                        span: e.span,
+                    }),
+                })
+                .collect::<NebulaResult<_>>()?;
+            return self.parse_lambda(params, start);
+        }
+        if tuple {
+            return Ok(Expr::new(ExprKind::Tuple(elements), self.span_from(start)));
+        }
+        Ok(elements.remove(0))
+    }
+    fn parse_lambda(&mut self, params: Vec<String>, start: Span) -> NebulaResult<Expr> {
+        let body = self.parse_expression()?;
+        Ok(Expr::new(
+            ExprKind::Lambda {
+                params: Arc::new(params),
+                body: Arc::new(body),
+            },
+            self.span_from(start),
+        ))
+    }
     pub fn parse_type(&mut self) -> NebulaResult<Type> {
+        let first = self.parse_single_type()?;
+        if !self.check(&TokenKind::Pipe) {
+            return Ok(first);
+        }
+        let mut members = vec![first];
+        while self.match_token(&TokenKind::Pipe) {
+            members.push(self.parse_single_type()?);
+        }
+        Ok(Type::Union(members))
+    }
+    fn parse_single_type(&mut self) -> NebulaResult<Type> {
         let base_type = match &self.peek().kind {
             TokenKind::Nb => {
                 self.advance();
@@ -983,6 +1339,10 @@ impl Parser {
                 self.advance();
                 Type::Set(None)
             }
+            TokenKind::Identifier(name) if name == "bool" => {
+                self.advance();
+                Type::Bool
+            }
             TokenKind::Identifier(name) => {
                 let name = name.clone();
                 self.advance();
@@ -1001,17 +1361,17 @@ impl Parser {
         Ok(base_type)
     }
     fn peek(&self) -> &Token {
-        self.tokens.get(self.current).unwrap_or_else(|| {
-            self.tokens
-                .last()
-                .expect("Token stream should not be empty")
-        })
+        let last = self.tokens.len() - 1;
+        &self.tokens[self.current.min(last)]
     }
     fn is_at_end(&self) -> bool {
         self.current >= self.tokens.len() || self.peek().kind == TokenKind::Eof
     }
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
+            if let Some(deprecation) = Deprecation::for_token(self.peek()) {
+                self.warnings.push(deprecation.warning(self.peek().span));
+            }
             self.current += 1;
         }
         self.previous()
@@ -1044,6 +1404,21 @@ impl Parser {
             })
         }
     }
+    fn expect_end(&mut self, opener: Span) -> NebulaResult<()> {
+        if !self.is_at_end() {
+            return self.expect(TokenKind::End).map(|_| ());
+        }
+        let eof = self.peek().span;
+        let diagnostic = Diagnostic::new(Severity::Error, ErrorCode::E003.message(), None)
+            .with_code(ErrorCode::E003)
+            .with_label(Label::primary(
+                eof,
+                "expected 'end' before the end of the file",
+            ))
+            .with_label(Label::secondary(opener, "block opened here"))
+            .with_help("add 'end' to close the block");
+        Err(NebulaError::Diagnostic(Box::new(diagnostic)))
+    }
     fn expect_identifier(&mut self) -> NebulaResult<String> {
         match &self.peek().kind {
             TokenKind::Identifier(name) => {
@@ -1057,9 +1432,216 @@ impl Parser {
             }),
         }
     }
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
+    }
     fn skip_newlines(&mut self) {
         while self.check(&TokenKind::Newline) {
             self.advance();
         }
     }
 }
+fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+    let span = left.span.to(right.span);
+    Expr::new(
+        ExprKind::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        },
+        span,
+    )
+}
+fn unary(op: UnaryOp, operand: Expr, start: Span) -> Expr {
+    let span = start.to(operand.span);
+    Expr::new(
+        ExprKind::Unary {
+            op,
+            operand: Box::new(operand),
+        },
+        span,
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    fn parse(source: &str) -> NebulaResult<Program> {
+        let tokens = Lexer::new(source).tokenize().map_err(|mut e| e.remove(0))?;
+        Parser::new(tokens).parse_program()
+    }
+    #[test]
+    fn test_degenerate_input_does_not_panic() {
+        assert!(Parser::new(Vec::new()).parse_program().is_ok());
+        assert!(parse("").unwrap().items.is_empty());
+        assert!(parse("\n\n").unwrap().items.is_empty());
+        let sample = "fn f(a, b) do\n  match a do\n    1 => give b[0]:len()\n  end\nend\n\
+            perm m = {\"k\": [1, 2.5, r\"x\"]}\nvar t = (1, -a ? b : ~c)\n\
+            each x in 1..10 do\n  if x % 2 == 0 do continue end\nend\n\
+            try do log(f(1, 2)) catch e do log(e) end\nwhile true do break end\n";
+        for end in (0..=sample.len()).filter(|i| sample.is_char_boundary(*i)) {
+            let _ = parse(&sample[..end]);
+            let _ = parse(&sample[end..]);
+        }
+        for source in [
+            "(", ")", "[", "{", "fn", "fn f(", "if", "perm", "x:", "x.", "-", "=>", "?",
+        ] {
+            assert!(parse(source).is_err(), "{:?} should not parse", source);
+        }
+    }
+    #[test]
+    fn test_function_return_types() {
+        let program =
+            parse("fn area(r: nb): nb do\n  give r\nend\nfn name() -> wrd? = empty").unwrap();
+        let returns: Vec<_> = program
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Function(f) => f.return_type.clone(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            returns,
+            [Some(Type::Nb), Some(Type::Optional(Box::new(Type::Wrd)))]
+        );
+        assert!(parse("fn f(): do\nend").is_err());
+        let tokens = Lexer::new("fn f() -> nb = 1").tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse_program().unwrap();
+        assert!(parser.warnings().is_empty());
+    }
+    #[test]
+    fn test_params_fields_and_match_arms_have_spans() {
+        let source = "struct P {\n  x: nb,\n  tag: wrd?\n}\n\
+            fn f(a: nb = 1, ...rest) do\n  match a do\n    1 => log(rest)\n    _ => 0\n  end\nend\n";
+        let program = parse(source).unwrap();
+        let text = |span: Span| &source[span.start..span.end()];
+        let Item::Struct(p) = &program.items[0] else {
+            panic!("expected a struct");
+        };
+        let fields: Vec<_> = p.fields.iter().map(|f| text(f.span)).collect();
+        assert_eq!(fields, ["x: nb", "tag: wrd?"]);
+        let Item::Function(f) = &program.items[1] else {
+            panic!("expected a function");
+        };
+        let params: Vec<_> = f.params.iter().map(|p| text(p.span)).collect();
+        assert_eq!(params, ["a: nb = 1", "...rest"]);
+        assert_eq!(f.params[0].span.line, 5);
+        let FunctionBody::Block(body) = &*f.body else {
+            panic!("expected a block body");
+        };
+        let StmtKind::Match { arms, .. } = &body[0].kind else {
+            panic!("expected a match");
+        };
+        let arms: Vec<_> = arms.iter().map(|arm| text(arm.span)).collect();
+        assert_eq!(arms, ["1 => log(rest)", "_ => 0"]);
+    }
+    #[test]
+    fn test_deprecated_syntax_warns_with_replacement() {
+        let source = "fn f(x) do\n  if x do\n    -> 1\n  elif x do\n    give 2\n  end\nend\n";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse_program().unwrap();
+        let warnings = parser.take_warnings();
+        let found: Vec<_> = warnings
+            .iter()
+            .map(|w| {
+                (
+                    w.message.as_str(),
+                    w.help.as_deref(),
+                    w.span.map(|s| s.line),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "syntax '->' is deprecated",
+                    Some("use 'give' instead"),
+                    Some(3)
+                ),
+                (
+                    "syntax 'elif' is deprecated",
+                    Some("use 'elsif' instead"),
+                    Some(4)
+                ),
+            ]
+        );
+        assert!(warnings.iter().all(|w| w.code == Some(ErrorCode::W001)));
+    }
+    #[test]
+    fn test_older_keywords_warn_in_favour_of_documented_ones() {
+        let older = "perm x = on\nfunction f() = empty\nif off do\n  x = empty\nend\n";
+        let current = "fb x = yes\nfn f() = nil\nif no do\n  x = nil\nend\n";
+        assert_eq!(
+            pretty(&parse(older).unwrap()),
+            pretty(&parse(current).unwrap())
+        );
+        let mut parser = Parser::new(Lexer::new(current).tokenize().unwrap());
+        parser.parse_program().unwrap();
+        assert!(parser.take_warnings().is_empty());
+        let mut parser = Parser::new(Lexer::new("cn y: nil = nil\n").tokenize().unwrap());
+        parser.parse_program().unwrap();
+        assert!(parser.take_warnings().is_empty());
+        let tokens = Lexer::new(older).tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse_program().unwrap();
+        let helps: Vec<_> = parser
+            .take_warnings()
+            .into_iter()
+            .filter_map(|w| w.help)
+            .collect();
+        assert_eq!(
+            helps,
+            [
+                "use 'fb' instead",
+                "use 'yes' instead",
+                "use 'fn' instead",
+                "use 'nil' instead",
+                "use 'no' instead",
+                "use 'nil' instead",
+            ]
+        );
+    }
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(deep_nesting)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+    fn deep_nesting() {
+        let depth = 10_000;
+        let source = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::E050));
+        for prefix in ["-", "!", "~"] {
+            let source = format!("log({}1)", prefix.repeat(depth));
+            assert_eq!(parse(&source).unwrap_err().code(), Some(ErrorCode::E050));
+        }
+        let power = format!("log(2{})", "^2".repeat(depth));
+        assert_eq!(parse(&power).unwrap_err().code(), Some(ErrorCode::E050));
+        let blocks = "if true do\n".repeat(depth);
+        assert_eq!(parse(&blocks).unwrap_err().code(), Some(ErrorCode::E050));
+        for chain in [" + 1", " * 1", " == 1", " ?? 1"] {
+            assert!(parse(&format!("log(a{})", chain.repeat(120))).is_ok());
+            let long = format!("log(a{})", chain.repeat(depth));
+            assert_eq!(parse(&long).unwrap_err().code(), Some(ErrorCode::E050));
+            let nested = format!("log({}a{}{})", "(".repeat(120), chain, ")".repeat(120));
+            assert_eq!(parse(&nested).unwrap_err().code(), Some(ErrorCode::E050));
+        }
+        for postfix in [".b", "[0]", "()", ":m()"] {
+            let source = format!("log(a{})", postfix.repeat(depth));
+            assert_eq!(parse(&source).unwrap_err().code(), Some(ErrorCode::E050));
+            assert!(parse(&format!("log(a{})", postfix.repeat(50))).is_ok());
+        }
+        let lists = format!("log({}{})", "[".repeat(depth), "]".repeat(depth));
+        assert_eq!(parse(&lists).unwrap_err().code(), Some(ErrorCode::E050));
+        let shallow = format!("{}1{}", "(".repeat(50), ")".repeat(50));
+        assert!(parse(&shallow).is_ok());
+    }
+}
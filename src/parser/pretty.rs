@@ -0,0 +1,721 @@
+use super::ast::*;
+use super::visit::Visitor;
+const INDENT: &str = "    ";
+const LAMBDA: u8 = 0;
+const TERNARY: u8 = 1;
+const NOT: u8 = 5;
+const RANGE: u8 = 11;
+const ADDITIVE: u8 = 12;
+const POWER: u8 = 14;
+const UNARY: u8 = 15;
+const POSTFIX: u8 = 16;
+pub fn pretty(program: &Program) -> String {
+    let mut printer = Printer::default();
+    printer.visit_program(program);
+    printer.out
+}
+pub fn pretty_expr(expr: &Expr) -> String {
+    let mut printer = Printer::default();
+    printer.expr(expr, LAMBDA);
+    printer.out
+}
+pub fn pretty_type(ty: &Type) -> String {
+    match ty {
+        Type::Nb => "nb".to_string(),
+        Type::Int => "int".to_string(),
+        Type::Fl => "fl".to_string(),
+        Type::Wrd => "wrd".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::By => "by".to_string(),
+        Type::Chr => "chr".to_string(),
+        Type::Any => "any".to_string(),
+        Type::Void => "void".to_string(),
+        Type::Nil => "nil".to_string(),
+        Type::Lst(None) => "lst".to_string(),
+        Type::Lst(Some(item)) => format!("lst[{}]", pretty_type(item)),
+        Type::Map(None, None) => "map".to_string(),
+        Type::Map(key, value) => {
+            let part =
+                |ty: &Option<Box<Type>>| ty.as_deref().map_or("any".to_string(), pretty_type);
+            format!("map[{}, {}]", part(key), part(value))
+        }
+        Type::Tup(types) if types.is_empty() => "tup".to_string(),
+        Type::Tup(types) => format!("tup({})", list(types.iter().map(pretty_type))),
+        Type::Set(None) => "set".to_string(),
+        Type::Set(Some(item)) => format!("set[{}]", pretty_type(item)),
+        Type::Optional(inner) if matches!(**inner, Type::Union(_)) => {
+            format!("({})?", pretty_type(inner))
+        }
+        Type::Optional(inner) => format!("{}?", pretty_type(inner)),
+        Type::Named(name) => name.clone(),
+        Type::Union(members) => members
+            .iter()
+            .map(pretty_type)
+            .collect::<Vec<_>>()
+            .join(" | "),
+    }
+}
+#[derive(Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+impl Printer {
+    fn line(&mut self, text: &str) {
+        self.start();
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+    fn start(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+    fn doc(&mut self, doc: &Option<String>) {
+        for line in doc.iter().flat_map(|doc| doc.lines()) {
+            if line.is_empty() {
+                self.line("##");
+            } else {
+                self.line(&format!("## {}", line));
+            }
+        }
+    }
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.indent += 1;
+        for stmt in stmts {
+            self.visit_stmt(stmt);
+        }
+        self.indent -= 1;
+    }
+    fn header(&mut self, keyword: &str, expr: &Expr) {
+        self.out.push_str(keyword);
+        self.expr(expr, TERNARY);
+        self.out.push_str(" do\n");
+    }
+    fn expr(&mut self, expr: &Expr, min: u8) {
+        let wrap = precedence(&expr.kind) < min;
+        if wrap {
+            self.out.push('(');
+        }
+        self.expr_kind(&expr.kind);
+        if wrap {
+            self.out.push(')');
+        }
+    }
+    fn expr_kind(&mut self, kind: &ExprKind) {
+        match kind {
+            ExprKind::Literal(lit) => self.out.push_str(&literal(lit)),
+            ExprKind::Variable(name) => self.out.push_str(name),
+            ExprKind::Nil => self.out.push_str("nil"),
+            ExprKind::Binary { left, op, right } => {
+                let level = binary_precedence(*op);
+                let (left_min, right_min) = match op {
+                    BinaryOp::Pow => (UNARY, POWER),
+                    _ => (level, level + 1),
+                };
+                self.expr(left, left_min);
+                self.out.push_str(&format!(" {} ", op.as_str()));
+                self.expr(right, right_min);
+            }
+            ExprKind::Unary { op, operand } => {
+                let (symbol, min) = match op {
+                    UnaryOp::Neg => ("-", UNARY),
+                    UnaryOp::BitNot => ("~", UNARY),
+                    UnaryOp::Not => ("!", NOT),
+                };
+                self.out.push_str(symbol);
+                self.expr(operand, min);
+            }
+            ExprKind::Length(operand) => self.prefix("#", operand),
+            ExprKind::Borrow(operand) => self.prefix("&", operand),
+            ExprKind::Receive(operand) => self.prefix("<-", operand),
+            ExprKind::Await(operand) => self.prefix("await ", operand),
+            ExprKind::Spawn(operand) => {
+                self.out.push_str("spawn ");
+                self.expr(operand, POSTFIX);
+            }
+            ExprKind::Call { callee, args } => {
+                self.expr(callee, POSTFIX);
+                self.call_args(args);
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                self.expr(receiver, POSTFIX);
+                self.out.push_str(&format!(":{}", method));
+                self.call_args(args);
+            }
+            ExprKind::Field { object, field } => {
+                self.expr(object, POSTFIX);
+                self.out.push_str(&format!(".{}", field));
+            }
+            ExprKind::Index { array, index } => {
+                self.expr(array, POSTFIX);
+                self.out.push('[');
+                self.expr(index, LAMBDA);
+                self.out.push(']');
+            }
+            ExprKind::Slice { array, start, end } => {
+                self.expr(array, POSTFIX);
+                self.out.push('[');
+                if let Some(start) = start {
+                    self.expr(start, LAMBDA);
+                }
+                self.out.push(':');
+                if let Some(end) = end {
+                    self.expr(end, LAMBDA);
+                }
+                self.out.push(']');
+            }
+            ExprKind::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expr(condition, TERNARY + 1);
+                self.out.push_str(" ? ");
+                self.expr(then_expr, TERNARY);
+                self.out.push_str(" : ");
+                self.expr(else_expr, TERNARY);
+            }
+            ExprKind::Lambda { params, body } => match &body.kind {
+                ExprKind::Block(stmts) => self.trailing_block(params, stmts),
+                _ => {
+                    if let [param] = params.as_slice() {
+                        self.out.push_str(param);
+                    } else {
+                        self.out.push_str(&format!("({})", params.join(", ")));
+                    }
+                    self.out.push_str(" => ");
+                    self.expr(body, LAMBDA);
+                }
+            },
+            ExprKind::List(elements) => {
+                self.out.push('[');
+                self.exprs(elements);
+                self.out.push(']');
+            }
+            ExprKind::Map(pairs) => {
+                self.out.push('{');
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key, LAMBDA);
+                    self.out.push_str(": ");
+                    self.expr(value, LAMBDA);
+                }
+                self.out.push('}');
+            }
+            ExprKind::Interpolation(parts) => {
+                self.out.push('"');
+                for part in parts {
+                    if let ExprKind::Literal(Literal::String(text)) = &part.kind {
+                        let quoted = quote(text);
+                        self.out.push_str(&quoted[1..quoted.len() - 1]);
+                    } else {
+                        self.out.push_str("${");
+                        self.expr(part, LAMBDA);
+                        self.out.push('}');
+                    }
+                }
+                self.out.push('"');
+            }
+            ExprKind::Tuple(elements) if elements.len() < 2 => {
+                self.out.push_str("tup(");
+                self.exprs(elements);
+                self.out.push(')');
+            }
+            ExprKind::Tuple(elements) => {
+                self.out.push('(');
+                self.exprs(elements);
+                self.out.push(')');
+            }
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.expr(start, ADDITIVE);
+                self.out.push_str(if *inclusive { ".." } else { "..<" });
+                self.expr(end, ADDITIVE);
+            }
+            ExprKind::StructInit { name, args } => {
+                self.out.push_str(name);
+                self.out.push('(');
+                self.exprs(args);
+                self.out.push(')');
+            }
+            ExprKind::Append { list, value } => {
+                self.expr(list, POSTFIX);
+                self.out.push_str(":push(");
+                self.expr(value, LAMBDA);
+                self.out.push(')');
+            }
+            ExprKind::Send { channel, value } => {
+                self.expr(channel, POSTFIX);
+                self.out.push_str(":send(");
+                self.expr(value, LAMBDA);
+                self.out.push(')');
+            }
+            ExprKind::Error { value, cause } => {
+                self.out.push_str("err(");
+                self.expr(value, LAMBDA);
+                if let Some(cause) = cause {
+                    self.out.push_str(", cause: ");
+                    self.expr(cause, LAMBDA);
+                }
+                self.out.push(')');
+            }
+            ExprKind::Assert { condition, message } => {
+                self.out.push_str("assert(");
+                self.expr(condition, LAMBDA);
+                if let Some(message) = message {
+                    self.out.push_str(", ");
+                    self.expr(message, LAMBDA);
+                }
+                self.out.push(')');
+            }
+            ExprKind::Cast { ty, value } => {
+                self.out.push_str(&pretty_type(ty));
+                self.out.push('(');
+                self.expr(value, LAMBDA);
+                self.out.push(')');
+            }
+            ExprKind::TypeOf(operand) => {
+                self.out.push_str("typeof(");
+                self.expr(operand, LAMBDA);
+                self.out.push(')');
+            }
+            ExprKind::Block(stmts) => self.trailing_block(&[], stmts),
+        }
+    }
+    fn prefix(&mut self, symbol: &str, operand: &Expr) {
+        self.out.push_str(symbol);
+        self.expr(operand, UNARY);
+    }
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr, LAMBDA);
+        }
+    }
+    fn call_args(&mut self, args: &[Expr]) {
+        let trailing = match args.last().map(|arg| &arg.kind) {
+            Some(ExprKind::Lambda { body, .. }) => matches!(body.kind, ExprKind::Block(_)),
+            _ => false,
+        };
+        let (args, block) = if trailing {
+            args.split_at(args.len() - 1)
+        } else {
+            (args, &[][..])
+        };
+        self.out.push('(');
+        self.exprs(args);
+        self.out.push(')');
+        if let [block] = block {
+            self.out.push(' ');
+            self.expr(block, LAMBDA);
+        }
+    }
+    fn trailing_block(&mut self, params: &[String], stmts: &[Stmt]) {
+        self.out.push_str("do");
+        if !params.is_empty() {
+            self.out.push_str(&format!(" |{}|", params.join(", ")));
+        }
+        self.out.push('\n');
+        self.block(stmts);
+        self.start();
+        self.out.push_str("end");
+    }
+}
+impl Visitor for Printer {
+    fn visit_program(&mut self, program: &Program) {
+        let mut previous: Option<&Item> = None;
+        for item in &program.items {
+            let statement = matches!(item, Item::Statement(_));
+            if previous.is_some_and(|p| !statement || !matches!(p, Item::Statement(_))) {
+                self.out.push('\n');
+            }
+            self.visit_item(item);
+            previous = Some(item);
+        }
+    }
+    fn visit_item(&mut self, item: &Item) {
+        match item {
+            Item::Function(f) => self.visit_function(f),
+            Item::Struct(s) => {
+                self.doc(&s.doc);
+                self.line(&format!("struct {} {{", s.name));
+                self.indent += 1;
+                for field in &s.fields {
+                    self.line(&format!("{}: {}", field.name, pretty_type(&field.ty)));
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Item::Enum(e) => {
+                self.doc(&e.doc);
+                self.line(&format!("enum {} {{ {} }}", e.name, e.variants.join(", ")));
+            }
+            Item::Trait(t) => {
+                self.doc(&t.doc);
+                self.line(&format!("trait {} {{", t.name));
+                self.indent += 1;
+                for method in &t.methods {
+                    self.doc(&method.doc);
+                    self.line(&signature(
+                        &method.name,
+                        &method.params,
+                        &method.return_type,
+                    ));
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Item::Impl(i) => {
+                let (open, close) = match &i.trait_name {
+                    Some(name) => (format!("impl {} for {} {{", name, i.target), "}"),
+                    None => (format!("impl {} do", i.target), "end"),
+                };
+                self.line(&open);
+                self.indent += 1;
+                for method in &i.methods {
+                    self.visit_function(method);
+                }
+                self.indent -= 1;
+                self.line(close);
+            }
+            Item::TypeAlias(a) => {
+                self.doc(&a.doc);
+                self.line(&format!("type {} = {}", a.name, pretty_type(&a.ty)));
+            }
+            Item::Module(m) => self.line(&format!("mod {}", m.name)),
+            Item::Use(u) if u.native => self.line(&format!("use native {}", quote(&u.path))),
+            Item::Use(u) => {
+                let mut text = format!("use {}", u.path);
+                if !u.items.is_empty() {
+                    text.push_str(&format!(".{{{}}}", u.items.join(", ")));
+                }
+                if let Some(alias) = &u.alias {
+                    text.push_str(&format!(" as {}", alias));
+                }
+                self.line(&text);
+            }
+            Item::Statement(stmt) => self.visit_stmt(stmt),
+        }
+    }
+    fn visit_function(&mut self, f: &Function) {
+        self.doc(&f.doc);
+        self.start();
+        if f.is_async {
+            self.out.push_str("async ");
+        }
+        self.out
+            .push_str(&signature(&f.name, &f.params, &f.return_type));
+        match &*f.body {
+            FunctionBody::Expression(expr) => {
+                self.out.push_str(" = ");
+                self.expr(expr, LAMBDA);
+                self.out.push('\n');
+            }
+            FunctionBody::Block(stmts) => {
+                self.out.push_str(" do\n");
+                self.block(stmts);
+                self.line("end");
+            }
+        }
+    }
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.start();
+        match &stmt.kind {
+            StmtKind::Var { name, ty, value } | StmtKind::Const { name, ty, value } => {
+                if matches!(stmt.kind, StmtKind::Const { .. }) {
+                    self.out.push_str("fb ");
+                }
+                self.out.push_str(name);
+                if let Some(ty) = ty {
+                    self.out.push_str(&format!(": {}", pretty_type(ty)));
+                }
+                self.out.push_str(" = ");
+                self.expr(value, LAMBDA);
+            }
+            StmtKind::Assignment { target, value } => {
+                self.expr(target, LAMBDA);
+                self.out.push_str(" = ");
+                self.expr(value, LAMBDA);
+            }
+            StmtKind::CompoundAssignment { target, op, value } => {
+                self.expr(target, LAMBDA);
+                self.out.push_str(match op {
+                    CompoundOp::Add => " += ",
+                    CompoundOp::Sub => " -= ",
+                    CompoundOp::Mul => " *= ",
+                    CompoundOp::Div => " /= ",
+                });
+                self.expr(value, LAMBDA);
+            }
+            StmtKind::If {
+                condition,
+                then_block,
+                elif_branches,
+                else_block,
+            } => {
+                self.header("if ", condition);
+                self.block(then_block);
+                for (condition, block) in elif_branches {
+                    self.start();
+                    self.header("elsif ", condition);
+                    self.block(block);
+                }
+                if let Some(block) = else_block {
+                    self.line("else");
+                    self.block(block);
+                }
+                self.start();
+                self.out.push_str("end");
+            }
+            StmtKind::While { condition, body } => {
+                self.header("while ", condition);
+                self.block(body);
+                self.start();
+                self.out.push_str("end");
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.out.push_str(&format!("for {} = ", var));
+                self.expr(start, TERNARY);
+                self.out.push_str(", ");
+                self.expr(end, TERNARY);
+                if let Some(step) = step {
+                    self.out.push_str(", ");
+                    self.expr(step, TERNARY);
+                }
+                self.out.push_str(" do\n");
+                self.block(body);
+                self.start();
+                self.out.push_str("end");
+            }
+            StmtKind::Each {
+                var,
+                iterator,
+                body,
+            } => {
+                self.header(&format!("each {} in ", var), iterator);
+                self.block(body);
+                self.start();
+                self.out.push_str("end");
+            }
+            StmtKind::Match { value, arms } => {
+                self.header("match ", value);
+                self.indent += 1;
+                for arm in arms {
+                    self.visit_arm(arm);
+                }
+                self.indent -= 1;
+                self.start();
+                self.out.push_str("end");
+            }
+            StmtKind::Try {
+                try_block,
+                catch_var,
+                catch_block,
+                finally_block,
+            } => {
+                self.out.push_str("try do\n");
+                self.block(try_block);
+                if let (Some(var), Some(block)) = (catch_var, catch_block) {
+                    self.line(&format!("catch {} do", var));
+                    self.block(block);
+                }
+                if let Some(block) = finally_block {
+                    self.line("finally do");
+                    self.block(block);
+                }
+                self.start();
+                self.out.push_str("end");
+            }
+            StmtKind::Return(None) => self.out.push_str("give"),
+            StmtKind::Return(Some(value)) => {
+                self.out.push_str("give ");
+                self.expr(value, LAMBDA);
+            }
+            StmtKind::Break => self.out.push_str("break"),
+            StmtKind::Continue => self.out.push_str("continue"),
+            StmtKind::Expression(expr) => self.expr(expr, LAMBDA),
+        }
+        self.out.push('\n');
+    }
+    fn visit_arm(&mut self, arm: &MatchArm) {
+        self.start();
+        self.out.push_str(&pattern(&arm.pattern));
+        self.out.push_str(" => ");
+        self.expr(&arm.body, LAMBDA);
+        self.out.push('\n');
+    }
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.expr(expr, LAMBDA);
+    }
+}
+fn signature(name: &str, params: &[Param], return_type: &Option<Type>) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|p| {
+            let mut text = if p.variadic {
+                format!("...{}", p.name)
+            } else {
+                p.name.clone()
+            };
+            if let Some(ty) = &p.ty {
+                text.push_str(&format!(": {}", pretty_type(ty)));
+            }
+            if let Some(default) = &p.default {
+                text.push_str(&format!(" = {}", pretty_expr(default)));
+            }
+            text
+        })
+        .collect();
+    let returns = return_type
+        .as_ref()
+        .map_or(String::new(), |ty| format!(": {}", pretty_type(ty)));
+    format!("fn {}({}){}", name, params.join(", "), returns)
+}
+fn precedence(kind: &ExprKind) -> u8 {
+    match kind {
+        ExprKind::Lambda { .. } => LAMBDA,
+        ExprKind::Ternary { .. } => TERNARY,
+        ExprKind::Binary { op, .. } => binary_precedence(*op),
+        ExprKind::Unary {
+            op: UnaryOp::Not, ..
+        } => NOT,
+        ExprKind::Range { .. } => RANGE,
+        ExprKind::Unary { .. }
+        | ExprKind::Length(_)
+        | ExprKind::Borrow(_)
+        | ExprKind::Receive(_)
+        | ExprKind::Await(_)
+        | ExprKind::Spawn(_) => UNARY,
+        _ => POSTFIX,
+    }
+}
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Coalesce => 2,
+        BinaryOp::Or => 3,
+        BinaryOp::And => 4,
+        BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+            6
+        }
+        BinaryOp::BitOr => 7,
+        BinaryOp::BitXor => 8,
+        BinaryOp::BitAnd => 9,
+        BinaryOp::Shl | BinaryOp::Shr => 10,
+        BinaryOp::Add | BinaryOp::Sub => ADDITIVE,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 13,
+        BinaryOp::Pow => POWER,
+    }
+}
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(n) => n.to_string(),
+        Literal::Float(n) => {
+            let text = format!("{:?}", n);
+            match text.split_once('e') {
+                Some((mantissa, exponent)) if !mantissa.contains('.') => {
+                    format!("{}.0e{}", mantissa, exponent)
+                }
+                _ => text,
+            }
+        }
+        Literal::String(s) => quote(s),
+        Literal::Bool(true) => "yes".to_string(),
+        Literal::Bool(false) => "no".to_string(),
+    }
+}
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Binding(name) => name.clone(),
+        Pattern::Literal(lit) => literal(lit),
+        Pattern::Nil => "nil".to_string(),
+    }
+}
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '$' => out.push_str("\\$"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+fn list(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::parse_source;
+    #[test]
+    fn test_pretty_output_is_canonical_and_reparses() {
+        let source = "## Adds.\nfn add(a: nb, b = 2,...rest): nb = a+b\nperm xs=[1,2.5,1.0e20]\nx = (1 + 2) * -3 ^ 2\n\
+            if !(x > 1) & xs[0:1] do log(\"a\\\"$b\\n\") elsif x do give\nelse log({\"k\": (1, 2)}) end\n\
+            each v in 0..<#xs do\n  match v do\n    1 => log(v)\n    _ => empty\n  end\nend\n\
+            ys = filter(xs) do |v|\n  give v > 1\nend\nf = () => x ?? 0\n";
+        let printed = pretty(&parse_source(source).unwrap());
+        assert_eq!(
+            printed,
+            "## Adds.\nfn add(a: nb, b = 2, ...rest): nb = a + b\n\nfb xs = [1, 2.5, 1.0e20]\n\
+             x = (1 + 2) * -3 ^ 2\nif !(x > 1) & xs[0:1] do\n    log(\"a\\\"\\$b\\n\")\nelsif x do\n    give\nelse\n    log({\"k\": (1, 2)})\nend\n\
+             each v in 0..<#xs do\n    match v do\n        1 => log(v)\n        _ => nil\n    end\nend\n\
+             ys = filter(xs) do |v|\n    give v > 1\nend\nf = () => x ?? 0\n"
+        );
+        assert_eq!(pretty(&parse_source(&printed).unwrap()), printed);
+    }
+    #[test]
+    fn test_pretty_parenthesizes_by_precedence() {
+        let cases = [
+            ("(a - b) - c", "a - b - c"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("(a ^ b) ^ c", "(a ^ b) ^ c"),
+            ("a ^ (b ^ c)", "a ^ b ^ c"),
+            ("-(a + b)", "-(a + b)"),
+            ("(a ? b : c) + 1", "(a ? b : c) + 1"),
+            ("(x => x)(1)", "(x => x)(1)"),
+            ("(1 .. 2):len()", "(1..2):len()"),
+            (
+                "\"a${ x+1 }\\$b${f(\"c\")}\"",
+                "\"a${x + 1}\\$b${f(\"c\")}\"",
+            ),
+        ];
+        for (source, expected) in cases {
+            let program = parse_source(source).unwrap();
+            let Item::Statement(stmt) = &program.items[0] else {
+                panic!("expected a statement");
+            };
+            let StmtKind::Expression(expr) = &stmt.kind else {
+                panic!("expected an expression");
+            };
+            assert_eq!(pretty_expr(expr), expected, "{}", source);
+        }
+    }
+}
@@ -0,0 +1,530 @@
+use super::ast::*;
+use std::sync::Arc;
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+    fn visit_item(&mut self, item: &Item) {
+        walk_item(self, item);
+    }
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+    fn visit_param(&mut self, param: &Param) {
+        walk_param(self, param);
+    }
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+    fn visit_arm(&mut self, arm: &MatchArm) {
+        walk_arm(self, arm);
+    }
+    fn visit_pattern(&mut self, _pattern: &Pattern) {}
+    fn visit_type(&mut self, _ty: &Type) {}
+}
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for item in &program.items {
+        visitor.visit_item(item);
+    }
+}
+pub fn walk_item<V: Visitor + ?Sized>(visitor: &mut V, item: &Item) {
+    match item {
+        Item::Function(f) => visitor.visit_function(f),
+        Item::Struct(s) => {
+            for field in &s.fields {
+                visitor.visit_type(&field.ty);
+            }
+        }
+        Item::Trait(t) => {
+            for method in &t.methods {
+                for param in &method.params {
+                    visitor.visit_param(param);
+                }
+                if let Some(ty) = &method.return_type {
+                    visitor.visit_type(ty);
+                }
+            }
+        }
+        Item::Impl(i) => {
+            for method in &i.methods {
+                visitor.visit_function(method);
+            }
+        }
+        Item::TypeAlias(a) => visitor.visit_type(&a.ty),
+        Item::Statement(stmt) => visitor.visit_stmt(stmt),
+        Item::Enum(_) | Item::Module(_) | Item::Use(_) => {}
+    }
+}
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    for param in function.params.iter() {
+        visitor.visit_param(param);
+    }
+    if let Some(ty) = &function.return_type {
+        visitor.visit_type(ty);
+    }
+    match &*function.body {
+        FunctionBody::Expression(expr) => visitor.visit_expr(expr),
+        FunctionBody::Block(stmts) => walk_block(visitor, stmts),
+    }
+}
+pub fn walk_param<V: Visitor + ?Sized>(visitor: &mut V, param: &Param) {
+    if let Some(ty) = &param.ty {
+        visitor.visit_type(ty);
+    }
+    if let Some(default) = &param.default {
+        visitor.visit_expr(default);
+    }
+}
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, stmts: &[Stmt]) {
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Var { ty, value, .. } | StmtKind::Const { ty, value, .. } => {
+            if let Some(ty) = ty {
+                visitor.visit_type(ty);
+            }
+            visitor.visit_expr(value);
+        }
+        StmtKind::Assignment { target, value }
+        | StmtKind::CompoundAssignment { target, value, .. } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+        StmtKind::If {
+            condition,
+            then_block,
+            elif_branches,
+            else_block,
+        } => {
+            visitor.visit_expr(condition);
+            walk_block(visitor, then_block);
+            for (condition, block) in elif_branches {
+                visitor.visit_expr(condition);
+                walk_block(visitor, block);
+            }
+            if let Some(block) = else_block {
+                walk_block(visitor, block);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            visitor.visit_expr(condition);
+            walk_block(visitor, body);
+        }
+        StmtKind::For {
+            start,
+            end,
+            step,
+            body,
+            ..
+        } => {
+            visitor.visit_expr(start);
+            visitor.visit_expr(end);
+            if let Some(step) = step {
+                visitor.visit_expr(step);
+            }
+            walk_block(visitor, body);
+        }
+        StmtKind::Each { iterator, body, .. } => {
+            visitor.visit_expr(iterator);
+            walk_block(visitor, body);
+        }
+        StmtKind::Match { value, arms } => {
+            visitor.visit_expr(value);
+            for arm in arms {
+                visitor.visit_arm(arm);
+            }
+        }
+        StmtKind::Try {
+            try_block,
+            catch_block,
+            finally_block,
+            ..
+        } => {
+            walk_block(visitor, try_block);
+            for block in [catch_block, finally_block].into_iter().flatten() {
+                walk_block(visitor, block);
+            }
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::Expression(expr) => visitor.visit_expr(expr),
+        StmtKind::Break | StmtKind::Continue => {}
+    }
+}
+pub fn walk_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &MatchArm) {
+    visitor.visit_pattern(&arm.pattern);
+    visitor.visit_expr(&arm.body);
+}
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::Call { callee, args } => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::MethodCall { receiver, args, .. } => {
+            visitor.visit_expr(receiver);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Index { array, index } => {
+            visitor.visit_expr(array);
+            visitor.visit_expr(index);
+        }
+        ExprKind::Slice { array, start, end } => {
+            visitor.visit_expr(array);
+            for bound in [start, end].into_iter().flatten() {
+                visitor.visit_expr(bound);
+            }
+        }
+        ExprKind::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_expr);
+            visitor.visit_expr(else_expr);
+        }
+        ExprKind::List(elements)
+        | ExprKind::Tuple(elements)
+        | ExprKind::Interpolation(elements)
+        | ExprKind::StructInit { args: elements, .. } => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        ExprKind::Map(pairs) => {
+            for (key, value) in pairs {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Range { start, end, .. } => {
+            visitor.visit_expr(start);
+            visitor.visit_expr(end);
+        }
+        ExprKind::Append {
+            list: left,
+            value: right,
+        }
+        | ExprKind::Send {
+            channel: left,
+            value: right,
+        } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::Error { value, cause } => {
+            visitor.visit_expr(value);
+            if let Some(cause) = cause {
+                visitor.visit_expr(cause);
+            }
+        }
+        ExprKind::Assert { condition, message } => {
+            visitor.visit_expr(condition);
+            if let Some(message) = message {
+                visitor.visit_expr(message);
+            }
+        }
+        ExprKind::Cast { ty, value } => {
+            visitor.visit_type(ty);
+            visitor.visit_expr(value);
+        }
+        ExprKind::Lambda { body, .. } => visitor.visit_expr(body),
+        ExprKind::Unary { operand, .. }
+        | ExprKind::Field {
+            object: operand, ..
+        }
+        | ExprKind::Length(operand)
+        | ExprKind::Await(operand)
+        | ExprKind::Spawn(operand)
+        | ExprKind::Receive(operand)
+        | ExprKind::Borrow(operand)
+        | ExprKind::TypeOf(operand) => visitor.visit_expr(operand),
+        ExprKind::Block(stmts) => walk_block(visitor, stmts),
+        ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::Nil => {}
+    }
+}
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item);
+    }
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function);
+    }
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for item in &mut program.items {
+        visitor.visit_item_mut(item);
+    }
+}
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::Function(f) => visitor.visit_function_mut(f),
+        Item::Trait(t) => {
+            for param in t.methods.iter_mut().flat_map(|m| &mut m.params) {
+                if let Some(default) = &mut param.default {
+                    visitor.visit_expr_mut(default);
+                }
+            }
+        }
+        Item::Impl(i) => {
+            for method in &mut i.methods {
+                visitor.visit_function_mut(method);
+            }
+        }
+        Item::Statement(stmt) => visitor.visit_stmt_mut(stmt),
+        Item::Struct(_) | Item::Enum(_) | Item::TypeAlias(_) | Item::Module(_) | Item::Use(_) => {}
+    }
+}
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    for param in Arc::make_mut(&mut function.params) {
+        if let Some(default) = &mut param.default {
+            visitor.visit_expr_mut(default);
+        }
+    }
+    match Arc::make_mut(&mut function.body) {
+        FunctionBody::Expression(expr) => visitor.visit_expr_mut(expr),
+        FunctionBody::Block(stmts) => walk_block_mut(visitor, stmts),
+    }
+}
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmts: &mut [Stmt]) {
+    for stmt in stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::Var { value, .. } | StmtKind::Const { value, .. } => {
+            visitor.visit_expr_mut(value)
+        }
+        StmtKind::Assignment { target, value }
+        | StmtKind::CompoundAssignment { target, value, .. } => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(value);
+        }
+        StmtKind::If {
+            condition,
+            then_block,
+            elif_branches,
+            else_block,
+        } => {
+            visitor.visit_expr_mut(condition);
+            walk_block_mut(visitor, then_block);
+            for (condition, block) in elif_branches {
+                visitor.visit_expr_mut(condition);
+                walk_block_mut(visitor, block);
+            }
+            if let Some(block) = else_block {
+                walk_block_mut(visitor, block);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            visitor.visit_expr_mut(condition);
+            walk_block_mut(visitor, body);
+        }
+        StmtKind::For {
+            start,
+            end,
+            step,
+            body,
+            ..
+        } => {
+            visitor.visit_expr_mut(start);
+            visitor.visit_expr_mut(end);
+            if let Some(step) = step {
+                visitor.visit_expr_mut(step);
+            }
+            walk_block_mut(visitor, body);
+        }
+        StmtKind::Each { iterator, body, .. } => {
+            visitor.visit_expr_mut(iterator);
+            walk_block_mut(visitor, body);
+        }
+        StmtKind::Match { value, arms } => {
+            visitor.visit_expr_mut(value);
+            for arm in arms {
+                visitor.visit_expr_mut(&mut arm.body);
+            }
+        }
+        StmtKind::Try {
+            try_block,
+            catch_block,
+            finally_block,
+            ..
+        } => {
+            walk_block_mut(visitor, try_block);
+            for block in [catch_block, finally_block].into_iter().flatten() {
+                walk_block_mut(visitor, block);
+            }
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        StmtKind::Expression(expr) => visitor.visit_expr_mut(expr),
+        StmtKind::Break | StmtKind::Continue => {}
+    }
+}
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        ExprKind::Call { callee, args } => {
+            visitor.visit_expr_mut(callee);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::MethodCall { receiver, args, .. } => {
+            visitor.visit_expr_mut(receiver);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::Index { array, index } => {
+            visitor.visit_expr_mut(array);
+            visitor.visit_expr_mut(index);
+        }
+        ExprKind::Slice { array, start, end } => {
+            visitor.visit_expr_mut(array);
+            for bound in [start, end].into_iter().flatten() {
+                visitor.visit_expr_mut(bound);
+            }
+        }
+        ExprKind::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_expr_mut(then_expr);
+            visitor.visit_expr_mut(else_expr);
+        }
+        ExprKind::List(elements)
+        | ExprKind::Tuple(elements)
+        | ExprKind::Interpolation(elements)
+        | ExprKind::StructInit { args: elements, .. } => {
+            for element in elements {
+                visitor.visit_expr_mut(element);
+            }
+        }
+        ExprKind::Map(pairs) => {
+            for (key, value) in pairs {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Range { start, end, .. } => {
+            visitor.visit_expr_mut(start);
+            visitor.visit_expr_mut(end);
+        }
+        ExprKind::Append {
+            list: left,
+            value: right,
+        }
+        | ExprKind::Send {
+            channel: left,
+            value: right,
+        } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        ExprKind::Error { value, cause } => {
+            visitor.visit_expr_mut(value);
+            if let Some(cause) = cause {
+                visitor.visit_expr_mut(cause);
+            }
+        }
+        ExprKind::Assert { condition, message } => {
+            visitor.visit_expr_mut(condition);
+            if let Some(message) = message {
+                visitor.visit_expr_mut(message);
+            }
+        }
+        ExprKind::Lambda { body, .. } => visitor.visit_expr_mut(Arc::make_mut(body)),
+        ExprKind::Unary { operand, .. }
+        | ExprKind::Field {
+            object: operand, ..
+        }
+        | ExprKind::Cast { value: operand, .. }
+        | ExprKind::Length(operand)
+        | ExprKind::Await(operand)
+        | ExprKind::Spawn(operand)
+        | ExprKind::Receive(operand)
+        | ExprKind::Borrow(operand)
+        | ExprKind::TypeOf(operand) => visitor.visit_expr_mut(operand),
+        ExprKind::Block(stmts) => walk_block_mut(visitor, stmts),
+        ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::Nil => {}
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::pretty;
+    use crate::project::parse_source;
+    struct Calls(Vec<String>);
+    impl Visitor for Calls {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Call { callee, .. } = &expr.kind {
+                if let ExprKind::Variable(name) = &callee.kind {
+                    self.0.push(name.clone());
+                }
+            }
+            walk_expr(self, expr);
+        }
+    }
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            if let ExprKind::Variable(name) = &mut expr.kind {
+                if name == "n" {
+                    *name = "count".to_string();
+                }
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+    #[test]
+    fn test_visitors_reach_nested_nodes() {
+        let source = "fn f(x = g(1)) do\n  match x do\n    1 => h(n)\n  end\nend\n\
+            each i in range(n) do\n  try do\n    log(i, [k(n)])\n  catch e do\n    m()\n  end\nend\n\
+            run() do |a|\n  give a + n\nend\n";
+        let mut program = parse_source(source).unwrap();
+        let mut calls = Calls(Vec::new());
+        calls.visit_program(&program);
+        assert_eq!(calls.0, ["g", "h", "range", "log", "k", "m", "run"]);
+        Rename.visit_program_mut(&mut program);
+        let printed = pretty(&program);
+        assert!(!printed.contains("(n)"), "{}", printed);
+        assert_eq!(printed.matches("count").count(), 4, "{}", printed);
+    }
+}
@@ -0,0 +1,556 @@
+use crate::builtins::SandboxPolicy;
+use crate::error::{ErrorCode, NebulaError, NebulaResult};
+use crate::lexer::{FileId, Lexer, SourceMap};
+use crate::parser::ast::{Item, Program, StmtKind, Use};
+use crate::parser::Parser;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "net")]
+use std::process::Command;
+pub const MANIFEST_FILE: &str = "nebula.toml";
+pub const DEFAULT_ENTRY: &str = "src/main.na";
+pub const VENDOR_DIR: &str = "vendor";
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub modules: Modules,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub name: String,
+    #[serde(default = "default_version")]
+    pub version: String,
+    #[serde(default = "default_entry")]
+    pub entry: String,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct Modules {
+    #[serde(default = "default_paths")]
+    pub paths: Vec<String>,
+}
+impl Default for Modules {
+    fn default() -> Self {
+        Self {
+            paths: default_paths(),
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct Dependency {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+fn io_error(context: impl std::fmt::Display, e: impl std::fmt::Display) -> NebulaError {
+    NebulaError::Io {
+        message: format!("{}: {}", context, e),
+    }
+}
+fn default_version() -> String {
+    "0.1.0".to_string()
+}
+fn default_entry() -> String {
+    DEFAULT_ENTRY.to_string()
+}
+fn default_paths() -> Vec<String> {
+    vec!["src".to_string()]
+}
+impl Manifest {
+    pub fn parse(text: &str) -> NebulaResult<Self> {
+        toml::from_str(text).map_err(|e| NebulaError::Io {
+            message: format!("invalid {}: {}", MANIFEST_FILE, e.message()),
+        })
+    }
+}
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+impl Project {
+    pub fn find_root(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .find(|dir| dir.join(MANIFEST_FILE).is_file())
+            .map(Path::to_path_buf)
+    }
+    pub fn load(root: &Path) -> NebulaResult<Self> {
+        let text = fs::read_to_string(root.join(MANIFEST_FILE)).map_err(|e| NebulaError::Io {
+            message: format!("cannot read {}: {}", MANIFEST_FILE, e),
+        })?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            manifest: Manifest::parse(&text)?,
+        })
+    }
+    pub fn entry_path(&self) -> PathBuf {
+        self.root.join(&self.manifest.package.entry)
+    }
+    pub fn module_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .manifest
+            .modules
+            .paths
+            .iter()
+            .map(|path| self.root.join(path))
+            .collect();
+        for name in self.manifest.dependencies.keys() {
+            let Ok(dir) = self.vendor_path(name) else {
+                continue;
+            };
+            match Project::load(&dir) {
+                Ok(dep) => paths.extend(dep.manifest.modules.paths.iter().map(|p| dir.join(p))),
+                Err(_) => paths.push(dir),
+            }
+        }
+        paths
+    }
+    pub fn vendor_path(&self, name: &str) -> NebulaResult<PathBuf> {
+        let mut components = Path::new(name).components();
+        let plain = matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(_)), None)
+        );
+        if !plain || name.contains(['/', '\\']) {
+            return Err(NebulaError::InvalidOperation {
+                message: format!("invalid dependency name '{}'", name),
+                span: None,
+            });
+        }
+        Ok(self.root.join(VENDOR_DIR).join(name))
+    }
+    pub fn add_dependency(&mut self, name: &str, dep: Dependency) -> NebulaResult<PathBuf> {
+        if dep.path.is_some() == dep.git.is_some() {
+            return Err(NebulaError::InvalidOperation {
+                message: format!("dependency '{}' needs exactly one of path or git", name),
+                span: None,
+            });
+        }
+        let dest = self.fetch_dependency(name, &dep)?;
+        let file = self.root.join(MANIFEST_FILE);
+        let text = fs::read_to_string(&file).map_err(|e| io_error(file.display(), e))?;
+        let mut table: toml::Table = text.parse().map_err(|e| io_error(file.display(), e))?;
+        let entry = toml::Value::try_from(&dep).map_err(|e| io_error(name, e))?;
+        match table
+            .entry("dependencies")
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            toml::Value::Table(deps) => {
+                deps.insert(name.to_string(), entry);
+            }
+            _ => return Err(io_error(file.display(), "[dependencies] must be a table")),
+        }
+        let text = toml::to_string(&table).map_err(|e| io_error(file.display(), e))?;
+        fs::write(&file, text).map_err(|e| io_error(file.display(), e))?;
+        self.manifest.dependencies.insert(name.to_string(), dep);
+        Ok(dest)
+    }
+    pub fn fetch(&self, name: &str) -> NebulaResult<PathBuf> {
+        let dep =
+            self.manifest
+                .dependencies
+                .get(name)
+                .ok_or_else(|| NebulaError::InvalidOperation {
+                    message: format!("no dependency named '{}'", name),
+                    span: None,
+                })?;
+        self.fetch_dependency(name, dep)
+    }
+    fn fetch_dependency(&self, name: &str, dep: &Dependency) -> NebulaResult<PathBuf> {
+        let dest = self.vendor_path(name)?;
+        if fs::symlink_metadata(&dest).is_ok() {
+            let vendor = self.root.join(VENDOR_DIR).canonicalize();
+            let parent = dest.parent().map(Path::canonicalize);
+            let is_dir = fs::symlink_metadata(&dest).is_ok_and(|meta| meta.is_dir());
+            if !is_dir || !matches!((vendor, parent), (Ok(v), Some(Ok(p))) if v == p) {
+                return Err(io_error(dest.display(), "not a vendored dependency"));
+            }
+            fs::remove_dir_all(&dest).map_err(|e| io_error(dest.display(), e))?;
+        }
+        let result = if let Some(path) = &dep.path {
+            copy_tree(&self.root.join(path), &dest).map_err(|e| io_error(path, e))
+        } else if let Some(url) = &dep.git {
+            fetch_git(url, dep.rev.as_deref(), &dest)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            let _ = fs::remove_dir_all(&dest);
+            return Err(e);
+        }
+        Ok(dest)
+    }
+    pub fn fetch_missing(&self) -> NebulaResult<Vec<String>> {
+        let mut fetched = Vec::new();
+        for name in self.manifest.dependencies.keys() {
+            if !self.vendor_path(name)?.exists() {
+                self.fetch(name)?;
+                fetched.push(name.clone());
+            }
+        }
+        Ok(fetched)
+    }
+}
+#[cfg(feature = "net")]
+fn fetch_git(url: &str, rev: Option<&str>, dest: &Path) -> NebulaResult<()> {
+    let cloned = git(
+        &["clone", "--quiet", "--", url, &dest.to_string_lossy()],
+        None,
+    );
+    match (rev, cloned) {
+        (Some(rev), Ok(_)) => {
+            let spec = format!("{}^{{commit}}", rev);
+            let args = ["rev-parse", "--verify", "--end-of-options", &spec];
+            git(&args, Some(dest))
+                .and_then(|commit| git(&["checkout", "--quiet", commit.trim(), "--"], Some(dest)))
+        }
+        (_, cloned) => cloned,
+    }
+    .map(drop)
+}
+#[cfg(not(feature = "net"))]
+fn fetch_git(url: &str, _rev: Option<&str>, _dest: &Path) -> NebulaResult<()> {
+    Err(io_error(
+        url,
+        "git dependencies require building with the 'net' feature",
+    ))
+}
+#[cfg(feature = "net")]
+fn git(args: &[&str], dir: Option<&Path>) -> NebulaResult<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|e| io_error("cannot run git", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io_error(
+            format!("git {}", args[0]),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ))
+    }
+}
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == ".git" || name == VENDOR_DIR {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            copy_tree(&path, &to.join(&name))?;
+        } else {
+            fs::copy(&path, to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+pub fn scaffold(dir: &Path) -> io::Result<()> {
+    if dir.join(MANIFEST_FILE).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", dir.join(MANIFEST_FILE).display()),
+        ));
+    }
+    let name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("app");
+    fs::create_dir_all(dir.join("src"))?;
+    fs::write(
+        dir.join(MANIFEST_FILE),
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"{}\"\nentry = \"{}\"\n\n[modules]\npaths = [\"src\"]\n",
+            name,
+            default_version(),
+            DEFAULT_ENTRY
+        ),
+    )?;
+    fs::write(dir.join(".gitignore"), format!("/{}/\n", VENDOR_DIR))?;
+    let entry = dir.join(DEFAULT_ENTRY);
+    if !entry.exists() {
+        fs::write(entry, format!("log(\"Hello from {}!\")\n", name))?;
+    }
+    Ok(())
+}
+pub fn parse_source(source: &str) -> NebulaResult<Program> {
+    let tokens = Lexer::new(source)
+        .tokenize()
+        .map_err(|errors| NebulaError::combine(errors, "lexical errors"))?;
+    Parser::new(tokens).parse_program()
+}
+pub fn parse_file(sources: &SourceMap, file: FileId) -> NebulaResult<Program> {
+    let (source, first_line) = sources
+        .get(file)
+        .map_or(("", 1), |file| (file.source(), file.first_line()));
+    let tokens = Lexer::new(source)
+        .with_file(file)
+        .with_first_line(first_line)
+        .tokenize()
+        .map_err(|errors| NebulaError::combine(errors, "lexical errors"))?;
+    Parser::new(tokens).parse_program()
+}
+pub fn module_file(name: &str) -> PathBuf {
+    name.split('.').collect::<PathBuf>().with_extension("na")
+}
+pub fn resolve_module(name: &str, search: &[PathBuf]) -> Option<PathBuf> {
+    search
+        .iter()
+        .map(|dir| dir.join(module_file(name)))
+        .find(|path| path.is_file())
+}
+fn check_items(program: &Program, module: &Use) -> NebulaResult<()> {
+    let declared = |item: &Item| match item {
+        Item::Function(f) => Some(f.name.clone()),
+        Item::Struct(s) => Some(s.name.clone()),
+        Item::Enum(e) => Some(e.name.clone()),
+        Item::Trait(t) => Some(t.name.clone()),
+        Item::TypeAlias(a) => Some(a.name.clone()),
+        Item::Statement(stmt) => match &stmt.kind {
+            StmtKind::Var { name, .. } | StmtKind::Const { name, .. } => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match module.items.iter().find(|name| {
+        !program
+            .items
+            .iter()
+            .any(|item| declared(item).as_ref() == Some(name))
+    }) {
+        Some(missing) => Err(NebulaError::coded_at(
+            ErrorCode::E063,
+            format!("'{}' in module {}", missing, module.path),
+            module.span,
+        )),
+        None => Ok(()),
+    }
+}
+pub fn load_modules(
+    program: Program,
+    search: &[PathBuf],
+    sources: &mut SourceMap,
+    policy: &SandboxPolicy,
+) -> NebulaResult<Program> {
+    let mut items = Vec::new();
+    let mut loaded = HashSet::new();
+    include_items(program, search, sources, policy, &mut loaded, &mut items)?;
+    Ok(Program { items })
+}
+fn include_items(
+    program: Program,
+    search: &[PathBuf],
+    sources: &mut SourceMap,
+    policy: &SandboxPolicy,
+    loaded: &mut HashSet<PathBuf>,
+    items: &mut Vec<Item>,
+) -> NebulaResult<()> {
+    for item in program.items {
+        let module = match item {
+            Item::Use(module) if module.native => {
+                crate::ext::load_native(&module.path, module.span, policy)?;
+                items.push(Item::Use(module));
+                continue;
+            }
+            Item::Use(module) => module,
+            other => {
+                items.push(other);
+                continue;
+            }
+        };
+        let Some(path) = resolve_module(&module.path, search) else {
+            if crate::ext::native_extensions()
+                .module_functions(&module.path)
+                .is_some()
+            {
+                items.push(Item::Use(module));
+                continue;
+            }
+            return Err(NebulaError::coded_at(
+                ErrorCode::E062,
+                module.path.clone(),
+                module.span,
+            ));
+        };
+        let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if !loaded.insert(key) {
+            continue;
+        }
+        policy
+            .check_read("use", &path.to_string_lossy())
+            .map_err(|message| NebulaError::coded_at(ErrorCode::E061, message, module.span))?;
+        let source = fs::read_to_string(&path).map_err(|e| NebulaError::Io {
+            message: format!("cannot read {}: {}", path.display(), e),
+        })?;
+        let file = sources.add(path.display().to_string(), source);
+        let program = parse_file(sources, file)?;
+        check_items(&program, &module)?;
+        include_items(program, search, sources, policy, loaded, items)?;
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_manifest_defaults() {
+        let manifest = Manifest::parse("[package]\nname = \"demo\"\n").unwrap();
+        assert_eq!(manifest.package.entry, DEFAULT_ENTRY);
+        assert_eq!(manifest.package.version, "0.1.0");
+        assert_eq!(manifest.modules.paths, vec!["src".to_string()]);
+        assert!(Manifest::parse("[package]\n").is_err());
+    }
+    #[test]
+    fn test_load_modules_inlines_each_module_once() {
+        let dir = std::env::temp_dir().join(format!("nebula_modules_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("util.na"), "use shared\nfn util(x) = x + 1\n").unwrap();
+        fs::write(dir.join("shared.na"), "use util\nperm SHARED = 1\n").unwrap();
+        let program = parse_source("use util\nuse shared\nlog(util(SHARED))\n").unwrap();
+        let search = vec![dir.clone()];
+        let mut sources = SourceMap::single("main.na", "");
+        let open = SandboxPolicy::default();
+        let locked = SandboxPolicy::locked();
+        let denied = load_modules(program.clone(), &search, &mut sources, &locked);
+        let loaded = load_modules(program, &search, &mut sources, &open).unwrap();
+        let missing = load_modules(
+            parse_source("use nope\n").unwrap(),
+            &search,
+            &mut sources,
+            &open,
+        );
+        fs::write(dir.join("broken.na"), "fn ok() = 1\nperm = 2\n").unwrap();
+        let broken = load_modules(
+            parse_source("use broken\n").unwrap(),
+            &search,
+            &mut sources,
+            &open,
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(sources.len(), 4);
+        let span = *broken.unwrap_err().span().unwrap();
+        let location = sources.location(span).unwrap();
+        assert!(location.ends_with("broken.na:2:6"), "{}", location);
+        assert_eq!(loaded.items.len(), 3);
+        assert!(matches!(loaded.items[0], Item::Statement(_)));
+        assert!(matches!(loaded.items[1], Item::Function(_)));
+        assert_eq!(missing.unwrap_err().code(), Some(ErrorCode::E062));
+        let denied = denied.unwrap_err();
+        assert_eq!(denied.code(), Some(ErrorCode::E061));
+        assert!(
+            denied.message().contains("denied read access"),
+            "{}",
+            denied.message()
+        );
+    }
+    #[test]
+    fn test_dotted_use_resolves_nested_module_files() {
+        let dir = std::env::temp_dir().join(format!("nebula_dotted_{}", std::process::id()));
+        fs::create_dir_all(dir.join("utils")).unwrap();
+        fs::write(
+            dir.join("utils/strings.na"),
+            "fn shout(s) = s\nfn whisper(s) = s\n",
+        )
+        .unwrap();
+        let search = vec![dir.clone()];
+        let mut sources = SourceMap::new();
+        let program = parse_source("use utils.strings as s\nlog(shout(1))\n").unwrap();
+        let loaded =
+            load_modules(program, &search, &mut sources, &SandboxPolicy::default()).unwrap();
+        let program = parse_source("use utils.strings.{shout, yell}\n").unwrap();
+        let missing = load_modules(program, &search, &mut sources, &SandboxPolicy::default());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            module_file("utils.strings"),
+            Path::new("utils").join("strings.na")
+        );
+        assert_eq!(loaded.items.len(), 3);
+        let err = missing.unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E063));
+        assert!(err.message().contains("'yell'"), "{}", err.message());
+    }
+    #[test]
+    fn test_path_dependency_is_vendored_and_searched() {
+        let base = std::env::temp_dir().join(format!("nebula_deps_{}", std::process::id()));
+        let (app, lib) = (base.join("app"), base.join("lib"));
+        scaffold(&app).unwrap();
+        scaffold(&lib).unwrap();
+        fs::write(lib.join("src/shout.na"), "fn shout(s) = s + \"!\"\n").unwrap();
+        let mut project = Project::load(&app).unwrap();
+        let dep = Dependency {
+            path: Some("../lib".to_string()),
+            ..Dependency::default()
+        };
+        let vendored = project.add_dependency("lib", dep.clone()).unwrap();
+        let reloaded = Project::load(&app).unwrap();
+        let found = resolve_module("shout", &reloaded.module_paths());
+        let bad = project.add_dependency("bad", Dependency::default());
+        fs::remove_dir_all(&base).unwrap();
+        assert_eq!(reloaded.manifest.dependencies.get("lib"), Some(&dep));
+        assert_eq!(found, Some(vendored.join("src/shout.na")));
+        assert!(bad.is_err());
+    }
+    #[cfg(not(feature = "net"))]
+    #[test]
+    fn test_git_dependencies_need_the_net_feature() {
+        let app = std::env::temp_dir().join(format!("nebula_offline_{}", std::process::id()));
+        scaffold(&app).unwrap();
+        let mut project = Project::load(&app).unwrap();
+        let dep = Dependency {
+            git: Some("https://example.com/lib.git".to_string()),
+            ..Dependency::default()
+        };
+        let added = project.add_dependency("lib", dep);
+        let vendored = app.join(VENDOR_DIR).join("lib").exists();
+        fs::remove_dir_all(&app).unwrap();
+        let err = added.unwrap_err();
+        assert!(
+            err.message()
+                .contains("require building with the 'net' feature"),
+            "{}",
+            err.message()
+        );
+        assert!(!vendored);
+    }
+    #[test]
+    fn test_hostile_dependency_names_are_rejected() {
+        let base = std::env::temp_dir().join(format!("nebula_hostile_{}", std::process::id()));
+        let (app, lib) = (base.join("app"), base.join("lib"));
+        scaffold(&app).unwrap();
+        scaffold(&lib).unwrap();
+        let mut project = Project::load(&app).unwrap();
+        let dep = Dependency {
+            path: Some("../lib".to_string()),
+            ..Dependency::default()
+        };
+        let rejected: Vec<_> = ["..", ".", "", "../app", "a/b", "a\\b", "/tmp"]
+            .iter()
+            .map(|name| project.add_dependency(name, dep.clone()).is_err())
+            .collect();
+        project.manifest.dependencies.insert("..".to_string(), dep);
+        let fetched = project.fetch("..");
+        let survived = app.join(MANIFEST_FILE).is_file() && app.join(DEFAULT_ENTRY).is_file();
+        fs::remove_dir_all(&base).unwrap();
+        assert!(rejected.iter().all(|&r| r), "{:?}", rejected);
+        assert!(fetched
+            .unwrap_err()
+            .message()
+            .contains("invalid dependency name"));
+        assert!(survived);
+    }
+}
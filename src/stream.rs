@@ -0,0 +1,157 @@
+use crate::error::{NebulaError, NebulaResult};
+use crate::lexer::{Lexer, SourceFile, TokenKind};
+use crate::parser::{Parser, Program};
+use std::io::BufRead;
+pub struct ItemReader<R> {
+    reader: R,
+    name: String,
+    pending: String,
+    scanned: usize,
+    first_line: usize,
+    next_line: usize,
+    blocks: usize,
+    brackets: usize,
+    branch: bool,
+    significant: bool,
+    current: Option<SourceFile>,
+}
+impl<R: BufRead> ItemReader<R> {
+    pub fn new(name: impl Into<String>, reader: R) -> Self {
+        Self {
+            reader,
+            name: name.into(),
+            pending: String::new(),
+            scanned: 0,
+            first_line: 1,
+            next_line: 1,
+            blocks: 0,
+            brackets: 0,
+            branch: false,
+            significant: false,
+            current: None,
+        }
+    }
+    pub fn current(&self) -> Option<&SourceFile> {
+        self.current.as_ref()
+    }
+    pub fn next_item(&mut self) -> NebulaResult<Option<Program>> {
+        self.current = None;
+        loop {
+            let read = self
+                .reader
+                .read_line(&mut self.pending)
+                .map_err(|e| NebulaError::Io {
+                    message: format!("Cannot read '{}': {}", self.name, e),
+                })?;
+            if read == 0 {
+                break;
+            }
+            self.next_line += 1;
+            if self.scan() {
+                return self.finish().map(Some);
+            }
+        }
+        if !self.significant {
+            self.pending.clear();
+            return Ok(None);
+        }
+        self.finish().map(Some)
+    }
+    fn scan(&mut self) -> bool {
+        let tail = &self.pending[self.scanned..];
+        let tokens: Vec<_> = Lexer::new(tail).collect();
+        let open = tokens
+            .iter()
+            .any(|t| matches!(t.kind, TokenKind::Error(_)) && t.span.end() >= tail.len());
+        if open {
+            return false;
+        }
+        for token in &tokens {
+            match token.kind {
+                TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                    self.brackets += 1
+                }
+                TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                    self.brackets = self.brackets.saturating_sub(1)
+                }
+                TokenKind::Elsif | TokenKind::Catch | TokenKind::Finally => self.branch = true,
+                TokenKind::Do if self.branch => self.branch = false,
+                TokenKind::Do => self.blocks += 1,
+                TokenKind::End => self.blocks = self.blocks.saturating_sub(1),
+                TokenKind::Newline | TokenKind::Eof | TokenKind::DocComment(_) => continue,
+                _ => {}
+            }
+            self.significant = true;
+        }
+        self.scanned = self.pending.len();
+        self.significant && self.blocks == 0 && self.brackets == 0
+    }
+    fn finish(&mut self) -> NebulaResult<Program> {
+        let source = std::mem::take(&mut self.pending);
+        let file = SourceFile::new(&self.name, source).with_first_line(self.first_line);
+        self.first_line = self.next_line;
+        self.scanned = 0;
+        self.blocks = 0;
+        self.brackets = 0;
+        self.branch = false;
+        self.significant = false;
+        let tokens = Lexer::new(file.source())
+            .with_first_line(file.first_line())
+            .tokenize()
+            .map_err(|errors| NebulaError::combine(errors, "lexical errors"));
+        self.current = Some(file);
+        Parser::new(tokens?).parse_program()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::Item;
+    fn items(source: &str) -> Vec<(usize, String)> {
+        let mut reader = ItemReader::new("big.na", source.as_bytes());
+        let mut out = Vec::new();
+        while let Some(program) = reader.next_item().unwrap() {
+            let file = reader.current().unwrap();
+            assert!(!program.items.is_empty());
+            out.push((file.first_line(), file.source().trim().to_string()));
+        }
+        out
+    }
+    #[test]
+    fn test_items_are_read_one_at_a_time() {
+        let source = "## adds\nfn add(a, b) do\n  give a + b\nend\n\n\
+            perm text = \"two\nlines\"\n\
+            if x do\n  log(1)\nelsif y do\n  log(2)\nelse\n  log(3)\nend\n\
+            try do\n  f()\ncatch e do\n  log(e)\nfinally do\n  log(0)\nend\n\
+            perm xs = [\n  1,\n  2\n]\n\
+            struct P {\n  x: nb\n}\n\
+            ''' a block\ncomment ''' log(4)\n\
+            log(5)";
+        let read = items(source);
+        let firsts: Vec<_> = read.iter().map(|(line, _)| *line).collect();
+        assert_eq!(firsts, [1, 5, 8, 15, 22, 26, 29, 31]);
+        assert!(read[0].1.starts_with("## adds\nfn add"));
+        assert_eq!(read[1].1, "perm text = \"two\nlines\"");
+        assert!(read[2].1.ends_with("log(3)\nend"));
+        assert_eq!(read[4].1, "perm xs = [\n  1,\n  2\n]");
+        assert_eq!(read[7].1, "log(5)");
+    }
+    #[test]
+    fn test_item_errors_point_at_the_original_lines() {
+        let source = "log(1)\n\nperm x = )\nlog(2)";
+        let mut reader = ItemReader::new("big.na", source.as_bytes());
+        let first = reader.next_item().unwrap().unwrap();
+        assert!(matches!(first.items[0], Item::Statement(_)));
+        let error = reader.next_item().unwrap_err();
+        assert_eq!(error.span().map(|span| span.line), Some(3));
+        let file = reader.current().unwrap();
+        assert_eq!(file.line(3), Some("perm x = )"));
+        assert!(reader.next_item().unwrap().is_some());
+        assert!(reader.next_item().unwrap().is_none());
+        let mut reader = ItemReader::new("big.na", "log(1)\nfn f() do\n  log(2)\n".as_bytes());
+        reader.next_item().unwrap();
+        let error = reader.next_item().unwrap_err();
+        assert_eq!(error.code(), Some(crate::error::ErrorCode::E003));
+        assert_eq!(reader.current().unwrap().first_line(), 2);
+    }
+}
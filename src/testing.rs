@@ -0,0 +1,76 @@
+use crate::builtins::SandboxPolicy;
+use crate::error::NebulaError;
+use crate::interp::Interpreter;
+use crate::parser::ast::{Item, Program};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+pub const TEST_PREFIX: &str = "test_";
+#[derive(Debug)]
+pub struct TestOutcome {
+    pub name: String,
+    pub elapsed: Duration,
+    pub error: Option<NebulaError>,
+}
+impl TestOutcome {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+pub fn is_test_file(path: &Path) -> bool {
+    let stem = match path.file_stem().and_then(|s| s.to_str()) {
+        Some(stem) => stem,
+        None => return false,
+    };
+    path.extension().is_some_and(|ext| ext == "na")
+        && (stem.starts_with(TEST_PREFIX) || stem.ends_with("_test"))
+}
+pub fn discover(root: &Path) -> io::Result<Vec<PathBuf>> {
+    if root.is_file() {
+        return Ok(vec![root.to_path_buf()]);
+    }
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_test_file(&path) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+pub fn test_names(program: &Program) -> Vec<String> {
+    program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(f) if f.name.starts_with(TEST_PREFIX) => Some(f.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+pub fn run_tests(
+    program: &Program,
+    policy: SandboxPolicy,
+) -> Result<Vec<TestOutcome>, NebulaError> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_policy(policy);
+    interpreter.interpret(program)?;
+    let mut outcomes = Vec::new();
+    for name in test_names(program) {
+        let start = Instant::now();
+        let error = interpreter.call_global(&name, &[]).err();
+        outcomes.push(TestOutcome {
+            name,
+            elapsed: start.elapsed(),
+            error,
+        });
+    }
+    Ok(outcomes)
+}
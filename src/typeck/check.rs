@@ -1,369 +1,1205 @@
-use crate::error::{NebulaError, NebulaResult};
+use super::exhaustive::analyze;
+use super::infer::{compatible, element, join, join_all};
+use super::types::{NumericHints, NumericKind, Ty, TypeDef, TypeEnv};
+use crate::error::{closest, suggest, Diagnostic, ErrorCode, NebulaError, NebulaResult};
+use crate::lexer::Span;
 use crate::parser::ast::*;
-use super::types::{Ty, TypeEnv, TypeDef};
-use super::infer::InferCtx;
+use std::collections::HashMap;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeMode {
+    #[default]
+    Gradual,
+    Strict,
+}
+struct Signature {
+    params: Vec<Ty>,
+    required: usize,
+    variadic: bool,
+}
 pub struct TypeChecker {
     env: TypeEnv,
-    infer: InferCtx,
+    signatures: HashMap<String, Signature>,
+    returns: Vec<(String, Ty)>,
+    open_world: bool,
+    mode: TypeMode,
+    warnings: Vec<Diagnostic>,
+    numeric: NumericHints,
 }
 impl TypeChecker {
     pub fn new() -> Self {
+        Self::with_mode(TypeMode::default())
+    }
+    pub fn with_mode(mode: TypeMode) -> Self {
         Self {
             env: TypeEnv::new(),
-            infer: InferCtx::new(),
+            signatures: HashMap::new(),
+            returns: Vec::new(),
+            open_world: false,
+            mode,
+            warnings: Vec::new(),
+            numeric: HashMap::new(),
         }
     }
+    pub fn mode(&self) -> TypeMode {
+        self.mode
+    }
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+    pub fn numeric_hints(&self) -> &NumericHints {
+        &self.numeric
+    }
+    pub fn define(&mut self, name: &str, ty: Ty) {
+        self.env.declare(name.to_string(), ty);
+    }
+    pub fn infer(&mut self, expr: &Expr) -> NebulaResult<Ty> {
+        self.check_expr(expr)
+    }
     pub fn check_program(&mut self, program: &Program) -> NebulaResult<()> {
+        for name in crate::ext::declared_globals(program) {
+            self.env.define(name, Ty::Any);
+        }
         for item in &program.items {
             match item {
-                Item::Struct(s) => self.register_struct(s)?,
-                Item::Enum(e) => self.register_enum(e)?,
+                Item::Struct(s) => self.register_struct(s),
+                Item::Trait(t) => self.register_trait(t)?,
+                Item::Enum(e) => {
+                    self.env
+                        .define_type(e.name.clone(), TypeDef::Enum(e.variants.clone()));
+                    self.env.declare(e.name.clone(), Ty::Named(e.name.clone()));
+                }
+                Item::TypeAlias(alias) => {
+                    let ty = self.resolve(&alias.ty);
+                    self.env.define_type(alias.name.clone(), TypeDef::Alias(ty));
+                }
+                Item::Use(module) if module.native => self.open_world = true,
+                Item::Use(module) => {
+                    if module.items.is_empty() {
+                        self.env.define(module.binding().to_string(), Ty::Any);
+                    }
+                    for item in &module.items {
+                        self.env.define(item.clone(), Ty::Any);
+                    }
+                }
                 _ => {}
             }
         }
         for item in &program.items {
-            if let Item::Function(f) = item {
-                self.register_function(f)?;
+            match item {
+                Item::Function(f) => self.register_function(f),
+                Item::Impl(imp) => self.register_impl(imp)?,
+                _ => {}
             }
         }
         for item in &program.items {
             match item {
-                Item::Function(f) => self.check_function(f)?,
-                Item::Impl(i) => self.check_impl(i)?,
-                Item::Statement(s) => { self.check_stmt(s)?; }
+                Item::Function(f) => self.check_function(f, None)?,
+                Item::Impl(imp) => {
+                    for method in &imp.methods {
+                        self.check_function(method, Some(&imp.target))?;
+                    }
+                }
+                Item::Statement(s) => self.check_stmt(s)?,
                 _ => {}
             }
         }
         Ok(())
     }
-    fn register_struct(&mut self, s: &Struct) -> NebulaResult<()> {
-        let fields: Vec<_> = s.fields.iter()
-            .map(|f| (f.name.clone(), Ty::from_ast(&f.ty)))
+    fn resolve(&self, ty: &Type) -> Ty {
+        self.expand(Ty::from_ast(ty))
+    }
+    fn expand(&self, ty: Ty) -> Ty {
+        match ty {
+            Ty::Named(name) => match self.env.lookup_type(&name) {
+                Some(TypeDef::Alias(target)) => target.clone(),
+                _ => Ty::Named(name),
+            },
+            Ty::Lst(item) => Ty::Lst(Box::new(self.expand(*item))),
+            Ty::Set(item) => Ty::Set(Box::new(self.expand(*item))),
+            Ty::Map(key, value) => {
+                Ty::Map(Box::new(self.expand(*key)), Box::new(self.expand(*value)))
+            }
+            Ty::Tup(types) => Ty::Tup(types.into_iter().map(|t| self.expand(t)).collect()),
+            Ty::Optional(inner) => Ty::Optional(Box::new(self.expand(*inner))),
+            Ty::Union(members) => Ty::union(members.into_iter().map(|m| self.expand(m))),
+            other => other,
+        }
+    }
+    fn register_struct(&mut self, s: &Struct) {
+        let fields: Vec<_> = s
+            .fields
+            .iter()
+            .map(|f| (f.name.clone(), self.resolve(&f.ty)))
             .collect();
-        self.env.define_type(s.name.clone(), TypeDef::Struct(fields));
+        let params = fields.iter().map(|(_, ty)| ty.clone()).collect();
+        self.env
+            .define_type(s.name.clone(), TypeDef::Struct(fields));
+        self.env.declare(
+            s.name.clone(),
+            Ty::Function(params, Box::new(Ty::Named(s.name.clone()))),
+        );
+    }
+    fn register_trait(&mut self, t: &Trait) -> NebulaResult<()> {
+        let mut methods = Vec::new();
+        for sig in &t.methods {
+            receiver_param(&t.name, &sig.name, &sig.params, sig.span)?;
+            methods.push((
+                sig.name.clone(),
+                self.method_type(&sig.params, sig.return_type.as_ref()),
+            ));
+        }
+        self.env
+            .define_type(t.name.clone(), TypeDef::Trait(methods));
         Ok(())
     }
-    fn register_enum(&mut self, e: &Enum) -> NebulaResult<()> {
-        let variants: Vec<_> = e.variants.iter()
-            .map(|v| (v.name.clone(), v.fields.iter().map(Ty::from_ast).collect()))
+    fn register_impl(&mut self, imp: &Impl) -> NebulaResult<()> {
+        let unsatisfied = |message: String| type_error(ErrorCode::E096, imp.span, message);
+        let is_struct = matches!(self.env.lookup_type(&imp.target), Some(TypeDef::Struct(_)));
+        let Some(trait_name) = &imp.trait_name else {
+            if !is_struct {
+                return Err(unsatisfied(format!(
+                    "methods can only be added to a struct, not '{}'",
+                    imp.target
+                )));
+            }
+            let provided = self.register_methods(imp, None)?;
+            self.env.define_impl(&imp.target, None, provided);
+            return Ok(());
+        };
+        let Some(TypeDef::Trait(required)) = self.env.lookup_type(trait_name).cloned() else {
+            return Err(unsatisfied(format!("unknown trait '{}'", trait_name)));
+        };
+        if !is_struct {
+            return Err(unsatisfied(format!(
+                "{} can only be implemented for a struct, not '{}'",
+                trait_name, imp.target
+            )));
+        }
+        let provided = self.register_methods(imp, Some((trait_name, &required)))?;
+        let missing: Vec<_> = required
+            .iter()
+            .filter(|(name, _)| !provided.iter().any(|(p, _)| p == name))
+            .map(|(name, _)| name.as_str())
             .collect();
-        self.env.define_type(e.name.clone(), TypeDef::Enum(variants));
+        if !missing.is_empty() {
+            return Err(unsatisfied(format!(
+                "impl {} for {} is missing {}",
+                trait_name,
+                imp.target,
+                missing.join(", ")
+            )));
+        }
+        self.env
+            .define_impl(&imp.target, Some(trait_name), provided);
         Ok(())
     }
-    fn register_function(&mut self, f: &Function) -> NebulaResult<()> {
-        let param_types: Vec<_> = f.params.iter()
-            .map(|p| Ty::from_ast(&p.ty))
+    fn register_methods(
+        &mut self,
+        imp: &Impl,
+        required: Option<(&str, &[(String, Ty)])>,
+    ) -> NebulaResult<Vec<(String, Ty)>> {
+        let mut provided = Vec::new();
+        for f in &imp.methods {
+            receiver_param(&imp.target, &f.name, &f.params, f.span)?;
+            let ty = self.method_type(&f.params, f.return_type.as_ref());
+            if let Some((trait_name, required)) = required {
+                let Some((_, expected)) = required.iter().find(|(name, _)| *name == f.name) else {
+                    return Err(type_error(
+                        ErrorCode::E096,
+                        f.span,
+                        format!("'{}' is not a method of trait {}", f.name, trait_name),
+                    ));
+                };
+                if !conforms(expected, &ty) {
+                    return Err(type_error(
+                        ErrorCode::E096,
+                        f.span,
+                        format!(
+                            "{}.{}() is {} but {} requires {}",
+                            imp.target, f.name, ty, trait_name, expected
+                        ),
+                    ));
+                }
+            }
+            if let Ty::Function(params, _) = &ty {
+                let signature = Signature {
+                    params: params.clone(),
+                    required: f.params[1..]
+                        .iter()
+                        .filter(|p| p.default.is_none() && !p.variadic)
+                        .count(),
+                    variadic: f.params.iter().any(|p| p.variadic),
+                };
+                self.signatures
+                    .insert(format!("{}.{}", imp.target, f.name), signature);
+            }
+            provided.push((f.name.clone(), ty));
+        }
+        Ok(provided)
+    }
+    fn method_type(&self, params: &[Param], return_type: Option<&Type>) -> Ty {
+        let params = params[1..]
+            .iter()
+            .map(|p| p.ty.as_ref().map_or(Ty::Any, |ty| self.resolve(ty)))
             .collect();
-        let return_type = f.return_type.as_ref()
-            .map(Ty::from_ast)
-            .unwrap_or(Ty::Unit);
-        let fn_type = Ty::Function(param_types, Box::new(return_type));
-        self.env.define(f.name.clone(), fn_type);
-        Ok(())
+        let ret = return_type.map_or(Ty::Any, |ty| self.resolve(ty));
+        Ty::Function(params, Box::new(ret))
     }
-    fn check_function(&mut self, f: &Function) -> NebulaResult<()> {
-        self.env.push_scope();
-        for param in &f.params {
-            let ty = Ty::from_ast(&param.ty);
-            self.env.define(param.name.clone(), ty);
+    fn lookup_method(
+        &self,
+        receiver: &Ty,
+        method: &str,
+        span: Span,
+    ) -> NebulaResult<Option<(String, Ty)>> {
+        let Ty::Named(name) = receiver else {
+            return Ok(None);
+        };
+        let methods = match self.env.lookup_type(name) {
+            Some(TypeDef::Trait(methods)) => methods.as_slice(),
+            Some(TypeDef::Struct(_)) => self.env.methods(name),
+            _ => return Ok(None),
+        };
+        match methods.iter().find(|(n, _)| n == method) {
+            Some((_, ty)) => Ok(Some((format!("{}.{}", name, method), ty.clone()))),
+            None => Err(type_error(
+                ErrorCode::E095,
+                span,
+                format!(
+                    "{} has no method '{}'{}",
+                    name,
+                    method,
+                    suggest(method, methods.iter().map(|(n, _)| n.as_str()))
+                ),
+            )),
         }
-        let return_type = f.return_type.as_ref()
-            .map(Ty::from_ast)
-            .unwrap_or(Ty::Unit);
-        for stmt in &f.body {
-            self.check_stmt(stmt)?;
+    }
+    fn accepts(&self, expected: &Ty, actual: &Ty) -> bool {
+        match (expected, actual) {
+            (Ty::Named(trait_name), Ty::Named(target))
+                if self.env.implements(target, trait_name) =>
+            {
+                true
+            }
+            (Ty::Named(_), Ty::Union(members)) => members.iter().all(|m| self.accepts(expected, m)),
+            (Ty::Optional(inner), Ty::Optional(other)) => self.accepts(inner, other),
+            (Ty::Optional(inner), _) if !actual.is_nil() => self.accepts(inner, actual),
+            (Ty::Int, Ty::Nb | Ty::Fl) => self.mode == TypeMode::Gradual,
+            _ => compatible(expected, actual),
         }
-        self.env.pop_scope();
-        Ok(())
     }
-    fn check_impl(&mut self, i: &Impl) -> NebulaResult<()> {
-        for method in &i.methods {
-            self.env.push_scope();
-            let self_type = Ty::Generic(i.type_name.clone(), vec![]);
-            self.env.define("self".to_string(), self_type);
-            for param in &method.params {
-                if param.name != "self" {
-                    let ty = Ty::from_ast(&param.ty);
-                    self.env.define(param.name.clone(), ty);
+    fn register_function(&mut self, f: &Function) {
+        let params: Vec<_> = f
+            .params
+            .iter()
+            .map(|p| p.ty.as_ref().map_or(Ty::Any, |ty| self.resolve(ty)))
+            .collect();
+        let return_type = f
+            .return_type
+            .as_ref()
+            .map_or(Ty::Any, |ty| self.resolve(ty));
+        let signature = Signature {
+            params: params.clone(),
+            required: f
+                .params
+                .iter()
+                .filter(|p| p.default.is_none() && !p.variadic)
+                .count(),
+            variadic: f.params.iter().any(|p| p.variadic),
+        };
+        self.signatures.insert(f.name.clone(), signature);
+        self.env
+            .declare(f.name.clone(), Ty::Function(params, Box::new(return_type)));
+    }
+    fn check_function(&mut self, f: &Function, receiver: Option<&str>) -> NebulaResult<()> {
+        self.env.push_scope();
+        for (index, param) in f.params.iter().enumerate() {
+            let ty = match (&param.ty, receiver) {
+                (None, Some(target)) if index == 0 => Ty::Named(target.to_string()),
+                (ty, _) => ty.as_ref().map_or(Ty::Any, |ty| self.resolve(ty)),
+            };
+            if let Some(default) = &param.default {
+                let default_type = self.check_expr(default)?;
+                if !self.accepts(&ty, &default_type) {
+                    return Err(mismatch(
+                        default.span,
+                        format!(
+                            "default for '{}' is {} but the parameter is {}",
+                            param.name, default_type, ty
+                        ),
+                    ));
                 }
             }
-            for stmt in &method.body {
-                self.check_stmt(stmt)?;
-            }
-            self.env.pop_scope();
+            let ty = if param.variadic {
+                Ty::Lst(Box::new(ty))
+            } else {
+                ty
+            };
+            self.env.declare(param.name.clone(), ty);
         }
-        Ok(())
+        let return_type = f
+            .return_type
+            .as_ref()
+            .map_or(Ty::Any, |ty| self.resolve(ty));
+        self.returns.push((f.name.clone(), return_type));
+        let result = match &*f.body {
+            FunctionBody::Expression(expr) => self
+                .check_expr(expr)
+                .and_then(|ty| self.check_return(&ty, expr.span)),
+            FunctionBody::Block(stmts) => stmts.iter().try_for_each(|s| self.check_stmt(s)),
+        };
+        self.returns.pop();
+        self.env.pop_scope();
+        result
     }
-    fn check_stmt(&mut self, stmt: &Stmt) -> NebulaResult<Ty> {
-        match stmt {
-            Stmt::Let { name, ty, value, .. } => {
+    fn check_block(&mut self, stmts: &[Stmt]) -> NebulaResult<()> {
+        self.env.push_scope();
+        let result = stmts.iter().try_for_each(|s| self.check_stmt(s));
+        self.env.pop_scope();
+        result
+    }
+    fn check_stmt(&mut self, stmt: &Stmt) -> NebulaResult<()> {
+        match &stmt.kind {
+            StmtKind::Var { name, ty, value } | StmtKind::Const { name, ty, value } => {
                 let value_type = self.check_expr(value)?;
-                let declared_type = ty.as_ref()
-                    .map(Ty::from_ast)
-                    .unwrap_or_else(|| self.infer.fresh_var());
-                if !self.infer.unify(&declared_type, &value_type) {
-                    return Err(NebulaError::TypeMismatch {
-                        expected: format!("{:?}", declared_type),
-                        got: format!("{:?}", value_type),
-                    });
-                }
-                let resolved = self.infer.resolve(&declared_type);
-                self.env.define(name.clone(), resolved);
-                Ok(Ty::Unit)
-            }
-            Stmt::Assignment { target, value } => {
+                match ty {
+                    Some(ty) => {
+                        let declared = self.resolve(ty);
+                        if !self.accepts(&declared, &value_type) {
+                            return Err(mismatch(
+                                value.span,
+                                format!(
+                                    "'{}' is declared {} but initialised with {}",
+                                    name, declared, value_type
+                                ),
+                            ));
+                        }
+                        self.env.declare(name.clone(), declared);
+                    }
+                    None => self.env.define(name.clone(), self.unannotated(value_type)),
+                }
+                Ok(())
+            }
+            StmtKind::Assignment { target, value } => {
+                let expected = match &target.kind {
+                    ExprKind::Variable(name) => self.env.lookup(name).map(|b| b.ty.clone()),
+                    _ => None,
+                };
+                let value_type = self.check_expecting(value, &expected.unwrap_or(Ty::Any))?;
+                self.check_assignment(target, value_type)
+            }
+            StmtKind::CompoundAssignment { target, op, value } => {
                 let target_type = self.check_expr(target)?;
                 let value_type = self.check_expr(value)?;
-                if !self.infer.unify(&target_type, &value_type) {
-                    return Err(NebulaError::TypeMismatch {
-                        expected: format!("{:?}", target_type),
-                        got: format!("{:?}", value_type),
-                    });
-                }
-                Ok(Ty::Unit)
-            }
-            Stmt::If { condition, then_block, elif_branches, else_block } => {
-                let cond_type = self.check_expr(condition)?;
-                if !self.infer.unify(&cond_type, &Ty::Bool) {
-                    return Err(NebulaError::TypeMismatch {
-                        expected: "bool".to_string(),
-                        got: format!("{:?}", cond_type),
-                    });
+                let op = match op {
+                    CompoundOp::Add => BinaryOp::Add,
+                    CompoundOp::Sub => BinaryOp::Sub,
+                    CompoundOp::Mul => BinaryOp::Mul,
+                    CompoundOp::Div => BinaryOp::Div,
+                };
+                let result = binary_type(op, &target_type, &value_type, stmt.span)?;
+                self.check_assignment(target, result)
+            }
+            StmtKind::If {
+                condition,
+                then_block,
+                elif_branches,
+                else_block,
+            } => {
+                let test = self.narrowing(condition);
+                self.check_expr(condition)?;
+                let when_true = test.as_ref().map(|(name, ty, _)| (name, ty));
+                let when_false = test
+                    .as_ref()
+                    .filter(|_| elif_branches.is_empty())
+                    .map(|(name, _, ty)| (name, ty));
+                self.with_narrowed(when_true, |c| c.check_block(then_block))?;
+                for (elif_condition, elif_body) in elif_branches {
+                    self.check_expr(elif_condition)?;
+                    self.check_block(elif_body)?;
+                }
+                if let Some(else_body) = else_block {
+                    self.with_narrowed(when_false, |c| c.check_block(else_body))?;
+                }
+                let after = match else_block {
+                    None if diverges(then_block) => when_false,
+                    Some(else_body) if diverges(else_body) && !diverges(then_block) => {
+                        when_true.filter(|_| elif_branches.is_empty())
+                    }
+                    _ => None,
+                };
+                if let Some((name, ty)) = after.map(|(n, t)| (n.clone(), t.clone())) {
+                    self.env.narrow(&name, ty);
+                }
+                Ok(())
+            }
+            StmtKind::While { condition, body } => {
+                self.check_expr(condition)?;
+                self.check_block(body)
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                for bound in [Some(start), Some(end), step.as_ref()]
+                    .into_iter()
+                    .flatten()
+                {
+                    let ty = self.check_expr(bound)?;
+                    if !compatible(&Ty::Int, &ty) {
+                        return Err(type_error(
+                            ErrorCode::E092,
+                            bound.span,
+                            format!("for loop bounds must be int, got {}", ty),
+                        ));
+                    }
                 }
                 self.env.push_scope();
-                for stmt in then_block {
-                    self.check_stmt(stmt)?;
+                self.env.define(var.clone(), Ty::Int);
+                let result = self.check_block(body);
+                self.env.pop_scope();
+                result
+            }
+            StmtKind::Each {
+                var,
+                iterator,
+                body,
+            } => {
+                let ty = self.check_expr(iterator)?;
+                let ty = self.present(ty, iterator)?;
+                if ty.is_numeric()
+                    || ty.is_nil()
+                    || matches!(ty, Ty::Bool | Ty::Chr | Ty::By | Ty::Function(..))
+                {
+                    return Err(type_error(
+                        ErrorCode::E092,
+                        iterator.span,
+                        format!("cannot iterate over {}", ty),
+                    ));
                 }
+                self.env.push_scope();
+                self.env.define(var.clone(), element(&ty));
+                let result = self.check_block(body);
                 self.env.pop_scope();
-                for (elif_cond, elif_body) in elif_branches {
-                    let elif_cond_type = self.check_expr(elif_cond)?;
-                    if !self.infer.unify(&elif_cond_type, &Ty::Bool) {
-                        return Err(NebulaError::TypeMismatch {
-                            expected: "bool".to_string(),
-                            got: format!("{:?}", elif_cond_type),
-                        });
-                    }
+                result
+            }
+            StmtKind::Match { value, arms } => {
+                let value_type = self.check_expr(value)?;
+                let mut remaining = value_type.clone();
+                for arm in arms {
                     self.env.push_scope();
-                    for stmt in elif_body {
-                        self.check_stmt(stmt)?;
+                    if let ExprKind::Variable(name) = &value.kind {
+                        if remaining != value_type {
+                            self.env.narrow(name, remaining.clone());
+                        }
                     }
+                    let result = self.check_arm(arm, &remaining);
                     self.env.pop_scope();
+                    result?;
+                    if matches!(arm.pattern, Pattern::Nil) {
+                        remaining = remaining.non_nil();
+                    }
                 }
-                if let Some(else_body) = else_block {
+                let report = analyze(arms, &value_type, &self.env);
+                for index in &report.unreachable {
+                    self.warnings.push(
+                        Diagnostic::warning("unreachable match arm", arms[*index].body.span)
+                            .with_help("an earlier arm already matches these values"),
+                    );
+                }
+                if !report.missing.is_empty() {
+                    return Err(type_error(
+                        ErrorCode::E093,
+                        stmt.span,
+                        format!(
+                            "non-exhaustive match on {}: missing {}",
+                            value_type,
+                            report.missing.join(", ")
+                        ),
+                    ));
+                }
+                if report.catch_all_needed {
+                    return Err(type_error(
+                        ErrorCode::E093,
+                        stmt.span,
+                        format!("non-exhaustive match on {}: add a '_' arm", value_type),
+                    ));
+                }
+                Ok(())
+            }
+            StmtKind::Try {
+                try_block,
+                catch_var,
+                catch_block,
+                finally_block,
+            } => {
+                self.check_block(try_block)?;
+                if let Some(catch_body) = catch_block {
                     self.env.push_scope();
-                    for stmt in else_body {
-                        self.check_stmt(stmt)?;
+                    if let Some(var) = catch_var {
+                        self.env.define(var.clone(), Ty::Any);
                     }
+                    let result = self.check_block(catch_body);
                     self.env.pop_scope();
+                    result?;
                 }
-                Ok(Ty::Unit)
-            }
-            Stmt::While { condition, body } => {
-                let cond_type = self.check_expr(condition)?;
-                if !self.infer.unify(&cond_type, &Ty::Bool) {
-                    return Err(NebulaError::TypeMismatch {
-                        expected: "bool".to_string(),
-                        got: format!("{:?}", cond_type),
-                    });
-                }
-                self.env.push_scope();
-                for stmt in body {
-                    self.check_stmt(stmt)?;
+                match finally_block {
+                    Some(finally_body) => self.check_block(finally_body),
+                    None => Ok(()),
                 }
-                self.env.pop_scope();
-                Ok(Ty::Unit)
             }
-            Stmt::For { binding, iterator, body } => {
-                let iter_type = self.check_expr(iterator)?;
-                self.env.push_scope();
-                self.env.define(binding.clone(), Ty::I64);
-                for stmt in body {
-                    self.check_stmt(stmt)?;
-                }
-                self.env.pop_scope();
-                Ok(Ty::Unit)
+            StmtKind::Return(expr) => {
+                let ty = match expr {
+                    Some(e) => self.check_expr(e)?,
+                    None => Ty::Nil,
+                };
+                self.check_return(&ty, stmt.span)
             }
-            Stmt::Loop { body } => {
-                self.env.push_scope();
-                for stmt in body {
-                    self.check_stmt(stmt)?;
+            StmtKind::Break | StmtKind::Continue => Ok(()),
+            StmtKind::Expression(expr) => self.check_expr(expr).map(drop),
+        }
+    }
+    fn check_arm(&mut self, arm: &MatchArm, value_type: &Ty) -> NebulaResult<()> {
+        match &arm.pattern {
+            Pattern::Binding(name) => self.env.define(name.clone(), value_type.clone()),
+            Pattern::Literal(lit) => {
+                let ty = literal_type(lit);
+                if !compatible(value_type, &ty) {
+                    return Err(type_error(
+                        ErrorCode::E094,
+                        arm.span,
+                        format!("{} pattern can never match a {}", ty, value_type),
+                    ));
                 }
-                self.env.pop_scope();
-                Ok(Ty::Unit)
             }
-            Stmt::Return(expr) => {
-                if let Some(e) = expr {
-                    self.check_expr(e)?;
-                }
-                Ok(Ty::Never)
+            Pattern::Nil if !compatible(value_type, &Ty::Nil) => {
+                return Err(type_error(
+                    ErrorCode::E094,
+                    arm.span,
+                    format!("empty pattern can never match a {}", value_type),
+                ));
             }
-            Stmt::Break | Stmt::Continue => Ok(Ty::Never),
-            Stmt::Expression(expr) => self.check_expr(expr),
-            Stmt::Match { value, arms } => {
-                let value_type = self.check_expr(value)?;
-                for arm in arms {
-                    self.check_expr(&arm.body)?;
+            Pattern::Nil | Pattern::Wildcard => {}
+        }
+        self.check_expr(&arm.body).map(drop)
+    }
+    fn check_return(&self, ty: &Ty, span: Span) -> NebulaResult<()> {
+        match self.returns.last() {
+            Some((name, expected)) if !self.accepts(expected, ty) => Err(mismatch(
+                span,
+                format!("{}() declares {} but gives {}", name, expected, ty),
+            )),
+            _ => Ok(()),
+        }
+    }
+    fn check_assignment(&mut self, target: &Expr, value_type: Ty) -> NebulaResult<()> {
+        let ExprKind::Variable(name) = &target.kind else {
+            let target_type = self.check_expr(target)?;
+            if !self.accepts(&target_type, &value_type) {
+                return Err(mismatch(
+                    target.span,
+                    format!("cannot assign {} to a {} slot", value_type, target_type),
+                ));
+            }
+            return Ok(());
+        };
+        let Some(binding) = self.env.lookup(name) else {
+            return self.undefined(name, target.span).map(drop);
+        };
+        if binding.annotated && !self.accepts(&binding.declared, &value_type) {
+            return Err(mismatch(
+                target.span,
+                format!(
+                    "cannot assign {} to '{}' of type {}",
+                    value_type, name, binding.declared
+                ),
+            ));
+        }
+        let retyped = if binding.annotated {
+            Some(binding.declared.clone()).filter(|_| !compatible(&binding.ty, &value_type))
+        } else {
+            Some(join(&binding.ty, &value_type)).filter(|_| binding.ty != value_type)
+        };
+        if let Some(ty) = retyped {
+            self.env.retype(name, ty);
+        }
+        Ok(())
+    }
+    fn narrowing(&self, condition: &Expr) -> Option<(String, Ty, Ty)> {
+        let ExprKind::Binary { left, op, right } = &condition.kind else {
+            return None;
+        };
+        let equal = match op {
+            BinaryOp::Eq => true,
+            BinaryOp::Ne => false,
+            _ => return None,
+        };
+        let (subject, probe) = match &left.kind {
+            ExprKind::Nil | ExprKind::Literal(Literal::String(_)) => (right, left),
+            _ => (left, right),
+        };
+        let (name, matched, rest) = match (&subject.kind, &probe.kind) {
+            (ExprKind::Variable(name), ExprKind::Nil) => match &self.env.lookup(name)?.ty {
+                Ty::Optional(inner) => (name, Ty::Nil, (**inner).clone()),
+                _ => return None,
+            },
+            (ExprKind::Call { callee, args }, ExprKind::Literal(Literal::String(type_name))) => {
+                let (ExprKind::Variable(func), [arg]) = (&callee.kind, args.as_slice()) else {
+                    return None;
+                };
+                let ExprKind::Variable(name) = &arg.kind else {
+                    return None;
+                };
+                if func != "typeof" || self.env.lookup(func).is_some() {
+                    return None;
                 }
-                Ok(Ty::Unit)
+                let (matched, rest) = self.split_by_type(&self.env.lookup(name)?.ty, type_name)?;
+                (name, matched, rest)
+            }
+            _ => return None,
+        };
+        Some(if equal {
+            (name.clone(), matched, rest)
+        } else {
+            (name.clone(), rest, matched)
+        })
+    }
+    fn split_by_type(&self, ty: &Ty, type_name: &str) -> Option<(Ty, Ty)> {
+        let members = match ty {
+            Ty::Any => return runtime_type(type_name).map(|matched| (matched, Ty::Any)),
+            Ty::Union(members) => members.clone(),
+            Ty::Optional(inner) => match &**inner {
+                Ty::Union(members) => members.iter().cloned().chain([Ty::Nil]).collect(),
+                other => vec![other.clone(), Ty::Nil],
+            },
+            _ => return None,
+        };
+        let numeric = runtime_type(type_name).is_some_and(|t| t.is_numeric());
+        let (matched, rest): (Vec<_>, Vec<_>) = members.into_iter().partition(|member| {
+            if numeric {
+                member.is_numeric()
+            } else {
+                self.runtime_name(member) == Some(type_name)
             }
+        });
+        if matched.is_empty() {
+            return None;
         }
+        Some((Ty::union(matched), Ty::union(rest)))
+    }
+    fn runtime_name(&self, ty: &Ty) -> Option<&'static str> {
+        Some(match ty {
+            Ty::Nb => "nb",
+            Ty::Int => "int",
+            Ty::Fl => "fl",
+            Ty::Wrd => "wrd",
+            Ty::Bool => "bool",
+            Ty::By => "by",
+            Ty::Chr => "chr",
+            Ty::Nil | Ty::Void => "nil",
+            Ty::Range => "range",
+            Ty::Lst(_) => "lst",
+            Ty::Map(..) => "map",
+            Ty::Tup(_) => "tup",
+            Ty::Set(_) => "set",
+            Ty::Function(..) => "fn",
+            Ty::Named(name) => match self.env.lookup_type(name) {
+                Some(TypeDef::Struct(_) | TypeDef::Trait(_)) => "struct",
+                _ => return None,
+            },
+            Ty::Any | Ty::Optional(_) | Ty::Union(_) => return None,
+        })
+    }
+    fn with_narrowed<T>(
+        &mut self,
+        narrowed: Option<(&String, &Ty)>,
+        check: impl FnOnce(&mut Self) -> NebulaResult<T>,
+    ) -> NebulaResult<T> {
+        self.env.push_scope();
+        if let Some((name, ty)) = narrowed {
+            self.env.narrow(name, ty.clone());
+        }
+        let result = check(self);
+        self.env.pop_scope();
+        result
+    }
+    fn present(&self, ty: Ty, expr: &Expr) -> NebulaResult<Ty> {
+        if !matches!(ty, Ty::Optional(_)) {
+            return Ok(ty);
+        }
+        let subject = match &expr.kind {
+            ExprKind::Variable(name) => format!("'{}'", name),
+            _ => "this value".to_string(),
+        };
+        Err(NebulaError::coded_at(
+            ErrorCode::E013,
+            format!(
+                "{} is {} and may be empty; check it with '!= empty', a match or '??' first",
+                subject, ty
+            ),
+            expr.span,
+        ))
+    }
+    fn unannotated(&self, inferred: Ty) -> Ty {
+        match self.mode {
+            TypeMode::Gradual => Ty::Any,
+            TypeMode::Strict => inferred,
+        }
+    }
+    fn undefined(&self, name: &str, span: Span) -> NebulaResult<Ty> {
+        if self.open_world || self.mode == TypeMode::Gradual || crate::builtins::is_builtin(name) {
+            return Ok(Ty::Any);
+        }
+        Err(NebulaError::UndefinedVariable {
+            name: name.to_string(),
+            suggestion: closest(name, self.env.names()).map(str::to_string),
+            span: Some(span),
+        })
     }
     fn check_expr(&mut self, expr: &Expr) -> NebulaResult<Ty> {
-        match expr {
-            Expr::Literal(lit) => Ok(self.literal_type(lit)),
-            Expr::Variable(name) => {
-                self.env.lookup(name)
-                    .cloned()
-                    .ok_or_else(|| NebulaError::UndefinedVariable { name: name.clone() })
-            }
-            Expr::Binary { left, op, right } => {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(lit) => Ok(literal_type(lit)),
+            ExprKind::Variable(name) => match self.env.lookup(name) {
+                Some(binding) => Ok(binding.ty.clone()),
+                None => self.undefined(name, span),
+            },
+            ExprKind::Binary {
+                left,
+                op: BinaryOp::Coalesce,
+                right,
+            } => {
                 let left_type = self.check_expr(left)?;
                 let right_type = self.check_expr(right)?;
-                match op {
-                    BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
-                        if !self.infer.unify(&left_type, &right_type) {
-                            return Err(NebulaError::TypeMismatch {
-                                expected: format!("{:?}", left_type),
-                                got: format!("{:?}", right_type),
-                            });
-                        }
-                        Ok(self.infer.resolve(&left_type))
-                    }
-                    BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt | 
-                    BinaryOp::Le | BinaryOp::Ge => {
-                        if !self.infer.unify(&left_type, &right_type) {
-                            return Err(NebulaError::TypeMismatch {
-                                expected: format!("{:?}", left_type),
-                                got: format!("{:?}", right_type),
-                            });
-                        }
-                        Ok(Ty::Bool)
-                    }
-                    BinaryOp::And | BinaryOp::Or => {
-                        if !self.infer.unify(&left_type, &Ty::Bool) {
-                            return Err(NebulaError::TypeMismatch {
-                                expected: "bool".to_string(),
-                                got: format!("{:?}", left_type),
-                            });
-                        }
-                        if !self.infer.unify(&right_type, &Ty::Bool) {
-                            return Err(NebulaError::TypeMismatch {
-                                expected: "bool".to_string(),
-                                got: format!("{:?}", right_type),
-                            });
-                        }
-                        Ok(Ty::Bool)
-                    }
-                    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor |
-                    BinaryOp::Shl | BinaryOp::Shr => {
-                        if !self.infer.unify(&left_type, &right_type) {
-                            return Err(NebulaError::TypeMismatch {
-                                expected: format!("{:?}", left_type),
-                                got: format!("{:?}", right_type),
-                            });
-                        }
-                        Ok(self.infer.resolve(&left_type))
-                    }
+                Ok(match left_type {
+                    Ty::Optional(inner) => join(&inner, &right_type),
+                    ty if ty.is_nil() => right_type,
+                    ty => ty,
+                })
+            }
+            ExprKind::Binary { left, op, right } => {
+                let mut left_type = self.check_expr(left)?;
+                let mut right_type = self.check_expr(right)?;
+                if !matches!(
+                    op,
+                    BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or
+                ) {
+                    left_type = self.present(left_type, left)?;
+                    right_type = self.present(right_type, right)?;
+                }
+                let kind = match (&left_type, &right_type) {
+                    (Ty::Int, Ty::Int) => Some(NumericKind::Int),
+                    (Ty::Fl, Ty::Fl) => Some(NumericKind::Float),
+                    _ => None,
+                };
+                if let Some(kind) =
+                    kind.filter(|_| matches!(op, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul))
+                {
+                    self.numeric.insert(span, kind);
                 }
+                binary_type(*op, &left_type, &right_type, span)
             }
-            Expr::Unary { op, operand } => {
-                let operand_type = self.check_expr(operand)?;
+            ExprKind::Unary { op, operand } => {
+                let ty = self.check_expr(operand)?;
+                let ty = match op {
+                    UnaryOp::Not => ty,
+                    _ => self.present(ty, operand)?,
+                };
                 match op {
-                    UnaryOp::Neg => Ok(operand_type),
-                    UnaryOp::Not => {
-                        if !self.infer.unify(&operand_type, &Ty::Bool) {
-                            return Err(NebulaError::TypeMismatch {
-                                expected: "bool".to_string(),
-                                got: format!("{:?}", operand_type),
-                            });
-                        }
-                        Ok(Ty::Bool)
+                    UnaryOp::Not => Ok(Ty::Bool),
+                    UnaryOp::Neg | UnaryOp::BitNot if ty.is_numeric() || ty.is_any() => {
+                        Ok(if *op == UnaryOp::BitNot { Ty::Int } else { ty })
                     }
-                    UnaryOp::BitNot => Ok(operand_type),
+                    _ => Err(type_error(
+                        ErrorCode::E091,
+                        span,
+                        format!("cannot negate {}", ty),
+                    )),
                 }
             }
-            Expr::Call { callee, args } => {
+            ExprKind::Call { callee, args } => {
                 let callee_type = self.check_expr(callee)?;
-                match callee_type {
-                    Ty::Function(params, ret) => {
-                        if params.len() != args.len() {
-                            return Err(NebulaError::InvalidOperation {
-                                message: format!("Expected {} arguments, got {}", params.len(), args.len()),
-                            });
-                        }
-                        for (param_type, arg) in params.iter().zip(args.iter()) {
-                            let arg_type = self.check_expr(arg)?;
-                            if !self.infer.unify(param_type, &arg_type) {
-                                return Err(NebulaError::TypeMismatch {
-                                    expected: format!("{:?}", param_type),
-                                    got: format!("{:?}", arg_type),
-                                });
-                            }
-                        }
-                        Ok(*ret)
+                let callee_type = self.present(callee_type, callee)?;
+                let name = match &callee.kind {
+                    ExprKind::Variable(name) => Some(name.as_str()),
+                    _ => None,
+                };
+                if let Some(builtin) = name.filter(|n| self.env.lookup(n).is_none()) {
+                    if let Some(ty) = self.check_higher_order(builtin, args)? {
+                        return Ok(ty);
                     }
-                    _ => Err(NebulaError::InvalidOperation {
-                        message: "Cannot call non-function".to_string(),
-                    })
-                }
-            }
-            Expr::Array(elements) => {
-                if elements.is_empty() {
-                    return Ok(Ty::Array(Box::new(self.infer.fresh_var()), 0));
-                }
-                let first_type = self.check_expr(&elements[0])?;
-                for elem in &elements[1..] {
-                    let elem_type = self.check_expr(elem)?;
-                    if !self.infer.unify(&first_type, &elem_type) {
-                        return Err(NebulaError::TypeMismatch {
-                            expected: format!("{:?}", first_type),
-                            got: format!("{:?}", elem_type),
-                        });
+                }
+                self.check_call(name, callee_type, args, span)
+            }
+            ExprKind::StructInit { name, args } => match self.env.lookup(name) {
+                Some(_) if matches!(self.env.lookup_type(name), Some(TypeDef::Struct(_))) => {
+                    self.check_struct_init(name, args, span)
+                }
+                Some(binding) => {
+                    let ty = binding.ty.clone();
+                    self.check_call(Some(name), ty, args, span)
+                }
+                None => {
+                    self.check_args(args)?;
+                    self.undefined(name, span)
+                }
+            },
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let ty = self.check_expr(receiver)?;
+                let ty = self.present(ty, receiver)?;
+                match self.lookup_method(&ty, method, span)? {
+                    Some((name, callee)) => self.check_call(Some(&name), callee, args, span),
+                    None => {
+                        self.check_args(args)?;
+                        Ok(Ty::Any)
                     }
                 }
-                Ok(Ty::Array(Box::new(self.infer.resolve(&first_type)), elements.len()))
             }
-            Expr::Tuple(elements) => {
-                let types: Result<Vec<_>, _> = elements.iter()
-                    .map(|e| self.check_expr(e))
-                    .collect();
-                Ok(Ty::Tuple(types?))
+            ExprKind::Field { object, field } => {
+                let ty = self.check_expr(object)?;
+                let ty = self.present(ty, object)?;
+                self.field_type(&ty, field, span)
             }
-            Expr::Unit => Ok(Ty::Unit),
-            Expr::StructLiteral { name, fields } => {
-                Ok(Ty::Generic(name.clone(), vec![]))
+            ExprKind::Index { array, index } => {
+                let container = self.check_expr(array)?;
+                let container = self.present(container, array)?;
+                let index_type = self.check_expr(index)?;
+                match container {
+                    Ty::Lst(item) if compatible(&Ty::Int, &index_type) => Ok(*item),
+                    Ty::Lst(_) | Ty::Tup(_) if !compatible(&Ty::Int, &index_type) => {
+                        Err(type_error(
+                            ErrorCode::E021,
+                            index.span,
+                            format!("list index must be int, got {}", index_type),
+                        ))
+                    }
+                    Ty::Map(_, value) => Ok(*value),
+                    _ => Ok(Ty::Any),
+                }
             }
-            Expr::Field { object, field } => {
-                let obj_type = self.check_expr(object)?;
-                Ok(self.infer.fresh_var())
+            ExprKind::Slice { array, start, end } => {
+                let ty = self.check_expr(array)?;
+                let ty = self.present(ty, array)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.check_expr(bound)?;
+                }
+                Ok(match ty {
+                    Ty::Lst(_) | Ty::Wrd => ty,
+                    _ => Ty::Any,
+                })
             }
-            Expr::MethodCall { receiver, method, args } => {
-                let _receiver_type = self.check_expr(receiver)?;
-                Ok(self.infer.fresh_var())
+            ExprKind::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                let test = self.narrowing(condition);
+                self.check_expr(condition)?;
+                let when_true = test.as_ref().map(|(name, ty, _)| (name, ty));
+                let when_false = test.as_ref().map(|(name, _, ty)| (name, ty));
+                let then_type = self.with_narrowed(when_true, |c| c.check_expr(then_expr))?;
+                let else_type = self.with_narrowed(when_false, |c| c.check_expr(else_expr))?;
+                Ok(join(&then_type, &else_type))
             }
-            Expr::Index { array, index } => {
-                let array_type = self.check_expr(array)?;
-                let index_type = self.check_expr(index)?;
-                if !index_type.is_integer() && !matches!(index_type, Ty::Var(_)) {
-                    return Err(NebulaError::TypeMismatch {
-                        expected: "integer".to_string(),
-                        got: format!("{:?}", index_type),
-                    });
+            ExprKind::Lambda { params, body } => {
+                self.check_lambda(params, body, &vec![Ty::Any; params.len()], &Ty::Any)
+            }
+            ExprKind::List(elements) => Ok(Ty::Lst(Box::new(join_all(self.check_args(elements)?)))),
+            ExprKind::Map(pairs) => {
+                let mut values = Vec::with_capacity(pairs.len());
+                for (key, value) in pairs {
+                    self.check_expr(key)?;
+                    values.push(self.check_expr(value)?);
                 }
-                match array_type {
-                    Ty::Array(elem, _) | Ty::Slice(elem) => Ok(*elem),
-                    _ => Ok(self.infer.fresh_var()),
+                Ok(Ty::Map(Box::new(Ty::Wrd), Box::new(join_all(values))))
+            }
+            ExprKind::Tuple(elements) => Ok(Ty::Tup(self.check_args(elements)?)),
+            ExprKind::Interpolation(parts) => {
+                self.check_args(parts)?;
+                Ok(Ty::Wrd)
+            }
+            ExprKind::Range { start, end, .. } => {
+                for bound in [start, end] {
+                    let ty = self.check_expr(bound)?;
+                    if !compatible(&Ty::Int, &ty) {
+                        return Err(type_error(
+                            ErrorCode::E092,
+                            bound.span,
+                            format!("range bounds must be int, got {}", ty),
+                        ));
+                    }
                 }
+                Ok(Ty::Range)
+            }
+            ExprKind::Length(inner) => {
+                let ty = self.check_expr(inner)?;
+                self.present(ty, inner)?;
+                Ok(Ty::Int)
+            }
+            ExprKind::Append { list, value } => {
+                let list_type = self.check_expr(list)?;
+                let list_type = self.present(list_type, list)?;
+                let value_type = self.check_expr(value)?;
+                match &list_type {
+                    Ty::Lst(item) if !compatible(item, &value_type) => Err(mismatch(
+                        value.span,
+                        format!("cannot append {} to {}", value_type, list_type),
+                    )),
+                    _ => Ok(list_type),
+                }
+            }
+            ExprKind::Error { value, cause } => {
+                self.check_expr(value)?;
+                if let Some(cause) = cause {
+                    self.check_expr(cause)?;
+                }
+                Ok(Ty::Any)
+            }
+            ExprKind::Assert { condition, message } => {
+                self.check_expr(condition)?;
+                if let Some(message) = message {
+                    self.check_expr(message)?;
+                }
+                Ok(Ty::Nil)
+            }
+            ExprKind::Send { channel, value } => {
+                self.check_expr(channel)?;
+                self.check_expr(value)?;
+                Ok(Ty::Nil)
+            }
+            ExprKind::Await(inner) | ExprKind::Spawn(inner) | ExprKind::Receive(inner) => {
+                self.check_expr(inner)?;
+                Ok(Ty::Any)
+            }
+            ExprKind::Borrow(inner) => self.check_expr(inner),
+            ExprKind::Cast { ty, value } => {
+                self.check_expr(value)?;
+                Ok(self.resolve(ty))
+            }
+            ExprKind::TypeOf(inner) => {
+                self.check_expr(inner)?;
+                Ok(Ty::Wrd)
+            }
+            ExprKind::Block(stmts) => {
+                self.check_block(stmts)?;
+                Ok(Ty::Any)
+            }
+            ExprKind::Nil => Ok(Ty::Nil),
+        }
+    }
+    fn check_expecting(&mut self, expr: &Expr, expected: &Ty) -> NebulaResult<Ty> {
+        match (&expr.kind, expected) {
+            (ExprKind::Lambda { params, body }, Ty::Function(types, ret))
+                if types.len() == params.len() =>
+            {
+                self.check_lambda(params, body, types, ret)
+            }
+            _ => self.check_expr(expr),
+        }
+    }
+    fn check_lambda(
+        &mut self,
+        params: &[String],
+        body: &Expr,
+        types: &[Ty],
+        ret: &Ty,
+    ) -> NebulaResult<Ty> {
+        self.env.push_scope();
+        for (param, ty) in params.iter().zip(types) {
+            self.env.define(param.clone(), ty.clone());
+        }
+        self.returns.push(("lambda".to_string(), ret.clone()));
+        let result = self
+            .check_expr(body)
+            .and_then(|ty| self.check_return(&ty, body.span).map(|_| ty));
+        self.returns.pop();
+        self.env.pop_scope();
+        let body_type = result?;
+        let returned = if ret.is_any() { body_type } else { ret.clone() };
+        Ok(Ty::Function(types.to_vec(), Box::new(returned)))
+    }
+    fn check_higher_order(&mut self, name: &str, args: &[Expr]) -> NebulaResult<Option<Ty>> {
+        let (list, callback, init) = match (name, args) {
+            ("map" | "filter" | "any" | "all" | "sort_by", [list, callback]) => {
+                (list, callback, None)
+            }
+            ("reduce", [list, callback, init]) => (list, callback, Some(init)),
+            _ => return Ok(None),
+        };
+        if !crate::builtins::is_builtin(name) {
+            return Ok(None);
+        }
+        let list_type = self.check_expr(list)?;
+        let item = element(&list_type);
+        let acc = match init {
+            Some(init) => Some(self.check_expr(init)?),
+            None => None,
+        };
+        let (params, ret) = match (name, &acc) {
+            ("sort_by", _) => (vec![item.clone(), item], Ty::Nb),
+            (_, Some(acc)) => (vec![acc.clone(), item], Ty::Any),
+            _ => (vec![item], Ty::Any),
+        };
+        let expected = Ty::Function(params, Box::new(ret));
+        let callback_type = self.check_expecting(callback, &expected)?;
+        if !compatible(&expected, &callback_type) {
+            return Err(mismatch(
+                callback.span,
+                format!(
+                    "{}() needs a callback {}, got {}",
+                    name, expected, callback_type
+                ),
+            ));
+        }
+        let returned = match &callback_type {
+            Ty::Function(_, ret) => (**ret).clone(),
+            _ => Ty::Any,
+        };
+        Ok(Some(match name {
+            "map" => Ty::Lst(Box::new(returned)),
+            "any" | "all" => Ty::Bool,
+            "reduce" => join(&acc.unwrap_or(Ty::Any), &returned),
+            _ => list_type,
+        }))
+    }
+    fn check_args(&mut self, args: &[Expr]) -> NebulaResult<Vec<Ty>> {
+        args.iter().map(|arg| self.check_expr(arg)).collect()
+    }
+    fn check_call(
+        &mut self,
+        name: Option<&str>,
+        callee: Ty,
+        args: &[Expr],
+        span: Span,
+    ) -> NebulaResult<Ty> {
+        let expected = match &callee {
+            Ty::Function(params, _) => params.clone(),
+            _ => Vec::new(),
+        };
+        let arg_types = args
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| self.check_expecting(arg, expected.get(index).unwrap_or(&Ty::Any)))
+            .collect::<NebulaResult<Vec<_>>>()?;
+        let (params, ret) = match callee {
+            Ty::Function(params, ret) => (params, *ret),
+            Ty::Any | Ty::Optional(_) | Ty::Named(_) => return Ok(Ty::Any),
+            other => {
+                return Err(type_error(
+                    ErrorCode::E011,
+                    span,
+                    format!("cannot call a {}", other),
+                ))
+            }
+        };
+        let (required, variadic) = match name.and_then(|n| self.signatures.get(n)) {
+            Some(sig) if sig.params == params => (sig.required, sig.variadic),
+            _ => (params.len(), false),
+        };
+        let max = if variadic { usize::MAX } else { params.len() };
+        if arg_types.len() < required || arg_types.len() > max {
+            return Err(NebulaError::coded_at(
+                ErrorCode::E012,
+                format!(
+                    "{}() expects {} args, got {}",
+                    name.unwrap_or("function"),
+                    params.len(),
+                    arg_types.len()
+                ),
+                span,
+            ));
+        }
+        for (index, (arg, actual)) in args.iter().zip(&arg_types).enumerate() {
+            let expected = params
+                .get(index)
+                .or(params.last().filter(|_| variadic))
+                .unwrap_or(&Ty::Any);
+            if !self.accepts(expected, actual) {
+                return Err(mismatch(
+                    arg.span,
+                    format!(
+                        "argument {} to {}() expects {}, got {}",
+                        index + 1,
+                        name.unwrap_or("function"),
+                        expected,
+                        actual
+                    ),
+                ));
             }
-            _ => Ok(self.infer.fresh_var()),
         }
+        Ok(ret)
     }
-    fn literal_type(&self, lit: &Literal) -> Ty {
-        match lit {
-            Literal::Integer(_) => Ty::I64,
-            Literal::Float(_) => Ty::F64,
-            Literal::String(_) => Ty::String,
-            Literal::Bool(_) => Ty::Bool,
+    fn check_struct_init(&mut self, name: &str, args: &[Expr], span: Span) -> NebulaResult<Ty> {
+        let Some(TypeDef::Struct(fields)) = self.env.lookup_type(name).cloned() else {
+            return Ok(Ty::Any);
+        };
+        if args.len() != fields.len() {
+            let names: Vec<_> = fields.iter().map(|(field, _)| field.as_str()).collect();
+            return Err(NebulaError::coded_at(
+                ErrorCode::E012,
+                format!(
+                    "{}() takes {} fields ({}), got {}",
+                    name,
+                    fields.len(),
+                    names.join(", "),
+                    args.len()
+                ),
+                span,
+            ));
+        }
+        for (arg, (field, expected)) in args.iter().zip(&fields) {
+            let actual = self.check_expecting(arg, expected)?;
+            if !self.accepts(expected, &actual) {
+                return Err(mismatch(
+                    arg.span,
+                    format!(
+                        "field '{}' of {} expects {}, got {}",
+                        field, name, expected, actual
+                    ),
+                ));
+            }
+        }
+        Ok(Ty::Named(name.to_string()))
+    }
+    fn field_type(&self, ty: &Ty, field: &str, span: Span) -> NebulaResult<Ty> {
+        let Ty::Named(name) = ty else {
+            return Ok(match ty {
+                Ty::Map(_, value) => (**value).clone(),
+                _ => Ty::Any,
+            });
+        };
+        match self.env.lookup_type(name) {
+            Some(TypeDef::Struct(fields)) => fields
+                .iter()
+                .find(|(n, _)| n == field)
+                .map(|(_, ty)| ty.clone())
+                .ok_or_else(|| {
+                    let names = fields.iter().map(|(n, _)| n.as_str());
+                    type_error(
+                        ErrorCode::E095,
+                        span,
+                        format!(
+                            "unknown field '{}' on {}{}",
+                            field,
+                            name,
+                            suggest(field, names)
+                        ),
+                    )
+                }),
+            Some(TypeDef::Enum(variants)) if !variants.iter().any(|v| v == field) => {
+                Err(type_error(
+                    ErrorCode::E095,
+                    span,
+                    format!("{} has no variant '{}'", name, field),
+                ))
+            }
+            _ => Ok(ty.clone()),
         }
     }
 }
@@ -372,3 +1208,416 @@ impl Default for TypeChecker {
         Self::new()
     }
 }
+fn receiver_param(owner: &str, method: &str, params: &[Param], span: Span) -> NebulaResult<()> {
+    if params.first().is_some_and(|p| p.name == "self") {
+        return Ok(());
+    }
+    Err(type_error(
+        ErrorCode::E096,
+        span,
+        format!(
+            "{}.{}() must take self as its first parameter",
+            owner, method
+        ),
+    ))
+}
+fn conforms(required: &Ty, provided: &Ty) -> bool {
+    match (required, provided) {
+        (Ty::Function(expected, want), Ty::Function(params, gives)) => {
+            expected.len() == params.len()
+                && expected.iter().zip(params).all(|(e, p)| compatible(p, e))
+                && compatible(want, gives)
+        }
+        _ => false,
+    }
+}
+fn mismatch(span: Span, message: String) -> NebulaError {
+    type_error(ErrorCode::E090, span, message)
+}
+fn type_error(code: ErrorCode, span: Span, message: String) -> NebulaError {
+    NebulaError::Type {
+        code,
+        message,
+        span,
+    }
+}
+fn runtime_type(name: &str) -> Option<Ty> {
+    Some(match name {
+        "nb" => Ty::Nb,
+        "int" => Ty::Int,
+        "fl" => Ty::Fl,
+        "wrd" => Ty::Wrd,
+        "bool" => Ty::Bool,
+        "by" => Ty::By,
+        "chr" => Ty::Chr,
+        "nil" => Ty::Nil,
+        "range" => Ty::Range,
+        "lst" => Ty::Lst(Box::new(Ty::Any)),
+        "map" => Ty::Map(Box::new(Ty::Any), Box::new(Ty::Any)),
+        "tup" => Ty::Tup(Vec::new()),
+        "set" => Ty::Set(Box::new(Ty::Any)),
+        _ => return None,
+    })
+}
+fn diverges(stmts: &[Stmt]) -> bool {
+    stmts.last().is_some_and(|stmt| match &stmt.kind {
+        StmtKind::Return(_) | StmtKind::Break | StmtKind::Continue => true,
+        StmtKind::Expression(expr) => matches!(expr.kind, ExprKind::Error { .. }),
+        _ => false,
+    })
+}
+fn literal_type(lit: &Literal) -> Ty {
+    match lit {
+        Literal::Integer(_) => Ty::Int,
+        Literal::Float(_) => Ty::Fl,
+        Literal::String(_) => Ty::Wrd,
+        Literal::Bool(_) => Ty::Bool,
+    }
+}
+fn binary_type(op: BinaryOp, left: &Ty, right: &Ty, span: Span) -> NebulaResult<Ty> {
+    let distributed = match (left, right) {
+        (Ty::Union(members), _) if !left.is_numeric() => members
+            .iter()
+            .map(|member| binary_type(op, member, right, span))
+            .collect::<NebulaResult<Vec<_>>>(),
+        (_, Ty::Union(members)) if !right.is_numeric() => members
+            .iter()
+            .map(|member| binary_type(op, left, member, span))
+            .collect::<NebulaResult<Vec<_>>>(),
+        _ => return simple_binary_type(op, left, right, span),
+    };
+    distributed.map(Ty::union).map_err(|_| {
+        type_error(
+            ErrorCode::E091,
+            span,
+            format!("cannot apply '{}' to {} and {}", op.as_str(), left, right),
+        )
+    })
+}
+fn simple_binary_type(op: BinaryOp, left: &Ty, right: &Ty, span: Span) -> NebulaResult<Ty> {
+    let numeric = |ty: &Ty| ty.is_numeric() || ty.is_any();
+    let ty = match op {
+        BinaryOp::Add if *left == Ty::Wrd || *right == Ty::Wrd => Some(Ty::Wrd),
+        BinaryOp::Add if left.is_any() || right.is_any() => Some(Ty::Any),
+        BinaryOp::Div if numeric(left) && numeric(right) => Some(Ty::Nb),
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Mod | BinaryOp::Pow
+            if numeric(left) && numeric(right) =>
+        {
+            Some(match (left, right) {
+                (Ty::Any, ty) | (ty, Ty::Any) => ty.clone(),
+                _ => join(left, right),
+            })
+        }
+        BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or => Some(Ty::Bool),
+        BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+            if (numeric(left) && numeric(right))
+                || (matches!(left, Ty::Wrd | Ty::Any) && matches!(right, Ty::Wrd | Ty::Any)) =>
+        {
+            Some(Ty::Bool)
+        }
+        BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::Shl | BinaryOp::Shr
+            if numeric(left) && numeric(right) =>
+        {
+            Some(Ty::Int)
+        }
+        _ => None,
+    };
+    ty.ok_or_else(|| {
+        type_error(
+            ErrorCode::E091,
+            span,
+            format!("cannot apply '{}' to {} and {}", op.as_str(), left, right),
+        )
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    fn check_with(mode: TypeMode, source: &str) -> NebulaResult<()> {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        TypeChecker::with_mode(mode).check_program(&program)
+    }
+    fn check(source: &str) -> NebulaResult<()> {
+        check_with(TypeMode::Strict, source)
+    }
+    #[test]
+    fn test_check_program() {
+        check(
+            "fn area(w: nb, h: nb = 1) do\n  give w * h\nend\n\
+             perm label: wrd = \"a\" + 1\n\
+             perm n = area(2) + area(2, 3)\n\
+             n = \"changed\"\n\
+             each c in label do\n  log(c)\nend\n\
+             perm later: int? = empty",
+        )
+        .unwrap();
+        let err = check("perm n: int = \"one\"").unwrap_err();
+        assert!(matches!(err, NebulaError::Type { .. }));
+        assert_eq!(
+            err.message(),
+            "'n' is declared int but initialised with wrd"
+        );
+        let err = check("fn f(a: wrd) do\n  give a\nend\nf(1)").unwrap_err();
+        assert_eq!(err.message(), "argument 1 to f() expects wrd, got int");
+        let err =
+            check("fn area(r: nb): nb do\n  if r < 0 do\n    give \"neg\"\n  end\n  give r\nend")
+                .unwrap_err();
+        assert_eq!(err.message(), "area() declares nb but gives wrd");
+        assert!(check("fn name() -> wrd = 1").is_err());
+        let err = check("fn f(a, b) do\n  give a\nend\nf(1)").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E012));
+        let err = check("perm total = 1\nlog(totl)").unwrap_err();
+        assert!(matches!(
+            err,
+            NebulaError::UndefinedVariable { suggestion: Some(ref s), .. } if s == "total"
+        ));
+        assert!(check("perm x = \"a\" - 1").is_err());
+        assert!(check("perm x: lst = 5").is_err());
+    }
+    #[test]
+    fn test_match_exhaustiveness() {
+        let checked = |source: &str| {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            let program = Parser::new(tokens).parse_program().unwrap();
+            let mut checker = TypeChecker::with_mode(TypeMode::Strict);
+            let result = checker.check_program(&program);
+            (result, checker.warnings().len())
+        };
+        let (result, warnings) =
+            checked("fn f(b: bool) do\n  match b do\n    on => 1\n    off => 2\n  end\nend");
+        assert!(result.is_ok());
+        assert_eq!(warnings, 0);
+        let (result, _) = checked("fn f(b: bool) do\n  match b do\n    on => 1\n  end\nend");
+        assert_eq!(
+            result.unwrap_err().message(),
+            "non-exhaustive match on bool: missing off"
+        );
+        let (result, _) = checked(
+            "enum Dir { Up, Down }\nfn f(d: Dir) do\n  match d do\n    Up => 1\n  end\nend",
+        );
+        assert_eq!(
+            result.unwrap_err().message(),
+            "non-exhaustive match on Dir: missing Down"
+        );
+        let (result, _) = checked("fn f(w: wrd) do\n  match w do\n    \"a\" => 1\n  end\nend");
+        assert!(result.unwrap_err().message().contains("add a '_' arm"));
+        let (result, warnings) = checked(
+            "fn f(x) do\n  match x do\n    1 => 1\n    1 => 2\n    _ => 3\n    y => 4\n  end\nend",
+        );
+        assert!(result.is_ok());
+        assert_eq!(warnings, 2);
+    }
+    #[test]
+    fn test_optionals_must_be_checked_before_use() {
+        let err = check("fn f(x: nb?) do\n  give x + 1\nend").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E013));
+        assert!(err.message().contains("'x' is nb? and may be empty"));
+        check("fn f(x: nb?) do\n  if x != empty do\n    give x + 1\n  end\n  give 0\nend").unwrap();
+        check("fn f(x: nb?) do\n  if x == empty do\n    give 0\n  end\n  give x * 2\nend").unwrap();
+        check("fn f(x: nb?) do\n  give (x ?? 0) + 1\nend").unwrap();
+        check("fn f(x: nb?) do\n  give x != empty ? x - 1 : 0\nend").unwrap();
+        check("fn f(x: nb?) do\n  match x do\n    empty => 0\n    n => n + 1\n  end\nend").unwrap();
+        let reassigned =
+            "fn f(x: nb?) do\n  if x != empty do\n    x = empty\n    give x + 1\n  end\nend";
+        assert_eq!(check(reassigned).unwrap_err().code(), Some(ErrorCode::E013));
+        let err = check("fn g(n: nb) = n\nfn f(x: nb?) = g(x)").unwrap_err();
+        assert_eq!(err.message(), "argument 1 to g() expects nb, got nb?");
+    }
+    #[test]
+    fn test_lambda_parameters_are_inferred_from_context() {
+        let err =
+            check("perm words = lst(\"a\", \"b\")\nperm n = map(words, (s) => s - 1)").unwrap_err();
+        assert_eq!(err.message(), "cannot apply '-' to wrd and int");
+        check(
+            "perm nums = lst(1, 2, 3)\n\
+             perm big: lst = filter(map(nums, (x) => x * 2), (x) => x > 2)\n\
+             perm total: int = reduce(nums, (a, b) => a + b, 0)",
+        )
+        .unwrap();
+        let err =
+            check("perm words = lst(\"a\")\nperm s = sort_by(words, (a, b) => a + b)").unwrap_err();
+        assert_eq!(err.message(), "lambda() declares nb but gives wrd");
+        let err = check("fn double(n: nb): nb = n * 2\nperm g = double\ng = (y) => y + \"!\"")
+            .unwrap_err();
+        assert_eq!(err.message(), "lambda() declares nb but gives wrd");
+        let err = check("fn shout(s: wrd): wrd = s\nperm n = map(lst(1, 2), shout)").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "map() needs a callback fn(int) -> any, got fn(wrd) -> wrd"
+        );
+    }
+    #[test]
+    fn test_struct_construction_and_fields() {
+        let point = "struct Point { x: nb, y: nb, label: wrd }\n";
+        check(&format!(
+            "{}perm p = Point(1, 2.5, \"a\")\nperm sum: nb = p.x + p.y\np.label = \"b\"",
+            point
+        ))
+        .unwrap();
+        let err = check(&format!("{}perm p = Point(1, 2)", point)).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E012));
+        assert!(err
+            .message()
+            .ends_with("Point() takes 3 fields (x, y, label), got 2"));
+        let err = check(&format!("{}perm p = Point(1, \"2\", \"a\")", point)).unwrap_err();
+        assert_eq!(err.message(), "field 'y' of Point expects nb, got wrd");
+        let err = check(&format!(
+            "{}perm p = Point(1, 2, \"a\")\nlog(p.lable)",
+            point
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unknown field 'lable' on Point (did you mean 'label'?)"
+        );
+        let err = check(&format!(
+            "{}perm p = Point(1, 2, \"a\")\np.x = \"far\"",
+            point
+        ))
+        .unwrap_err();
+        assert_eq!(err.message(), "cannot assign wrd to a nb slot");
+    }
+    #[test]
+    fn test_union_annotations() {
+        check("perm v: int | wrd = 5\nv = \"five\"").unwrap();
+        let err = check("perm v: int | wrd = 5\nv = on").unwrap_err();
+        assert_eq!(err.message(), "cannot assign bool to 'v' of type int | wrd");
+        check("fn f(v: int | wrd): wrd do\n  give v + \"!\"\nend").unwrap();
+        let err = check("fn f(v: int | wrd) do\n  give v * 2\nend").unwrap_err();
+        assert_eq!(err.message(), "cannot apply '*' to int | wrd and int");
+        check("fn f(v: int | wrd) do\n  if typeof(v) == \"wrd\" do\n    give 0\n  end\n  give v * 2\nend")
+            .unwrap();
+        let err = check("perm x: int | wrd | empty = on").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "'x' is declared (int | wrd)? but initialised with bool"
+        );
+        let err = check("perm pick = on ? 1 : \"a\"\nperm n: int = pick").unwrap_err();
+        assert_eq!(
+            err.message(),
+            "'n' is declared int but initialised with int | wrd"
+        );
+    }
+    #[test]
+    fn test_gradual_mode_only_enforces_annotations() {
+        let gradual = |source| check_with(TypeMode::Gradual, source);
+        let dynamic = "perm n = 1\nperm s: wrd = n\nlog(defined_elsewhere(n))";
+        assert!(gradual(dynamic).is_ok());
+        assert!(check(dynamic).is_err());
+        assert!(gradual("fn f(a: wrd) do\n  give a\nend\nf(2)").is_err());
+        assert!(gradual("fn f(a): nb do\n  give \"x\"\nend").is_err());
+        assert!(gradual("perm x: nb = 1\nx = \"one\"").is_err());
+        assert_eq!(TypeChecker::new().mode(), TypeMode::Gradual);
+    }
+    #[test]
+    fn test_type_errors_carry_codes_and_spans() {
+        let located = |source: &str| {
+            let err = check(source).unwrap_err();
+            let span = *err.span().unwrap();
+            (err.code().unwrap(), span.line, span.column)
+        };
+        assert_eq!(
+            located(
+                "perm a = 1
+perm n: wrd = a"
+            ),
+            (ErrorCode::E090, 2, 15)
+        );
+        assert_eq!(located("perm w = \"a\"\nlog(-w)"), (ErrorCode::E091, 2, 5));
+        assert_eq!(
+            located("perm n = 3\neach c in n do\n  log(c)\nend"),
+            (ErrorCode::E092, 2, 11)
+        );
+        assert_eq!(
+            located("perm xs = lst(1)\nlog(xs[\"a\"])"),
+            (ErrorCode::E021, 2, 8)
+        );
+        assert_eq!(
+            located("enum Dir { Up, Down }\nperm d = Dir.Left"),
+            (ErrorCode::E095, 2, 10)
+        );
+        let err = check("perm n = 1\nn()").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E011));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "[E011] Type error at {}: cannot call a int",
+                err.span().unwrap()
+            )
+        );
+    }
+    #[test]
+    fn test_trait_conformance() {
+        let shapes = "struct Circle { r: nb }\nstruct Square { side: nb }\n\
+                      trait Shape {\n  fn area(self): nb\n  fn scaled(self, k: nb): nb\n}\n";
+        let circle = "impl Shape for Circle {\n  fn area(self): nb = self.r * self.r\n  \
+                      fn scaled(self, k: nb): nb = self:area() * k\n}\n";
+        check(&format!(
+            "{}{}impl Shape for Square {{\n  fn area(self) = self.side\n  \
+             fn scaled(self, k) = k\n}}\n\
+             fn total(s: Shape): nb = s:area() + s:scaled(2)\n\
+             perm n: nb = total(Circle(1)) + total(Square(2))",
+            shapes, circle
+        ))
+        .unwrap();
+        let err = check(&format!(
+            "{}impl Shape for Square {{\n  fn area(self): nb = 1\n}}",
+            shapes
+        ))
+        .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E096));
+        assert_eq!(err.message(), "impl Shape for Square is missing scaled");
+        let err = check(&format!(
+            "{}impl Shape for Square {{\n  fn area(self): wrd = \"big\"\n  \
+             fn scaled(self, k: nb): nb = k\n}}",
+            shapes
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Square.area() is fn() -> wrd but Shape requires fn() -> nb"
+        );
+        let err = check(&format!(
+            "{}impl Shape for Circle {{\n  fn area() = 1\n}}",
+            shapes
+        ))
+        .unwrap_err();
+        assert!(err
+            .message()
+            .ends_with("must take self as its first parameter"));
+        let err = check(&format!("{}{}perm x = Circle(1):aera()", shapes, circle)).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E095));
+        assert_eq!(
+            err.message(),
+            "Circle has no method 'aera' (did you mean 'area'?)"
+        );
+        let err = check(&format!(
+            "{}{}fn total(s: Shape): nb = s:area()\nperm n = total(Square(2))",
+            shapes, circle
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "argument 1 to total() expects Shape, got Square"
+        );
+    }
+    #[test]
+    fn test_inherent_impl_methods() {
+        let point = "struct Point { x: nb, y: nb }\nimpl Point do\n  \
+                     fn sum(self): nb = self.x + self.y\nend\n";
+        check(&format!("{}perm n: nb = Point(1, 2):sum()", point)).unwrap();
+        let err = check(&format!("{}perm n: wrd = Point(1, 2):sum()", point)).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E090));
+        let err = check(&format!("{}log(Point(1, 2):sum(3))", point)).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E012));
+        let err = check("impl Nope do\n  fn f(self) = 1\nend").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::E096));
+        assert_eq!(
+            err.message(),
+            "methods can only be added to a struct, not 'Nope'"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use super::types::{Ty, TypeDef, TypeEnv};
+use crate::parser::ast::{Literal, MatchArm, Pattern};
+#[derive(Debug, Default, PartialEq)]
+pub struct MatchReport {
+    pub missing: Vec<String>,
+    pub catch_all_needed: bool,
+    pub unreachable: Vec<usize>,
+}
+impl MatchReport {
+    pub fn is_exhaustive(&self) -> bool {
+        self.missing.is_empty() && !self.catch_all_needed
+    }
+}
+enum Key {
+    CatchAll,
+    Case(String),
+}
+pub fn analyze(arms: &[MatchArm], scrutinee: &Ty, env: &TypeEnv) -> MatchReport {
+    let cases = finite_cases(scrutinee, env);
+    let mut report = MatchReport::default();
+    let mut seen: Vec<String> = Vec::new();
+    let mut covered = false;
+    for (index, arm) in arms.iter().enumerate() {
+        let key = match &arm.pattern {
+            Pattern::Wildcard => Key::CatchAll,
+            Pattern::Binding(name) if cases.as_ref().is_some_and(|c| c.contains(name)) => {
+                Key::Case(name.clone())
+            }
+            Pattern::Binding(_) => Key::CatchAll,
+            Pattern::Literal(lit) => Key::Case(literal_key(lit)),
+            Pattern::Nil => Key::Case("empty".to_string()),
+        };
+        if covered {
+            report.unreachable.push(index);
+            continue;
+        }
+        match key {
+            Key::CatchAll => covered = true,
+            Key::Case(case) if seen.contains(&case) => report.unreachable.push(index),
+            Key::Case(case) => seen.push(case),
+        }
+    }
+    if covered {
+        return report;
+    }
+    match cases {
+        Some(cases) => report.missing = cases.into_iter().filter(|c| !seen.contains(c)).collect(),
+        None => report.catch_all_needed = !scrutinee.is_any(),
+    }
+    report
+}
+fn finite_cases(ty: &Ty, env: &TypeEnv) -> Option<Vec<String>> {
+    match ty {
+        Ty::Bool => Some(vec!["on".to_string(), "off".to_string()]),
+        Ty::Optional(inner) => finite_cases(inner, env).map(|mut cases| {
+            cases.push("empty".to_string());
+            cases
+        }),
+        Ty::Named(name) => match env.lookup_type(name) {
+            Some(TypeDef::Enum(variants)) => Some(variants.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+fn literal_key(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(n) => (*n as f64).to_string(),
+        Literal::Float(n) => n.to_string(),
+        Literal::String(s) => format!("{:?}", s),
+        Literal::Bool(true) => "on".to_string(),
+        Literal::Bool(false) => "off".to_string(),
+    }
+}
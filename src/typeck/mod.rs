@@ -1,5 +1,8 @@
-mod types;
-mod infer;
 mod check;
+mod exhaustive;
+mod infer;
+mod types;
+pub use check::{TypeChecker, TypeMode};
+pub use exhaustive::{analyze, MatchReport};
+pub use infer::{compatible, join};
 pub use types::*;
-pub use check::TypeChecker;
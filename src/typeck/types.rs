@@ -1,88 +1,176 @@
-use std::collections::HashMap;
+use crate::lexer::Span;
 use crate::parser::ast::Type as AstType;
+use std::collections::HashMap;
+use std::fmt;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ty {
-    I8, I16, I32, I64,
-    U8, U16, U32, U64,
-    F32, F64,
+    Nb,
+    Int,
+    Fl,
+    Wrd,
     Bool,
-    Str,
-    String,
-    Unit,
-    Never,
-    Array(Box<Ty>, usize),
-    Slice(Box<Ty>),
-    Tuple(Vec<Ty>),
-    Struct(String, Vec<(String, Ty)>),
-    Enum(String, Vec<(String, Vec<Ty>)>),
-    Var(usize), 
-    Generic(String, Vec<Ty>),
+    By,
+    Chr,
+    Nil,
+    Void,
+    Any,
+    Range,
+    Lst(Box<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    Tup(Vec<Ty>),
+    Set(Box<Ty>),
+    Optional(Box<Ty>),
+    Named(String),
     Function(Vec<Ty>, Box<Ty>),
-    Error,
+    Union(Vec<Ty>),
 }
 impl Ty {
     pub fn from_ast(ast_type: &AstType) -> Self {
+        let elem =
+            |ty: &Option<Box<AstType>>| Box::new(ty.as_deref().map_or(Ty::Any, Ty::from_ast));
         match ast_type {
-            AstType::I8 => Ty::I8,
-            AstType::I16 => Ty::I16,
-            AstType::I32 => Ty::I32,
-            AstType::I64 => Ty::I64,
-            AstType::U8 => Ty::U8,
-            AstType::U16 => Ty::U16,
-            AstType::U32 => Ty::U32,
-            AstType::U64 => Ty::U64,
-            AstType::F32 => Ty::F32,
-            AstType::F64 => Ty::F64,
+            AstType::Nb => Ty::Nb,
+            AstType::Int => Ty::Int,
+            AstType::Fl => Ty::Fl,
+            AstType::Wrd => Ty::Wrd,
             AstType::Bool => Ty::Bool,
-            AstType::Str => Ty::Str,
-            AstType::String => Ty::String,
-            AstType::Unit => Ty::Unit,
-            AstType::SelfType => Ty::Error, 
-            AstType::Array(elem, size) => Ty::Array(Box::new(Ty::from_ast(elem)), *size),
-            AstType::Slice(elem) => Ty::Slice(Box::new(Ty::from_ast(elem))),
-            AstType::Tuple(types) => Ty::Tuple(types.iter().map(Ty::from_ast).collect()),
-            AstType::Named(name) => Ty::Generic(name.clone(), vec![]),
-            AstType::Generic(name, args) => {
-                Ty::Generic(name.clone(), args.iter().map(Ty::from_ast).collect())
+            AstType::By => Ty::By,
+            AstType::Chr => Ty::Chr,
+            AstType::Any => Ty::Any,
+            AstType::Void => Ty::Void,
+            AstType::Nil => Ty::Nil,
+            AstType::Lst(item) => Ty::Lst(elem(item)),
+            AstType::Map(key, value) => Ty::Map(elem(key), elem(value)),
+            AstType::Tup(types) => Ty::Tup(types.iter().map(Ty::from_ast).collect()),
+            AstType::Set(item) => Ty::Set(elem(item)),
+            AstType::Optional(inner) => Ty::Optional(Box::new(Ty::from_ast(inner))),
+            AstType::Named(name) => Ty::Named(name.clone()),
+            AstType::Union(types) => Ty::union(types.iter().map(Ty::from_ast)),
+        }
+    }
+    pub fn union(types: impl IntoIterator<Item = Ty>) -> Self {
+        let mut members: Vec<Ty> = Vec::new();
+        let mut nullable = false;
+        for ty in types {
+            let flattened = match ty {
+                Ty::Union(inner) => inner,
+                Ty::Optional(inner) => {
+                    nullable = true;
+                    vec![*inner]
+                }
+                other => vec![other],
+            };
+            for member in flattened {
+                if member.is_any() {
+                    return Ty::Any;
+                }
+                if member.is_nil() {
+                    nullable = true;
+                } else if !members.contains(&member) {
+                    members.push(member);
+                }
             }
-            AstType::Infer => Ty::Error,
+        }
+        let ty = match members.len() {
+            0 => return Ty::Nil,
+            1 => members.remove(0),
+            _ => Ty::Union(members),
+        };
+        if nullable {
+            Ty::Optional(Box::new(ty))
+        } else {
+            ty
         }
     }
     pub fn is_numeric(&self) -> bool {
-        matches!(self,
-            Ty::I8 | Ty::I16 | Ty::I32 | Ty::I64 |
-            Ty::U8 | Ty::U16 | Ty::U32 | Ty::U64 |
-            Ty::F32 | Ty::F64
-        )
+        match self {
+            Ty::Nb | Ty::Int | Ty::Fl => true,
+            Ty::Union(members) => members.iter().all(Ty::is_numeric),
+            _ => false,
+        }
     }
-    pub fn is_integer(&self) -> bool {
-        matches!(self,
-            Ty::I8 | Ty::I16 | Ty::I32 | Ty::I64 |
-            Ty::U8 | Ty::U16 | Ty::U32 | Ty::U64
-        )
+    pub fn is_any(&self) -> bool {
+        matches!(self, Ty::Any)
     }
-    pub fn is_signed(&self) -> bool {
-        matches!(self, Ty::I8 | Ty::I16 | Ty::I32 | Ty::I64)
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Ty::Nil | Ty::Void)
     }
-    pub fn is_float(&self) -> bool {
-        matches!(self, Ty::F32 | Ty::F64)
+    pub fn non_nil(&self) -> Ty {
+        match self {
+            Ty::Optional(inner) => (**inner).clone(),
+            other => other.clone(),
+        }
     }
 }
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |types: &[Ty]| {
+            types
+                .iter()
+                .map(Ty::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Ty::Nb => write!(f, "nb"),
+            Ty::Int => write!(f, "int"),
+            Ty::Fl => write!(f, "fl"),
+            Ty::Wrd => write!(f, "wrd"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::By => write!(f, "by"),
+            Ty::Chr => write!(f, "chr"),
+            Ty::Nil => write!(f, "nil"),
+            Ty::Void => write!(f, "void"),
+            Ty::Any => write!(f, "any"),
+            Ty::Range => write!(f, "range"),
+            Ty::Lst(item) => write!(f, "lst[{}]", item),
+            Ty::Map(key, value) => write!(f, "map[{}, {}]", key, value),
+            Ty::Tup(types) => write!(f, "tup({})", list(types)),
+            Ty::Set(item) => write!(f, "set[{}]", item),
+            Ty::Optional(inner) if matches!(**inner, Ty::Union(_)) => write!(f, "({})?", inner),
+            Ty::Optional(inner) => write!(f, "{}?", inner),
+            Ty::Named(name) => write!(f, "{}", name),
+            Ty::Function(params, ret) => write!(f, "fn({}) -> {}", list(params), ret),
+            Ty::Union(members) => {
+                let names: Vec<_> = members.iter().map(Ty::to_string).collect();
+                write!(f, "{}", names.join(" | "))
+            }
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericKind {
+    Int,
+    Float,
+}
+pub type NumericHints = HashMap<Span, NumericKind>;
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub ty: Ty,
+    pub declared: Ty,
+    pub annotated: bool,
+}
 #[derive(Debug, Clone)]
 pub struct TypeEnv {
-    scopes: Vec<HashMap<String, Ty>>,
+    scopes: Vec<HashMap<String, Binding>>,
     type_defs: HashMap<String, TypeDef>,
+    methods: HashMap<String, Vec<(String, Ty)>>,
+    impls: HashMap<String, Vec<String>>,
 }
 #[derive(Debug, Clone)]
 pub enum TypeDef {
     Struct(Vec<(String, Ty)>),
-    Enum(Vec<(String, Vec<Ty>)>),
+    Enum(Vec<String>),
+    Trait(Vec<(String, Ty)>),
+    Alias(Ty),
 }
 impl TypeEnv {
     pub fn new() -> Self {
         Self {
             scopes: vec![HashMap::new()],
             type_defs: HashMap::new(),
+            methods: HashMap::new(),
+            impls: HashMap::new(),
         }
     }
     pub fn push_scope(&mut self) {
@@ -92,17 +180,44 @@ impl TypeEnv {
         self.scopes.pop();
     }
     pub fn define(&mut self, name: String, ty: Ty) {
+        self.bind(name, ty, false);
+    }
+    pub fn declare(&mut self, name: String, ty: Ty) {
+        self.bind(name, ty, true);
+    }
+    fn bind(&mut self, name: String, ty: Ty, annotated: bool) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name, ty);
+            let declared = ty.clone();
+            scope.insert(
+                name,
+                Binding {
+                    ty,
+                    declared,
+                    annotated,
+                },
+            );
         }
     }
-    pub fn lookup(&self, name: &str) -> Option<&Ty> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(ty) = scope.get(name) {
-                return Some(ty);
-            }
+    pub fn narrow(&mut self, name: &str, ty: Ty) {
+        let Some(binding) = self.lookup(name).cloned() else {
+            return;
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Binding { ty, ..binding });
         }
-        None
+    }
+    pub fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+    pub fn retype(&mut self, name: &str, ty: Ty) {
+        if let Some(binding) = self.scopes.iter_mut().rev().find_map(|s| s.get_mut(name)) {
+            binding.ty = ty;
+        }
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.keys().map(String::as_str))
     }
     pub fn define_type(&mut self, name: String, def: TypeDef) {
         self.type_defs.insert(name, def);
@@ -110,6 +225,31 @@ impl TypeEnv {
     pub fn lookup_type(&self, name: &str) -> Option<&TypeDef> {
         self.type_defs.get(name)
     }
+    pub fn define_impl(
+        &mut self,
+        target: &str,
+        trait_name: Option<&str>,
+        methods: Vec<(String, Ty)>,
+    ) {
+        self.methods
+            .entry(target.to_string())
+            .or_default()
+            .extend(methods);
+        if let Some(trait_name) = trait_name {
+            self.impls
+                .entry(target.to_string())
+                .or_default()
+                .push(trait_name.to_string());
+        }
+    }
+    pub fn methods(&self, target: &str) -> &[(String, Ty)] {
+        self.methods.get(target).map_or(&[], Vec::as_slice)
+    }
+    pub fn implements(&self, target: &str, trait_name: &str) -> bool {
+        self.impls
+            .get(target)
+            .is_some_and(|traits| traits.iter().any(|t| t == trait_name))
+    }
 }
 impl Default for TypeEnv {
     fn default() -> Self {
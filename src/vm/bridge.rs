@@ -0,0 +1,63 @@
+use super::nanbox::{HeapData, HeapObject, NanBoxed};
+use crate::interp::Value;
+pub fn to_value(nb: NanBoxed) -> Value {
+    if nb.is_nil() {
+        Value::Nil
+    } else if nb.is_bool() {
+        Value::Bool(nb.as_bool())
+    } else if nb.is_number() {
+        Value::Number(nb.as_number())
+    } else if nb.is_integer() {
+        Value::Integer(nb.as_integer())
+    } else if nb.is_ptr() {
+        let obj = unsafe { &*nb.as_ptr() };
+        match &obj.data {
+            HeapData::String(s) => Value::String(s.as_ref().into()),
+            HeapData::List(items) => Value::List(items.iter().map(|v| to_value(*v)).collect()),
+            HeapData::Tuple(items) => Value::Tuple(items.iter().map(|v| to_value(*v)).collect()),
+            HeapData::Map(map) => Value::Map(
+                map.iter()
+                    .map(|(k, v)| (k.to_string(), to_value(*v)))
+                    .collect(),
+            ),
+            HeapData::Function(f) => Value::VmFunction {
+                name: f.name.to_string(),
+                handle: nb.as_ptr() as usize,
+            },
+            HeapData::Extern(handle) => Value::Extern(handle.clone()),
+            HeapData::Future(future) => Value::Future(future.clone()),
+            HeapData::Range(range) => Value::Range(*range),
+        }
+    } else {
+        Value::Nil
+    }
+}
+pub fn from_value(value: &Value) -> NanBoxed {
+    match value {
+        Value::Nil => NanBoxed::nil(),
+        Value::Bool(b) => NanBoxed::boolean(*b),
+        Value::Number(n) | Value::Float(n) => NanBoxed::number(*n),
+        Value::Integer(n) => NanBoxed::from_i64(*n),
+        Value::Byte(b) => NanBoxed::integer(*b as i64),
+        Value::String(s) => NanBoxed::ptr(HeapObject::new_string(s)),
+        Value::Char(c) => NanBoxed::ptr(HeapObject::new_string(&c.to_string())),
+        Value::Tuple(items) => NanBoxed::ptr(HeapObject::new_tuple(
+            items.iter().map(from_value).collect(),
+        )),
+        Value::List(items) | Value::Set(items) => {
+            NanBoxed::ptr(HeapObject::new_list(items.iter().map(from_value).collect()))
+        }
+        Value::Map(map) => NanBoxed::ptr(HeapObject::new_map(
+            map.iter()
+                .map(|(k, v)| (k.as_str().into(), from_value(v)))
+                .collect(),
+        )),
+        Value::Range(range) => NanBoxed::ptr(HeapObject::new_range(*range)),
+        Value::VmFunction { handle, .. } => NanBoxed::ptr(*handle as *mut HeapObject),
+        Value::Extension(func) => NanBoxed::ptr(HeapObject::new_string(&func.name)),
+        Value::Extern(handle) => NanBoxed::ptr(HeapObject::new_extern(handle.clone())),
+        Value::Future(future) => NanBoxed::ptr(HeapObject::new_future(future.clone())),
+        Value::Frozen(inner) => from_value(inner),
+        other => NanBoxed::ptr(HeapObject::new_string(&other.to_display_string())),
+    }
+}
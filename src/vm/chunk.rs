@@ -1,45 +1,45 @@
 use super::OpCode;
+use crate::error::{ErrorCode, NebulaError, NebulaResult};
 use crate::interp::Value;
+use crate::lexer::Span;
 #[derive(Debug, Clone)]
 pub struct Chunk {
     code: Vec<u8>,
     constants: Vec<Value>,
-    lines: Vec<usize>,
+    spans: Vec<Span>,
 }
 impl Chunk {
     pub fn new() -> Self {
         Self {
             code: Vec::with_capacity(256),
             constants: Vec::with_capacity(16),
-            lines: Vec::with_capacity(256),
+            spans: Vec::with_capacity(256),
         }
     }
-    pub fn write_op(&mut self, op: OpCode, line: usize) {
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
         self.code.push(op as u8);
-        self.lines.push(line);
+        self.spans.push(span);
     }
-    pub fn write_byte(&mut self, byte: u8, line: usize) {
+    pub fn write_byte(&mut self, byte: u8, span: Span) {
         self.code.push(byte);
-        self.lines.push(line);
+        self.spans.push(span);
     }
-    pub fn write_u16(&mut self, value: u16, line: usize) {
+    pub fn write_u16(&mut self, value: u16, span: Span) {
         self.code.push((value >> 8) as u8);
-        self.lines.push(line);
+        self.spans.push(span);
         self.code.push((value & 0xff) as u8);
-        self.lines.push(line);
+        self.spans.push(span);
     }
-    pub fn add_constant(&mut self, value: Value) -> u8 {
+    pub fn add_constant(&mut self, value: Value) -> NebulaResult<u16> {
         for (i, c) in self.constants.iter().enumerate() {
             if values_equal(c, &value) {
-                return i as u8;
+                return Ok(i as u16);
             }
         }
-        let idx = self.constants.len();
-        if idx > 255 {
-            return 255;
-        }
+        let idx = u16::try_from(self.constants.len())
+            .map_err(|_| NebulaError::coded(ErrorCode::E004, "too many constants in one chunk"))?;
         self.constants.push(value);
-        idx as u8
+        Ok(idx)
     }
     pub fn len(&self) -> usize {
         self.code.len()
@@ -59,11 +59,14 @@ impl Chunk {
     pub fn read_u16(&self, offset: usize) -> u16 {
         ((self.code[offset] as u16) << 8) | (self.code[offset + 1] as u16)
     }
-    pub fn get_constant(&self, idx: u8) -> &Value {
+    pub fn get_constant(&self, idx: u16) -> &Value {
         &self.constants[idx as usize]
     }
     pub fn get_line(&self, offset: usize) -> usize {
-        self.lines.get(offset).copied().unwrap_or(0)
+        self.get_span(offset).map_or(0, |span| span.line)
+    }
+    pub fn get_span(&self, offset: usize) -> Option<Span> {
+        self.spans.get(offset).copied().filter(|span| span.line > 0)
     }
     pub fn code(&self) -> &[u8] {
         &self.code
@@ -71,9 +74,9 @@ impl Chunk {
     pub fn code_mut(&mut self) -> &mut Vec<u8> {
         &mut self.code
     }
-    pub fn write(&mut self, byte: u8, line: usize) {
+    pub fn write(&mut self, byte: u8, span: Span) {
         self.code.push(byte);
-        self.lines.push(line);
+        self.spans.push(span);
     }
 }
 impl Default for Chunk {
@@ -97,9 +100,11 @@ mod tests {
     #[test]
     fn test_chunk_write() {
         let mut chunk = Chunk::new();
-        chunk.write_op(OpCode::PushConst, 1);
-        chunk.write_byte(0, 1);
-        chunk.write_op(OpCode::Return, 1);
+        let span = Span::new(0, 1, 1, 1);
+        chunk.write_op(OpCode::PushConst, span);
+        chunk.write_byte(0, span);
+        chunk.write_op(OpCode::Return, span);
+        assert_eq!(chunk.get_line(2), 1);
         assert_eq!(chunk.len(), 3);
         assert_eq!(chunk.read_byte(0), OpCode::PushConst as u8);
     }
@@ -108,6 +113,13 @@ mod tests {
         let mut chunk = Chunk::new();
         let idx1 = chunk.add_constant(Value::Number(42.0));
         let idx2 = chunk.add_constant(Value::Number(42.0));
-        assert_eq!(idx1, idx2);
+        assert_eq!(idx1.unwrap(), idx2.unwrap());
+    }
+    #[test]
+    fn test_constant_table_overflow() {
+        let mut chunk = Chunk::new();
+        chunk.constants.resize(u16::MAX as usize, Value::Nil);
+        assert_eq!(chunk.add_constant(Value::Integer(1)).unwrap(), u16::MAX);
+        assert!(chunk.add_constant(Value::Integer(2)).is_err());
     }
 }
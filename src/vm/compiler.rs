@@ -1,7 +1,10 @@
 use super::{Chunk, OpCode};
-use crate::error::NebulaResult;
+use crate::builtins::SandboxPolicy;
+use crate::error::{Diagnostic, NebulaResult, Severity};
 use crate::interp::Value;
+use crate::lexer::Span;
 use crate::parser::ast::*;
+use crate::typeck::{NumericHints, NumericKind};
 struct CompilerScope {
     locals: Vec<String>,
     scope_depth: usize,
@@ -45,6 +48,7 @@ impl CompilerScope {
         None
     }
 }
+const COLLECTION_BATCH: usize = 64;
 const BUILTIN_NAMES: [&str; 21] = [
     "log", "typeof", "sqrt", "abs", "len", "floor", "ceil", "round", "pow", "sin", "cos", "tan",
     "exp", "ln", "get", "rnd", "dbg", "now", "sleep", "str", "num",
@@ -54,6 +58,12 @@ pub struct Compiler {
     scope: CompilerScope,
     global_names: Vec<String>,
     functions: Vec<super::CompiledFunction>,
+    warnings: Vec<Diagnostic>,
+    ext_modules: Vec<(String, String)>,
+    ext_imports: Vec<(String, String)>,
+    script_globals: Vec<String>,
+    numeric_hints: NumericHints,
+    policy: SandboxPolicy,
 }
 impl Compiler {
     pub fn new() -> Self {
@@ -66,35 +76,122 @@ impl Compiler {
             scope: CompilerScope::new(),
             global_names,
             functions: Vec::new(),
+            warnings: Vec::new(),
+            ext_modules: Vec::new(),
+            ext_imports: Vec::new(),
+            script_globals: Vec::new(),
+            numeric_hints: NumericHints::new(),
+            policy: SandboxPolicy::default(),
         }
     }
+    pub fn set_numeric_hints(&mut self, hints: NumericHints) {
+        self.numeric_hints = hints;
+    }
+    pub fn set_policy(&mut self, policy: SandboxPolicy) {
+        self.policy = policy;
+    }
     pub fn compile(&mut self, program: &Program) -> NebulaResult<Chunk> {
+        self.script_globals = crate::ext::declared_globals(program);
         for item in &program.items {
-            self.compile_item(item)?;
+            self.compile_item(program, item)?;
         }
-        self.emit(OpCode::PushNil, 0);
-        self.emit(OpCode::Return, 0);
+        self.emit(OpCode::PushNil, Span::default());
+        self.emit(OpCode::Return, Span::default());
         Ok(std::mem::take(&mut self.chunk))
     }
     pub fn global_names(&self) -> &[String] {
         &self.global_names
     }
+    pub fn declare_global(&mut self, name: &str) -> usize {
+        self.resolve_global(name) as usize
+    }
     pub fn functions(&self) -> &[super::CompiledFunction] {
         &self.functions
     }
-    fn compile_item(&mut self, item: &Item) -> NebulaResult<()> {
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+    fn check_builtin_shadow(&mut self, name: &str, span: Option<Span>) {
+        if BUILTIN_NAMES.contains(&name) {
+            self.warnings.push(Diagnostic::new(
+                Severity::Warning,
+                format!("'{}' shadows the builtin of the same name", name),
+                span,
+            ));
+        }
+    }
+    fn compile_item(&mut self, program: &Program, item: &Item) -> NebulaResult<()> {
         match item {
             Item::Statement(stmt) => self.compile_stmt(stmt),
             Item::Function(f) => self.compile_function_def(f),
+            Item::Use(module) if module.native => {
+                crate::ext::load_native(&module.path, module.span, &self.policy).map(|_| ())
+            }
+            Item::Use(module) => {
+                if crate::ext::import_module(program, module)?.is_some() {
+                    if module.items.is_empty() {
+                        self.ext_modules
+                            .push((module.binding().to_string(), module.path.clone()));
+                    }
+                    self.ext_imports.extend(
+                        module
+                            .items
+                            .iter()
+                            .map(|item| (item.clone(), format!("{}.{}", module.path, item))),
+                    );
+                }
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
+    fn nested(&self) -> Compiler {
+        let mut compiler = Compiler::new();
+        compiler.global_names = self.global_names.clone();
+        compiler.ext_modules = self.ext_modules.clone();
+        compiler.ext_imports = self.ext_imports.clone();
+        compiler.script_globals = self.script_globals.clone();
+        compiler.numeric_hints = self.numeric_hints.clone();
+        compiler.scope.begin_scope();
+        compiler
+    }
+    fn ext_function(&self, callee: &Expr) -> Option<String> {
+        let (object, field) = match &callee.kind {
+            ExprKind::Variable(name) => {
+                if self.scope.resolve_local(name).is_some()
+                    || self.global_names.contains(name)
+                    || self.script_globals.contains(name)
+                {
+                    return None;
+                }
+                if let Some((_, function)) = self.ext_imports.iter().find(|(n, _)| n == name) {
+                    return Some(function.clone());
+                }
+                let registered = crate::ext::native_extensions().get_function(name).is_some();
+                return registered.then(|| name.clone());
+            }
+            ExprKind::Field { object, field } => (object, field),
+            _ => return None,
+        };
+        let ExprKind::Variable(module) = &object.kind else {
+            return None;
+        };
+        if self.scope.resolve_local(module).is_some() {
+            return None;
+        }
+        self.ext_modules
+            .iter()
+            .find(|(binding, _)| binding == module)
+            .map(|(_, path)| format!("{}.{}", path, field))
+    }
     fn compile_function_def(&mut self, f: &Function) -> NebulaResult<()> {
-        let mut func_compiler = Compiler::new();
-        for param in &f.params {
+        self.check_builtin_shadow(&f.name, Some(f.span));
+        let global_idx = self.add_global(f.name.clone());
+        let mut func_compiler = self.nested();
+        for param in f.params.iter() {
             func_compiler.scope.add_local(param.name.clone());
         }
-        match &f.body {
+        match &*f.body {
             crate::parser::ast::FunctionBody::Block(stmts) => {
                 for stmt in stmts {
                     func_compiler.compile_stmt(stmt)?;
@@ -102,11 +199,13 @@ impl Compiler {
             }
             crate::parser::ast::FunctionBody::Expression(expr) => {
                 func_compiler.compile_expr(expr)?;
-                func_compiler.emit(OpCode::Return, 0);
+                func_compiler.emit(OpCode::Return, expr.span);
             }
         }
-        func_compiler.emit(OpCode::PushNil, 0);
-        func_compiler.emit(OpCode::Return, 0);
+        func_compiler.emit(OpCode::PushNil, f.span);
+        func_compiler.emit(OpCode::Return, f.span);
+        self.global_names = std::mem::take(&mut func_compiler.global_names);
+        self.warnings.append(&mut func_compiler.warnings);
         let compiled = super::CompiledFunction {
             name: f.name.clone().into_boxed_str(),
             arity: f.params.len() as u8,
@@ -115,44 +214,45 @@ impl Compiler {
         };
         let func_idx = self.functions.len() as u8;
         self.functions.push(compiled);
-        let global_idx = self.add_global(f.name.clone());
-        self.emit(OpCode::Closure, 0);
-        self.chunk.write_byte(func_idx, 0);
-        self.emit(OpCode::DefineGlobal, 0);
-        self.chunk.write_byte(global_idx, 0);
+        self.emit(OpCode::Closure, f.span);
+        self.emit_byte(func_idx, f.span);
+        self.emit(OpCode::DefineGlobal, f.span);
+        self.emit_byte(global_idx, f.span);
         Ok(())
     }
     fn compile_stmt(&mut self, stmt: &Stmt) -> NebulaResult<()> {
-        let line = 0;
-        match stmt {
-            Stmt::Var { name, value, .. } => {
+        let span = stmt.span;
+        match &stmt.kind {
+            StmtKind::Var { name, value, .. } => {
                 self.compile_expr(value)?;
                 if self.scope.scope_depth > 0 {
                     self.scope.add_local(name.clone());
                 } else {
+                    self.check_builtin_shadow(name, None);
                     let idx = self.add_global(name.clone());
-                    self.emit(OpCode::DefineGlobal, line);
-                    self.emit_byte(idx, line);
+                    self.emit(OpCode::DefineGlobal, span);
+                    self.emit_byte(idx, span);
                 }
                 Ok(())
             }
-            Stmt::Const { name, value, .. } => {
+            StmtKind::Const { name, value, .. } => {
                 self.compile_expr(value)?;
                 if self.scope.scope_depth > 0 {
                     self.scope.add_local(name.clone());
                 } else {
+                    self.check_builtin_shadow(name, None);
                     let idx = self.add_global(name.clone());
-                    self.emit(OpCode::DefineGlobal, line);
-                    self.emit_byte(idx, line);
+                    self.emit(OpCode::DefineGlobal, span);
+                    self.emit_byte(idx, span);
                 }
                 Ok(())
             }
-            Stmt::Expression(expr) => {
+            StmtKind::Expression(expr) => {
                 self.compile_expr(expr)?;
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Pop, span);
                 Ok(())
             }
-            Stmt::If {
+            StmtKind::If {
                 condition,
                 then_block,
                 elif_branches,
@@ -160,20 +260,20 @@ impl Compiler {
             } => {
                 let mut end_jumps = Vec::new();
                 self.compile_expr(condition)?;
-                let then_jump = self.emit_jump(OpCode::JumpIfFalse, line);
-                self.emit(OpCode::Pop, line);
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit(OpCode::Pop, span);
                 self.compile_block(then_block)?;
-                end_jumps.push(self.emit_jump(OpCode::Jump, line));
+                end_jumps.push(self.emit_jump(OpCode::Jump, span));
                 self.patch_jump(then_jump);
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Pop, span);
                 for (elif_cond, elif_body) in elif_branches {
                     self.compile_expr(elif_cond)?;
-                    let elif_jump = self.emit_jump(OpCode::JumpIfFalse, line);
-                    self.emit(OpCode::Pop, line);
+                    let elif_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                    self.emit(OpCode::Pop, span);
                     self.compile_block(elif_body)?;
-                    end_jumps.push(self.emit_jump(OpCode::Jump, line));
+                    end_jumps.push(self.emit_jump(OpCode::Jump, span));
                     self.patch_jump(elif_jump);
-                    self.emit(OpCode::Pop, line);
+                    self.emit(OpCode::Pop, span);
                 }
                 if let Some(else_body) = else_block {
                     self.compile_block(else_body)?;
@@ -183,28 +283,28 @@ impl Compiler {
                 }
                 Ok(())
             }
-            Stmt::While { condition, body } => {
+            StmtKind::While { condition, body } => {
                 let loop_start = self.chunk.len();
-                self.emit(OpCode::CheckIterLimit, line);
+                self.emit(OpCode::CheckIterLimit, span);
                 self.compile_expr(condition)?;
-                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, line);
-                self.emit(OpCode::Pop, line);
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit(OpCode::Pop, span);
                 self.compile_block(body)?;
-                self.emit_loop(loop_start, line);
+                self.emit_loop(loop_start, span);
                 self.patch_jump(exit_jump);
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Pop, span);
                 Ok(())
             }
-            Stmt::Return(value) => {
+            StmtKind::Return(value) => {
                 if let Some(expr) = value {
                     self.compile_expr(expr)?;
                 } else {
-                    self.emit(OpCode::PushNil, line);
+                    self.emit(OpCode::PushNil, span);
                 }
-                self.emit(OpCode::Return, line);
+                self.emit(OpCode::Return, span);
                 Ok(())
             }
-            Stmt::For {
+            StmtKind::For {
                 var,
                 start,
                 end,
@@ -215,92 +315,90 @@ impl Compiler {
                 self.compile_expr(start)?;
                 let var_slot = self.scope.add_local(var.clone());
                 let loop_start = self.chunk.len();
-                self.emit(OpCode::CheckIterLimit, line);
-                self.emit(OpCode::LoadLocal, line);
-                self.emit_byte(var_slot, line);
+                self.emit(OpCode::CheckIterLimit, span);
+                self.emit(OpCode::LoadLocal, span);
+                self.emit_byte(var_slot, span);
                 self.compile_expr(end)?;
-                self.emit(OpCode::Le, line);
-                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, line);
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Le, span);
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+                self.emit(OpCode::Pop, span);
                 self.compile_block(body)?;
-                self.emit(OpCode::LoadLocal, line);
-                self.emit_byte(var_slot, line);
+                self.emit(OpCode::LoadLocal, span);
+                self.emit_byte(var_slot, span);
                 if let Some(step_expr) = step {
                     self.compile_expr(step_expr)?;
                 } else {
-                    let idx = self.chunk.add_constant(Value::Integer(1));
-                    self.emit(OpCode::PushConst, line);
-                    self.emit_byte(idx, line);
+                    self.emit_constant(Value::Integer(1), span)?;
                 }
-                self.emit(OpCode::Add, line);
-                self.emit(OpCode::StoreLocal, line);
-                self.emit_byte(var_slot, line);
-                self.emit(OpCode::Pop, line);
-                self.emit_loop(loop_start, line);
+                self.emit(OpCode::Add, span);
+                self.emit(OpCode::StoreLocal, span);
+                self.emit_byte(var_slot, span);
+                self.emit(OpCode::Pop, span);
+                self.emit_loop(loop_start, span);
                 self.patch_jump(exit_jump);
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Pop, span);
                 self.scope.end_scope();
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Pop, span);
                 Ok(())
             }
-            Stmt::Each {
+            StmtKind::Each {
                 var,
                 iterator,
                 body,
             } => {
                 self.scope.begin_scope();
                 self.compile_expr(iterator)?;
-                self.emit(OpCode::IterInit, line);
-                self.emit(OpCode::PushNil, line);
+                self.emit(OpCode::IterInit, span);
+                self.emit(OpCode::PushNil, span);
                 let var_slot = self.scope.add_local(var.clone());
                 let loop_start = self.chunk.len();
-                self.emit(OpCode::CheckIterLimit, line);
-                let exit_jump = self.emit_jump(OpCode::IterNext, line);
-                self.emit(OpCode::StoreLocal, line);
-                self.emit_byte(var_slot, line);
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::CheckIterLimit, span);
+                let exit_jump = self.emit_jump(OpCode::IterNext, span);
+                self.emit(OpCode::StoreLocal, span);
+                self.emit_byte(var_slot, span);
+                self.emit(OpCode::Pop, span);
                 self.compile_block(body)?;
-                self.emit_loop(loop_start, line);
+                self.emit_loop(loop_start, span);
                 self.patch_jump(exit_jump);
                 let pops = self.scope.end_scope();
                 for _ in 0..pops {
-                    self.emit(OpCode::Pop, line);
+                    self.emit(OpCode::Pop, span);
                 }
-                self.emit(OpCode::Pop, line);
+                self.emit(OpCode::Pop, span);
                 Ok(())
             }
-            Stmt::Assignment { target, value } => {
+            StmtKind::Assignment { target, value } => {
                 self.compile_expr(value)?;
-                if let Expr::Variable(name) = target {
+                if let ExprKind::Variable(name) = &target.kind {
                     if let Some(slot) = self.scope.resolve_local(name) {
                         match slot {
-                            0 => self.emit(OpCode::StoreLocal0, line),
-                            1 => self.emit(OpCode::StoreLocal1, line),
-                            2 => self.emit(OpCode::StoreLocal2, line),
+                            0 => self.emit(OpCode::StoreLocal0, span),
+                            1 => self.emit(OpCode::StoreLocal1, span),
+                            2 => self.emit(OpCode::StoreLocal2, span),
                             _ => {
-                                self.emit(OpCode::StoreLocal, line);
-                                self.emit_byte(slot, line);
+                                self.emit(OpCode::StoreLocal, span);
+                                self.emit_byte(slot, span);
                             }
                         }
-                        self.emit(OpCode::Pop, line);
+                        self.emit(OpCode::Pop, span);
                     } else if let Some(idx) = self.global_names.iter().position(|n| n == name) {
                         let idx = idx as u8;
                         match idx {
-                            21 => self.emit(OpCode::StoreGlobal0, line),
-                            22 => self.emit(OpCode::StoreGlobal1, line),
-                            23 => self.emit(OpCode::StoreGlobal2, line),
+                            21 => self.emit(OpCode::StoreGlobal0, span),
+                            22 => self.emit(OpCode::StoreGlobal1, span),
+                            23 => self.emit(OpCode::StoreGlobal2, span),
                             _ => {
-                                self.emit(OpCode::StoreGlobal, line);
-                                self.emit_byte(idx, line);
+                                self.emit(OpCode::StoreGlobal, span);
+                                self.emit_byte(idx, span);
                             }
                         }
-                        self.emit(OpCode::Pop, line);
+                        self.emit(OpCode::Pop, span);
                     } else if self.scope.scope_depth > 0 {
                         self.scope.add_local(name.clone());
                     } else {
                         let idx = self.add_global(name.clone());
-                        self.emit(OpCode::DefineGlobal, line);
-                        self.emit_byte(idx, line);
+                        self.emit(OpCode::DefineGlobal, span);
+                        self.emit_byte(idx, span);
                     }
                 }
                 Ok(())
@@ -315,29 +413,23 @@ impl Compiler {
         }
         let pops = self.scope.end_scope();
         for _ in 0..pops {
-            self.emit(OpCode::Pop, 0);
+            self.emit(OpCode::Pop, Span::default());
         }
         Ok(())
     }
     fn compile_expr(&mut self, expr: &Expr) -> NebulaResult<()> {
-        let line = 0;
-        match expr {
-            Expr::Literal(lit) => {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(lit) => {
                 match lit {
                     Literal::Integer(n) => {
-                        let idx = self.chunk.add_constant(Value::Integer(*n));
-                        self.emit(OpCode::PushConst, line);
-                        self.emit_byte(idx, line);
+                        self.emit_constant(Value::Integer(*n), span)?;
                     }
                     Literal::Float(f) => {
-                        let idx = self.chunk.add_constant(Value::Number(*f));
-                        self.emit(OpCode::PushConst, line);
-                        self.emit_byte(idx, line);
+                        self.emit_constant(Value::Number(*f), span)?;
                     }
                     Literal::String(s) => {
-                        let idx = self.chunk.add_constant(Value::String(s.clone()));
-                        self.emit(OpCode::PushConst, line);
-                        self.emit_byte(idx, line);
+                        self.emit_constant(Value::String(s.as_str().into()), span)?;
                     }
                     Literal::Bool(b) => {
                         self.emit(
@@ -346,123 +438,360 @@ impl Compiler {
                             } else {
                                 OpCode::PushFalse
                             },
-                            line,
+                            span,
                         );
                     }
                 }
                 Ok(())
             }
-            Expr::Variable(name) => {
+            ExprKind::Nil => {
+                self.emit(OpCode::PushNil, span);
+                Ok(())
+            }
+            ExprKind::Variable(name) => {
                 if let Some(slot) = self.scope.resolve_local(name) {
                     match slot {
-                        0 => self.emit(OpCode::LoadLocal0, line),
-                        1 => self.emit(OpCode::LoadLocal1, line),
-                        2 => self.emit(OpCode::LoadLocal2, line),
+                        0 => self.emit(OpCode::LoadLocal0, span),
+                        1 => self.emit(OpCode::LoadLocal1, span),
+                        2 => self.emit(OpCode::LoadLocal2, span),
                         _ => {
-                            self.emit(OpCode::LoadLocal, line);
-                            self.emit_byte(slot, line);
+                            self.emit(OpCode::LoadLocal, span);
+                            self.emit_byte(slot, span);
                         }
                     }
                 } else {
                     let idx = self.resolve_global(name);
                     match idx {
-                        21 => self.emit(OpCode::LoadGlobal0, line),
-                        22 => self.emit(OpCode::LoadGlobal1, line),
-                        23 => self.emit(OpCode::LoadGlobal2, line),
+                        21 => self.emit(OpCode::LoadGlobal0, span),
+                        22 => self.emit(OpCode::LoadGlobal1, span),
+                        23 => self.emit(OpCode::LoadGlobal2, span),
                         _ => {
-                            self.emit(OpCode::LoadGlobal, line);
-                            self.emit_byte(idx, line);
+                            self.emit(OpCode::LoadGlobal, span);
+                            self.emit_byte(idx, span);
                         }
                     }
                 }
                 Ok(())
             }
-            Expr::Binary { left, op, right } => {
+            ExprKind::Binary { left, op, right } => {
                 if let Some(result) = self.try_fold_binary(left, op, right)? {
-                    let idx = self.chunk.add_constant(result);
-                    self.emit(OpCode::PushConst, line);
-                    self.emit_byte(idx, line);
-                } else {
-                    self.compile_expr(left)?;
-                    self.compile_expr(right)?;
-                    self.emit_binary_op(op, line);
+                    self.emit_constant(result, span)?;
+                    return Ok(());
+                }
+                let mut links = vec![(op, right, span)];
+                let mut left = &**left;
+                while let ExprKind::Binary {
+                    left: inner,
+                    op,
+                    right,
+                } = &left.kind
+                {
+                    if self.try_fold_binary(inner, op, right)?.is_some() {
+                        break;
+                    }
+                    links.push((op, right, left.span));
+                    left = inner;
+                }
+                self.compile_expr(left)?;
+                for (op, right, span) in links.into_iter().rev() {
+                    self.compile_operand(op, right, span)?;
                 }
                 Ok(())
             }
-            Expr::Unary { op, operand } => {
+            ExprKind::Unary { op, operand } => {
                 self.compile_expr(operand)?;
                 match op {
-                    UnaryOp::Neg => self.emit(OpCode::Neg, line),
-                    UnaryOp::Not => self.emit(OpCode::Not, line),
+                    UnaryOp::Neg => self.emit(OpCode::Neg, span),
+                    UnaryOp::Not => self.emit(OpCode::Not, span),
                     _ => {}
                 }
                 Ok(())
             }
-            Expr::Call { callee, args } => {
-                if let Expr::Variable(name) = callee.as_ref() {
+            ExprKind::Call { callee, args } => {
+                if let ExprKind::Variable(name) = &callee.kind {
                     if let Some(builtin_idx) = BUILTIN_NAMES.iter().position(|n| *n == name) {
                         for arg in args {
                             self.compile_expr(arg)?;
                         }
-                        self.emit(OpCode::CallBuiltin, line);
-                        self.emit_byte(builtin_idx as u8, line);
-                        self.emit_byte(args.len() as u8, line);
+                        let argc = arg_count(args)?;
+                        self.emit(OpCode::CallBuiltin, span);
+                        self.emit_byte(builtin_idx as u8, span);
+                        self.emit_byte(argc, span);
                         return Ok(());
                     }
                 }
-                self.compile_expr(callee)?;
+                if let Some(name) = self.ext_function(callee) {
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    let argc = arg_count(args)?;
+                    let idx = self.chunk.add_constant(Value::String(name.into()))?;
+                    self.emit(OpCode::CallExt, span);
+                    self.chunk.write_u16(idx, span);
+                    self.emit_byte(argc, span);
+                    return Ok(());
+                }
+                match &callee.kind {
+                    ExprKind::Variable(name) if self.scope.resolve_local(name).is_none() => {
+                        let idx = self.resolve_global(name);
+                        self.emit(OpCode::LoadCallee, span);
+                        self.emit_byte(idx, span);
+                    }
+                    _ => self.compile_expr(callee)?,
+                }
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                let argc = arg_count(args)?;
+                self.emit(OpCode::Call, span);
+                self.emit_byte(argc, span);
+                Ok(())
+            }
+            ExprKind::List(items) => {
+                self.compile_collection(items, OpCode::List, span, |this, item| {
+                    this.compile_expr(item)
+                })
+            }
+            ExprKind::Interpolation(parts) => {
+                let mut pending = 0;
+                for part in parts {
+                    if pending == u8::MAX {
+                        self.emit(OpCode::Concat, span);
+                        self.emit_byte(pending, span);
+                        pending = 1;
+                    }
+                    self.compile_expr(part)?;
+                    pending += 1;
+                }
+                self.emit(OpCode::Concat, span);
+                self.emit_byte(pending, span);
+                Ok(())
+            }
+            ExprKind::Map(pairs) => {
+                self.compile_collection(pairs, OpCode::Map, span, |this, (key, value)| {
+                    this.compile_expr(key)?;
+                    this.compile_expr(value)
+                })
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                self.compile_expr(receiver)?;
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                let argc = arg_count(args)?;
+                let idx = self
+                    .chunk
+                    .add_constant(Value::String(method.as_str().into()))?;
+                self.emit(OpCode::Invoke, span);
+                self.chunk.write_u16(idx, span);
+                self.emit_byte(argc, span);
+                Ok(())
+            }
+            ExprKind::Await(operand) => {
+                self.compile_expr(operand)?;
+                self.emit(OpCode::Await, span);
+                Ok(())
+            }
+            ExprKind::Spawn(operand) => {
+                let args: &[Expr] = match &operand.kind {
+                    ExprKind::Call { callee, args } => {
+                        match &callee.kind {
+                            ExprKind::Variable(name)
+                                if self.scope.resolve_local(name).is_none() =>
+                            {
+                                let idx = self.resolve_global(name);
+                                self.emit(OpCode::LoadCallee, span);
+                                self.emit_byte(idx, span);
+                            }
+                            _ => self.compile_expr(callee)?,
+                        }
+                        args
+                    }
+                    _ => {
+                        self.compile_expr(operand)?;
+                        &[]
+                    }
+                };
                 for arg in args {
                     self.compile_expr(arg)?;
                 }
-                self.emit(OpCode::Call, line);
-                self.emit_byte(args.len() as u8, line);
+                let argc = arg_count(args)?;
+                self.emit(OpCode::Spawn, span);
+                self.emit_byte(argc, span);
                 Ok(())
             }
-            Expr::List(items) => {
-                for item in items {
-                    self.compile_expr(item)?;
+            ExprKind::Lambda { params, body } => {
+                let mut func_compiler = self.nested();
+                for param in params.iter() {
+                    func_compiler.scope.add_local(param.clone());
+                }
+                if let ExprKind::Block(stmts) = &body.kind {
+                    match stmts.split_last() {
+                        Some((
+                            Stmt {
+                                kind: StmtKind::Expression(last),
+                                ..
+                            },
+                            rest,
+                        )) => {
+                            for stmt in rest {
+                                func_compiler.compile_stmt(stmt)?;
+                            }
+                            func_compiler.compile_expr(last)?;
+                        }
+                        _ => {
+                            for stmt in stmts {
+                                func_compiler.compile_stmt(stmt)?;
+                            }
+                            func_compiler.emit(OpCode::PushNil, span);
+                        }
+                    }
+                } else {
+                    func_compiler.compile_expr(body)?;
                 }
-                self.emit(OpCode::List, line);
-                self.emit_byte(items.len() as u8, line);
+                func_compiler.emit(OpCode::Return, span);
+                self.global_names = std::mem::take(&mut func_compiler.global_names);
+                self.warnings.append(&mut func_compiler.warnings);
+                let compiled = super::CompiledFunction {
+                    name: "<lambda>".into(),
+                    arity: params.len() as u8,
+                    local_count: func_compiler.scope.locals.len() as u8,
+                    chunk: func_compiler.chunk,
+                };
+                let func_idx = self.functions.len() as u8;
+                self.functions.push(compiled);
+                self.emit(OpCode::Closure, span);
+                self.emit_byte(func_idx, span);
+                Ok(())
+            }
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.compile_expr(start)?;
+                self.compile_expr(end)?;
+                self.emit(OpCode::Range, span);
+                self.emit_byte(*inclusive as u8, span);
+                Ok(())
+            }
+            ExprKind::Length(operand) => {
+                self.compile_expr(operand)?;
+                let len_idx = BUILTIN_NAMES.iter().position(|n| *n == "len").unwrap_or(0);
+                self.emit(OpCode::CallBuiltin, span);
+                self.emit_byte(len_idx as u8, span);
+                self.emit_byte(1, span);
                 Ok(())
             }
             _ => Ok(()),
         }
     }
-    fn emit(&mut self, op: OpCode, line: usize) {
-        self.chunk.write_op(op, line);
+    fn emit(&mut self, op: OpCode, span: Span) {
+        self.chunk.write_op(op, span);
+    }
+    fn emit_byte(&mut self, byte: u8, span: Span) {
+        self.chunk.write_byte(byte, span);
+    }
+    fn emit_constant(&mut self, value: Value, span: Span) -> NebulaResult<()> {
+        let idx = self.chunk.add_constant(value)?;
+        match u8::try_from(idx) {
+            Ok(idx) => {
+                self.emit(OpCode::PushConst, span);
+                self.emit_byte(idx, span);
+            }
+            Err(_) => {
+                self.emit(OpCode::PushConstLong, span);
+                self.chunk.write_u16(idx, span);
+            }
+        }
+        Ok(())
     }
-    fn emit_byte(&mut self, byte: u8, line: usize) {
-        self.chunk.write_byte(byte, line);
+    fn compile_collection<T>(
+        &mut self,
+        items: &[T],
+        op: OpCode,
+        span: Span,
+        mut compile: impl FnMut(&mut Self, &T) -> NebulaResult<()>,
+    ) -> NebulaResult<()> {
+        if items.is_empty() {
+            self.emit(op, span);
+            self.emit_byte(0, span);
+        }
+        for (i, batch) in items.chunks(COLLECTION_BATCH).enumerate() {
+            for item in batch {
+                compile(self, item)?;
+            }
+            self.emit(op, span);
+            self.emit_byte(batch.len() as u8, span);
+            if i > 0 {
+                self.emit(OpCode::Extend, span);
+            }
+        }
+        Ok(())
     }
-    fn emit_jump(&mut self, op: OpCode, line: usize) -> usize {
-        self.emit(op, line);
-        self.chunk.write_u16(0xffff, line);
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit(op, span);
+        self.chunk.write_u16(0xffff, span);
         self.chunk.len() - 2
     }
     fn patch_jump(&mut self, offset: usize) {
         self.chunk.patch_jump(offset);
     }
-    fn emit_loop(&mut self, loop_start: usize, line: usize) {
-        self.emit(OpCode::Loop, line);
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        self.emit(OpCode::Loop, span);
         let offset = self.chunk.len().saturating_sub(loop_start) + 2;
         let offset = offset.min(u16::MAX as usize);
-        self.chunk.write_u16(offset as u16, line);
+        self.chunk.write_u16(offset as u16, span);
+    }
+    fn compile_operand(&mut self, op: &BinaryOp, right: &Expr, span: Span) -> NebulaResult<()> {
+        if *op != BinaryOp::Coalesce {
+            self.compile_expr(right)?;
+            self.emit_binary_op(op, span);
+            return Ok(());
+        }
+        self.emit(OpCode::Dup, span);
+        self.emit(OpCode::PushNil, span);
+        self.emit(OpCode::Eq, span);
+        let present = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit(OpCode::Pop, span);
+        self.emit(OpCode::Pop, span);
+        self.compile_expr(right)?;
+        let end = self.emit_jump(OpCode::Jump, span);
+        self.patch_jump(present);
+        self.emit(OpCode::Pop, span);
+        self.patch_jump(end);
+        Ok(())
     }
-    fn emit_binary_op(&mut self, op: &BinaryOp, line: usize) {
+    fn emit_binary_op(&mut self, op: &BinaryOp, span: Span) {
+        let specialized = match (self.numeric_hints.get(&span), op) {
+            (Some(NumericKind::Int), BinaryOp::Add) => Some(OpCode::AddInt),
+            (Some(NumericKind::Int), BinaryOp::Sub) => Some(OpCode::SubInt),
+            (Some(NumericKind::Int), BinaryOp::Mul) => Some(OpCode::MulInt),
+            (Some(NumericKind::Float), BinaryOp::Add) => Some(OpCode::AddFloat),
+            (Some(NumericKind::Float), BinaryOp::Sub) => Some(OpCode::SubFloat),
+            (Some(NumericKind::Float), BinaryOp::Mul) => Some(OpCode::MulFloat),
+            _ => None,
+        };
+        if let Some(opcode) = specialized {
+            return self.emit(opcode, span);
+        }
         match op {
-            BinaryOp::Add => self.emit(OpCode::Add, line),
-            BinaryOp::Sub => self.emit(OpCode::Sub, line),
-            BinaryOp::Mul => self.emit(OpCode::Mul, line),
-            BinaryOp::Div => self.emit(OpCode::Div, line),
-            BinaryOp::Mod => self.emit(OpCode::Mod, line),
-            BinaryOp::Pow => self.emit(OpCode::Pow, line),
-            BinaryOp::Eq => self.emit(OpCode::Eq, line),
-            BinaryOp::Ne => self.emit(OpCode::Ne, line),
-            BinaryOp::Lt => self.emit(OpCode::Lt, line),
-            BinaryOp::Gt => self.emit(OpCode::Gt, line),
-            BinaryOp::Le => self.emit(OpCode::Le, line),
-            BinaryOp::Ge => self.emit(OpCode::Ge, line),
+            BinaryOp::Add => self.emit(OpCode::Add, span),
+            BinaryOp::Sub => self.emit(OpCode::Sub, span),
+            BinaryOp::Mul => self.emit(OpCode::Mul, span),
+            BinaryOp::Div => self.emit(OpCode::Div, span),
+            BinaryOp::Mod => self.emit(OpCode::Mod, span),
+            BinaryOp::Pow => self.emit(OpCode::Pow, span),
+            BinaryOp::Eq => self.emit(OpCode::Eq, span),
+            BinaryOp::Ne => self.emit(OpCode::Ne, span),
+            BinaryOp::Lt => self.emit(OpCode::Lt, span),
+            BinaryOp::Gt => self.emit(OpCode::Gt, span),
+            BinaryOp::Le => self.emit(OpCode::Le, span),
+            BinaryOp::Ge => self.emit(OpCode::Ge, span),
             _ => {}
         }
     }
@@ -530,10 +859,10 @@ impl Compiler {
         }
     }
     fn extract_number(&self, expr: &Expr) -> Option<f64> {
-        match expr {
-            Expr::Literal(Literal::Integer(n)) => Some(*n as f64),
-            Expr::Literal(Literal::Float(f)) => Some(*f),
-            Expr::Binary { left, op, right } => self
+        match &expr.kind {
+            ExprKind::Literal(Literal::Integer(n)) => Some(*n as f64),
+            ExprKind::Literal(Literal::Float(f)) => Some(*f),
+            ExprKind::Binary { left, op, right } => self
                 .try_fold_binary(left, op, right)
                 .ok()?
                 .and_then(|v| match v {
@@ -541,7 +870,7 @@ impl Compiler {
                     Value::Number(f) => Some(f),
                     _ => None,
                 }),
-            Expr::Unary {
+            ExprKind::Unary {
                 op: UnaryOp::Neg,
                 operand,
             } => self.extract_number(operand).map(|n| -n),
@@ -549,8 +878,88 @@ impl Compiler {
         }
     }
 }
+fn arg_count(args: &[Expr]) -> NebulaResult<u8> {
+    u8::try_from(args.len()).map_err(|_| {
+        crate::error::NebulaError::coded(
+            crate::error::ErrorCode::E012,
+            format!("too many arguments ({}, at most 255)", args.len()),
+        )
+    })
+}
 impl Default for Compiler {
     fn default() -> Self {
         Self::new()
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::typeck::{TypeChecker, TypeMode};
+
+    fn specialized(code: &str) -> Vec<(OpCode, OpCode)> {
+        let tokens = Lexer::new(code).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let mut checker = TypeChecker::with_mode(TypeMode::Gradual);
+        checker.check_program(&program).unwrap();
+        let generic = Compiler::new().compile(&program).unwrap();
+        let mut compiler = Compiler::new();
+        compiler.set_numeric_hints(checker.numeric_hints().clone());
+        let typed = compiler.compile(&program).unwrap();
+        assert_eq!(generic.code().len(), typed.code().len());
+        generic
+            .code()
+            .iter()
+            .zip(typed.code())
+            .filter(|(a, b)| a != b)
+            .map(|(&a, &b)| (OpCode::from_byte(a).unwrap(), OpCode::from_byte(b).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_specializes_arithmetic_only_for_known_numeric_types() {
+        assert_eq!(
+            specialized("fb a: int = 2\nfb b: int = 3\nlog(a + b, a - b, a * b)"),
+            [
+                (OpCode::Add, OpCode::AddInt),
+                (OpCode::Sub, OpCode::SubInt),
+                (OpCode::Mul, OpCode::MulInt)
+            ]
+        );
+        assert_eq!(
+            specialized("fb a: fl = 1.5\nfb b: fl = 2.5\nlog(a + b, a - b, a * b)"),
+            [
+                (OpCode::Add, OpCode::AddFloat),
+                (OpCode::Sub, OpCode::SubFloat),
+                (OpCode::Mul, OpCode::MulFloat)
+            ]
+        );
+        assert!(specialized("fb a: int = 2\nfb b: fl = 2.5\nlog(a + b, a * b)").is_empty());
+        assert!(specialized("fb a = num(\"2\")\nfb b: int = 3\nlog(a + b, b - a)").is_empty());
+    }
+    #[test]
+    fn test_specialized_ops_fall_back_when_the_value_disagrees() {
+        let code =
+            "fn scale(n: int): int do\n  give n * 2 + 1\nend\nexit(scale(num(\"2.5\")) + scale(3))";
+        let tokens = Lexer::new(code).tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let mut checker = TypeChecker::with_mode(TypeMode::Gradual);
+        checker.check_program(&program).unwrap();
+        let mut compiler = Compiler::new();
+        compiler.set_numeric_hints(checker.numeric_hints().clone());
+        let chunk = compiler.compile(&program).unwrap();
+        assert!(compiler
+            .functions()
+            .iter()
+            .any(|f| f.chunk.code().contains(&(OpCode::MulInt as u8))));
+        let err = crate::vm::VM::new()
+            .run_with_functions(&chunk, compiler.global_names(), compiler.functions())
+            .unwrap_err();
+        assert!(
+            matches!(err, crate::NebulaError::Exit { code: 13 }),
+            "{}",
+            err
+        );
+    }
+}
@@ -43,6 +43,10 @@ impl StringInterner {
     pub fn len(&self) -> usize {
         self.strings.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
 }
 
 impl Default for StringInterner {
@@ -1,3 +1,4 @@
+mod bridge;
 mod chunk;
 mod compiler;
 mod intern;
@@ -5,6 +6,7 @@ mod nanbox;
 mod opcode;
 mod peephole;
 mod vm_nanbox;
+pub use bridge::{from_value, to_value};
 pub use chunk::Chunk;
 pub use compiler::Compiler;
 pub use intern::StringInterner;
@@ -13,5 +15,6 @@ pub use nanbox::{CompiledFunction, HeapData, HeapObject, NanBoxed, ObjectTag};
 pub use opcode::OpCode;
 pub use peephole::optimize as peephole_optimize;
 pub use vm_nanbox::VMNanBox;
+pub use vm_nanbox::BUILTIN_NAMES;
 pub use vm_nanbox::VMNanBox as VM;
 
@@ -69,6 +69,14 @@ impl NanBoxed {
         Self(QNAN | TAG_INT | bits)
     }
     #[inline(always)]
+    pub fn from_i64(n: i64) -> Self {
+        if (n << 16) >> 16 == n {
+            Self::integer(n)
+        } else {
+            Self::number(n as f64)
+        }
+    }
+    #[inline(always)]
     pub const fn nil() -> Self {
         Self(NIL)
     }
@@ -202,6 +210,10 @@ pub enum ObjectTag {
     Closure = 4,
     Native = 5,
     Struct = 6,
+    Extern = 7,
+    Future = 8,
+    Tuple = 9,
+    Range = 10,
 }
 #[repr(C)]
 pub struct HeapObject {
@@ -212,8 +224,12 @@ pub struct HeapObject {
 pub enum HeapData {
     String(Box<str>),
     List(Vec<NanBoxed>),
+    Tuple(Vec<NanBoxed>),
     Map(std::collections::HashMap<Box<str>, NanBoxed>),
     Function(CompiledFunction),
+    Extern(crate::ext::Extern),
+    Future(crate::ext::ExtFuture),
+    Range(crate::interp::RangeValue),
 }
 #[derive(Debug, Clone)]
 pub struct CompiledFunction {
@@ -236,23 +252,39 @@ impl fmt::Display for HeapObject {
                 }
                 write!(f, ")")
             }
+            HeapData::Tuple(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
             HeapData::Map(map) => {
                 write!(f, "map(")?;
-                for (i, (k, v)) in map.iter().enumerate() {
+                let mut keys: Vec<_> = map.keys().collect();
+                keys.sort();
+                for (i, k) in keys.into_iter().enumerate() {
                     if i > 0 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "\"{}\": {}", k, v)?;
+                    write!(f, "\"{}\": {}", k, map[k])?;
                 }
                 write!(f, ")")
             }
             HeapData::Function(func) => write!(f, "<fn {}>", func.name),
+            HeapData::Extern(handle) => write!(f, "{:?}", handle),
+            HeapData::Future(future) => write!(f, "{:?}", future),
+            HeapData::Range(range) => write!(f, "{}", crate::interp::Value::Range(*range)),
         }
     }
 }
 impl HeapObject {
     pub fn new_string(s: &str) -> *mut Self {
         track_alloc();
+        crate::limits::record(std::mem::size_of::<HeapObject>() + s.len());
         let obj = Box::new(HeapObject {
             tag: ObjectTag::String,
             rc: std::sync::atomic::AtomicU32::new(1),
@@ -262,6 +294,9 @@ impl HeapObject {
     }
     pub fn new_list(items: Vec<NanBoxed>) -> *mut Self {
         track_alloc();
+        crate::limits::record(
+            std::mem::size_of::<HeapObject>() + items.len() * std::mem::size_of::<NanBoxed>(),
+        );
         let obj = Box::new(HeapObject {
             tag: ObjectTag::List,
             rc: std::sync::atomic::AtomicU32::new(1),
@@ -269,8 +304,47 @@ impl HeapObject {
         });
         Box::into_raw(obj)
     }
+    pub fn new_tuple(items: Vec<NanBoxed>) -> *mut Self {
+        track_alloc();
+        crate::limits::record(
+            std::mem::size_of::<HeapObject>() + items.len() * std::mem::size_of::<NanBoxed>(),
+        );
+        let obj = Box::new(HeapObject {
+            tag: ObjectTag::Tuple,
+            rc: std::sync::atomic::AtomicU32::new(1),
+            data: HeapData::Tuple(items),
+        });
+        Box::into_raw(obj)
+    }
+    pub fn new_range(range: crate::interp::RangeValue) -> *mut Self {
+        track_alloc();
+        crate::limits::record(std::mem::size_of::<HeapObject>());
+        let obj = Box::new(HeapObject {
+            tag: ObjectTag::Range,
+            rc: std::sync::atomic::AtomicU32::new(1),
+            data: HeapData::Range(range),
+        });
+        Box::into_raw(obj)
+    }
+    pub fn new_map(map: std::collections::HashMap<Box<str>, NanBoxed>) -> *mut Self {
+        track_alloc();
+        crate::limits::record(
+            std::mem::size_of::<HeapObject>()
+                + map
+                    .keys()
+                    .map(|key| key.len() + std::mem::size_of::<(Box<str>, NanBoxed)>())
+                    .sum::<usize>(),
+        );
+        let obj = Box::new(HeapObject {
+            tag: ObjectTag::Map,
+            rc: std::sync::atomic::AtomicU32::new(1),
+            data: HeapData::Map(map),
+        });
+        Box::into_raw(obj)
+    }
     pub fn new_function(func: CompiledFunction) -> *mut Self {
         track_alloc();
+        crate::limits::record(std::mem::size_of::<HeapObject>());
         let obj = Box::new(HeapObject {
             tag: ObjectTag::Function,
             rc: std::sync::atomic::AtomicU32::new(1),
@@ -278,6 +352,40 @@ impl HeapObject {
         });
         Box::into_raw(obj)
     }
+    pub fn new_extern(handle: crate::ext::Extern) -> *mut Self {
+        track_alloc();
+        crate::limits::record(std::mem::size_of::<HeapObject>());
+        let obj = Box::new(HeapObject {
+            tag: ObjectTag::Extern,
+            rc: std::sync::atomic::AtomicU32::new(1),
+            data: HeapData::Extern(handle),
+        });
+        Box::into_raw(obj)
+    }
+    pub fn new_future(future: crate::ext::ExtFuture) -> *mut Self {
+        track_alloc();
+        crate::limits::record(std::mem::size_of::<HeapObject>());
+        let obj = Box::new(HeapObject {
+            tag: ObjectTag::Future,
+            rc: std::sync::atomic::AtomicU32::new(1),
+            data: HeapData::Future(future),
+        });
+        Box::into_raw(obj)
+    }
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<HeapObject>()
+            + match &self.data {
+                HeapData::String(s) => s.len(),
+                HeapData::List(items) | HeapData::Tuple(items) => {
+                    items.len() * std::mem::size_of::<NanBoxed>()
+                }
+                HeapData::Map(map) => map
+                    .keys()
+                    .map(|key| key.len() + std::mem::size_of::<(Box<str>, NanBoxed)>())
+                    .sum(),
+                _ => 0,
+            }
+    }
     #[allow(clippy::missing_safety_doc)]
     pub unsafe fn free(ptr: *mut Self) {
         if !ptr.is_null() {
@@ -336,9 +444,9 @@ mod tests {
     }
     #[test]
     fn test_numbers() {
-        let pi = NanBoxed::number(3.14159);
+        let pi = NanBoxed::number(std::f64::consts::PI);
         assert!(pi.is_number());
-        assert!((pi.as_number() - 3.14159).abs() < 1e-10);
+        assert!((pi.as_number() - std::f64::consts::PI).abs() < 1e-10);
         let zero = NanBoxed::number(0.0);
         assert!(!zero.is_truthy());
         let one = NanBoxed::number(1.0);
@@ -354,6 +462,10 @@ mod tests {
         assert_eq!(neg.as_integer(), -1000);
         let large_neg = NanBoxed::integer(-123456789);
         assert_eq!(large_neg.as_integer(), -123456789);
+        let wide = NanBoxed::from_i64(1 << 50);
+        assert!(wide.is_number());
+        assert_eq!(wide.as_number(), (1u64 << 50) as f64);
+        assert!(NanBoxed::from_i64(-(1 << 47)).is_integer());
     }
     #[test]
     fn test_string_ptr() {
@@ -7,6 +7,7 @@ pub enum OpCode {
     PushFalse = 3,
     Pop = 4,
     Dup = 5,
+    PushConstLong = 6,
     LoadLocal = 10,
     StoreLocal = 11,
     LoadUpvalue = 12,
@@ -43,16 +44,22 @@ pub enum OpCode {
     Call = 60,
     Return = 61,
     Closure = 62,
+    LoadCallee = 63,
     List = 70,
     Map = 71,
     Index = 72,
     StoreIndex = 73,
     Len = 74,
+    Concat = 75,
+    Range = 76,
+    Extend = 77,
     IterInit = 80,
     IterNext = 81,
     CheckIterLimit = 90,
     CheckRecursion = 91,
     Throw = 100,
+    Await = 101,
+    Spawn = 102,
     AddInt = 110,
     SubInt = 111,
     MulInt = 112,
@@ -60,6 +67,9 @@ pub enum OpCode {
     DecLocal = 114,
     Inc = 115,
     Dec = 116,
+    AddFloat = 117,
+    SubFloat = 118,
+    MulFloat = 119,
     LoadGlobal0 = 120,
     LoadGlobal1 = 121,
     LoadGlobal2 = 122,
@@ -67,6 +77,8 @@ pub enum OpCode {
     StoreGlobal1 = 124,
     StoreGlobal2 = 125,
     CallBuiltin = 130,
+    Invoke = 131,
+    CallExt = 132,
 }
 impl OpCode {
     pub fn operand_size(self) -> usize {
@@ -94,6 +106,8 @@ impl OpCode {
             | OpCode::Index
             | OpCode::StoreIndex
             | OpCode::Len
+            | OpCode::Extend
+            | OpCode::Await
             | OpCode::IterInit
             | OpCode::CheckIterLimit
             | OpCode::CheckRecursion
@@ -106,6 +120,9 @@ impl OpCode {
             | OpCode::AddInt
             | OpCode::SubInt
             | OpCode::MulInt
+            | OpCode::AddFloat
+            | OpCode::SubFloat
+            | OpCode::MulFloat
             | OpCode::Inc
             | OpCode::Dec
             | OpCode::LoadGlobal0
@@ -120,18 +137,24 @@ impl OpCode {
             | OpCode::LoadUpvalue
             | OpCode::StoreUpvalue
             | OpCode::LoadGlobal
+            | OpCode::LoadCallee
             | OpCode::StoreGlobal
             | OpCode::DefineGlobal
             | OpCode::Call
             | OpCode::Closure
             | OpCode::List
+            | OpCode::Concat
+            | OpCode::Range
+            | OpCode::Spawn
             | OpCode::Map
             | OpCode::IterNext
             | OpCode::Throw
             | OpCode::IncLocal
             | OpCode::DecLocal
             | OpCode::CallBuiltin => 2,
-            OpCode::Jump
+            OpCode::Invoke | OpCode::CallExt => 3,
+            OpCode::PushConstLong
+            | OpCode::Jump
             | OpCode::JumpIfFalse
             | OpCode::JumpIfTrue
             | OpCode::Loop
@@ -147,6 +170,7 @@ impl OpCode {
             3 => Some(OpCode::PushFalse),
             4 => Some(OpCode::Pop),
             5 => Some(OpCode::Dup),
+            6 => Some(OpCode::PushConstLong),
             10 => Some(OpCode::LoadLocal),
             11 => Some(OpCode::StoreLocal),
             12 => Some(OpCode::LoadUpvalue),
@@ -177,16 +201,22 @@ impl OpCode {
             60 => Some(OpCode::Call),
             61 => Some(OpCode::Return),
             62 => Some(OpCode::Closure),
+            63 => Some(OpCode::LoadCallee),
             70 => Some(OpCode::List),
             71 => Some(OpCode::Map),
             72 => Some(OpCode::Index),
             73 => Some(OpCode::StoreIndex),
             74 => Some(OpCode::Len),
+            75 => Some(OpCode::Concat),
+            76 => Some(OpCode::Range),
+            77 => Some(OpCode::Extend),
             80 => Some(OpCode::IterInit),
             81 => Some(OpCode::IterNext),
             90 => Some(OpCode::CheckIterLimit),
             91 => Some(OpCode::CheckRecursion),
             100 => Some(OpCode::Throw),
+            101 => Some(OpCode::Await),
+            102 => Some(OpCode::Spawn),
             17 => Some(OpCode::LoadLocal0),
             18 => Some(OpCode::LoadLocal1),
             19 => Some(OpCode::LoadLocal2),
@@ -200,6 +230,9 @@ impl OpCode {
             114 => Some(OpCode::DecLocal),
             115 => Some(OpCode::Inc),
             116 => Some(OpCode::Dec),
+            117 => Some(OpCode::AddFloat),
+            118 => Some(OpCode::SubFloat),
+            119 => Some(OpCode::MulFloat),
             120 => Some(OpCode::LoadGlobal0),
             121 => Some(OpCode::LoadGlobal1),
             122 => Some(OpCode::LoadGlobal2),
@@ -207,6 +240,8 @@ impl OpCode {
             124 => Some(OpCode::StoreGlobal1),
             125 => Some(OpCode::StoreGlobal2),
             130 => Some(OpCode::CallBuiltin),
+            131 => Some(OpCode::Invoke),
+            132 => Some(OpCode::CallExt),
             _ => None,
         }
     }
@@ -65,14 +65,14 @@ fn collapse_push_pop(chunk: &mut Chunk) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::interp::Value;
+    use crate::lexer::Span;
 
     #[test]
     fn test_peephole_basic() {
         let mut chunk = Chunk::new();
-        chunk.write(OpCode::PushNil as u8, 1);
-        chunk.write(OpCode::Pop as u8, 1);
-        chunk.write(OpCode::PushTrue as u8, 1);
+        chunk.write(OpCode::PushNil as u8, Span::default());
+        chunk.write(OpCode::Pop as u8, Span::default());
+        chunk.write(OpCode::PushTrue as u8, Span::default());
         
         let initial_len = chunk.code().len();
         optimize(&mut chunk);
@@ -1,6 +1,11 @@
 use super::intern::StringInterner;
-use super::{Chunk, CompiledFunction, HeapObject, NanBoxed, OpCode};
+use super::{from_value, to_value, Chunk, CompiledFunction, HeapObject, NanBoxed, OpCode};
+use crate::builtins::SandboxPolicy;
 use crate::error::{ErrorCode, NebulaError, NebulaResult};
+use crate::ext::{ExtFunction, ExtFuture};
+use crate::interp::{NativeContext, NativeFn, RangeValue, Stdio, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
 const STACK_SIZE: usize = 256;
 const MAX_GLOBALS: usize = 256;
 const MAX_FRAMES: usize = 64;
@@ -28,10 +33,32 @@ macro_rules! binary_op {
 }
 
 macro_rules! int_op {
-    ($self:expr, $op:tt) => {{
+    ($self:expr, $op:tt, $name:literal) => {{
+        let b = $self.pop()?;
+        let a = $self.pop()?;
+        if a.is_integer() && b.is_integer() {
+            $self.push(NanBoxed::integer(a.as_integer() $op b.as_integer()))?;
+        } else if let (Some(na), Some(nb)) = (a.as_numeric(), b.as_numeric()) {
+            $self.push(NanBoxed::number(na $op nb))?;
+        } else {
+            return Err(NebulaError::coded(ErrorCode::E031, $name));
+        }
+    }};
+}
+
+macro_rules! float_op {
+    ($self:expr, $op:tt, $name:literal) => {{
         let b = $self.pop()?;
         let a = $self.pop()?;
-        $self.push(NanBoxed::integer(a.as_integer() $op b.as_integer()))?;
+        if a.is_number() && b.is_number() {
+            $self.push(NanBoxed::number(a.as_number() $op b.as_number()))?;
+        } else if a.is_integer() && b.is_integer() {
+            $self.push(NanBoxed::integer(a.as_integer() $op b.as_integer()))?;
+        } else if let (Some(na), Some(nb)) = (a.as_numeric(), b.as_numeric()) {
+            $self.push(NanBoxed::number(na $op nb))?;
+        } else {
+            return Err(NebulaError::coded(ErrorCode::E031, $name));
+        }
     }};
 }
 
@@ -61,7 +88,14 @@ pub struct VMNanBox {
     globals: Vec<NanBoxed>,
     global_names: Vec<String>,
     iteration_count: usize,
+    instructions: u64,
+    calls: u64,
+    depth: usize,
     interner: StringInterner,
+    natives: HashMap<Box<str>, NativeFn>,
+    stdio: Stdio,
+    policy: SandboxPolicy,
+    usage: crate::limits::ResourceUsage,
 }
 impl VMNanBox {
     pub fn new() -> Self {
@@ -73,7 +107,17 @@ impl VMNanBox {
             globals: vec![NanBoxed::nil(); MAX_GLOBALS],
             global_names: Vec::new(),
             iteration_count: 0,
+            instructions: 0,
+            calls: 0,
+            depth: 0,
             interner: StringInterner::new(),
+            natives: crate::builtins::stdlib()
+                .into_iter()
+                .map(|(name, native)| (name.into(), native))
+                .collect(),
+            stdio: Stdio::default(),
+            policy: SandboxPolicy::default(),
+            usage: crate::limits::ResourceUsage::default(),
         };
         for (i, name) in BUILTIN_NAMES.iter().enumerate() {
             vm.globals[i] = vm.interner.intern(name);
@@ -83,6 +127,40 @@ impl VMNanBox {
     pub fn run(&mut self, chunk: &Chunk, global_names: &[String]) -> NebulaResult<NanBoxed> {
         self.run_with_functions(chunk, global_names, &[])
     }
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.stdio.set_output(output);
+    }
+    pub fn set_error(&mut self, error: Box<dyn Write>) {
+        self.stdio.set_error(error);
+    }
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.stdio.set_input(input);
+    }
+    pub fn set_policy(&mut self, policy: SandboxPolicy) {
+        self.policy = policy;
+    }
+    pub fn register_native(&mut self, native: NativeFn) {
+        self.natives.insert(native.name.as_str().into(), native);
+    }
+    pub fn global(&self, index: usize) -> Option<Value> {
+        self.globals
+            .get(index)
+            .filter(|value| !value.is_nil())
+            .map(|value| to_value(*value))
+    }
+    pub fn set_global(&mut self, index: usize, value: &Value) -> NebulaResult<()> {
+        let slot = self.globals.get_mut(index).ok_or_else(|| {
+            NebulaError::coded(
+                ErrorCode::E004,
+                format!("global slot {} out of range", index),
+            )
+        })?;
+        *slot = from_value(value);
+        Ok(())
+    }
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
     pub fn run_with_functions(
         &mut self,
         chunk: &Chunk,
@@ -92,7 +170,23 @@ impl VMNanBox {
         self.ip = 0;
         self.frame_base = 0;
         self.iteration_count = 0;
+        self.instructions = 0;
+        self.calls = 0;
+        self.usage = crate::limits::usage();
         self.global_names = global_names.to_vec();
+        let constants = crate::builtins::math_constants();
+        let extensions = crate::ext::native_extensions();
+        for (i, name) in global_names.iter().enumerate().skip(BUILTIN_COUNT) {
+            if i >= MAX_GLOBALS || !self.globals[i].is_nil() {
+                continue;
+            }
+            if self.natives.contains_key(name.as_str()) || extensions.get_function(name).is_some() {
+                self.globals[i] = self.interner.intern(name);
+            } else if let Some((_, value)) = constants.iter().find(|(c, _)| c == name) {
+                self.globals[i] = from_value(value);
+            }
+        }
+        drop(extensions);
         self.frames.clear();
         self.stack.clear();
         self.frames.push(CallFrame {
@@ -100,7 +194,20 @@ impl VMNanBox {
             ip: 0,
             base: 0,
         });
-        self.run_main_loop(chunk, functions)
+        let result = self.run_main_loop(chunk, functions);
+        match result {
+            Ok(value) => crate::ext::run_tasks(self).map(|()| value),
+            Err(e) => {
+                crate::ext::drop_tasks();
+                Err(self.locate(e, chunk))
+            }
+        }
+    }
+    fn locate(&self, error: NebulaError, chunk: &Chunk) -> NebulaError {
+        match chunk.get_span(self.ip.saturating_sub(1)) {
+            Some(span) => error.with_span(span),
+            None => error,
+        }
     }
     fn run_main_loop(
         &mut self,
@@ -122,10 +229,18 @@ impl VMNanBox {
                 }
             };
             self.ip += 1;
+            self.instructions += 1;
             match op {
                 OpCode::PushConst => {
                     let idx = chunk.read_byte(self.ip);
                     self.ip += 1;
+                    let value = chunk.get_constant(idx.into());
+                    let nb = self.value_to_nanbox(value);
+                    self.push(nb)?;
+                }
+                OpCode::PushConstLong => {
+                    let idx = chunk.read_u16(self.ip);
+                    self.ip += 2;
                     let value = chunk.get_constant(idx);
                     let nb = self.value_to_nanbox(value);
                     self.push(nb)?;
@@ -236,9 +351,12 @@ impl VMNanBox {
                     let value = self.peek(0)?;
                     self.globals[23] = value;
                 }
-                OpCode::AddInt => int_op!(self, +),
-                OpCode::SubInt => int_op!(self, -),
-                OpCode::MulInt => int_op!(self, *),
+                OpCode::AddInt => int_op!(self, +, "add"),
+                OpCode::SubInt => int_op!(self, -, "sub"),
+                OpCode::MulInt => int_op!(self, *, "mul"),
+                OpCode::AddFloat => float_op!(self, +, "add"),
+                OpCode::SubFloat => float_op!(self, -, "sub"),
+                OpCode::MulFloat => float_op!(self, *, "mul"),
                 OpCode::IncLocal => {
                     let slot = chunk.read_byte(self.ip) as usize;
                     self.ip += 1;
@@ -393,17 +511,19 @@ impl VMNanBox {
                     return Ok(result);
                 }
                 OpCode::CheckIterLimit => {
+                    crate::limits::poll()?;
                     self.iteration_count += 1;
                     if self.iteration_count > MAX_ITERATIONS {
                         return Err(NebulaError::coded(ErrorCode::E071, "vm loop"));
                     }
                 }
+                OpCode::LoadCallee => self.load_callee(chunk)?,
                 OpCode::Call => {
+                    crate::limits::poll()?;
                     let argc = chunk.read_byte(self.ip) as usize;
                     self.ip += 1;
                     let callee = self.peek(argc)?;
-                    if callee.is_ptr() {
-                        debug_assert!(!callee.as_ptr().is_null(), "null pointer in Call");
+                    if callee.is_ptr() && !callee.as_ptr().is_null() {
                         let obj = unsafe { &*callee.as_ptr() };
                         match &obj.data {
                             super::HeapData::String(name) => {
@@ -444,11 +564,11 @@ impl VMNanBox {
                                 self.push(result)?;
                             }
                             _ => {
-                                return Err(NebulaError::coded(ErrorCode::E011, "not callable"));
+                                return Err(self.not_callable(callee));
                             }
                         }
                     } else {
-                        return Err(NebulaError::coded(ErrorCode::E011, "not callable"));
+                        return Err(self.not_callable(callee));
                     }
                 }
                 OpCode::CallBuiltin => {
@@ -462,6 +582,28 @@ impl VMNanBox {
                     }
                     self.push(result)?;
                 }
+                OpCode::Invoke => {
+                    let name_idx = chunk.read_u16(self.ip);
+                    self.ip += 2;
+                    let argc = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    let result = self.invoke_method(chunk.get_constant(name_idx), argc)?;
+                    for _ in 0..=argc {
+                        self.pop()?;
+                    }
+                    self.push(result)?;
+                }
+                OpCode::CallExt => {
+                    let name_idx = chunk.read_u16(self.ip);
+                    self.ip += 2;
+                    let argc = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    let result = self.call_extension(chunk.get_constant(name_idx), argc)?;
+                    for _ in 0..argc {
+                        self.pop()?;
+                    }
+                    self.push(result)?;
+                }
                 OpCode::List => {
                     let count = chunk.read_byte(self.ip) as usize;
                     self.ip += 1;
@@ -473,6 +615,35 @@ impl VMNanBox {
                     let ptr = HeapObject::new_list(items);
                     self.push(NanBoxed::ptr(ptr))?;
                 }
+                OpCode::Concat => {
+                    let count = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    self.concat(count)?;
+                }
+                OpCode::Extend => self.extend()?,
+                OpCode::Await => self.await_top()?,
+                OpCode::Spawn => {
+                    let argc = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    self.spawn_task(argc)?;
+                }
+                OpCode::Range => {
+                    let inclusive = chunk.read_byte(self.ip) != 0;
+                    self.ip += 1;
+                    self.make_range(inclusive)?;
+                }
+                OpCode::Map => {
+                    let count = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    let mut map = HashMap::with_capacity(count);
+                    for _ in 0..count {
+                        let value = self.pop()?;
+                        let key = to_value(self.pop()?).to_display_string();
+                        map.entry(key.into_boxed_str()).or_insert(value);
+                    }
+                    let ptr = HeapObject::new_map(map);
+                    self.push(NanBoxed::ptr(ptr))?;
+                }
                 OpCode::Closure => {
                     let func_idx = chunk.read_byte(self.ip) as usize;
                     self.ip += 1;
@@ -502,6 +673,13 @@ impl VMNanBox {
         })
     }
     fn execute_function_body(&mut self, chunk: &Chunk) -> NebulaResult<NanBoxed> {
+        self.calls += 1;
+        self.depth += 1;
+        let result = self.run_function_loop(chunk);
+        self.depth -= 1;
+        result.map_err(|e| self.locate(e, chunk))
+    }
+    fn run_function_loop(&mut self, chunk: &Chunk) -> NebulaResult<NanBoxed> {
         loop {
             if self.ip >= chunk.code().len() {
                 break;
@@ -517,6 +695,7 @@ impl VMNanBox {
                 }
             };
             self.ip += 1;
+            self.instructions += 1;
             match op {
                 OpCode::Return => {
                     return Ok(if self.stack.len() > self.frame_base {
@@ -528,6 +707,13 @@ impl VMNanBox {
                 OpCode::PushConst => {
                     let idx = chunk.read_byte(self.ip);
                     self.ip += 1;
+                    let value = chunk.get_constant(idx.into());
+                    let nb = self.value_to_nanbox(value);
+                    self.push(nb)?;
+                }
+                OpCode::PushConstLong => {
+                    let idx = chunk.read_u16(self.ip);
+                    self.ip += 2;
                     let value = chunk.get_constant(idx);
                     let nb = self.value_to_nanbox(value);
                     self.push(nb)?;
@@ -538,6 +724,33 @@ impl VMNanBox {
                 OpCode::Pop => {
                     self.pop()?;
                 }
+                OpCode::Concat => {
+                    let count = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    self.concat(count)?;
+                }
+                OpCode::Extend => self.extend()?,
+                OpCode::Await => self.await_top()?,
+                OpCode::Spawn => {
+                    let argc = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    self.spawn_task(argc)?;
+                }
+                OpCode::Range => {
+                    let inclusive = chunk.read_byte(self.ip) != 0;
+                    self.ip += 1;
+                    self.make_range(inclusive)?;
+                }
+                OpCode::AddInt => int_op!(self, +, "add"),
+                OpCode::SubInt => int_op!(self, -, "sub"),
+                OpCode::MulInt => int_op!(self, *, "mul"),
+                OpCode::AddFloat => float_op!(self, +, "add"),
+                OpCode::SubFloat => float_op!(self, -, "sub"),
+                OpCode::MulFloat => float_op!(self, *, "mul"),
+                OpCode::Dup => {
+                    let value = self.peek(0)?;
+                    self.push(value)?;
+                }
                 OpCode::LoadLocal
                 | OpCode::LoadLocal0
                 | OpCode::LoadLocal1
@@ -685,11 +898,13 @@ impl VMNanBox {
                     let value = self.peek(0)?;
                     self.globals[23] = value;
                 }
+                OpCode::LoadCallee => self.load_callee(chunk)?,
                 OpCode::Call => {
+                    crate::limits::poll()?;
                     let argc = chunk.read_byte(self.ip) as usize;
                     self.ip += 1;
                     let callee = self.peek(argc)?;
-                    if callee.is_ptr() {
+                    if callee.is_ptr() && !callee.as_ptr().is_null() {
                         let obj = unsafe { &*callee.as_ptr() };
                         if let super::HeapData::String(name) = &obj.data {
                             let result = self.call_builtin(name, argc)?;
@@ -714,10 +929,10 @@ impl VMNanBox {
                             }
                             self.push(result)?;
                         } else {
-                            return Err(NebulaError::coded(ErrorCode::E011, "not callable in fn"));
+                            return Err(self.not_callable(callee));
                         }
                     } else {
-                        return Err(NebulaError::coded(ErrorCode::E011, "not callable in fn"));
+                        return Err(self.not_callable(callee));
                     }
                 }
                 OpCode::Jump => {
@@ -748,7 +963,29 @@ impl VMNanBox {
                     }
                     self.push(result)?;
                 }
-                OpCode::CheckIterLimit => {}
+                OpCode::Invoke => {
+                    let name_idx = chunk.read_u16(self.ip);
+                    self.ip += 2;
+                    let argc = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    let result = self.invoke_method(chunk.get_constant(name_idx), argc)?;
+                    for _ in 0..=argc {
+                        self.pop()?;
+                    }
+                    self.push(result)?;
+                }
+                OpCode::CallExt => {
+                    let name_idx = chunk.read_u16(self.ip);
+                    self.ip += 2;
+                    let argc = chunk.read_byte(self.ip) as usize;
+                    self.ip += 1;
+                    let result = self.call_extension(chunk.get_constant(name_idx), argc)?;
+                    for _ in 0..argc {
+                        self.pop()?;
+                    }
+                    self.push(result)?;
+                }
+                OpCode::CheckIterLimit => crate::limits::poll()?,
                 _ => {
                     return Err(NebulaError::coded(
                         ErrorCode::E004,
@@ -768,6 +1005,90 @@ impl VMNanBox {
         Ok(())
     }
     #[inline(always)]
+    fn await_top(&mut self) -> NebulaResult<()> {
+        let value = self.pop()?;
+        if value.is_ptr() {
+            let obj = unsafe { &*value.as_ptr() };
+            if let super::HeapData::Future(future) = &obj.data {
+                let settled = future.clone().join(self)?;
+                return self.push(from_value(&settled));
+            }
+        }
+        self.push(value)
+    }
+    fn live_bytes(&self) -> usize {
+        let mut pending: Vec<_> = self.globals.iter().chain(&self.stack).copied().collect();
+        let mut seen = HashSet::new();
+        let mut total = 0;
+        while let Some(value) = pending.pop() {
+            if !value.is_ptr() || value.as_ptr().is_null() || !seen.insert(value.as_ptr()) {
+                continue;
+            }
+            let obj = unsafe { &*value.as_ptr() };
+            total += obj.size();
+            match &obj.data {
+                super::HeapData::List(items) | super::HeapData::Tuple(items) => {
+                    pending.extend(items)
+                }
+                super::HeapData::Map(map) => pending.extend(map.values()),
+                _ => {}
+            }
+        }
+        total
+    }
+    fn spawn_task(&mut self, argc: usize) -> NebulaResult<()> {
+        let mut args = Vec::with_capacity(argc);
+        for _ in 0..argc {
+            args.push(to_value(self.pop()?));
+        }
+        args.reverse();
+        let callee = to_value(self.pop()?);
+        self.push(from_value(&Value::Future(ExtFuture::task(callee, args))))
+    }
+    fn concat(&mut self, count: usize) -> NebulaResult<()> {
+        let mut parts = Vec::with_capacity(count);
+        for _ in 0..count {
+            parts.push(self.pop()?);
+        }
+        let text: String = parts.iter().rev().map(|part| part.to_string()).collect();
+        self.push(NanBoxed::ptr(HeapObject::new_string(&text)))
+    }
+    fn extend(&mut self) -> NebulaResult<()> {
+        let tail = self.pop()?;
+        let head = self.pop()?;
+        let mismatched =
+            || NebulaError::coded(ErrorCode::E030, "cannot extend mismatched collections");
+        if !head.is_ptr() || !tail.is_ptr() {
+            return Err(mismatched());
+        }
+        let (head, tail) = unsafe { (&*head.as_ptr(), &*tail.as_ptr()) };
+        let merged = match (&head.data, &tail.data) {
+            (super::HeapData::List(a), super::HeapData::List(b)) => {
+                HeapObject::new_list(a.iter().chain(b).copied().collect())
+            }
+            (super::HeapData::Map(a), super::HeapData::Map(b)) => {
+                let mut map = a.clone();
+                map.extend(b.iter().map(|(k, v)| (k.clone(), *v)));
+                HeapObject::new_map(map)
+            }
+            _ => return Err(mismatched()),
+        };
+        self.push(NanBoxed::ptr(merged))
+    }
+    fn make_range(&mut self, inclusive: bool) -> NebulaResult<()> {
+        let end = self.pop()?;
+        let start = self.pop()?;
+        let bound = |value: NanBoxed, which: &str| {
+            to_value(value)
+                .as_integer()
+                .ok_or_else(|| NebulaError::InvalidOperation {
+                    message: format!("Range {} must be integer", which),
+                    span: None,
+                })
+        };
+        let range = RangeValue::new(bound(start, "start")?, bound(end, "end")?, inclusive);
+        self.push(NanBoxed::ptr(HeapObject::new_range(range)))
+    }
     fn pop(&mut self) -> NebulaResult<NanBoxed> {
         self.stack
             .pop()
@@ -784,7 +1105,7 @@ impl VMNanBox {
         use crate::interp::Value;
         match value {
             Value::Number(n) => NanBoxed::number(*n),
-            Value::Integer(n) => NanBoxed::integer(*n),
+            Value::Integer(n) => NanBoxed::from_i64(*n),
             Value::Float(f) => NanBoxed::number(*f),
             Value::Bool(b) => NanBoxed::boolean(*b),
             Value::Nil => NanBoxed::nil(),
@@ -792,6 +1113,40 @@ impl VMNanBox {
             _ => NanBoxed::nil(),
         }
     }
+    fn load_callee(&mut self, chunk: &Chunk) -> NebulaResult<()> {
+        let idx = chunk.read_byte(self.ip) as usize;
+        self.ip += 1;
+        let value = *self.globals.get(idx).ok_or_else(|| {
+            NebulaError::coded(
+                ErrorCode::E013,
+                format!("global index {} out of bounds", idx),
+            )
+        })?;
+        if value.is_nil() {
+            let name = self.global_names.get(idx).cloned().unwrap_or_default();
+            let defined = self
+                .global_names
+                .iter()
+                .zip(&self.globals)
+                .filter(|(_, value)| !value.is_nil())
+                .map(|(name, _)| name.as_str());
+            let natives = self.natives.keys().map(|name| &**name);
+            let suggestion =
+                crate::error::closest(&name, defined.chain(natives)).map(str::to_string);
+            return Err(NebulaError::UndefinedVariable {
+                name,
+                suggestion,
+                span: None,
+            });
+        }
+        self.push(value)
+    }
+    fn not_callable(&self, callee: NanBoxed) -> NebulaError {
+        NebulaError::InvalidOperation {
+            message: format!("Cannot call {}", to_value(callee).type_name()),
+            span: None,
+        }
+    }
     fn values_equal(&self, a: NanBoxed, b: NanBoxed) -> bool {
         if a.bits() == b.bits() {
             return true;
@@ -811,7 +1166,7 @@ impl VMNanBox {
         }
         false
     }
-    fn call_builtin(&self, name: &str, argc: usize) -> NebulaResult<NanBoxed> {
+    fn call_builtin(&mut self, name: &str, argc: usize) -> NebulaResult<NanBoxed> {
         let mut args = Vec::with_capacity(argc);
         for i in 0..argc {
             args.push(self.peek(argc - 1 - i)?);
@@ -819,7 +1174,7 @@ impl VMNanBox {
         match name {
             "log" => {
                 let output: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
-                println!("{}", output.join(" "));
+                self.write_line(&output.join(" "))?;
                 Ok(NanBoxed::nil())
             }
             "typeof" => {
@@ -839,8 +1194,12 @@ impl VMNanBox {
                     match &obj.data {
                         super::HeapData::String(_) => "wrd",
                         super::HeapData::List(_) => "lst",
+                        super::HeapData::Tuple(_) => "tup",
+                        super::HeapData::Range(_) => "range",
                         super::HeapData::Map(_) => "map",
                         super::HeapData::Function(_) => "fn",
+                        super::HeapData::Extern(_) => "extern",
+                        super::HeapData::Future(_) => "future",
                     }
                 } else {
                     "unknown"
@@ -876,12 +1235,15 @@ impl VMNanBox {
                 if args[0].is_ptr() {
                     let obj = unsafe { &*args[0].as_ptr() };
                     let len = match &obj.data {
-                        super::HeapData::String(s) => s.len(),
-                        super::HeapData::List(l) => l.len(),
+                        super::HeapData::String(s) => s.chars().count(),
+                        super::HeapData::List(l) | super::HeapData::Tuple(l) => l.len(),
+                        super::HeapData::Range(r) => r.len(),
                         super::HeapData::Map(m) => m.len(),
-                        super::HeapData::Function(_) => 0,
+                        super::HeapData::Function(_)
+                        | super::HeapData::Extern(_)
+                        | super::HeapData::Future(_) => 0,
                     };
-                    Ok(NanBoxed::integer(len as i64))
+                    Ok(NanBoxed::from_i64(len as i64))
                 } else {
                     Err(NebulaError::coded(ErrorCode::E031, "len"))
                 }
@@ -943,10 +1305,119 @@ impl VMNanBox {
                     .ok_or_else(|| NebulaError::coded(ErrorCode::E031, "cos"))?;
                 Ok(NanBoxed::number(n.cos()))
             }
-            _ => Err(NebulaError::coded(ErrorCode::E010, name)),
+            _ => match self.natives.get(name).cloned() {
+                Some(native) => self.call_native(&native, &args),
+                None => match crate::ext::native_extensions().get_function(name).cloned() {
+                    Some(func) => {
+                        let values: Vec<_> = args.iter().map(|a| to_value(*a)).collect();
+                        self.call_ext_function(&func, &values)
+                    }
+                    None => Err(NebulaError::coded(
+                        ErrorCode::E010,
+                        format!("{}{}", name, self.builtin_suggestion(name)),
+                    )),
+                },
+            },
+        }
+    }
+    fn builtin_suggestion(&self, name: &str) -> String {
+        let natives = self.natives.keys().map(|n| n.as_ref());
+        crate::error::suggest(name, BUILTIN_NAMES.iter().copied().chain(natives))
+    }
+    fn call_native(&mut self, native: &NativeFn, args: &[NanBoxed]) -> NebulaResult<NanBoxed> {
+        if let Some(arity) = native.arity {
+            if args.len() != arity {
+                return Err(NebulaError::coded(
+                    ErrorCode::E012,
+                    format!(
+                        "{}: expected {} args, got {}",
+                        native.name,
+                        arity,
+                        args.len()
+                    ),
+                ));
+            }
+        }
+        let values: Vec<_> = args.iter().map(|a| to_value(*a)).collect();
+        let result = (native.func)(self, &values).map_err(crate::builtins::native_error)?;
+        Ok(from_value(&result))
+    }
+    fn invoke_method(&mut self, name: &Value, argc: usize) -> NebulaResult<NanBoxed> {
+        let method = name
+            .as_string()
+            .ok_or_else(|| NebulaError::coded(ErrorCode::E004, "invalid method name"))?;
+        let receiver = to_value(self.peek(argc)?);
+        let mut args = Vec::with_capacity(argc);
+        for i in 0..argc {
+            args.push(to_value(self.peek(argc - 1 - i)?));
+        }
+        if let Value::Extern(handle) = &receiver {
+            let result = crate::ext::call_extern_method(self, handle, method, &args)?;
+            return Ok(from_value(&result));
+        }
+        let result = crate::builtins::call_method(&receiver, method, &args).map_err(|message| {
+            NebulaError::Runtime {
+                message,
+                span: None,
+            }
+        })?;
+        Ok(from_value(&result))
+    }
+    fn call_extension(&mut self, name: &Value, argc: usize) -> NebulaResult<NanBoxed> {
+        let name = name
+            .as_string()
+            .ok_or_else(|| NebulaError::coded(ErrorCode::E004, "invalid extension name"))?;
+        let func = crate::ext::native_extensions()
+            .get_function(name)
+            .cloned()
+            .ok_or_else(|| NebulaError::coded(ErrorCode::E010, name))?;
+        let mut args = Vec::with_capacity(argc);
+        for i in 0..argc {
+            args.push(to_value(self.peek(argc - 1 - i)?));
         }
+        self.call_ext_function(&func, &args)
     }
-    fn call_builtin_by_index(&self, index: usize, argc: usize) -> NebulaResult<NanBoxed> {
+    fn call_ext_function(&mut self, func: &ExtFunction, args: &[Value]) -> NebulaResult<NanBoxed> {
+        Ok(from_value(&func.call_with(self, args)?))
+    }
+    fn call_compiled(&mut self, func: &CompiledFunction, args: &[Value]) -> NebulaResult<NanBoxed> {
+        if args.len() != func.arity as usize {
+            return Err(NebulaError::coded(
+                ErrorCode::E012,
+                format!(
+                    "{}: expected {} args, got {}",
+                    func.name,
+                    func.arity,
+                    args.len()
+                ),
+            ));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(NebulaError::coded(
+                ErrorCode::E071,
+                format!("stack overflow: max {} frames", MAX_FRAMES),
+            ));
+        }
+        let base = self.stack.len();
+        for arg in args {
+            self.push(from_value(arg))?;
+        }
+        let saved_ip = self.ip;
+        let saved_frame_base = self.frame_base;
+        self.ip = 0;
+        self.frame_base = base;
+        let result = self.execute_function_body(&func.chunk);
+        self.ip = saved_ip;
+        self.frame_base = saved_frame_base;
+        self.stack.truncate(base);
+        result
+    }
+    fn write_line(&mut self, text: &str) -> NebulaResult<()> {
+        self.stdio
+            .write(&format!("{}\n", text))
+            .map_err(crate::builtins::native_error)
+    }
+    fn call_builtin_by_index(&mut self, index: usize, argc: usize) -> NebulaResult<NanBoxed> {
         let mut args = Vec::with_capacity(argc);
         for i in 0..argc {
             args.push(self.peek(argc - 1 - i)?);
@@ -954,7 +1425,7 @@ impl VMNanBox {
         match index {
             0 => {
                 let output: Vec<_> = args.iter().map(|a| format!("{}", a)).collect();
-                println!("{}", output.join(" "));
+                self.write_line(&output.join(" "))?;
                 Ok(NanBoxed::nil())
             }
             1 => {
@@ -974,8 +1445,12 @@ impl VMNanBox {
                     match &obj.data {
                         super::HeapData::String(_) => "wrd",
                         super::HeapData::List(_) => "lst",
+                        super::HeapData::Tuple(_) => "tup",
+                        super::HeapData::Range(_) => "range",
                         super::HeapData::Map(_) => "map",
                         super::HeapData::Function(_) => "fn",
+                        super::HeapData::Extern(_) => "extern",
+                        super::HeapData::Future(_) => "future",
                     }
                 } else {
                     "unknown"
@@ -1011,12 +1486,15 @@ impl VMNanBox {
                 if args[0].is_ptr() {
                     let obj = unsafe { &*args[0].as_ptr() };
                     let len = match &obj.data {
-                        super::HeapData::String(s) => s.len(),
-                        super::HeapData::List(l) => l.len(),
+                        super::HeapData::String(s) => s.chars().count(),
+                        super::HeapData::List(l) | super::HeapData::Tuple(l) => l.len(),
+                        super::HeapData::Range(r) => r.len(),
                         super::HeapData::Map(m) => m.len(),
-                        super::HeapData::Function(_) => 0,
+                        super::HeapData::Function(_)
+                        | super::HeapData::Extern(_)
+                        | super::HeapData::Future(_) => 0,
                     };
-                    Ok(NanBoxed::integer(len as i64))
+                    Ok(NanBoxed::from_i64(len as i64))
                 } else {
                     Err(NebulaError::coded(ErrorCode::E031, "len"))
                 }
@@ -1105,19 +1583,20 @@ impl VMNanBox {
                     .ok_or_else(|| NebulaError::coded(ErrorCode::E031, "ln"))?;
                 Ok(NanBoxed::number(n.ln()))
             }
-            14 => Ok(NanBoxed::nil()),
-            15 => {
-                use std::time::{SystemTime, UNIX_EPOCH};
-                let seed = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .map(|d| d.as_nanos())
-                    .unwrap_or(0);
-                let random = ((seed as u64).wrapping_mul(1103515245).wrapping_add(12345) >> 16) as f64 / 32768.0;
-                Ok(NanBoxed::number(random % 1.0))
-            }
+            14 => match self
+                .stdio
+                .read_line()
+                .map_err(crate::builtins::native_error)?
+            {
+                Some(line) => Ok(NanBoxed::ptr(HeapObject::new_string(line.trim()))),
+                None => Ok(NanBoxed::nil()),
+            },
+            15 => Ok(NanBoxed::number(crate::builtins::random_f64())),
             16 => {
                 for arg in &args {
-                    eprintln!("[DBG] {:?}", arg);
+                    self.stdio
+                        .write_error(&format!("[DBG] {:?}\n", arg))
+                        .map_err(crate::builtins::native_error)?;
                 }
                 Ok(NanBoxed::nil())
             }
@@ -1133,10 +1612,11 @@ impl VMNanBox {
                 if args.is_empty() {
                     return Err(NebulaError::coded(ErrorCode::E012, "sleep"));
                 }
-                let ms = args[0]
-                    .as_numeric()
-                    .ok_or_else(|| NebulaError::coded(ErrorCode::E031, "sleep"))?;
-                std::thread::sleep(std::time::Duration::from_millis(ms as u64));
+                if args[0].as_numeric().is_none() {
+                    return Err(NebulaError::coded(ErrorCode::E031, "sleep"));
+                }
+                crate::builtins::sleep(self, &to_value(args[0]))
+                    .map_err(crate::builtins::native_error)?;
                 Ok(NanBoxed::nil())
             }
             19 => {
@@ -1174,6 +1654,112 @@ impl VMNanBox {
         }
     }
 }
+impl NativeContext for VMNanBox {
+    fn call(&mut self, callee: &Value, args: &[Value]) -> Result<Value, String> {
+        match callee {
+            Value::VmFunction { handle, .. } => {
+                let obj = unsafe { &*(*handle as *const HeapObject) };
+                match &obj.data {
+                    super::HeapData::Function(func) => self
+                        .call_compiled(func, args)
+                        .map(to_value)
+                        .map_err(crate::builtins::callback_error),
+                    _ => Err("not callable".to_string()),
+                }
+            }
+            Value::String(name)
+                if BUILTIN_NAMES.contains(&name.as_ref())
+                    || self.natives.contains_key(name.as_ref()) =>
+            {
+                let base = self.stack.len();
+                for arg in args {
+                    self.push(from_value(arg))
+                        .map_err(crate::builtins::callback_error)?;
+                }
+                let result = match BUILTIN_NAMES.iter().position(|n| *n == name.as_ref()) {
+                    Some(index) => self.call_builtin_by_index(index, args.len()),
+                    None => self.call_builtin(name, args.len()),
+                };
+                self.stack.truncate(base);
+                result
+                    .map(to_value)
+                    .map_err(crate::builtins::callback_error)
+            }
+            Value::NativeFunction(native) => (native.func)(self, args),
+            Value::Extension(func) => func
+                .call_with(self, args)
+                .map_err(crate::builtins::callback_error),
+            other => Err(format!("cannot call {}", other.type_name())),
+        }
+    }
+    fn lookup(&self, name: &str) -> Option<Value> {
+        let i = self.global_names.iter().position(|n| n == name)?;
+        self.globals
+            .get(i)
+            .filter(|value| !value.is_nil())
+            .map(|value| to_value(*value))
+    }
+    fn stdio(&mut self) -> Option<&mut Stdio> {
+        Some(&mut self.stdio)
+    }
+    fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+    fn define(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let i = match self.global_names.iter().position(|n| n == name) {
+            Some(i) => i,
+            None => {
+                self.global_names.push(name.to_string());
+                self.global_names.len() - 1
+            }
+        };
+        if i >= MAX_GLOBALS {
+            return Err(format!("cannot define '{}': too many globals", name));
+        }
+        self.globals[i] = from_value(&value);
+        Ok(())
+    }
+    fn globals(&self) -> Vec<String> {
+        let constants = crate::builtins::math_constants();
+        let extensions = crate::ext::native_extensions();
+        let mut names: Vec<_> = self
+            .global_names
+            .iter()
+            .enumerate()
+            .skip(BUILTIN_COUNT)
+            .filter(|(i, name)| {
+                *i < MAX_GLOBALS
+                    && !self.globals[*i].is_nil()
+                    && !self.natives.contains_key(name.as_str())
+                    && extensions.get_function(name).is_none()
+                    && !constants.iter().any(|(c, _)| c == name)
+            })
+            .map(|(_, name)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+    fn engine_stats(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("engine", Value::String("vm".into())),
+            ("frames", Value::Integer(self.depth as i64)),
+            ("calls", Value::Integer(self.calls as i64)),
+            ("instructions", Value::Integer(self.instructions as i64)),
+            ("stack", Value::Integer(self.stack.len() as i64)),
+            ("iterations", Value::Integer(self.iteration_count as i64)),
+        ]
+    }
+    fn memory_stats(&self) -> Vec<(&'static str, Value)> {
+        let allocations = crate::limits::usage()
+            .allocations
+            .saturating_sub(self.usage.allocations);
+        vec![
+            ("engine", Value::String("vm".into())),
+            ("allocations", Value::Integer(allocations as i64)),
+            ("live_bytes", Value::Integer(self.live_bytes() as i64)),
+        ]
+    }
+}
 impl Default for VMNanBox {
     fn default() -> Self {
         Self::new()
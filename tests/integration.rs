@@ -14,6 +14,7 @@ fn run(code: &str) -> Result<(), String> {
     let mut compiler = Compiler::new();
     let chunk = compiler.compile(&program).map_err(|e| e.message())?;
     let mut vm = VM::new();
+    vm.set_output(Box::new(nebula::interp::OutputCapture::new()));
     vm.run_with_functions(&chunk, compiler.global_names(), compiler.functions())
         .map_err(|e| e.message())?;
     Ok(())
@@ -24,6 +25,40 @@ fn expect_err(code: &str) -> bool {
     run(code).is_err()
 }
 
+/// Parse code, panicking on syntax errors
+fn parse(code: &str) -> nebula::Program {
+    let tokens: Vec<_> = Lexer::new(code).collect();
+    Parser::new(tokens).parse_program().unwrap()
+}
+
+/// Run code through the VM, expecting it to stop with an error
+fn vm_exit_code(code: &str) -> nebula::NebulaError {
+    let program = parse(code);
+    let mut compiler = Compiler::new();
+    let chunk = compiler.compile(&program).unwrap();
+    VM::new()
+        .run_with_functions(&chunk, compiler.global_names(), compiler.functions())
+        .unwrap_err()
+}
+
+/// Run code through the interpreter and the VM, returning both errors
+fn errors_in_both_engines(code: &str) -> [nebula::NebulaError; 2] {
+    let program = parse(code);
+    let interp = nebula::Interpreter::new().interpret(&program).unwrap_err();
+    [interp, vm_exit_code(code)]
+}
+
+/// Assert that both engines stop with the given exit code
+fn assert_exit_in_both_engines(code: &str, expected: i32) {
+    for err in errors_in_both_engines(code) {
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code } if code == expected),
+            "{:?}",
+            err
+        );
+    }
+}
+
 // === Compile & Run Tests (no crashes) ===
 
 #[test]
@@ -193,3 +228,1199 @@ fn test_block_function() {
 fn test_zero_param_function() {
     run("fn zero() = 0\nfb r = zero()").unwrap();
 }
+
+// === Environment Tests ===
+
+#[test]
+#[cfg(feature = "io-builtins")]
+fn test_env_builtins() {
+    let code = "set_env(\"NEBULA_TEST_VAR\", \"hi\")\n\
+        assert_eq(env(\"NEBULA_TEST_VAR\"), \"hi\")\n\
+        assert_eq(envs():has(\"NEBULA_TEST_VAR\"), yes)\n\
+        assert_eq(env(\"NEBULA_TEST_MISSING_VAR\"), nil)\n\
+        exit(1)";
+    assert_exit_in_both_engines(code, 1);
+    assert_eq!(std::env::var("NEBULA_TEST_VAR").unwrap(), "hi");
+    assert!(expect_err("set_env(\"A=B\", \"x\")"));
+}
+
+#[test]
+fn test_script_args() {
+    nebula::builtins::set_script_args(vec!["a".to_string(), "b".to_string()]);
+    assert_exit_in_both_engines("assert_eq(args(), lst(\"a\", \"b\"))\nexit(len(args()))", 2);
+    assert_eq!(nebula::builtins::script_args(), vec!["a", "b"]);
+}
+
+#[test]
+fn test_exit_propagates_code() {
+    assert_exit_in_both_engines("log(1)\nexit(7)\nlog(2)", 7);
+}
+
+#[test]
+fn test_sort_by_callbacks_in_both_engines() {
+    let code = "fn by_len(a, b) do\n  give len(a) - len(b)\nend\n\
+        perm nums = sort_by(lst(3, 1, 2), (a, b) => b - a)\n\
+        perm words = sort_by(lst(\"ccc\", \"a\", \"bb\"), by_len)\n\
+        if str(nums) == \"lst(3, 2, 1)\" do\n  if str(words) == \"lst(a, bb, ccc)\" do\n    exit(9)\n  end\nend";
+    assert_exit_in_both_engines(code, 9);
+}
+
+#[test]
+fn test_higher_order_builtins_in_both_engines() {
+    let code = "perm xs = lst(1, 2, 3, 4)\n\
+        perm doubled = map(xs, (x) => x * 2)\n\
+        perm big = filter(doubled, (x) => x > 4)\n\
+        perm total = reduce(big, (a, b) => a + b, 0)\n\
+        if any(xs, (x) => x == 4) do\n  if all(xs, (x) => x > 0) do\n    exit(total)\n  end\nend";
+    assert_exit_in_both_engines(code, 14);
+}
+
+#[test]
+fn test_zero_param_and_bare_param_lambdas() {
+    let code = "perm seven = () => 7\n\
+        perm doubled = map(lst(1, 2, 3), x => x * 2)\n\
+        perm total = reduce(doubled, (a, b) => a + b, seven())\n\
+        exit(total + (1))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval(code)
+            .unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 20 }),
+            "{:?}",
+            err
+        );
+    }
+    for bad in ["perm f = (1) => 2", "perm f = ()", "perm f = (x, 2) => x"] {
+        let tokens: Vec<_> = Lexer::new(bad).collect();
+        assert!(Parser::new(tokens).parse_program().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_range_lengths_saturate_alike_in_both_engines() {
+    let code = "fb lo = -9223372036854775807 - 1\nfb hi = 9223372036854775807\n\
+        log(#(lo..hi) == hi, len(range(lo, hi)) == hi, (lo..hi):len() == hi)\n\
+        log(#(1..4), len(1..<4), 1..4, typeof(2..3))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.eval(code).unwrap();
+        assert_eq!(
+            output.take(),
+            "yes yes yes\n4 3 1..4 range\n",
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn test_trailing_block_yields_its_last_expression() {
+    let code = "fn twice(x, f) = f(f(x))\n\
+        fb r = twice(3) do |k|\n  fb d = k * 2\n  d + 1\nend\n\
+        exit(r)";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval(code)
+            .unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 15 }),
+            "{:?}: {:?}",
+            backend,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_trailing_block_arguments() {
+    let code = "fn twice(x, f) do\n  give f(f(x))\nend\n\
+        fn run(f) = f()\n\
+        perm base = run() do\n  give 5\nend\n\
+        perm kept = filter(lst(1, 2, 3)) do |x|\n  give x > 1\nend\n\
+        if len(kept) == 2 do\n\
+          exit(twice(base, n => n + 1) + twice(len(kept)) do |n|\n    give n * 2\n  end)\n\
+        end";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval(code)
+            .unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 15 }),
+            "{:?}",
+            err
+        );
+    }
+    let tokens: Vec<_> = Lexer::new("run() do |a,\n  give a\nend").collect();
+    assert!(Parser::new(tokens).parse_program().is_err());
+}
+
+#[test]
+fn test_list_methods_match_across_engines() {
+    let code = "perm xs = lst(3, 1, 2)\n\
+        perm ys = xs:insert(0, 7):reverse()\n\
+        if ys:join(\"-\") == \"2-1-3-7\" do\n  exit(ys:index_of(7))\nend";
+    assert_exit_in_both_engines(code, 3);
+}
+
+#[test]
+fn test_map_methods_match_across_engines() {
+    let code = "perm m = map(\"a\": 1, \"b\": 2)\n\
+        perm n = m:merge(map(\"c\": 3)):remove(\"a\")\n\
+        if n:has(\"c\") do\n  exit(n:entries():len() + n:keys():len())\nend";
+    assert_exit_in_both_engines(code, 4);
+}
+
+#[test]
+fn test_runner_reports_each_test_function() {
+    let code = "fn double(x) do\n  give x * 2\nend\n\
+        fn test_double() do\n  assert_eq(double(2), 4)\nend\n\
+        fn test_broken() do\n  assert_eq(double(2), 5)\nend\n\
+        fn helper() do\n  give 1\nend";
+    let program = parse(code);
+    let outcomes = nebula::testing::run_tests(&program, nebula::SandboxPolicy::default()).unwrap();
+    let names: Vec<_> = outcomes.iter().map(|o| o.name.as_str()).collect();
+    assert_eq!(names, ["test_double", "test_broken"]);
+    assert!(outcomes[0].passed());
+    assert!(!outcomes[1].passed());
+    assert!(nebula::testing::is_test_file(std::path::Path::new(
+        "dir/math_test.na"
+    )));
+    assert!(!nebula::testing::is_test_file(std::path::Path::new(
+        "dir/math.na"
+    )));
+}
+
+#[test]
+fn test_introspection_builtins_in_both_engines() {
+    let code = "fn depth() do\n  give vm_stats():get(\"frames\")\nend\n\
+        perm names = globals()\n\
+        if names:join(\",\") == \"depth\" do\n  if depth() == 1 do\n    exit(5)\n  end\nend";
+    assert_exit_in_both_engines(code, 5);
+}
+
+#[test]
+fn test_mem_stats_report_each_engine() {
+    let code = "fb before = mem_stats()\nfb xs = lst(\"a\", \"b\", \"c\")\nfb ys = lst(xs, xs)\n\
+        fb after = mem_stats()\n\
+        log(after:get(\"engine\"), after:get(\"allocations\") > before:get(\"allocations\"), \
+        after:get(\"live_bytes\") > before:get(\"live_bytes\"))";
+    for (backend, engine_name) in [
+        (nebula::Backend::Interpreter, "interp"),
+        (nebula::Backend::Vm, "vm"),
+    ] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.eval(code).unwrap();
+        assert_eq!(output.take(), format!("{} yes yes\n", engine_name));
+        assert!(engine.eval("gc_stats()").is_err(), "{:?}", backend);
+    }
+}
+
+#[test]
+fn test_each_over_lazy_range() {
+    let code = "perm last = 0\n\
+        each i in range(0, 50000000, 1000000) do\n  last = i\nend\n\
+        if last == 49000000 do\n  exit(len(range(10, 0, -3)))\nend";
+    let program = parse(code);
+    let mut interp = nebula::Interpreter::new();
+    let err = interp.interpret(&program).unwrap_err();
+    assert!(matches!(err, nebula::NebulaError::Exit { code: 4 }));
+}
+
+#[test]
+fn test_copy_builtins_in_both_engines() {
+    let code = "perm a = lst(lst(1, 2), map(\"k\": lst(3)))\n\
+        perm b = deep_copy(a)\n\
+        perm c = clone(b)\n\
+        perm d = copy(c)\n\
+        exit(len(d) + len(b:first()) + len(copy(\"abc\")))";
+    assert_exit_in_both_engines(code, 7);
+}
+
+#[test]
+fn test_frozen_collections_reject_mutation() {
+    let code = "perm xs = freeze(lst(1, 2))\n\
+        perm ys = clone(xs)\n\
+        ys[0] = 5\n\
+        if xs[0] + ys[0] == 6 do\n  xs[1] = 0\nend";
+    let program = parse(code);
+    let mut interp = nebula::Interpreter::new();
+    let err = interp.interpret(&program).unwrap_err();
+    assert_eq!(err.code(), Some(nebula::ErrorCode::E033));
+}
+
+#[test]
+fn test_parser_and_compiler_warnings() {
+    let code = "fn f(a, a) do\n  give a\n  log(a)\nend\nfb len = 1";
+    let tokens: Vec<_> = Lexer::new(code).collect();
+    let mut parser = Parser::new(tokens);
+    let program = parser.parse_program().unwrap();
+    let messages: Vec<_> = parser
+        .warnings()
+        .iter()
+        .map(|w| w.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "duplicate parameter 'a' shadows an earlier one",
+            "unreachable statement"
+        ]
+    );
+    assert_eq!(parser.warnings()[1].span.map(|s| s.line), Some(3));
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+    assert_eq!(compiler.warnings().len(), 1);
+    assert_eq!(compiler.warnings()[0].severity, nebula::Severity::Warning);
+}
+
+#[test]
+fn test_unclosed_block_points_at_opener_and_eof() {
+    let code = "fn main() do\n  while 1 do\n  end\n";
+    let tokens: Vec<_> = Lexer::new(code).collect();
+    let err = Parser::new(tokens).parse_program().unwrap_err();
+    assert_eq!(err.code(), Some(nebula::ErrorCode::E003));
+    let diagnostic = nebula::Diagnostic::from(&err);
+    let opener = diagnostic.labels.iter().find(|l| !l.primary).unwrap();
+    assert_eq!((opener.span.line, opener.span.column), (1, 11));
+    assert_eq!(err.span().map(|s| s.line), Some(4));
+    let text = nebula::Renderer::plain().render(&diagnostic, "main.na", code);
+    assert!(text.contains("-- block opened here"));
+}
+
+#[test]
+fn test_runtime_errors_carry_expression_spans() {
+    let code = "perm b = 0\nfn half(x) do\n  give x / b\nend\nlog(half(4))";
+    let [interp, vm] = errors_in_both_engines(code);
+    let span = interp.span().copied().unwrap();
+    assert_eq!((span.line, span.column, span.length), (3, 8, 5));
+    assert_eq!(vm.code(), Some(nebula::ErrorCode::E040));
+    assert_eq!(vm.span().map(|s| (s.line, s.column)), Some((3, 8)));
+}
+
+#[test]
+fn test_structured_error_payloads() {
+    let code = "fn find(id) do\n  err(map(\"code\": \"NOT_FOUND\", \"id\": id))\nend\n\
+        perm seen = 0\ntry do\n  find(3)\ncatch e do\n  if e.code == \"NOT_FOUND\" do\n    seen = e[\"id\"]\n  end\nend\n\
+        try do\n  err(\"plain\")\ncatch e do\n  assert(typeof(e) == \"wrd\")\nend\n\
+        err(map(\"code\": \"RETHROWN\", \"seen\": seen))";
+    let program = parse(code);
+    let err = nebula::Interpreter::new().interpret(&program).unwrap_err();
+    let payload = err.payload().expect("structured payload");
+    assert_eq!(payload.repr(), "map(\"code\": \"RETHROWN\", \"seen\": 3)");
+    assert_eq!(err.message(), payload.repr());
+    assert_eq!(err.span().map(|s| s.line), Some(17));
+}
+
+#[test]
+fn test_error_cause_chains() {
+    let code = "fn read_config() do\n  err(map(\"code\": \"NOT_FOUND\"))\nend\n\
+        try do\n  read_config()\ncatch e do\n  err(\"failed to load config\", cause: e)\nend";
+    let program = parse(code);
+    let err = nebula::Interpreter::new().interpret(&program).unwrap_err();
+    assert_eq!(err.message(), "failed to load config");
+    let cause = err.cause().expect("cause");
+    assert_eq!(
+        cause.payload().map(|v| v.repr()).as_deref(),
+        Some("map(\"code\": \"NOT_FOUND\")")
+    );
+    assert_eq!(cause.span().map(|s| s.line), Some(2));
+    let text = nebula::Diagnostic::from(&err).render("main.na", code);
+    assert!(text.starts_with("error: failed to load config\n --> main.na:7:3"));
+    assert!(text.ends_with("  = caused by: map(\"code\": \"NOT_FOUND\")"));
+}
+
+#[test]
+fn test_use_native_reports_unloadable_library() {
+    let code = "use native \"no_such_nebula_ext\"\nlog(1)";
+    let program = parse(code);
+    match &program.items[0] {
+        nebula::parser::Item::Use(module) => {
+            assert!(module.native);
+            assert_eq!(module.path, "no_such_nebula_ext");
+        }
+        other => panic!("expected use item, got {:?}", other),
+    }
+    let err = nebula::Interpreter::new().interpret(&program).unwrap_err();
+    assert_eq!(err.code(), Some(nebula::error::ErrorCode::E080));
+    assert_eq!(err.span().map(|s| s.line), Some(1));
+    let err = Compiler::new().compile(&program).unwrap_err();
+    assert_eq!(err.code(), Some(nebula::error::ErrorCode::E080));
+}
+
+struct Geometry;
+impl nebula::ext::Extension for Geometry {
+    fn name(&self) -> &str {
+        "geometry"
+    }
+    fn functions(&self) -> Vec<nebula::ext::ExtFunction> {
+        vec![nebula::ext::ExtFunction::with_arity(
+            "area",
+            2,
+            |_ctx, args| {
+                let side = |i: usize| args[i].as_number().unwrap_or(0.0);
+                Ok(nebula::Value::Number(side(0) * side(1)))
+            },
+        )]
+    }
+    fn module(&self) -> Option<&str> {
+        Some("geometry")
+    }
+}
+
+#[test]
+fn test_use_extension_module_in_both_engines() {
+    nebula::ext::native_extensions()
+        .register(Box::new(Geometry))
+        .unwrap();
+    assert_exit_in_both_engines("use geometry\nexit(geometry.area(2, 3))", 6);
+    let program = parse("use geometry\nperm geometry = 1");
+    let err = nebula::Interpreter::new().interpret(&program).unwrap_err();
+    assert!(err.message().contains("collides with the global"));
+    assert!(Compiler::new().compile(&program).is_err());
+}
+
+struct FlatShapes;
+impl nebula::ext::Extension for FlatShapes {
+    fn name(&self) -> &str {
+        "flat_shapes"
+    }
+    fn functions(&self) -> Vec<nebula::ext::ExtFunction> {
+        vec![
+            nebula::ext::ExtFunction::with_arity("square", 1, |_ctx, args| {
+                let side = args[0].as_number().unwrap_or(0.0);
+                Ok(nebula::Value::Number(side * side))
+            }),
+            nebula::ext::ExtFunction::with_arity("double", 1, |_ctx, args| {
+                Ok(nebula::Value::Number(
+                    args[0].as_number().unwrap_or(0.0) * 2.0,
+                ))
+            }),
+        ]
+    }
+    fn module(&self) -> Option<&str> {
+        Some("shapes.flat")
+    }
+}
+
+#[test]
+fn test_dotted_aliased_and_selective_module_imports() {
+    nebula::ext::native_extensions()
+        .register(Box::new(FlatShapes))
+        .unwrap();
+    let cases = [
+        ("use shapes.flat\nexit(flat.square(3))", 9),
+        ("use shapes.flat as f\nexit(f.double(f.square(2)))", 8),
+        (
+            "use shapes.flat.{square, double}\nexit(double(square(2)) + 1)",
+            9,
+        ),
+    ];
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        for (code, expected) in cases {
+            let err = nebula::ScriptEngine::with_backend(backend)
+                .eval(code)
+                .unwrap_err();
+            assert!(
+                matches!(err, nebula::NebulaError::Exit { code } if code == expected),
+                "{}: {:?}",
+                code,
+                err
+            );
+        }
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval("use shapes.flat.{cube}\nexit(1)")
+            .unwrap_err();
+        assert_eq!(err.code(), Some(nebula::error::ErrorCode::E063));
+    }
+    let tokens: Vec<_> = Lexer::new("use shapes.flat.{}").collect();
+    assert!(Parser::new(tokens).parse_program().is_err());
+}
+
+struct Tripler;
+impl nebula::ext::Extension for Tripler {
+    fn name(&self) -> &str {
+        "tripler"
+    }
+    fn functions(&self) -> Vec<nebula::ext::ExtFunction> {
+        vec![nebula::ext::ExtFunction::with_arity(
+            "triple",
+            1,
+            |_ctx, args| {
+                Ok(nebula::Value::Number(
+                    args[0].as_number().unwrap_or(0.0) * 3.0,
+                ))
+            },
+        )]
+    }
+}
+
+#[test]
+fn test_vm_calls_flat_extension_functions() {
+    nebula::ext::native_extensions()
+        .register(Box::new(Tripler))
+        .unwrap();
+    let code = "fn twice(n) do\n  give triple(n) + triple(n)\nend\nexit(twice(2) + triple(1))";
+    let err = vm_exit_code(code);
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 15 }),
+        "{:?}",
+        err
+    );
+    let code = "fn run() do\n  give triple(4)\nend\nfn triple(n) do\n  give n\nend\nexit(run())";
+    let err = vm_exit_code(code);
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 4 }),
+        "{:?}",
+        err
+    );
+}
+
+struct Events;
+impl nebula::ext::Extension for Events {
+    fn name(&self) -> &str {
+        "events"
+    }
+    fn functions(&self) -> Vec<nebula::ext::ExtFunction> {
+        vec![nebula::ext::ExtFunction::with_arity(
+            "emit",
+            2,
+            |ctx, args| {
+                ctx.call(&args[0], &args[1..])?;
+                let total = ctx.lookup("total").and_then(|v| v.as_number());
+                ctx.define("fired", nebula::Value::Number(total.unwrap_or(0.0) * 10.0))?;
+                Ok(nebula::Value::Nil)
+            },
+        )]
+    }
+    fn module(&self) -> Option<&str> {
+        Some("events")
+    }
+}
+
+#[test]
+fn test_extension_callbacks_and_globals_in_both_engines() {
+    nebula::ext::native_extensions()
+        .register(Box::new(Events))
+        .unwrap();
+    let code = "use events\nperm total = 0\nfn handler(n) do\n  total = total + n\nend\n\
+        events.emit(handler, 2)\nexit(fired + total)";
+    assert_exit_in_both_engines(code, 22);
+}
+
+static SLOW_RELEASED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+struct Slow;
+impl nebula::ext::Extension for Slow {
+    fn name(&self) -> &str {
+        "slow"
+    }
+    fn functions(&self) -> Vec<nebula::ext::ExtFunction> {
+        vec![
+            nebula::ext::ExtFunction::with_arity("double", 1, |_ctx, args| {
+                let n = args[0].as_number().unwrap_or(0.0);
+                let work = move || {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    Ok(n * 2.0)
+                };
+                Ok(nebula::Value::Future(nebula::ext::ExtFuture::spawn(
+                    work,
+                    nebula::Value::Number,
+                )))
+            }),
+            nebula::ext::ExtFunction::with_arity("hold", 1, |_ctx, args| {
+                let gate = args[0].as_number().unwrap_or(0.0) as usize;
+                let work = move || {
+                    for _ in 0..5000 {
+                        if SLOW_RELEASED.load(std::sync::atomic::Ordering::SeqCst) >= gate {
+                            return Ok(gate as f64);
+                        }
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                    Err(nebula::ext::ExtError::new("never released"))
+                };
+                Ok(nebula::Value::Future(nebula::ext::ExtFuture::spawn(
+                    work,
+                    nebula::Value::Number,
+                )))
+            }),
+            nebula::ext::ExtFunction::with_arity("release", 0, |_ctx, _args| {
+                SLOW_RELEASED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(nebula::Value::Nil)
+            }),
+        ]
+    }
+    fn module(&self) -> Option<&str> {
+        Some("slow")
+    }
+}
+
+#[test]
+fn test_await_extension_futures_in_both_engines() {
+    nebula::ext::native_extensions()
+        .register(Box::new(Slow))
+        .unwrap();
+    let code = "use slow\nfb a = slow.double(2)\nfb b = slow.double(3)\nexit(await a + await b)";
+    assert_exit_in_both_engines(code, 10);
+    for (gate, backend) in [(1, nebula::Backend::Interpreter), (2, nebula::Backend::Vm)] {
+        let code = format!(
+            "use slow\nfb a = slow.hold({})\nfb early = ready(a)\nslow.release()\n\
+            fb n = await a\nif early do\n  exit(100)\nend\nexit(n + 10)",
+            gate
+        );
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval(&code)
+            .unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code } if code == gate + 10),
+            "{:?}: {:?}",
+            backend,
+            err
+        );
+    }
+}
+
+static STORES_DROPPED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+struct Store(std::cell::RefCell<std::collections::HashMap<String, nebula::Value>>);
+impl Drop for Store {
+    fn drop(&mut self) {
+        STORES_DROPPED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+struct KeyValue;
+impl nebula::ext::Extension for KeyValue {
+    fn name(&self) -> &str {
+        "kv"
+    }
+    fn functions(&self) -> Vec<nebula::ext::ExtFunction> {
+        vec![nebula::ext::ExtFunction::with_arity(
+            "open",
+            0,
+            |_ctx, _args| {
+                let store = Store(Default::default());
+                Ok(nebula::Value::Extern(nebula::ext::Extern::new(
+                    "kv", "kv.Store", store,
+                )))
+            },
+        )]
+    }
+    fn module(&self) -> Option<&str> {
+        Some("kv")
+    }
+    fn call_method(
+        &self,
+        _ctx: &mut nebula::ext::ExtensionContext,
+        handle: &nebula::ext::Extern,
+        method: &str,
+        args: &[nebula::Value],
+    ) -> nebula::ext::ExtResult<nebula::Value> {
+        let store = handle.downcast_ref::<Store>().unwrap();
+        let key = args[0].to_display_string();
+        match method {
+            "put" => Ok(store
+                .0
+                .borrow_mut()
+                .insert(key, args[1].clone())
+                .unwrap_or(nebula::Value::Nil)),
+            "get" => Ok(store
+                .0
+                .borrow()
+                .get(&key)
+                .cloned()
+                .unwrap_or(nebula::Value::Nil)),
+            _ => Err(nebula::ext::ExtError::new(format!("no method {}", method))),
+        }
+    }
+}
+
+#[test]
+fn test_extern_handles_dispatch_methods_and_drop() {
+    nebula::ext::native_extensions()
+        .register(Box::new(KeyValue))
+        .unwrap();
+    let code = "use kv\nperm db = kv.open()\ndb:put(\"a\", 5)\nexit(db:get(\"a\"))";
+    let err = vm_exit_code(code);
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 5 }),
+        "{:?}",
+        err
+    );
+    let dropped = STORES_DROPPED.load(std::sync::atomic::Ordering::SeqCst);
+    let mut interp = nebula::Interpreter::new();
+    let err = interp.interpret(&parse(code)).unwrap_err();
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 5 }),
+        "{:?}",
+        err
+    );
+    drop(interp);
+    assert_eq!(
+        STORES_DROPPED.load(std::sync::atomic::Ordering::SeqCst),
+        dropped + 1
+    );
+}
+
+#[test]
+fn test_capability_features_gate_builtins() {
+    for name in ["stat", "mkdir", "env", "exec"] {
+        assert_eq!(
+            nebula::builtins::is_builtin(name),
+            cfg!(feature = "io-builtins")
+        );
+    }
+    assert!(nebula::builtins::is_builtin("print"));
+    assert_eq!(
+        nebula::SandboxPolicy::permissive()
+            .check_net("connect")
+            .is_ok(),
+        cfg!(feature = "net")
+    );
+}
+
+#[test]
+#[cfg(feature = "io-builtins")]
+fn test_sandbox_policy_from_embedding_api() {
+    let dir = std::env::temp_dir();
+    let stat = format!("stat(\"{}\")", dir.display());
+    let mkdir = format!("mkdir(\"{}\")", dir.join("nebula_denied").display());
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let (stat, mkdir, dir) = (stat.clone(), mkdir.clone(), dir.clone());
+        let results = std::thread::spawn(move || {
+            let mut engine = nebula::ScriptEngine::with_backend(backend);
+            engine.set_policy(nebula::SandboxPolicy::locked().allow_read(&dir));
+            [
+                stat.as_str(),
+                &mkdir,
+                "env(\"HOME\")",
+                "use native \"libanything\"",
+            ]
+            .map(|code| engine.eval(code).map(drop).map_err(|e| e.message()))
+        })
+        .join()
+        .unwrap();
+        let [allowed, denied_write, denied_env, denied_native] = results;
+        assert!(allowed.is_ok(), "{:?}: {:?}", backend, allowed);
+        assert!(denied_write.unwrap_err().contains("denied write access"));
+        assert!(denied_env
+            .unwrap_err()
+            .contains("denied environment access"));
+        assert!(denied_native.unwrap_err().contains("denied process access"));
+    }
+}
+
+#[test]
+fn test_capturing_closures_as_natives_and_extensions() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&log);
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.register_native(nebula::interp::NativeFn::new(
+            "record",
+            Some(1),
+            move |_ctx, args| {
+                sink.borrow_mut().push(args[0].to_display_string());
+                Ok(nebula::Value::Number(sink.borrow().len() as f64))
+            },
+        ));
+        engine
+            .eval("record(\"a\")\nperm n = record(\"b\")")
+            .unwrap();
+        assert_eq!(*log.borrow(), ["a", "b"], "{:?}", backend);
+        assert_eq!(engine.get_global("n").unwrap().as_number(), Some(2.0));
+    }
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&calls);
+    let func = nebula::ExtFunction::new("tick", move |_ctx, _args| {
+        Ok(nebula::Value::Number(
+            counter.fetch_add(1, Ordering::SeqCst) as f64,
+        ))
+    });
+    func.call(&[]).unwrap();
+    func.clone().call(&[]).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_typed_native_arguments_report_coded_errors() {
+    let program = parse("log2(\"eight\")");
+    let err = nebula::Interpreter::new().interpret(&program).unwrap_err();
+    assert_eq!(err.code(), Some(nebula::ErrorCode::E031));
+    assert!(err
+        .message()
+        .contains("log2() argument 1 expects nb, got wrd"));
+}
+
+#[test]
+fn test_map_entries_and_display_match_across_engines() {
+    let code = "fb m = map(\"d\": 4, \"b\": 2, \"a\": 1, \"c\": 3)\n\
+        log(m)\nlog(m:entries())\nlog(typeof(m:entries():first()))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.eval(code).unwrap();
+        assert_eq!(
+            output.take(),
+            "map(\"a\": 1, \"b\": 2, \"c\": 3, \"d\": 4)\n\
+            lst((a, 1), (b, 2), (c, 3), (d, 4))\ntup\n",
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn test_calling_unknown_names_suggests_in_both_engines() {
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval("fn twice(x) = sqr(x) * 2\nlog(twice(4))")
+            .unwrap_err();
+        assert_eq!(err.code(), Some(nebula::ErrorCode::E010), "{:?}", backend);
+        assert!(
+            matches!(
+                &err,
+                nebula::NebulaError::UndefinedVariable { name, suggestion, .. }
+                    if name == "sqr" && suggestion.as_deref() == Some("sqrt")
+            ),
+            "{:?}: {:?}",
+            backend,
+            err
+        );
+        let err = nebula::ScriptEngine::with_backend(backend)
+            .eval("fb flag = yes\nflag(1)")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid operation: Cannot call bool",
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn test_string_lengths_and_indices_count_chars_in_both_engines() {
+    let code = "fb s = \"héllo wörld 🌍\"\nfb at = s:find(\"wörld\")\n\
+        log(len(s), #s, s:len(), at, s:substr(at, #\"wörld\"), s:substr(len(s) - 1))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.eval(code).unwrap();
+        assert_eq!(output.take(), "13 13 13 6 wörld 🌍\n", "{:?}", backend);
+    }
+}
+
+#[test]
+fn test_redirected_stdio_in_both_engines() {
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.set_input(Box::new(std::io::Cursor::new("neb\n")));
+        engine
+            .eval("perm name = get()\nlog(\"hi\", name)\nlog(1 + 2)")
+            .unwrap();
+        assert_eq!(output.take(), "hi neb\n3\n", "{:?}", backend);
+        assert!(engine.get_global("name").is_some());
+    }
+}
+
+#[test]
+fn test_stderr_builtins_write_to_the_error_sink_in_both_engines() {
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let errors = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.set_error(Box::new(errors.clone()));
+        engine
+            .eval("log(\"out\")\nelog(\"warn\", 1)\neprint(\"no newline\")\nflush()")
+            .unwrap();
+        assert_eq!(output.take(), "out\n", "{:?}", backend);
+        assert_eq!(errors.take(), "warn 1\nno newline", "{:?}", backend);
+    }
+}
+
+#[test]
+#[cfg(all(unix, feature = "io-builtins"))]
+fn test_exec_stream_writes_to_the_engine_sinks_in_both_engines() {
+    let code = "perm code = exec_stream(\"sh\", lst(\"-c\", \"echo out; echo err >&2; exit 3\"))\n\
+        log(\"code\", code)";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let errors = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.set_error(Box::new(errors.clone()));
+        engine.eval(code).unwrap();
+        assert_eq!(output.take(), "out\ncode 3\n", "{:?}", backend);
+        assert_eq!(errors.take(), "err\n", "{:?}", backend);
+    }
+}
+
+#[test]
+fn test_resource_limits_stop_runaway_scripts() {
+    use std::time::{Duration, Instant};
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_limits(nebula::ResourceLimits::new().timeout(Duration::from_millis(50)));
+        let started = Instant::now();
+        let err = engine
+            .eval("perm n = 0\nwhile on do\n  n = n + 1\nend")
+            .unwrap_err();
+        assert_eq!(err.code(), Some(nebula::ErrorCode::E070), "{:?}", backend);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        engine.set_limits(nebula::ResourceLimits::new().interrupt(|| true));
+        let err = engine
+            .eval("fn spin() do\n  while on do\n  end\nend\nspin()")
+            .unwrap_err();
+        assert!(err.message().contains("interrupted"), "{:?}", backend);
+        engine.set_limits(nebula::ResourceLimits::new().max_heap_bytes(64 * 1024));
+        let err = engine
+            .eval("perm xs = lst(0)\nfor i = 1, 100000 do\n  xs = xs:push(i)\nend")
+            .unwrap_err();
+        assert_eq!(err.code(), Some(nebula::ErrorCode::E072), "{:?}", backend);
+        engine.set_limits(nebula::ResourceLimits::new());
+        engine.eval("perm ok = lst(1, 2, 3)").unwrap();
+    }
+}
+
+#[test]
+fn test_timers_and_sleep_wait_on_the_run_deadline_in_both_engines() {
+    use std::time::{Duration, Instant};
+    let code = "fb t = timer(20)\nfb early = ready(t)\nfb done = await t\n\
+        fb iv = interval(40)\nfb a = await iv\nfb b = await iv\n\
+        log(early, done, ready(t), a, b, ready(iv))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        engine.eval(code).unwrap();
+        assert_eq!(output.take(), "no nil yes 1 2 no\n", "{:?}", backend);
+        engine.set_limits(nebula::ResourceLimits::new().timeout(Duration::from_millis(50)));
+        let started = Instant::now();
+        for script in ["sleep(10000)", "await timer(10000)"] {
+            let err = engine.eval(script).unwrap_err();
+            assert_eq!(err.code(), Some(nebula::ErrorCode::E070), "{:?}", backend);
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(engine.eval("interval(0)").is_err());
+    }
+}
+
+#[test]
+fn test_spawned_sleeps_overlap_in_both_engines() {
+    use std::time::{Duration, Instant};
+    let code = "fn nap(name, ms) do\n  sleep(ms)\n  log(name)\n  give ms\nend\n\
+        fb a = spawn nap(\"a\", 150)\nfb b = spawn nap(\"b\", 150)\nfb c = spawn sleep(150)\n\
+        log(ready(a), ready(b))\nlog(await c)\nexit(await a + await b)";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let output = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(output.clone()));
+        let started = Instant::now();
+        let err = engine.eval(code).unwrap_err();
+        let elapsed = started.elapsed();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 300 }),
+            "{:?}: {}",
+            backend,
+            err
+        );
+        assert!(elapsed >= Duration::from_millis(150), "{:?}", backend);
+        assert!(
+            elapsed < Duration::from_millis(400),
+            "{:?}: {:?}",
+            backend,
+            elapsed
+        );
+        let mut lines: Vec<_> = output.take().lines().map(String::from).collect();
+        assert_eq!(lines.remove(0), "no no", "{:?}", backend);
+        assert_eq!(lines.pop().as_deref(), Some("nil"), "{:?}", backend);
+        lines.sort();
+        assert_eq!(lines, ["a", "b"], "{:?}", backend);
+    }
+}
+
+#[test]
+fn test_heap_limit_counts_live_interpreter_values() {
+    let mut engine = nebula::ScriptEngine::new();
+    engine.set_limits(nebula::ResourceLimits::new().max_heap_bytes(64 * 1024));
+    engine
+        .eval(
+            "perm xs = 0\nperm s = \"\"\nfor i = 1, 100000 do\n  \
+            xs = lst(i, i, i, i)\n  s = \"item \" + str(i)\nend",
+        )
+        .unwrap();
+    assert_eq!(engine.get_global("s").unwrap().to_string(), "item 100000");
+    let err = engine
+        .eval(
+            "fn keep(n) do\n  perm block = \"xxxxxxxx\"\n  for i = 1, 10 do\n    \
+            block = block + block\n  end\n  if n == 0 do\n    give 0\n  end\n  \
+            give keep(n - 1) + 1\nend\nkeep(12)",
+        )
+        .unwrap_err();
+    assert_eq!(err.code(), Some(nebula::ErrorCode::E072));
+}
+
+#[test]
+fn test_nil_coalescing_in_both_engines() {
+    let code = "perm missing = empty\n\
+        fn fallback(x) do\n  give x ?? 10\nend\n\
+        exit((missing ?? 4) + fallback(empty) + fallback(1))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        let err = engine.eval(code).unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 15 }),
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn test_strict_types_reject_floats_for_int_params() {
+    let check = |code: &str, mode| {
+        let program = parse(code);
+        nebula::TypeChecker::with_mode(mode)
+            .check_program(&program)
+            .map(|_| program)
+    };
+    let scale = "fn scale(n: int): int do\n  give n * 3 + 1\nend\n";
+    let mixed = format!("{}fb a = 1.5\nexit(scale(4) + scale(a * 2.0))", scale);
+    let err = check(&mixed, nebula::TypeMode::Strict).unwrap_err();
+    assert_eq!(err.code(), Some(nebula::ErrorCode::E090));
+    assert!(err.to_string().contains("expects int, got fl"), "{}", err);
+    assert!(check(&mixed, nebula::TypeMode::Gradual).is_ok());
+    let exact = format!("{}exit(scale(4) + scale(2))", scale);
+    assert!(check(&exact, nebula::TypeMode::Strict).is_ok());
+    let err = vm_exit_code(&exact);
+    assert!(matches!(err, nebula::NebulaError::Exit { code: 20 }));
+}
+
+#[test]
+fn test_trait_methods_dispatch_on_structs() {
+    let code = "struct Rect { w: nb, h: nb }\nstruct Dot { size: nb }\n\
+        trait Area {\n  fn area(self): nb\n}\n\
+        impl Area for Rect {\n  fn area(self) = self.w * self.h\n}\n\
+        impl Area for Dot {\n  fn area(self) do\n    give self.size\n  end\n}\n\
+        perm total = 0\n\
+        each shape in lst(Rect(2, 3), Dot(1)) do\n  total = total + shape:area()\nend\n\
+        exit(total)";
+    let mut engine = nebula::ScriptEngine::with_backend(nebula::Backend::Interpreter);
+    let err = engine.eval(code).unwrap_err();
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 7 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn test_inherent_impl_methods_dispatch() {
+    let code = "struct Counter { n: nb }\n\
+        impl Counter do\n  fn doubled(self): nb do\n    give self.n * 2\n  end\n\
+        fn plus(self, k: nb): nb = self.n + k\nend\n\
+        perm c = Counter(4)\n\
+        exit(c:doubled() + c:plus(1))";
+    let mut engine = nebula::ScriptEngine::with_backend(nebula::Backend::Interpreter);
+    let err = engine.eval(code).unwrap_err();
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 13 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn test_method_calls_on_variables_keep_evaluation_order() {
+    let code = "perm xs = lst(1, 2, 3)\n\
+        fn grow() do\n  xs = lst(9)\n  give 4\nend\n\
+        perm ys = xs:push(grow())\n\
+        perm frozen = freeze(lst(5, 6))\n\
+        exit(ys:first() * 100 + xs:first() * 10 + frozen:last() - ys:last())";
+    let mut engine = nebula::ScriptEngine::with_backend(nebula::Backend::Interpreter);
+    let err = engine.eval(code).unwrap_err();
+    assert!(
+        matches!(err, nebula::NebulaError::Exit { code: 192 }),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn test_function_values_share_their_ast_bodies() {
+    use nebula::parser::ast::{ExprKind, Item, StmtKind};
+    let program = parse("fn twice(x) = x * 2\nperm add = (a, b) => a + b");
+    let mut interp = nebula::Interpreter::new();
+    interp.interpret(&program).unwrap();
+    let globals = interp.global_bindings();
+    let global = |name: &str| globals.iter().find(|(n, _)| n == name).unwrap().1.clone();
+    let (Item::Function(twice), Item::Statement(add)) = (&program.items[0], &program.items[1])
+    else {
+        panic!("expected a function and a statement");
+    };
+    let nebula::Value::Function(value) = global("twice") else {
+        panic!("expected a function value");
+    };
+    assert!(std::sync::Arc::ptr_eq(&value.body, &twice.body));
+    assert!(std::sync::Arc::ptr_eq(&value.params, &twice.params));
+    let StmtKind::Const { value: lambda, .. } = &add.kind else {
+        panic!("expected a declaration");
+    };
+    let (ExprKind::Lambda { body, .. }, nebula::Value::Lambda(value)) =
+        (&lambda.kind, global("add"))
+    else {
+        panic!("expected a lambda");
+    };
+    assert!(std::sync::Arc::ptr_eq(&value.body, body));
+}
+
+#[test]
+fn test_string_values_share_their_buffer() {
+    let program = parse("perm a = \"abc\" + \"def\"\nperm b = a\nperm xs = lst(a, b)");
+    let mut interp = nebula::Interpreter::new();
+    interp.interpret(&program).unwrap();
+    let globals = interp.global_bindings();
+    let global = |name: &str| globals.iter().find(|(n, _)| n == name).unwrap().1.clone();
+    let (nebula::Value::String(a), nebula::Value::String(b), nebula::Value::List(xs)) =
+        (global("a"), global("b"), global("xs"))
+    else {
+        panic!("expected strings and a list");
+    };
+    assert_eq!(&*a, "abcdef");
+    assert!(std::rc::Rc::ptr_eq(&a, &b));
+    for x in &xs {
+        let nebula::Value::String(x) = x else {
+            panic!("expected a string");
+        };
+        assert!(std::rc::Rc::ptr_eq(&a, x));
+    }
+}
+
+#[test]
+fn test_string_interpolation_in_both_engines() {
+    let code = "fb n = 4\nfb items = lst(\"a\")\nfn label(x) = \"<${x}>\"\n\
+        fb s = \"n=${n + 1}, ${len(items)} item ${label(nil)} ${items}\"";
+    let many = format!("fb long = \"{}\"", "${n}.".repeat(300));
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.eval(code).unwrap();
+        assert_eq!(
+            engine.get_global("s").unwrap().to_display_string(),
+            "n=5, 1 item <nil> lst(a)",
+            "{:?}",
+            backend
+        );
+        engine.eval(&many).unwrap();
+        let long = engine.get_global("long").unwrap().to_display_string();
+        assert_eq!(long, "4.".repeat(300), "{:?}", backend);
+    }
+    let tokens: Vec<_> = Lexer::new("log(\"${1 2}\")").collect();
+    let err = Parser::new(tokens).parse_program().unwrap_err();
+    assert!(err.message().contains("in interpolation"), "{}", err);
+    assert_eq!(err.span().unwrap().column, 10);
+}
+
+#[test]
+fn test_hash_length_operator_beside_comments() {
+    let code = "# header comment\n\
+        perm xs = lst(1, 2, 3) # trailing comment\n\
+        perm total = #xs + #\"ab\"\n\
+        exit(total)";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        let err = engine.eval(code).unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 5 }),
+            "{:?}: {:?}",
+            backend,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_bracket_list_and_map_literals() {
+    let code = "perm xs = [\n  1, 2, 3,\n]\n\
+        perm m = {\"a\": lst(1), \"b\": [2]}\n\
+        perm none = [] # empty list\n\
+        exit(len(xs) + len(m) + len(none) + len({}))";
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        let err = engine.eval(code).unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 5 }),
+            "{:?}: {:?}",
+            backend,
+            err
+        );
+    }
+    let mut engine = nebula::ScriptEngine::with_backend(nebula::Backend::Interpreter);
+    engine
+        .eval("perm xs = [10, 20]\nperm second = xs[1]\nperm m = {\"k\": [xs[0]]}")
+        .unwrap();
+    assert_eq!(
+        engine.get_global("second").unwrap().to_display_string(),
+        "20"
+    );
+    assert_eq!(
+        engine.get_global("m").unwrap().to_display_string(),
+        "map(\"k\": lst(10))"
+    );
+    let tokens: Vec<_> = Lexer::new("perm m = {\"a\" 1}").collect();
+    let err = Parser::new(tokens).parse_program().unwrap_err();
+    assert!(err.message().contains("Colon"), "{}", err);
+}
+
+#[test]
+fn test_literals_past_255_elements_in_both_engines() {
+    let items: Vec<String> = (0..300).map(|i| i.to_string()).collect();
+    let pairs: Vec<String> = (0..300)
+        .map(|i| format!("\"k{}\": {}", i % 280, i))
+        .collect();
+    let code = format!(
+        "fb xs = lst({})\nfb m = map({})\nlog(#xs, xs:last(), #m, m:get(\"k0\"), m:get(\"k279\"))",
+        items.join(", "),
+        pairs.join(", ")
+    );
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let capture = nebula::interp::OutputCapture::new();
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        engine.set_output(Box::new(capture.clone()));
+        engine.eval(&code).unwrap();
+        assert_eq!(capture.take(), "300 299 280 280 279\n", "{:?}", backend);
+    }
+    let args = vec!["1"; 256].join(", ");
+    let mut engine = nebula::ScriptEngine::with_backend(nebula::Backend::Vm);
+    let err = engine
+        .eval(&format!("fn f(x) = x\nlog(f({}))", args))
+        .unwrap_err();
+    assert!(err.message().contains("too many arguments"), "{}", err);
+}
+
+#[test]
+fn test_flat_operator_chain_is_not_nesting() {
+    let code = format!("exit(1{})", " + 1".repeat(119));
+    for backend in [nebula::Backend::Interpreter, nebula::Backend::Vm] {
+        let mut engine = nebula::ScriptEngine::with_backend(backend);
+        let err = engine.eval(&code).unwrap_err();
+        assert!(
+            matches!(err, nebula::NebulaError::Exit { code: 120 }),
+            "{:?}",
+            err
+        );
+    }
+}